@@ -45,7 +45,7 @@ use std::default::Default;
 /// Re-export our version of clap.
 pub use clap;
 
-#[derive(Args, Debug, Clone, Default, PartialEq, Eq)]
+#[derive(Args, Debug, Clone, Default, PartialEq)]
 /// Options that configure CirC
 pub struct CircOpt {
     /// Options for the R1cs backend
@@ -72,6 +72,12 @@ pub struct CircOpt {
     /// Options for C frontend
     #[command(flatten)]
     pub c: COpt,
+    /// Options for the MPC backend
+    #[command(flatten)]
+    pub mpc: MpcOpt,
+    /// Options for ILP solving
+    #[command(flatten)]
+    pub ilp: IlpOpt,
 }
 
 /// Options for the R1cs backend
@@ -101,6 +107,19 @@ pub struct R1csOpt {
     )]
     /// linear combination constraints up to this size will be eliminated
     pub lc_elim_thresh: usize,
+
+    #[arg(long = "r1cs-lc-elim-gauss", env = "R1CS_LC_ELIM_GAUSS", action = ArgAction::Set, default_value = "false")]
+    /// After threshold-based elimination, run sparse Gaussian elimination over all linear
+    /// constraints
+    pub lc_elim_gauss: bool,
+
+    #[arg(
+        long = "r1cs-lc-elim-gauss-fill",
+        env = "R1CS_LC_ELIM_GAUSS_FILL",
+        default_value = "1000"
+    )]
+    /// Gaussian elimination only pivots when the (Markowitz) fill-in estimate is at most this
+    pub lc_elim_gauss_fill: usize,
 }
 
 impl Default for R1csOpt {
@@ -110,6 +129,8 @@ impl Default for R1csOpt {
             profile: false,
             div_by_zero: FieldDivByZero::Incomplete,
             lc_elim_thresh: 50,
+            lc_elim_gauss: false,
+            lc_elim_gauss_fill: 1000,
         }
     }
 }
@@ -293,6 +314,16 @@ pub struct ZsharpOpt {
     /// [ZoKrates](https://zokrates.github.io/language/control_flow.html).
     #[arg(long = "zsharp-isolate-asserts", env = "ZSHARP_ISOLATE_ASSERTS", action = ArgAction::Set, default_value = "false")]
     pub isolate_asserts: bool,
+    /// In Z#, embed each instantiation of a function (its generic values, constant arguments, and
+    /// argument sorts) once, and reuse the result for later calls.
+    ///
+    /// Not used when assertions are isolated.
+    #[arg(long = "zsharp-memoize", env = "ZSHARP_MEMOIZE", action = ArgAction::Set, default_value = "false")]
+    pub memoize: bool,
+    /// In Z#, emit calls to memoized instantiations as `Op::Call`s to a computation for each
+    /// instantiation, rather than inlining them. Link the calls before lowering.
+    #[arg(long = "zsharp-calls", env = "ZSHARP_CALLS", action = ArgAction::Set, default_value = "false")]
+    pub calls: bool,
 }
 
 /// Options for the datalog frontend
@@ -322,7 +353,7 @@ impl Default for DatalogOpt {
 }
 
 /// Options for the C frontend
-#[derive(Args, Debug, Default, Clone, PartialEq, Eq)]
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
 pub struct COpt {
     /// Enable SV competition builtin functions
     #[arg(long = "c-sv-functions", env = "C_SV_FUNCTIONS", action = ArgAction::SetTrue, default_value = "false")]
@@ -331,6 +362,65 @@ pub struct COpt {
     /// Assert no undefined behavior
     #[arg(long = "c-assert-no-ub", env = "C_ASSERT_NO_UB", action = ArgAction::SetTrue, default_value = "false")]
     pub assert_no_ub: bool,
+
+    /// How many data-dependent iterations of a C loop to unroll. In proof mode, a loop that would
+    /// run longer is an assertion failure.
+    #[arg(long = "c-loop-bound", env = "C_LOOP_BOUND", default_value = "100")]
+    pub loop_bound: usize,
+}
+
+impl Default for COpt {
+    fn default() -> Self {
+        Self {
+            sv_functions: false,
+            assert_no_ub: false,
+            loop_bound: 100,
+        }
+    }
+}
+
+/// Options for the MPC backend
+#[derive(Args, Debug, Default, Clone, PartialEq)]
+pub struct MpcOpt {
+    /// The cost of one communication round, in cost-model units. Sharing assignment minimizes
+    /// total cost plus this times the number of rounds. Raise it for high-latency networks.
+    #[arg(long = "mpc-round-cost", env = "MPC_ROUND_COST", default_value = "0")]
+    pub round_cost: f64,
+}
+
+/// Options for ILP solving
+#[derive(Args, Debug, Default, Clone, PartialEq, Eq)]
+pub struct IlpOpt {
+    /// Which ILP solver to use
+    #[arg(
+        long = "ilp-solver",
+        env = "ILP_SOLVER",
+        value_enum,
+        default_value = "cbc"
+    )]
+    pub solver: IlpSolver,
+    /// Time limit for the branch-and-bound ILP solver, in seconds (0 for none)
+    #[arg(long = "ilp-time-limit", env = "ILP_TIME_LIMIT", default_value = "0")]
+    pub time_limit: u64,
+}
+
+#[derive(ValueEnum, Debug, PartialEq, Eq, Clone, Copy)]
+/// Which ILP solver to use
+pub enum IlpSolver {
+    /// CBC, linked into CirC
+    Cbc,
+    /// The `cbc` binary, given an LP file
+    CbcBin,
+    /// The `glpsol` binary, given an LP file
+    Glpk,
+    /// CirC's own branch-and-bound solver, for small instances
+    Bnb,
+}
+
+impl Default for IlpSolver {
+    fn default() -> Self {
+        IlpSolver::Cbc
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,5 @@
+#include "mul.h"
+
+int main(__attribute__((private(0))) int a, __attribute__((private(1))) int b) { 
+    return mul(a, b) + OFFSET;
+}
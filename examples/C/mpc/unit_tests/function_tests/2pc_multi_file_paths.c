@@ -0,0 +1,6 @@
+#include "lib/mul.h"
+#include "lib/sq.h"
+
+int main(__attribute__((private(0))) int a, __attribute__((private(1))) int b) { 
+    return sq(a) + mul(a, b);
+}
@@ -0,0 +1,5 @@
+#include "mul.h"
+
+int mul(int a, int b) {
+    return a * b;
+}
@@ -0,0 +1,6 @@
+#ifndef MUL_H
+#define MUL_H
+
+int mul(int a, int b);
+
+#endif
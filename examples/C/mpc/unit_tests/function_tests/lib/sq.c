@@ -0,0 +1,7 @@
+// reaches mul.c as lib/./mul.c, while the main file reaches it as lib/mul.c
+#include "./mul.h"
+#include "sq.h"
+
+int sq(int x) {
+    return mul(x, x);
+}
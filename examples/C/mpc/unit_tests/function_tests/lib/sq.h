@@ -0,0 +1 @@
+int sq(int x);
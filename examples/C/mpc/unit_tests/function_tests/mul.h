@@ -0,0 +1,3 @@
+#define OFFSET 4
+
+int mul(int a, int b);
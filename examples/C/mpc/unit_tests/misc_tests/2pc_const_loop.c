@@ -0,0 +1,7 @@
+int main(__attribute__((private(0))) int a, __attribute__((private(1))) int b) {
+    int sum = 0;
+    for (int i = 0; i < 200; i++) {
+        sum += a;
+    }
+    return sum + b;
+}
@@ -0,0 +1,9 @@
+int main(__attribute__((private(0))) int a)
+{
+    int s = 0;
+    for (int i = 0; i < 300; i++) {
+        s += 1;
+    }
+    __VERIFIER_assert(s == 300);
+    return s;
+}
@@ -0,0 +1,10 @@
+int main(
+    __attribute__((private(0))) int a,
+    __attribute__((private(1))) int b)
+{
+    int i = 0;
+    while (i < a) {
+        i++;
+    }
+    __VERIFIER_assert(i >= a);
+}
@@ -0,0 +1,33 @@
+int main(
+    __attribute__((private(0))) int a,
+    __attribute__((private(1))) int b)
+{
+    __VERIFIER_assume(a >= 0 && a < 10);
+    int i = 0;
+    int n = 0;
+    while (1) {
+        if (i == a) break;
+        i++;
+        if (i % 2 == 0) continue;
+        n++;
+    }
+    int j = 0;
+    do {
+        j++;
+    } while (j < a);
+    int k = 0;
+    switch (b) {
+        case 0:
+        case 1:
+            k = 1;
+            break;
+        case 2:
+            k = 2;
+        default:
+            k = 3;
+    }
+    __VERIFIER_assert(i == a);
+    __VERIFIER_assert(n == (a + 1) / 2);
+    __VERIFIER_assert(j == a || (a == 0 && j == 1));
+    __VERIFIER_assert(k == 1 || k == 3);
+}
@@ -0,0 +1,20 @@
+int main(
+    __attribute__((private(0))) int a,
+    __attribute__((private(1))) int b)
+{
+    int *pool = malloc(4 * sizeof(int));
+    int *p = pool;
+    for (int i = 0; i < 4; i++) {
+        *p = i;
+        p++;
+    }
+    int *q = pool + 2;
+    if (a > b) q = &pool[1];
+    *q = a;
+    __VERIFIER_assert(pool[0] == 0);
+    __VERIFIER_assert(*(pool + 3) == 3);
+    __VERIFIER_assert(a <= b || pool[1] == a);
+    __VERIFIER_assert(a > b || pool[2] == a);
+    __VERIFIER_assert(q - pool == 1 || q - pool == 2);
+    free(pool);
+}
@@ -0,0 +1,8 @@
+int main(
+    __attribute__((private(0))) int a,
+    __attribute__((private(1))) int b)
+{
+    int *p = malloc(2 * sizeof(int));
+    p[a] = b;
+    __VERIFIER_assert(1);
+}
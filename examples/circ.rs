@@ -33,8 +33,12 @@ use circ::ir::{
 };
 #[cfg(feature = "aby")]
 use circ::target::aby::trans::to_aby;
+#[cfg(feature = "bristol")]
+use circ::target::bristol::trans::to_bristol;
 #[cfg(feature = "lp")]
 use circ::target::ilp::{assignment_to_values, trans::to_ilp};
+#[cfg(feature = "plonk")]
+use circ::target::plonk::trans::to_plonk;
 #[cfg(feature = "spartan")]
 use circ::target::r1cs::spartan::write_data;
 #[cfg(feature = "bellman")]
@@ -44,13 +48,19 @@ use circ::target::r1cs::{
     proof::{CommitProofSystem, ProofSystem},
 };
 #[cfg(feature = "r1cs")]
-use circ::target::r1cs::{opt::reduce_linearities, trans::to_r1cs};
+use circ::target::r1cs::{
+    opt::{reduce_linearities_with_stats, remove_redundancies},
+    trans::to_r1cs,
+};
+#[cfg(feature = "shamir")]
+use circ::target::shamir::{sim::simulate, trans::to_shamir};
 #[cfg(feature = "smt")]
-use circ::target::smt::find_model;
+use circ::target::smt::{
+    bmc::{assertions_fail, failed_assertions, find_violation, Verdict},
+    find_model,
+};
 use circ_fields::FieldT;
 use fxhash::FxHashMap as HashMap;
-#[cfg(feature = "lp")]
-use good_lp::default_solver;
 use log::trace;
 use std::fs::File;
 use std::io::Read;
@@ -105,8 +115,32 @@ enum Backend {
         #[arg(long, default_value = "groth16")]
         proof_impl: ProofImpl,
     },
-    Smt {},
-    Ilp {},
+    Plonk {
+        #[arg(long, default_value = "P")]
+        prover_key: PathBuf,
+        #[arg(long, default_value = "V")]
+        verifier_key: PathBuf,
+    },
+    Smt {
+        #[arg(long)]
+        /// where to write a counterexample (a value map), if one is found
+        counterexample: Option<PathBuf>,
+    },
+    Ilp {
+        #[arg(long)]
+        /// instead of solving, write the ILP to this LP file (for external solvers)
+        lp_file: Option<PathBuf>,
+    },
+    Bristol {
+        #[arg(long, default_value = "circuit.txt")]
+        /// where to write the Bristol Fashion circuit
+        output: PathBuf,
+    },
+    Shamir {
+        #[arg(long)]
+        /// inputs (a value map) to simulate the protocol on
+        inputs: Option<PathBuf>,
+    },
     Mpc {
         #[arg(long, default_value = "hycc", name = "cost_model")]
         cost_model: String,
@@ -186,8 +220,11 @@ fn main() {
             Some(t) => Mode::ProofOfHighValue(t),
             None => Mode::Proof,
         },
+        Backend::Plonk { .. } => Mode::Proof,
         Backend::Ilp { .. } => Mode::Opt,
         Backend::Mpc { .. } => Mode::Mpc(options.parties),
+        Backend::Bristol { .. } => Mode::Mpc(options.parties),
+        Backend::Shamir { .. } => Mode::Mpc(options.parties),
         Backend::Smt { .. } => Mode::Proof,
     };
     let language = determine_language(&options.frontend.language, &options.path);
@@ -198,7 +235,18 @@ fn main() {
                 file: options.path,
                 mode,
             };
-            ZSharpFE::gen(inputs)
+            let cs = ZSharpFE::try_gen(inputs).unwrap_or_else(|ds| {
+                println!("{ds}");
+                std::process::exit(1)
+            });
+            if options.circ.zsharp.calls {
+                // inline the calls to memoized instantiations
+                let mut cs = opt(cs, vec![Opt::Link]);
+                cs.comps.retain(|name, _| name == "main");
+                cs
+            } else {
+                cs
+            }
         }
         #[cfg(not(all(feature = "smt", feature = "zok")))]
         DeterminedLanguage::Zsharp => {
@@ -220,6 +268,7 @@ fn main() {
                 mode,
                 sv_functions: options.circ.c.sv_functions,
                 assert_no_ub: options.circ.c.assert_no_ub,
+                loop_bound: options.circ.c.loop_bound,
             };
             C::gen(inputs)
         }
@@ -248,8 +297,8 @@ fn main() {
                     Opt::Obliv,
                     // The obliv elim pass produces more tuples, that must be eliminated
                     Opt::Tuple,
-                    Opt::LinearScan,
-                    // The linear scan pass produces more tuples, that must be eliminated
+                    Opt::MpcArrays,
+                    // The array pass produces more tuples, that must be eliminated
                     Opt::Tuple,
                     Opt::ConstantFold(Box::new(ignore)),
                     // Binarize nary terms
@@ -258,6 +307,14 @@ fn main() {
                 // vec![Opt::Sha, Opt::ConstantFold, Opt::Mem, Opt::ConstantFold],
             )
         }
+        // The SMT back-end supports tuples and arrays, so we keep them (and the names of the
+        // inputs, so that counterexamples can be replayed).
+        Mode::Proof
+            if matches!(options.backend, Backend::Smt { .. })
+                && !options.circ.datalog.lint_prim_rec =>
+        {
+            opt(cs, vec![Opt::Flatten, Opt::ConstantFold(Box::new([]))])
+        }
         Mode::Proof | Mode::ProofOfHighValue(_) => {
             let mut opts = Vec::new();
 
@@ -303,7 +360,13 @@ fn main() {
             let mut r1cs = to_r1cs(cs, cfg());
 
             println!("Pre-opt R1cs size: {}", r1cs.constraints().len());
-            r1cs = reduce_linearities(r1cs, cfg());
+            let (reduced, stats) = reduce_linearities_with_stats(r1cs, cfg());
+            r1cs = reduced;
+            println!(
+                "Eliminated {} vars by substitution, {} by Gaussian elimination",
+                stats.thresh_elims, stats.gauss_elims
+            );
+            r1cs = remove_redundancies(r1cs);
 
             println!("Final R1cs size: {}", r1cs.constraints().len());
             let (prover_data, verifier_data) = r1cs.finalize(cs);
@@ -360,6 +423,23 @@ fn main() {
         Backend::R1cs { .. } => {
             panic!("Missing feature: r1cs");
         }
+        #[cfg(feature = "plonk")]
+        Backend::Plonk {
+            prover_key,
+            verifier_key,
+        } => {
+            println!("Converting to plonk");
+            let cs = cs.get("main");
+            let plonk = to_plonk(cs, cfg());
+            println!("Final Plonk size: {} rows", plonk.num_rows());
+            let (prover_data, verifier_data) = plonk.finalize(cs);
+            bincode::serialize_into(File::create(prover_key).unwrap(), &prover_data).unwrap();
+            bincode::serialize_into(File::create(verifier_key).unwrap(), &verifier_data).unwrap();
+        }
+        #[cfg(not(feature = "plonk"))]
+        Backend::Plonk { .. } => {
+            panic!("Missing feature: plonk");
+        }
         #[cfg(feature = "aby")]
         Backend::Mpc {
             cost_model,
@@ -379,8 +459,39 @@ fn main() {
         Backend::Mpc { .. } => {
             panic!("Missing feature: aby");
         }
+        #[cfg(feature = "bristol")]
+        Backend::Bristol { output } => {
+            println!("Converting to Bristol Fashion");
+            let circuit = to_bristol(cs.get("main"));
+            println!("AND gates: {}", circuit.netlist.and_count());
+            std::fs::write(output, circuit.netlist.to_string()).unwrap();
+        }
+        #[cfg(not(feature = "bristol"))]
+        Backend::Bristol { .. } => {
+            panic!("Missing feature: bristol");
+        }
+        #[cfg(feature = "shamir")]
+        Backend::Shamir { inputs } => {
+            println!("Converting to Shamir");
+            let circuit = to_shamir(cs.get("main"), cfg().field(), options.parties as usize);
+            println!("Parties: {}", circuit.num_parties);
+            println!("Multiplications: {}", circuit.mul_count());
+            println!("Multiplicative depth: {}", circuit.mul_depth());
+            if let Some(inputs) = inputs {
+                let values = parse_value_map(&std::fs::read(inputs).unwrap());
+                let (outputs, stats) = simulate(&circuit, &values, &mut rand::thread_rng());
+                for o in outputs {
+                    println!("Output: {o}");
+                }
+                println!("{stats:?}");
+            }
+        }
+        #[cfg(not(feature = "shamir"))]
+        Backend::Shamir { .. } => {
+            panic!("Missing feature: shamir");
+        }
         #[cfg(feature = "lp")]
-        Backend::Ilp { .. } => {
+        Backend::Ilp { lp_file } => {
             println!("Converting to ilp");
             let inputs_and_sorts: HashMap<_, _> = cs
                 .get("main")
@@ -394,27 +505,33 @@ fn main() {
                 })
                 .collect();
             let ilp = to_ilp(cs.get("main").clone());
-            let solver_result = ilp.solve(default_solver);
-            let (max, vars) = solver_result.expect("ILP could not be solved");
-            println!("Max value: {}", max.round() as u64);
-            println!("Assignment:");
-            for (var, val) in &vars {
-                println!("  {}: {}", var, val.round() as u64);
+            if let Some(path) = lp_file {
+                println!("Writing {}", path.display());
+                let mut file = std::fs::File::create(path).unwrap();
+                ilp.write_lp(&mut file).unwrap();
+            } else {
+                let solver_result = ilp.default_solve();
+                let (max, vars) = solver_result.expect("ILP could not be solved");
+                println!("Max value: {}", max.round() as i64);
+                println!("Assignment:");
+                for (var, val) in &vars {
+                    println!("  {}: {}", var, val.round() as i64);
+                }
+                let values = assignment_to_values(&vars, &inputs_and_sorts);
+                let values_as_str = serialize_value_map(&values);
+                std::fs::write("assignment.txt", values_as_str).unwrap();
             }
-            let values = assignment_to_values(&vars, &inputs_and_sorts);
-            let values_as_str = serialize_value_map(&values);
-            std::fs::write("assignment.txt", values_as_str).unwrap();
         }
         #[cfg(not(feature = "lp"))]
         Backend::Ilp { .. } => {
             panic!("Missing feature: lp");
         }
         #[cfg(feature = "smt")]
-        Backend::Smt { .. } => {
+        Backend::Smt { counterexample } => {
             let main_comp = cs.get("main").clone();
-            assert_eq!(main_comp.outputs.len(), 1);
-            let model = find_model(&main_comp.outputs[0]);
             if options.circ.datalog.lint_prim_rec {
+                assert_eq!(main_comp.outputs.len(), 1);
+                let model = find_model(&main_comp.outputs[0]);
                 match model {
                     Some(m) => {
                         println!("Not primitive recursive!");
@@ -428,15 +545,30 @@ fn main() {
                     }
                 }
             } else {
-                match model {
-                    Some(m) => {
+                // The C front-end's output is already the condition under which an assertion
+                // fails; the others output their assertions.
+                let violation = match language {
+                    DeterminedLanguage::C => {
+                        assert_eq!(main_comp.outputs.len(), 1);
+                        main_comp.outputs[0].clone()
+                    }
+                    _ => assertions_fail(&main_comp),
+                };
+                match find_violation(&main_comp, violation) {
+                    Verdict::Violated(inputs) => {
                         println!("Property does not hold!\nCounterexample:");
-                        for (var, val) in m {
+                        for (var, val) in &inputs {
                             println!("{var} -> {val}");
                         }
+                        for loc in failed_assertions(&main_comp, &inputs) {
+                            println!("Assertion failed: {loc}");
+                        }
+                        if let Some(path) = counterexample {
+                            std::fs::write(path, serialize_value_map(&inputs)).unwrap();
+                        }
                         std::process::exit(1)
                     }
-                    None => {
+                    Verdict::Holds => {
                         println!("Property holds");
                     }
                 }
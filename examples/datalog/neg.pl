@@ -0,0 +1,7 @@
+blocked(U: u8) :- U = 0x07; U = 0x0d.
+
+allowed(R: (u8, u8)) :-
+    R.0 < R.1,
+    !blocked(R.0).
+
+main(U: private u8, L: public u8) :- allowed((U, L)), L <= 0x80.
@@ -0,0 +1,3 @@
+square(X: field) :- exists R: field. R * R = X.
+
+main(X: private field) :- X = 0 || !square(X).
@@ -0,0 +1,3 @@
+small(X: u8) :- X < 0x10.
+
+main(X: private u8) :- X = 0x80 || small(X), !(X = 0x05 || small(X + 0x01)).
@@ -0,0 +1,5 @@
+p(X: u8) :- !q(X).
+
+q(X: u8) :- p(X), X > 0x00.
+
+main(X: private u8) :- p(X).
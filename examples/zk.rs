@@ -7,7 +7,11 @@ use std::path::PathBuf;
 #[cfg(feature = "bellman")]
 use bls12_381::Bls12;
 #[cfg(feature = "bellman")]
-use circ::target::r1cs::{bellman::Bellman, mirage::Mirage, proof::ProofSystem};
+use circ::target::r1cs::{
+    bellman::{solidity, Bellman},
+    mirage::Mirage,
+    proof::ProofSystem,
+};
 
 #[cfg(feature = "spartan")]
 use circ::ir::term::text::parse_value_map;
@@ -29,6 +33,10 @@ struct Options {
     pin: PathBuf,
     #[arg(long, default_value = "vin")]
     vin: PathBuf,
+    #[arg(long, default_value = "Verifier.sol")]
+    contract: PathBuf,
+    #[arg(long, default_value = "calldata")]
+    calldata: PathBuf,
     #[arg(long, default_value = "groth16")]
     proof_impl: ProofImpl,
     #[arg(long)]
@@ -40,10 +48,14 @@ struct Options {
 #[derive(PartialEq, Debug, Clone, ValueEnum)]
 /// `Prove`/`Verify` execute proving/verifying in bellman separately
 /// `Spartan` executes both proving/verifying in spartan
+/// `Solidity` writes a Groth16 verifier contract
+/// `Calldata` writes calldata for that contract
 enum ProofAction {
     Prove,
     Verify,
     Spartan,
+    Solidity,
+    Calldata,
 }
 
 #[derive(PartialEq, Debug, Clone, ValueEnum)]
@@ -87,8 +99,35 @@ fn main() {
                 "invalid proof"
             );
         }
+        #[cfg(feature = "bellman")]
+        (ProofAction::Solidity, ProofImpl::Groth16) => {
+            println!("Writing verifier contract");
+            solidity::write_contract_fs::<Bls12>(opts.verifier_key, opts.contract, "Verifier")
+                .unwrap();
+        }
+        #[cfg(feature = "bellman")]
+        (ProofAction::Calldata, ProofImpl::Groth16) => {
+            println!("Writing calldata");
+            solidity::write_calldata_fs::<Bls12>(
+                opts.verifier_key,
+                opts.inputs,
+                opts.proof,
+                opts.calldata,
+            )
+            .unwrap();
+        }
+        #[cfg(feature = "bellman")]
+        (ProofAction::Solidity | ProofAction::Calldata, ProofImpl::Mirage) => {
+            panic!("Solidity verifiers are only available for Groth16")
+        }
         #[cfg(not(feature = "bellman"))]
-        (ProofAction::Prove | ProofAction::Verify, _) => panic!("Missing feature: bellman"),
+        (
+            ProofAction::Prove
+            | ProofAction::Verify
+            | ProofAction::Solidity
+            | ProofAction::Calldata,
+            _,
+        ) => panic!("Missing feature: bellman"),
         #[cfg(feature = "spartan")]
         (ProofAction::Spartan, _) => {
             let prover_input_map = parse_value_map(&std::fs::read(opts.pin).unwrap());
@@ -0,0 +1,336 @@
+//! A language server for Z#, speaking the language server protocol over stdin/stdout.
+//!
+//! It checks a document whenever it is opened, changed, or saved, and answers go-to-definition,
+//! hover, and completion requests from the latest check. See [circ::front::zsharp::ide].
+
+use circ::cfg::{
+    clap::{self, Parser},
+    CircOpt,
+};
+use circ::front::diagnostic::{Diagnostic, Position, Severity, SourceRange};
+use circ::front::zsharp::ide::{stdlib_completions, Analysis, Completion, SymbolKind};
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Parser)]
+#[command(name = "zls", about = "The Z# language server")]
+struct Options {
+    #[command(flatten)]
+    /// CirC options
+    circ: CircOpt,
+}
+
+/// Read a message, or `None` at the end of the input.
+fn read_message(r: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut len = None;
+    loop {
+        let mut header = String::new();
+        if r.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                len = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let len = len.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no Content-Length"))?;
+    let mut body = vec![0; len];
+    r.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+fn write_message(w: &mut impl Write, v: &Value) -> io::Result<()> {
+    let body = v.to_string();
+    write!(w, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    w.flush()
+}
+
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    // undo percent-encoding
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut it = path.bytes();
+    while let Some(b) = it.next() {
+        if b == b'%' {
+            let hex: Vec<u8> = it.by_ref().take(2).collect();
+            if let Some(d) = std::str::from_utf8(&hex)
+                .ok()
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+            {
+                bytes.push(d);
+                continue;
+            }
+            bytes.push(b);
+            bytes.extend(hex);
+        } else {
+            bytes.push(b);
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let mut uri = "file://".to_owned();
+    for b in path.to_string_lossy().bytes() {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            uri.push(b as char);
+        } else {
+            uri.push_str(&format!("%{b:02X}"));
+        }
+    }
+    uri
+}
+
+/// LSP positions count from 0; ours count from 1.
+fn lsp_position(p: Position) -> Value {
+    json!({ "line": p.line.saturating_sub(1), "character": p.column.saturating_sub(1) })
+}
+
+fn lsp_range(r: &SourceRange) -> Value {
+    json!({ "start": lsp_position(r.start), "end": lsp_position(r.end) })
+}
+
+fn lsp_diagnostic(d: &Diagnostic) -> Value {
+    let severity = match d.severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+        Severity::Note => 3,
+    };
+    let start = Position::new(1, 1);
+    let range = match &d.range {
+        Some(r) => lsp_range(r),
+        None => json!({ "start": lsp_position(start), "end": lsp_position(start) }),
+    };
+    let mut message = d.message.clone();
+    for n in &d.notes {
+        message.push_str(&format!("\nnote: {n}"));
+    }
+    for s in &d.suggestions {
+        message.push_str(&format!("\nhelp: {s}"));
+    }
+    json!({
+        "range": range,
+        "severity": severity,
+        "code": d.code,
+        "source": "zls",
+        "message": message,
+    })
+}
+
+fn lsp_completion(c: &Completion) -> Value {
+    let kind = match c.kind {
+        SymbolKind::Function => 3,
+        SymbolKind::Field => 5,
+        SymbolKind::Variable => 6,
+        SymbolKind::Struct => 22,
+        SymbolKind::Type => 7,
+        SymbolKind::Constant => 21,
+    };
+    json!({ "label": c.label, "kind": kind, "detail": c.detail })
+}
+
+/// The document and position that a request is about
+fn text_document_position(params: &Value) -> Option<(PathBuf, Position)> {
+    let uri = params["textDocument"]["uri"].as_str()?;
+    let p = &params["position"];
+    let line = p["line"].as_u64()? as usize + 1;
+    let column = p["character"].as_u64()? as usize + 1;
+    Some((uri_to_path(uri), Position::new(line, column)))
+}
+
+#[derive(Default)]
+struct Server {
+    /// the text of open documents
+    docs: HashMap<PathBuf, String>,
+    /// the latest analysis of each open document
+    analyses: HashMap<PathBuf, Analysis>,
+    stdlib: Vec<Completion>,
+}
+
+impl Server {
+    /// Check `path` and publish its diagnostics.
+    fn analyze(&mut self, path: PathBuf, out: &mut impl Write) -> io::Result<()> {
+        let docs = self.docs.clone();
+        // the front-end panics on some problems (e.g., missing imports)
+        let diagnostics = match catch_unwind(AssertUnwindSafe(|| Analysis::new(&path, docs))) {
+            Ok(a) => {
+                let mut by_file: HashMap<PathBuf, Vec<Value>> = HashMap::new();
+                by_file.insert(path.clone(), Vec::new());
+                for d in a.diagnostics().iter() {
+                    let file = d.range.as_ref().map_or(&path, |r| &r.file);
+                    by_file
+                        .entry(file.clone())
+                        .or_default()
+                        .push(lsp_diagnostic(d));
+                }
+                self.analyses.insert(path.clone(), a);
+                by_file
+            }
+            Err(e) => {
+                let msg = e
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or_else(|| e.downcast_ref::<&str>().map(|s| s.to_string()))
+                    .unwrap_or_else(|| "the front-end crashed".to_owned());
+                let d = Diagnostic::error("internal", msg);
+                HashMap::from([(path.clone(), vec![lsp_diagnostic(&d)])])
+            }
+        };
+        for (file, ds) in diagnostics {
+            if file == path || self.docs.contains_key(&file) {
+                notify(
+                    out,
+                    "textDocument/publishDiagnostics",
+                    json!({ "uri": path_to_uri(&file), "diagnostics": ds }),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle a notification
+    fn notification(
+        &mut self,
+        method: &str,
+        params: &Value,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let path = uri_to_path(uri);
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.docs.insert(path.clone(), text.to_owned());
+                self.analyze(path, out)
+            }
+            "textDocument/didChange" => {
+                // we ask for full-document sync, so the last change is the whole text
+                if let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|cs| cs.last())
+                    .and_then(|c| c["text"].as_str())
+                {
+                    self.docs.insert(path.clone(), text.to_owned());
+                }
+                self.analyze(path, out)
+            }
+            "textDocument/didSave" => self.analyze(path, out),
+            "textDocument/didClose" => {
+                self.docs.remove(&path);
+                self.analyses.remove(&path);
+                notify(
+                    out,
+                    "textDocument/publishDiagnostics",
+                    json!({ "uri": uri, "diagnostics": [] }),
+                )
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Handle a request, returning its result
+    fn request(&self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": { "triggerCharacters": ["."] },
+                },
+                "serverInfo": { "name": "zls" },
+            })),
+            "shutdown" => Ok(Value::Null),
+            "textDocument/definition" => Ok(self
+                .query(params, |a, p, pos| {
+                    let r = &a.definition(p, pos)?.range;
+                    Some(json!({ "uri": path_to_uri(&r.file), "range": lsp_range(r) }))
+                })
+                .unwrap_or(Value::Null)),
+            "textDocument/hover" => Ok(self
+                .query(params, |a, p, pos| {
+                    let value = format!("```zokrates\n{}\n```", a.hover(p, pos)?);
+                    Some(json!({ "contents": { "kind": "markdown", "value": value } }))
+                })
+                .unwrap_or(Value::Null)),
+            "textDocument/completion" => {
+                let stdlib = &self.stdlib;
+                Ok(self
+                    .query(params, |a, p, pos| {
+                        let cs = a.completions(p, pos, stdlib);
+                        Some(Value::from(
+                            cs.iter().map(lsp_completion).collect::<Vec<_>>(),
+                        ))
+                    })
+                    .unwrap_or_else(|| json!([])))
+            }
+            _ => Err((-32601, format!("Unsupported method: {method}"))),
+        }
+    }
+
+    /// Answer a question about the document and position in `params` from its latest analysis
+    fn query(
+        &self,
+        params: &Value,
+        f: impl FnOnce(&Analysis, &Path, Position) -> Option<Value>,
+    ) -> Option<Value> {
+        let (path, pos) = text_document_position(params)?;
+        let a = self.analyses.get(&path)?;
+        f(a, &path, pos)
+    }
+}
+
+fn notify(out: &mut impl Write, method: &str, params: Value) -> io::Result<()> {
+    write_message(
+        out,
+        &json!({ "jsonrpc": "2.0", "method": method, "params": params }),
+    )
+}
+
+fn main() -> io::Result<()> {
+    env_logger::Builder::from_default_env()
+        .format_level(false)
+        .format_timestamp(None)
+        .init();
+    let options = Options::parse();
+    circ::cfg::set(&options.circ);
+    let mut server = Server {
+        stdlib: stdlib_completions(),
+        ..Default::default()
+    };
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut shutdown = false;
+    while let Some(msg) = read_message(&mut input)? {
+        let method = msg["method"].as_str().unwrap_or_default();
+        let params = &msg["params"];
+        match msg.get("id") {
+            Some(id) => {
+                let response = match server.request(method, params) {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err((code, message)) => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": code, "message": message },
+                    }),
+                };
+                shutdown |= method == "shutdown";
+                write_message(&mut out, &response)?;
+            }
+            None if method == "exit" => std::process::exit(if shutdown { 0 } else { 1 }),
+            None => server.notification(method, params, &mut out)?,
+        }
+    }
+    Ok(())
+}
@@ -91,7 +91,10 @@ fn main() {
             file: options.path,
             mode: Mode::Proof,
         };
-        ZSharpFE::gen(inputs)
+        ZSharpFE::try_gen(inputs).unwrap_or_else(|ds| {
+            println!("{ds}");
+            std::process::exit(1)
+        })
     };
 
     print!("Optimizing IR... ");
@@ -4,7 +4,10 @@ use circ::cfg::{
     clap::{self, Parser},
     CircOpt,
 };
+use circ::front::diagnostic::Diagnostics;
 use circ::front::Mode;
+use circ::ir::term::text::parse_value_map;
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 #[derive(Debug, Parser)]
@@ -14,6 +17,11 @@ struct Options {
     #[arg()]
     zsharp_path: PathBuf,
 
+    /// Arguments for main (and, optionally, its expected return value), as in a .pin or .vin
+    /// file. May be given more than once.
+    #[arg(long)]
+    inputs: Vec<PathBuf>,
+
     #[command(flatten)]
     /// CirC options
     circ: CircOpt,
@@ -31,8 +39,24 @@ fn main() {
         file: options.zsharp_path,
         mode: Mode::Proof,
     };
-    let cs = ZSharpFE::interpret(inputs);
+    let cs = if options.inputs.is_empty() {
+        ZSharpFE::try_interpret(inputs)
+    } else {
+        let mut values = HashMap::new();
+        for path in &options.inputs {
+            let src = std::fs::read(path).expect("could not read inputs");
+            values.extend(parse_value_map(&src));
+        }
+        ZSharpFE::try_interpret_on(inputs, &values)
+    }
+    .unwrap_or_else(exit_with);
     cs.pretty(&mut std::io::stdout().lock())
         .expect("error pretty-printing value");
     println!();
 }
+
+/// Print `ds` and exit.
+fn exit_with<T>(ds: Diagnostics) -> T {
+    println!("{ds}");
+    std::process::exit(1)
+}
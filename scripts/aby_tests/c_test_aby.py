@@ -19,6 +19,7 @@ if __name__ == "__main__":
         mod_tests + \
         struct_tests + \
         ptr_tests + \
+        c_function_tests + \
         c_misc_tests
     # array_tests + \
     # c_array_tests + \
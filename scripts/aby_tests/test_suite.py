@@ -389,6 +389,19 @@ function_tests = [
     ],
 ]
 
+c_function_tests = [
+    [
+        "Function from another file",
+        "2pc_multi_file_add",
+        "./scripts/aby_tests/test_inputs/add_2.txt",
+    ],
+    [
+        "File reached through two include paths",
+        "2pc_multi_file_paths",
+        "./scripts/aby_tests/test_inputs/multi_file_paths.txt",
+    ],
+]
+
 struct_tests = [
     [
         "Struct add",
@@ -513,6 +526,11 @@ c_misc_tests = [
         "Multivariables",
         "2pc_multi_var",
         "./scripts/aby_tests/test_inputs/multi.txt",
+    ],
+    [
+        "Constant loop longer than the loop bound",
+        "2pc_const_loop",
+        "./scripts/aby_tests/test_inputs/const_loop.txt",
     ]
 ]
 
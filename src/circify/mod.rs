@@ -641,12 +641,15 @@ impl<E: Embeddable> Circify<E> {
     /// End a breakable block
     pub fn exit_breakable(&mut self) {
         self.fn_stack.last_mut().expect("No fn").exit_breakable();
+        self.condition = self.condition();
     }
 
     #[track_caller]
     /// Emit a break statement for the breakable block, `name`.
     pub fn break_(&mut self, name: &str) -> Result<()> {
-        self.fn_stack.last_mut().expect("No fn").break_(name)
+        self.fn_stack.last_mut().expect("No fn").break_(name)?;
+        self.condition = self.condition();
+        Ok(())
     }
 
     #[track_caller]
@@ -735,6 +738,27 @@ impl<E: Embeddable> Circify<E> {
         self.cir_ctx.cs.borrow_mut().assert(t);
     }
 
+    /// Record that `t` came from `loc` in the source program
+    pub fn locate(&self, t: Term, loc: SourceLoc) {
+        self.cir_ctx
+            .cs
+            .borrow_mut()
+            .metadata
+            .source_map_mut()
+            .insert(t, loc);
+    }
+
+    /// Where `t` came from in the source program, if it was located
+    pub fn location(&self, t: &Term) -> Option<SourceLoc> {
+        self.cir_ctx
+            .cs
+            .borrow()
+            .metadata
+            .source_map()
+            .get(t)
+            .cloned()
+    }
+
     #[track_caller]
     /// Exit a function call.
     ///
@@ -749,6 +773,7 @@ impl<E: Embeddable> Circify<E> {
                 None
             };
             self.fn_stack.pop().unwrap();
+            self.condition = self.condition();
             ret
         } else {
             panic!("No fn to exit")
@@ -774,12 +799,29 @@ impl<E: Embeddable> Circify<E> {
             }
 
             self.fn_stack.pop().unwrap();
+            self.condition = self.condition();
             Some(rets)
         } else {
             panic!("No fn to exit")
         }
     }
 
+    /// The number of functions being executed
+    pub fn fn_depth(&self) -> usize {
+        self.fn_stack.len()
+    }
+
+    /// Abandon function calls until only `depth` remain, e.g., after an error in a callee.
+    pub fn unwind_fns(&mut self, depth: usize) {
+        self.fn_stack.truncate(depth);
+        self.condition = self.condition();
+    }
+
+    /// The name of the function being executed, if any
+    pub fn fn_name(&self) -> Option<&str> {
+        self.fn_stack.last().map(|f| f.name.as_str())
+    }
+
     /// Get the current value of a location
     pub fn get_value(&self, loc: Loc) -> Result<Val<E::T>> {
         let l = self.get_lex_ref(&loc)?;
@@ -841,6 +883,11 @@ impl<E: Embeddable> Circify<E> {
         self.cir_ctx.mem.borrow_mut().load(id, offset)
     }
 
+    /// Whether `offset` is in bounds for an AllocId
+    pub fn in_bounds(&self, id: AllocId, offset: Term) -> Term {
+        self.cir_ctx.mem.borrow().in_bounds(id, offset)
+    }
+
     /// Conditional store to an AllocId based on an explicit condition
     pub fn cond_store(&mut self, id: AllocId, offset: Term, val: Term, cond: Term) {
         self.cir_ctx.mem.borrow_mut().store(id, offset, val, cond);
@@ -14,6 +14,8 @@ pub struct FnInfo {
     pub ret_ty: Option<Ty>,
     pub params: Vec<ParamInfo>,
     pub body: Statement,
+    /// The translation unit that defines this function
+    pub unit: usize,
 }
 
 #[derive(Clone)]
@@ -29,10 +31,6 @@ pub struct ParamInfo {
     pub vis: Option<PartyId>,
 }
 
-pub struct ConstIteration {
-    pub val: i32,
-}
-
 impl Display for FnInfo {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(
@@ -52,6 +50,15 @@ pub fn name_from_expr(expr: &Expression) -> String {
     }
 }
 
+/// Whether `expr` is a call to the function `name`
+pub fn is_call_to(expr: &Expression, name: &str) -> bool {
+    if let Expression::Call(call) = expr {
+        matches!(&call.node.callee.node, Identifier(i) if i.node.name == name)
+    } else {
+        false
+    }
+}
+
 pub fn name_from_decl(decl: &Declarator) -> String {
     match decl.kind.node {
         DeclaratorKind::Identifier(ref id) => id.node.name.to_string(),
@@ -85,6 +92,18 @@ pub fn compress_type(ts: Vec<Option<Ty>>) -> Option<Ty> {
     }
 }
 
+/// Whether `decl` declares a function (that is defined elsewhere)
+pub fn declares_fn(decl: &Declaration) -> bool {
+    decl.declarators.iter().any(|d| {
+        d.node.declarator.node.derived.iter().any(|d| {
+            matches!(
+                d.node,
+                DerivedDeclarator::Function(_) | DerivedDeclarator::KRFunction(_)
+            )
+        })
+    })
+}
+
 pub fn name_from_func(fn_def: &FunctionDefinition) -> String {
     let decl = &fn_def.declarator.node;
     name_from_decl(decl)
@@ -143,11 +162,11 @@ pub fn interpret_visibility(ext: &DeclarationSpecifier, mode: Mode) -> Option<Pa
                                 num_val = i.number.parse::<u8>().unwrap();
                             }
                         }
-                        if num_val <= n_parties {
+                        if num_val < n_parties {
                             Some(num_val)
                         } else {
                             panic!(
-                                "Party number {} greater than the number of parties ({})",
+                                "Party number {} out of range for {} parties (numbered from 0)",
                                 num_val, n_parties
                             )
                         }
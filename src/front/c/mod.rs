@@ -7,6 +7,7 @@ mod term;
 mod types;
 
 use super::{FrontEnd, Mode};
+use crate::circify::includer::Loader;
 use crate::circify::mem::AllocId;
 use crate::circify::{CircError, Circify, Loc, Val};
 use crate::front::c::ast_utils::*;
@@ -18,8 +19,8 @@ use crate::ir::proof::ConstraintMetadata;
 use crate::ir::term::Node as IrNode;
 use crate::ir::term::*;
 use lang_c::ast::*;
-use lang_c::span::Node;
-use log::debug;
+use lang_c::span::{Node, Span};
+use log::{debug, warn};
 
 use fxhash::FxHashMap;
 use std::cell::RefCell;
@@ -37,6 +38,8 @@ pub struct Inputs {
     pub sv_functions: bool,
     /// assert no undefined behavior
     pub assert_no_ub: bool,
+    /// how many data-dependent iterations of a loop to unroll
+    pub loop_bound: usize,
 }
 
 /// The C front-end. Implements [FrontEnd].
@@ -45,9 +48,24 @@ pub struct C;
 impl FrontEnd for C {
     type Inputs = Inputs;
     fn gen(i: Inputs) -> Computations {
-        let parser = parser::CParser::new();
-        let p = parser.parse_file(&i.file).unwrap();
-        let mut g = CGen::new(i, p.unit);
+        let mut parser = parser::CParser::new();
+        if let Some(dir) = i.file.parent().filter(|d| !d.as_os_str().is_empty()) {
+            parser.include_dir(dir);
+        }
+        // units are keyed by canonical path; see `includes`
+        let file = i
+            .file
+            .canonicalize()
+            .unwrap_or_else(|e| panic!("Cannot open {}: {e}", i.file.display()));
+        let mut units = parser.recursive_load(&file).unwrap();
+        // main's unit comes first; the rest are in a fixed order
+        let main_unit = units.remove(&file).unwrap();
+        let mut others: Vec<_> = units.into_iter().collect();
+        others.sort_by(|a, b| a.0.cmp(&b.0));
+        let units = std::iter::once(main_unit)
+            .chain(others.into_iter().map(|(_, u)| u))
+            .collect();
+        let mut g = CGen::new(i, units);
         g.visit_files();
         g.entry_fn("main");
         let mut cs = Computations::new();
@@ -81,6 +99,8 @@ enum CLoc {
     Var(Loc),
     Member(Box<CLoc>, String),
     Idx(Box<CLoc>, CTerm),
+    /// The memory that a pointer points to
+    Deref(CTerm),
 }
 
 impl CLoc {
@@ -89,14 +109,21 @@ impl CLoc {
             CLoc::Var(l) => l,
             CLoc::Idx(i, _) => i.loc(),
             CLoc::Member(i, _) => i.loc(),
+            CLoc::Deref(_) => panic!("Memory is not a variable"),
         }
     }
 }
 
+/// Loops whose condition stays constant are unrolled exactly, but no further than this.
+const MAX_CONST_LOOP_ITERS: usize = 1 << 20;
+
 struct CGen {
     circ: RefCell<Circify<Ct>>,
     mode: Mode,
-    tu: TranslationUnit,
+    /// The translation units: the file with `main`, then the files it includes the headers of
+    units: Vec<parser::Unit>,
+    /// The unit that we're generating code from
+    cur_unit: usize,
     structs: HashMap<String, Ty>,
     functions: HashMap<String, FnInfo>,
     typedefs: HashMap<String, Ty>,
@@ -111,14 +138,21 @@ struct CGen {
     sv_functions: bool,
     /// assert no undefined behavior
     assert_no_ub: bool,
+    /// how many data-dependent iterations of a loop to unroll
+    loop_bound: usize,
+    /// The enclosing loops and switches: the breakable blocks that `break` (and `continue`, if
+    /// any) leave.
+    breakables: Vec<(String, Option<String>)>,
+    breakable_ctr: usize,
 }
 
 impl CGen {
-    fn new(cfg: Inputs, tu: TranslationUnit) -> Self {
+    fn new(cfg: Inputs, units: Vec<parser::Unit>) -> Self {
         let this = Self {
             circ: RefCell::new(Circify::new(Ct::new())),
             mode: cfg.mode,
-            tu,
+            units,
+            cur_unit: 0,
             structs: HashMap::default(),
             functions: HashMap::default(),
             function_queue: Vec::new(),
@@ -129,14 +163,18 @@ impl CGen {
             assumptions: Vec::new(),
             sv_functions: cfg.sv_functions,
             assert_no_ub: cfg.assert_no_ub,
+            loop_bound: cfg.loop_bound,
+            breakables: Vec::new(),
+            breakable_ctr: 0,
         };
-        this.circ
-            .borrow()
-            .cir_ctx()
-            .cs
-            .borrow_mut()
-            .metadata
-            .add_prover_and_verifier();
+        {
+            let circ = this.circ.borrow();
+            let mut cs = circ.cir_ctx().cs.borrow_mut();
+            match this.mode {
+                Mode::Mpc(n_parties) => cs.metadata.add_mpc_parties(n_parties),
+                _ => cs.metadata.add_prover_and_verifier(),
+            }
+        }
         this
     }
 
@@ -369,6 +407,18 @@ impl CGen {
             ret_ty,
             params,
             body,
+            unit: self.cur_unit,
+        }
+    }
+
+    /// The type named in a cast or `sizeof`
+    fn type_name_(&mut self, tn: &TypeName) -> Option<Ty> {
+        let base = self.s_type_(&tn.specifiers);
+        match &tn.declarator {
+            Some(d) if !d.node.derived.is_empty() => {
+                Some(self.get_derived_type(&mut base.unwrap_or(Ty::Void), &d.node.derived))
+            }
+            _ => base,
         }
     }
 
@@ -403,10 +453,12 @@ impl CGen {
         }
     }
 
-    fn array_select(&self, array: &CTerm, idx: &CTerm) -> Result<CTerm, String> {
+    fn array_select(&mut self, array: &CTerm, idx: &CTerm) -> Result<CTerm, String> {
         match (array.clone().term, idx.clone().term) {
             (CTermData::Array(ty, id), CTermData::Int(_, _, idx)) => {
                 let i = id.unwrap_or_else(|| panic!("Unknown AllocID: {:#?}", array));
+                let in_bounds = self.circ.borrow().in_bounds(i, idx.clone());
+                self.assert_in_bounds(in_bounds);
                 let inner_ty = ty.inner_ty();
                 Ok(cterm(match inner_ty {
                     Ty::Bool => CTermData::Bool(self.circ_load(i, idx)),
@@ -418,12 +470,18 @@ impl CGen {
                 let i = id.unwrap_or_else(|| panic!("Unknown AllocID: {:#?}", array));
                 let inner_ty = ty.inner_ty();
                 let new_offset = term![BV_ADD; offset, idx];
+                let in_bounds = self.circ.borrow().in_bounds(i, new_offset.clone());
+                self.assert_in_bounds(in_bounds);
                 Ok(cterm(match inner_ty {
                     Ty::Bool => CTermData::Bool(self.circ_load(i, new_offset)),
                     Ty::Int(s, w) => CTermData::Int(s, w, self.circ_load(i, new_offset)),
                     _ => unimplemented!(),
                 }))
             }
+            (CTermData::Ptr(..), CTermData::Int(..)) => {
+                let ptr = add(array.clone(), idx.clone())?;
+                Ok(self.deref(ptr))
+            }
             (a, b) => Err(format!("[Array Select] cannot index {a} by {b}")),
         }
     }
@@ -440,6 +498,8 @@ impl CGen {
                 let vals = val.term.terms(self.circ.borrow().cir_ctx());
                 for (o, v) in vals.iter().enumerate() {
                     let updated_idx = term![BV_ADD; idx_term.clone(), bv_lit(o as i32, 32)];
+                    let in_bounds = self.circ.borrow().in_bounds(i, updated_idx.clone());
+                    self.assert_in_bounds(in_bounds);
                     self.circ_store(i, updated_idx, v.clone());
                 }
                 if vals.len() > 1 {
@@ -454,6 +514,8 @@ impl CGen {
                 for (o, v) in vals.iter().enumerate() {
                     let updated_idx =
                         term![BV_ADD; idx_term.clone(), offset.clone(), bv_lit(o as i32, 32)];
+                    let in_bounds = self.circ.borrow().in_bounds(i, updated_idx.clone());
+                    self.assert_in_bounds(in_bounds);
                     self.circ_store(i, updated_idx, v.clone());
                 }
                 if vals.len() > 1 {
@@ -462,10 +524,167 @@ impl CGen {
                     Ok(val.clone())
                 }
             }
+            (CTermData::Ptr(..), CTermData::Int(..)) => {
+                let ptr = add(array.clone(), idx.clone())?;
+                Ok(self.store_through(ptr, val.clone()))
+            }
             (a, b) => Err(format!("[Array Store] cannot index {a} by {b}")),
         }
     }
 
+    /// View an array (or a pointer) as a pointer.
+    fn as_ptr(&self, t: CTerm) -> CTerm {
+        match &t.term {
+            CTermData::Ptr(..) => t,
+            CTermData::Array(..) | CTermData::StackPtr(..) => {
+                cast(Some(Ty::Ptr(32, Box::new(Ty::Void))), t)
+            }
+            _ => self.err(format!("{t} is not a pointer")),
+        }
+    }
+
+    /// The allocations that a pointer with allocation id `alloc` may point into. A null pointer
+    /// points into none.
+    fn ptr_allocs(&self, alloc: &Term) -> Vec<AllocId> {
+        let mut allocs = Vec::new();
+        let mut stack = vec![fold(alloc, &[])];
+        while let Some(a) = stack.pop() {
+            match a.op() {
+                Op::Ite => stack.extend(a.cs().iter().skip(1).cloned()),
+                Op::Const(Value::BitVector(b)) => {
+                    let id = b.uint().to_usize().unwrap();
+                    if id != NULL_ALLOC && !allocs.contains(&id) {
+                        allocs.push(id);
+                    }
+                }
+                _ => self.err(format!("Pointer into an unknown allocation: {a}")),
+            }
+        }
+        allocs
+    }
+
+    /// Whether a pointer with allocation id `alloc` and `offset` is in bounds for one of
+    /// `allocs`.
+    fn ptr_in_bounds(&self, alloc: &Term, offset: &Term, allocs: &[AllocId]) -> Term {
+        let circ = self.circ.borrow();
+        let cases = allocs.iter().map(|id| {
+            term![AND; term![EQ; alloc.clone(), bv_lit(*id, 32)], circ.in_bounds(*id, offset.clone())]
+        });
+        term(OR, cases.chain([bool_lit(false)]).collect())
+    }
+
+    /// If we're checking for undefined behavior, assert that `in_bounds` holds on this path.
+    fn assert_in_bounds(&mut self, in_bounds: Term) {
+        if self.assert_no_ub {
+            let path = self.circ.borrow().condition();
+            self.assertions.push(term![IMPLIES; path, in_bounds]);
+        }
+    }
+
+    /// Load the value that `ptr` points to.
+    fn deref(&mut self, ptr: CTerm) -> CTerm {
+        let ptr = self.as_ptr(ptr);
+        match ptr.term {
+            CTermData::Ptr(ty, alloc, offset) => {
+                if !matches!(ty, Ty::Bool | Ty::Int(..)) {
+                    self.err(format!("Cannot load a {ty} from memory"));
+                }
+                let allocs = self.ptr_allocs(&alloc);
+                let in_bounds = self.ptr_in_bounds(&alloc, &offset, &allocs);
+                self.assert_in_bounds(in_bounds);
+                let val = allocs.iter().fold(ty.sort().default_term(), |rest, id| {
+                    let v = self.circ_load(*id, offset.clone());
+                    let here = fold(&term![EQ; alloc.clone(), bv_lit(*id, 32)], &[]);
+                    match here.as_bool_opt() {
+                        Some(true) => v,
+                        _ => term![ITE; here, v, rest],
+                    }
+                });
+                let data = match ty {
+                    Ty::Bool => CTermData::Bool(val),
+                    Ty::Int(s, w) => CTermData::Int(s, w, val),
+                    _ => unreachable!(),
+                };
+                CTerm {
+                    term: data,
+                    udef: ptr.udef,
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Store `val` where `ptr` points, returning the stored value.
+    fn store_through(&mut self, ptr: CTerm, val: CTerm) -> CTerm {
+        let ptr = self.as_ptr(ptr);
+        match ptr.term {
+            CTermData::Ptr(ty, alloc, offset) => {
+                if !matches!(ty, Ty::Bool | Ty::Int(..)) {
+                    self.err(format!("Cannot store a {ty} to memory"));
+                }
+                let allocs = self.ptr_allocs(&alloc);
+                let in_bounds = self.ptr_in_bounds(&alloc, &offset, &allocs);
+                self.assert_in_bounds(in_bounds);
+                let val = cast(Some(ty), val);
+                let v = val.term.simple_term();
+                let path = self.circ.borrow().condition();
+                for id in allocs {
+                    let here = term![AND; path.clone(), term![EQ; alloc.clone(), bv_lit(id, 32)]];
+                    self.circ
+                        .borrow_mut()
+                        .cond_store(id, offset.clone(), v.clone(), here);
+                }
+                val
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Allocate `size` bytes of `ty`s, returning a pointer to them. The size must be constant.
+    fn malloc(&mut self, ty: &Ty, size: CTerm) -> CTerm {
+        if !matches!(ty, Ty::Bool | Ty::Int(..)) {
+            self.err(format!("Cannot allocate {ty}s"));
+        }
+        let size_term = fold(&size.term.simple_term(), &[]);
+        if !matches!(size_term.op(), Op::Const(_)) {
+            self.err(format!("malloc of a non-constant size: {size_term}"));
+        }
+        let n = (self.fold_(&size) as usize).div_ceil(ty.size_of());
+        let id = self.circ_zero_allocate(n, 32, ty.num_bits());
+        ptr_to(ty.clone(), id, bv_lit(0, 32))
+    }
+
+    /// Generate a pointer to the lvalue `expr`.
+    fn gen_address(&mut self, expr: &Expression) -> CTerm {
+        match expr {
+            Expression::UnaryOperator(node)
+                if matches!(node.node.operator.node, UnaryOperator::Indirection) =>
+            {
+                let ptr = self.gen_expr(&node.node.operand.node);
+                self.as_ptr(ptr)
+            }
+            Expression::BinaryOperator(node)
+                if matches!(node.node.operator.node, BinaryOperator::Index) =>
+            {
+                let index = self.gen_index(expr);
+                let offset = self.index_offset(&index);
+                let base = self.as_ptr(index.base);
+                self.unwrap(add(base, cterm(CTermData::Int(true, 32, offset))))
+            }
+            Expression::Identifier(_) => {
+                let v = self.gen_expr(expr);
+                if matches!(v.term, CTermData::Array(..) | CTermData::StackPtr(..)) {
+                    self.as_ptr(v)
+                } else {
+                    self.err(format!(
+                        "Cannot take the address of {v}, which is not in memory"
+                    ))
+                }
+            }
+            _ => unimplemented!("Cannot take the address of {:#?}", expr),
+        }
+    }
+
     /// Computes base[val / loc]    
     fn rebuild_lval(&mut self, base: CTerm, loc: CLoc, val: CTerm) -> Result<CTerm, String> {
         match loc {
@@ -480,6 +699,7 @@ impl CGen {
                 let new_inner = self.rebuild_lval(old_inner, *inner_loc, val)?;
                 self.field_store(&base, &field, &new_inner)
             }
+            CLoc::Deref(_) => Err(format!("Cannot update memory inside {base}")),
         }
     }
 
@@ -488,7 +708,7 @@ impl CGen {
     #[allow(clippy::only_used_in_recursion)]
     fn base_loc(&self, loc: CLoc) -> CLoc {
         match loc {
-            CLoc::Var(_) => loc,
+            CLoc::Var(_) | CLoc::Deref(_) => loc,
             CLoc::Member(l, _) => self.base_loc(*l),
             CLoc::Idx(l, _) => self.base_loc(*l),
         }
@@ -536,6 +756,11 @@ impl CGen {
                     field_name.to_string(),
                 )
             }
+            Expression::UnaryOperator(node)
+                if matches!(node.node.operator.node, UnaryOperator::Indirection) =>
+            {
+                CLoc::Deref(self.gen_expr(&node.node.operand.node))
+            }
             _ => unimplemented!("Invalid left hand value"),
         }
     }
@@ -553,6 +778,7 @@ impl CGen {
                 // unsigned type casting
                 let new_val = match (&org_type, &new_type) {
                     (Ty::Int(sa, _), Ty::Int(sb, _)) if sa != sb => cast(Some(org_type), val),
+                    (Ty::Ptr(..), _) => cast(Some(org_type), val),
                     (_, _) => val,
                 };
                 Ok(self
@@ -580,9 +806,11 @@ impl CGen {
                             .unwrap_term();
                         self.array_select(&base, &idx).unwrap()
                     }
+                    CLoc::Deref(ptr) => self.deref(ptr),
                 };
                 self.array_store(&old_inner, &idx, &val)
             }
+            CLoc::Deref(ptr) => Ok(self.store_through(ptr, val)),
             CLoc::Member(l, field) => {
                 let inner_loc = l.loc().clone();
                 let base = self
@@ -733,7 +961,12 @@ impl CGen {
                 match bin_op.operator.node {
                     BinaryOperator::Assign => {
                         let loc = self.gen_lval(&bin_op.lhs.node);
-                        let val = self.gen_expr(&bin_op.rhs.node);
+                        let val = if is_call_to(&bin_op.rhs.node, "malloc") {
+                            let ty = self.gen_expr(&bin_op.lhs.node).term.type_();
+                            self.gen_expr_as(&ty, &bin_op.rhs.node)
+                        } else {
+                            self.gen_expr(&bin_op.rhs.node)
+                        };
                         self.gen_assign(loc, val)
                     }
                     BinaryOperator::AssignPlus | BinaryOperator::AssignDivide => {
@@ -817,8 +1050,16 @@ impl CGen {
                             }
                             _ => unimplemented!("Unimplemented Sizeof: {:#?}", u_op.operand.node),
                         };
-                        let _size = ty.num_bits();
-                        Ok(cterm(CTermData::Int(true, 32, bv_lit(1, 32))))
+                        Ok(cterm(CTermData::Int(true, 32, bv_lit(ty.size_of(), 32))))
+                    }
+                    UnaryOperator::Indirection => {
+                        let ptr = self.gen_expr(&u_op.operand.node);
+                        Ok(self.deref(ptr))
+                    }
+                    UnaryOperator::Address => Ok(self.gen_address(&u_op.operand.node)),
+                    UnaryOperator::Negate => {
+                        let operand = self.gen_expr(&u_op.operand.node);
+                        Ok(cterm(CTermData::Bool(term![NOT; cast_to_bool(operand)])))
                     }
                     _ => unimplemented!("UnaryOperator {:#?} hasn't been implemented", u_op),
                 }
@@ -828,8 +1069,11 @@ impl CGen {
                     type_name,
                     expression,
                 } = &node.node;
-                let to_ty = self.s_type_(&type_name.node.specifiers);
-                let expr = self.gen_expr(&expression.node);
+                let to_ty = self.type_name_(&type_name.node);
+                let expr = match &to_ty {
+                    Some(ty) => self.gen_expr_as(ty, &expression.node),
+                    None => self.gen_expr(&expression.node),
+                };
                 Ok(cast(to_ty, expr))
             }
             Expression::Call(node) => {
@@ -843,7 +1087,7 @@ impl CGen {
                     .map(|e| self.gen_expr(&e.node))
                     .collect::<Vec<_>>();
 
-                let maybe_return = self.maybe_handle_builtins(&fname, &args);
+                let maybe_return = self.maybe_handle_builtins(&fname, &args, &node.span);
 
                 if let Some(r) = maybe_return {
                     Ok(r)
@@ -934,12 +1178,9 @@ impl CGen {
                 self.field_select(&base, field)
             }
             Expression::SizeOf(s) => {
-                let ty = self.s_type_(&s.node.specifiers);
+                let ty = self.type_name_(&s.node);
                 match ty {
-                    Some(t) => {
-                        let _size = t.num_bits();
-                        Ok(cterm(CTermData::Int(true, 32, bv_lit(1, 32))))
-                    }
+                    Some(t) => Ok(cterm(CTermData::Int(true, 32, bv_lit(t.size_of(), 32)))),
                     None => {
                         panic!("Cannot determine size of type: {:#?}", s);
                     }
@@ -950,9 +1191,21 @@ impl CGen {
         self.unwrap(res)
     }
 
+    /// Generate `expr`, which is used as a `ty`. This tells `malloc` what it allocates.
+    fn gen_expr_as(&mut self, ty: &Ty, expr: &Expression) -> CTerm {
+        if let (Ty::Ptr(_, elem_ty), Expression::Call(call)) = (ty, expr) {
+            if is_call_to(expr, "malloc") {
+                assert!(call.node.arguments.len() == 1, "malloc takes one argument");
+                let size = self.gen_expr(&call.node.arguments[0].node);
+                return self.malloc(elem_ty, size);
+            }
+        }
+        self.gen_expr(expr)
+    }
+
     fn gen_init(&mut self, ty: &Ty, init: &Initializer) -> CTerm {
         match init {
-            Initializer::Expression(e) => self.gen_expr(&e.node),
+            Initializer::Expression(e) => self.gen_expr_as(ty, &e.node),
             Initializer::List(ref l) => match ty.clone() {
                 Ty::Array(n, _, _) => {
                     let mut values: Vec<CTerm> = Vec::new();
@@ -990,10 +1243,14 @@ impl CGen {
             };
             let decl_infos = self.get_decl_info(&new_decl);
             for info in decl_infos.iter() {
-                if !self.typedefs.contains_key(&info.name) {
-                    self.typedefs.insert(info.name.clone(), info.ty.clone());
-                } else {
-                    panic!("Typedef already defined for: {}", info.name);
+                // headers included by several units repeat their typedefs
+                match self.typedefs.get(&info.name) {
+                    Some(ty) if *ty != info.ty => {
+                        panic!("Typedef already defined for: {}", info.name)
+                    }
+                    _ => {
+                        self.typedefs.insert(info.name.clone(), info.ty.clone());
+                    }
                 }
             }
             Vec::new()
@@ -1006,6 +1263,9 @@ impl CGen {
                 } else {
                     info.ty.default(self.circ.borrow().cir_ctx())
                 };
+                if let CTermData::Int(_, _, t) | CTermData::Bool(t) = &expr.term {
+                    self.locate(t, &d.span, Some(&info.name));
+                }
                 let res = self.circ_declare_init(
                     info.name.clone(),
                     info.ty.clone(),
@@ -1018,76 +1278,172 @@ impl CGen {
         }
     }
 
-    //TODO: This function is not quite right because the loop body could modify the iteration variable.
-    fn get_const_iters(&mut self, for_stmt: &ForStatement) -> ConstIteration {
-        let init: Option<ConstIteration> = match &for_stmt.initializer.node {
-            ForInitializer::Declaration(d) => {
-                // TODO: need to identify which is the looping variable
-                let exprs = self.gen_decl(&d.node);
-                assert!(exprs.len() == 1);
-                let val = self.fold_(&exprs[0]);
-                Some(ConstIteration { val })
-            }
-            ForInitializer::Expression(e) => {
-                if let Expression::BinaryOperator(_) = e.node {
-                    let expr = self.gen_expr(&e.node);
-                    let val = self.fold_(&expr);
-                    Some(ConstIteration { val })
-                } else {
-                    None
-                }
-            }
-            _ => None,
+    /// Name a fresh breakable block of some `kind`.
+    fn new_breakable(&mut self, kind: &str) -> String {
+        self.breakable_ctr += 1;
+        format!("{kind}{}", self.breakable_ctr)
+    }
+
+    /// The condition under which a loop with condition `cond` runs another iteration, or `None`
+    /// if it certainly stops here.
+    fn loop_cond(&mut self, cond: Option<&Expression>) -> Option<Term> {
+        if fold(&self.circ.borrow().condition(), &[]).as_bool_opt() == Some(false) {
+            return None;
+        }
+        let c = match cond {
+            Some(cond) => cast_to_bool(self.gen_expr(cond)),
+            None => bool_lit(true),
         };
+        match fold(&c, &[]).as_bool_opt() {
+            Some(false) => None,
+            Some(true) => Some(bool_lit(true)),
+            None => Some(c),
+        }
+    }
 
-        let cond: Option<ConstIteration> = match &for_stmt.condition.as_ref().unwrap().node {
-            Expression::BinaryOperator(bin_op) => {
-                let expr = self.gen_expr(&bin_op.node.rhs.node);
-                let val = self.fold_(&expr);
-                match bin_op.node.operator.node {
-                    BinaryOperator::Less => Some(ConstIteration { val }),
-                    BinaryOperator::LessOrEqual => Some(ConstIteration { val: val + 1 }),
-                    BinaryOperator::Greater => Some(ConstIteration { val }),
-                    BinaryOperator::GreaterOrEqual => Some(ConstIteration { val: val - 1 }),
-                    _ => None,
+    /// Unroll a loop that runs `body` then `step` while `cond` holds. The condition is checked
+    /// before each iteration if `check_first` (`while`, `for`) and after it otherwise
+    /// (`do`-`while`).
+    ///
+    /// While the condition folds to a constant (and nothing data-dependent has left the loop),
+    /// the loop is unrolled exactly, up to [MAX_CONST_LOOP_ITERS] iterations. Once it depends on
+    /// the data, at most `loop_bound` more iterations are unrolled, and we assert that the loop
+    /// is done by then.
+    fn gen_loop(
+        &mut self,
+        cond: Option<&Expression>,
+        body: &Statement,
+        step: Option<&Expression>,
+        check_first: bool,
+        span: &Span,
+    ) {
+        let brk = self.new_breakable("loop");
+        let cont = self.new_breakable("continue");
+        self.breakables.push((brk.clone(), Some(cont.clone())));
+        self.circ_enter_breakable(brk.clone());
+        let entry_path = fold(&self.circ.borrow().condition(), &[]);
+        let mut const_iters = 0;
+        let mut data_iters = 0;
+        for i in 0.. {
+            let c = if check_first || i > 0 {
+                match self.loop_cond(cond) {
+                    None => break,
+                    c => c,
+                }
+            } else {
+                None
+            };
+            let cond_true = c.as_ref().is_none_or(|c| c.as_bool_opt() == Some(true));
+            if cond_true && fold(&self.circ.borrow().condition(), &[]) == entry_path {
+                const_iters += 1;
+                if const_iters > MAX_CONST_LOOP_ITERS {
+                    self.err(format!(
+                        "Loop at {span:?} is still running after {MAX_CONST_LOOP_ITERS} iterations"
+                    ))
+                }
+            } else {
+                if data_iters == self.loop_bound {
+                    if !matches!(self.mode, Mode::Proof) {
+                        warn!(
+                            "Loop at {span:?} is cut off after {} data-dependent iterations",
+                            self.loop_bound
+                        );
+                    }
+                    // if the condition is still true, only leaving the loop (e.g., by a `break`)
+                    // keeps within the bound, so this path must not be taken
+                    let exits = match c {
+                        Some(c) if !cond_true => term![NOT; c],
+                        _ => bool_lit(false),
+                    };
+                    let path = self.circ.borrow().condition();
+                    let within_bound = term![IMPLIES; path, exits];
+                    self.locate(&within_bound, span, None);
+                    self.assertions.push(within_bound);
+                    break;
+                }
+                data_iters += 1;
+                if !cond_true {
+                    self.circ_enter_condition(term![NOT; c.unwrap()]);
+                    self.circ_break_(&brk);
+                    self.circ_exit_condition();
                 }
             }
-            _ => None,
+            self.circ_enter_breakable(cont.clone());
+            self.circ_enter_scope();
+            self.gen_stmt(body);
+            self.circ_exit_scope();
+            self.circ_exit_breakable();
+            if let Some(step) = step {
+                self.gen_expr(step);
+            }
+        }
+        self.circ_exit_breakable();
+        self.breakables.pop();
+    }
+
+    /// Generate a `switch`. Each statement in its body runs if a case label at or before it
+    /// matched (so cases fall through), until a `break`.
+    fn gen_switch(&mut self, switch: &SwitchStatement) {
+        let val = self.gen_expr(&switch.expression.node);
+        let items: Vec<BlockItem> = match &switch.statement.node {
+            Statement::Compound(items) => items.iter().map(|i| i.node.clone()).collect(),
+            s => vec![BlockItem::Statement(Node::new(
+                s.clone(),
+                switch.statement.span,
+            ))],
         };
 
-        let step: Option<ConstIteration> = match &for_stmt.step.as_ref().unwrap().node {
-            Expression::UnaryOperator(u_op) => match u_op.node.operator.node {
-                UnaryOperator::PostIncrement | UnaryOperator::PreIncrement => {
-                    Some(ConstIteration { val: 1 })
+        // whether each case label (in order) matches
+        let mut matches = Vec::new();
+        for item in &items {
+            if let BlockItem::Statement(s) = item {
+                let mut s = &s.node;
+                while let Statement::Labeled(l) = s {
+                    if let Label::Case(c) = &l.node.label.node {
+                        let c = self.gen_expr(&c.node);
+                        let m = eq(val.clone(), c);
+                        matches.push(cast_to_bool(self.unwrap(m)));
+                    }
+                    s = &l.node.statement.node;
                 }
-                UnaryOperator::PostDecrement | UnaryOperator::PreDecrement => {
-                    Some(ConstIteration { val: -1 })
+            }
+        }
+        let default =
+            term![NOT; term(OR, matches.iter().cloned().chain([bool_lit(false)]).collect())];
+
+        let brk = self.new_breakable("switch");
+        self.breakables.push((brk.clone(), None));
+        self.circ_enter_breakable(brk);
+        self.circ_enter_scope();
+        let mut matches = matches.into_iter();
+        let mut active = bool_lit(false);
+        for item in &items {
+            match item {
+                BlockItem::Declaration(decl) => {
+                    self.gen_decl(&decl.node);
                 }
-                _ => None,
-            },
-            Expression::BinaryOperator(bin_op) => match bin_op.node.operator.node {
-                BinaryOperator::AssignPlus => {
-                    let expr = self.gen_expr(&bin_op.node.rhs.node);
-                    let val = self.fold_(&expr);
-                    Some(ConstIteration { val })
+                BlockItem::Statement(stmt) => {
+                    let mut s = &stmt.node;
+                    while let Statement::Labeled(l) = s {
+                        active = match &l.node.label.node {
+                            Label::Case(_) => term![OR; active, matches.next().unwrap()],
+                            Label::Default => term![OR; active, default.clone()],
+                            label => unimplemented!("Label {:#?} hasn't been implemented", label),
+                        };
+                        s = &l.node.statement.node;
+                    }
+                    self.circ_enter_condition(active.clone());
+                    self.gen_stmt(s);
+                    self.circ_exit_condition();
                 }
-                _ => None,
-            },
-            _ => None,
-        };
-
-        // TODO: error checking here
-        let init_ = init.unwrap();
-        let cond_ = cond.unwrap();
-        let incr_ = step.unwrap();
-
-        let start: f32 = init_.val as f32;
-        let end: f32 = cond_.val as f32;
-        let incr: f32 = incr_.val as f32;
-
-        ConstIteration {
-            val: ((end - start) / incr).ceil() as i32,
+                BlockItem::StaticAssert(_sa) => {
+                    unimplemented!("Static Assert not supported yet")
+                }
+            }
         }
+        self.circ_exit_scope();
+        self.circ_exit_breakable();
+        self.breakables.pop();
     }
 
     fn gen_stmt(&mut self, stmt: &Statement) {
@@ -1109,7 +1465,7 @@ impl CGen {
             }
             Statement::If(node) => {
                 let cond = self.gen_expr(&node.node.condition.node);
-                let cond_term = cond.term.term(self.circ.borrow().cir_ctx());
+                let cond_term = cast_to_bool(cond);
                 self.circ_enter_condition(cond_term.clone());
                 self.gen_stmt(&node.node.then_statement.node);
                 self.circ_exit_condition();
@@ -1141,21 +1497,78 @@ impl CGen {
                 None => {}
             },
             Statement::For(for_stmt) => {
-                // TODO: Add enter_breakable
+                let ForStatement {
+                    initializer,
+                    condition,
+                    step,
+                    statement,
+                } = &for_stmt.node;
                 self.circ_enter_scope();
-                let const_iters = self.get_const_iters(&for_stmt.node);
-                // TODO: Loop 5 times if const not specified
-                let bound = const_iters.val;
-
-                for _ in 0..bound {
-                    self.circ_enter_scope();
-                    self.gen_stmt(&for_stmt.node.statement.node);
-                    self.circ_exit_scope();
-                    self.gen_expr(&for_stmt.node.step.as_ref().unwrap().node);
+                match &initializer.node {
+                    ForInitializer::Empty => {}
+                    ForInitializer::Expression(e) => {
+                        self.gen_expr(&e.node);
+                    }
+                    ForInitializer::Declaration(d) => {
+                        self.gen_decl(&d.node);
+                    }
+                    ForInitializer::StaticAssert(_sa) => {
+                        unimplemented!("Static Assert not supported yet")
+                    }
                 }
+                self.gen_loop(
+                    condition.as_ref().map(|c| &c.node),
+                    &statement.node,
+                    step.as_ref().map(|s| &s.node),
+                    true,
+                    &for_stmt.span,
+                );
                 self.circ_exit_scope();
             }
-            // TODO: add while loop
+            Statement::While(while_stmt) => {
+                let WhileStatement {
+                    expression,
+                    statement,
+                } = &while_stmt.node;
+                self.gen_loop(
+                    Some(&expression.node),
+                    &statement.node,
+                    None,
+                    true,
+                    &while_stmt.span,
+                );
+            }
+            Statement::DoWhile(do_stmt) => {
+                let DoWhileStatement {
+                    statement,
+                    expression,
+                } = &do_stmt.node;
+                self.gen_loop(
+                    Some(&expression.node),
+                    &statement.node,
+                    None,
+                    false,
+                    &do_stmt.span,
+                );
+            }
+            Statement::Switch(switch) => self.gen_switch(&switch.node),
+            Statement::Break => {
+                let (brk, _) = self
+                    .breakables
+                    .last()
+                    .expect("break outside of a loop or switch")
+                    .clone();
+                self.circ_break_(&brk);
+            }
+            Statement::Continue => {
+                let cont = self
+                    .breakables
+                    .iter()
+                    .rev()
+                    .find_map(|(_, c)| c.clone())
+                    .expect("continue outside of a loop");
+                self.circ_break_(&cont);
+            }
             _ => unimplemented!("Statement {:#?} hasn't been implemented", stmt),
         }
     }
@@ -1171,6 +1584,7 @@ impl CGen {
 
         // Add return ty
         self.ret_ty_put(f.ret_ty.clone());
+        self.cur_unit = f.unit;
 
         // setup stack frame for entry function
         self.circ_enter_fn(f.name.to_owned(), f.ret_ty.clone());
@@ -1226,17 +1640,29 @@ impl CGen {
     }
 
     /// Returns whether this was a builtin, and thus has been handled.
-    fn maybe_handle_builtins(&mut self, name: &String, args: &Vec<CTerm>) -> Option<CTerm> {
+    fn maybe_handle_builtins(
+        &mut self,
+        name: &String,
+        args: &Vec<CTerm>,
+        span: &Span,
+    ) -> Option<CTerm> {
         if self.sv_functions && (name == "__VERIFIER_assert" || name == "__VERIFIER_assume") {
             assert!(args.len() == 1);
             let bool_arg = cast_to_bool(args[0].clone());
             assert!(matches!(check(&bool_arg), Sort::Bool));
             if name == "__VERIFIER_assert" {
+                self.locate(&bool_arg, span, None);
                 self.assertions.push(bool_arg);
             } else {
                 self.assumptions.push(bool_arg);
             }
             Some(Ty::Bool.default(self.circ.borrow().cir_ctx()))
+        } else if name == "free" {
+            // Allocations are never reused, so freeing one does nothing.
+            assert!(args.len() == 1);
+            Some(Ty::Bool.default(self.circ.borrow().cir_ctx()))
+        } else if name == "malloc" {
+            self.err("malloc must be cast, assigned, or initialized to a pointer")
         } else {
             None
         }
@@ -1253,6 +1679,7 @@ impl CGen {
             .clone();
 
         // setup stack frame for function call
+        self.cur_unit = f.unit;
         self.circ_enter_fn(name.to_owned(), f.ret_ty);
 
         // Keep track of the names of arguments that are references
@@ -1301,20 +1728,58 @@ impl CGen {
         };
     }
 
+    /// Record that `t` came from `span` (and is bound to `name`, if any) in the source map.
+    fn locate(&self, t: &Term, span: &Span, name: Option<&str>) {
+        if matches!(t.op(), Op::Const(_)) {
+            return;
+        }
+        let lines = &self.units[self.cur_unit].lines;
+        let (file, line, column) = lines.locate(span.start);
+        let (_, end_line, end_column) = lines.locate(span.end);
+        let circ = self.circ.borrow();
+        let loc = SourceLoc {
+            file: file.to_owned(),
+            line,
+            column,
+            end_line,
+            end_column,
+            function: circ.fn_name().unwrap_or_default().to_owned(),
+            name: name.map(str::to_owned),
+        };
+        circ.locate(t.clone(), loc);
+    }
+
+    /// The file of translation unit `unit`
+    fn unit_file(&self, unit: usize) -> &str {
+        self.units[unit].lines.files().next().unwrap_or_default()
+    }
+
     fn visit_files(&mut self) {
-        let TranslationUnit(nodes) = self.tu.clone();
-        for n in nodes.iter() {
-            match &n.node {
-                ExternalDeclaration::Declaration(decl) => {
-                    self.gen_decl(&decl.node);
-                }
-                ExternalDeclaration::FunctionDefinition(ref fn_def) => {
-                    let fn_info = self.get_fn_info(&fn_def.node);
-                    let fname = fn_info.name.clone();
-                    self.functions.insert(fname, fn_info);
-                }
-                _ => unimplemented!("Haven't implemented node: {:?}", n.node),
-            };
+        for unit in 0..self.units.len() {
+            self.cur_unit = unit;
+            let TranslationUnit(nodes) = self.units[unit].tu.clone();
+            for n in nodes.iter() {
+                match &n.node {
+                    // a prototype; the function is defined in some unit
+                    ExternalDeclaration::Declaration(decl) if declares_fn(&decl.node) => {}
+                    ExternalDeclaration::Declaration(decl) => {
+                        self.gen_decl(&decl.node);
+                    }
+                    ExternalDeclaration::FunctionDefinition(ref fn_def) => {
+                        let fn_info = self.get_fn_info(&fn_def.node);
+                        let fname = fn_info.name.clone();
+                        if let Some(prev) = self.functions.get(&fname) {
+                            self.err(format!(
+                                "Function '{fname}' is defined in both {} and {}",
+                                self.unit_file(prev.unit),
+                                self.unit_file(unit)
+                            ))
+                        }
+                        self.functions.insert(fname, fn_info);
+                    }
+                    _ => unimplemented!("Haven't implemented node: {:?}", n.node),
+                };
+            }
         }
     }
 
@@ -1408,6 +1873,18 @@ impl CGen {
         self.circ.borrow_mut().exit_fn_call(ret_names)
     }
 
+    fn circ_enter_breakable(&self, name: String) {
+        self.circ.borrow_mut().enter_breakable(name)
+    }
+
+    fn circ_exit_breakable(&self) {
+        self.circ.borrow_mut().exit_breakable()
+    }
+
+    fn circ_break_(&self, name: &str) {
+        self.circ.borrow_mut().break_(name).unwrap()
+    }
+
     fn circ_enter_scope(&self) {
         self.circ.borrow_mut().enter_scope()
     }
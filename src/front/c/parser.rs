@@ -1,8 +1,10 @@
 //! Parsing and recursively loading C.
 
+use crate::circify::includer::Loader;
+use lang_c::ast::TranslationUnit;
 use lang_c::driver::Error;
 use lang_c::driver::{parse, Config, Parse};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub struct CParser {
     config: Config,
@@ -15,7 +17,110 @@ impl CParser {
         }
     }
 
+    /// Have the preprocessor search `dir` for headers.
+    pub fn include_dir(&mut self, dir: &Path) {
+        self.config.cpp_options.push(format!("-I{}", dir.display()));
+    }
+
     pub fn parse_file(&self, path: &Path) -> Result<Parse, Error> {
         parse(&self.config, path)
     }
 }
+
+/// A preprocessed and parsed translation unit
+pub struct Unit {
+    pub tu: TranslationUnit,
+    pub lines: LineMap,
+}
+
+impl Loader for CParser {
+    type AST = Unit;
+    type ParseError = Error;
+
+    fn parse<P: AsRef<Path>>(&self, p: &P) -> Result<Self::AST, Self::ParseError> {
+        let parse = self.parse_file(p.as_ref())?;
+        Ok(Unit {
+            lines: LineMap::new(&parse.source, p.as_ref()),
+            tu: parse.unit,
+        })
+    }
+
+    /// A unit that includes a header `foo.h` also loads the `foo.c` beside it, if any: the
+    /// header declares what the `.c` file defines.
+    ///
+    /// The paths are canonical, so a file reached through several spellings is loaded once.
+    fn includes<P: AsRef<Path>>(&self, ast: &Self::AST, p: &P) -> Vec<PathBuf> {
+        let this = p.as_ref().canonicalize().ok();
+        ast.lines
+            .files()
+            .map(Path::new)
+            .filter(|f| f.extension().is_some_and(|e| e == "h"))
+            .map(|f| f.with_extension("c"))
+            .filter(|c| c.is_file())
+            .filter_map(|c| c.canonicalize().ok())
+            .filter(|c| Some(c) != this.as_ref())
+            .collect()
+    }
+}
+
+/// Maps byte offsets in preprocessed source back to the original files, following the
+/// preprocessor's line markers (`# LINE "FILE" ...`).
+pub struct LineMap {
+    files: Vec<String>,
+    /// For each line of the preprocessed source: its offset, its file, and its line in that file.
+    lines: Vec<(usize, usize, usize)>,
+}
+
+impl LineMap {
+    /// Map the preprocessed `source` of `file`.
+    pub fn new(source: &str, file: &Path) -> Self {
+        let mut files = vec![file.to_string_lossy().into_owned()];
+        let mut lines = Vec::new();
+        let (mut cur_file, mut cur_line) = (0, 1);
+        let mut offset = 0;
+        for l in source.split_inclusive('\n') {
+            match Self::marker(l) {
+                Some((line, f)) => {
+                    cur_file = match files.iter().position(|g| g == f) {
+                        Some(i) => i,
+                        None => {
+                            files.push(f.to_owned());
+                            files.len() - 1
+                        }
+                    };
+                    cur_line = line;
+                }
+                None => {
+                    lines.push((offset, cur_file, cur_line));
+                    cur_line += 1;
+                }
+            }
+            offset += l.len();
+        }
+        Self { files, lines }
+    }
+
+    /// Parse a line marker: `# LINE "FILE" ...` or `#line LINE "FILE"`.
+    fn marker(l: &str) -> Option<(usize, &str)> {
+        let rest = l.trim_start().strip_prefix('#')?.trim_start();
+        let rest = rest.strip_prefix("line").unwrap_or(rest).trim_start();
+        let (line, rest) = rest.split_once(char::is_whitespace)?;
+        let line = line.parse().ok()?;
+        let file = rest.trim_start().strip_prefix('"')?.split('"').next()?;
+        Some((line, file))
+    }
+
+    /// The files that the source came from
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().map(String::as_str)
+    }
+
+    /// The file, line, and column (counting from 1) of `offset`
+    pub fn locate(&self, offset: usize) -> (&str, usize, usize) {
+        let i = self.lines.partition_point(|(o, _, _)| *o <= offset);
+        match i.checked_sub(1).map(|i| self.lines[i]) {
+            Some((start, file, line)) => (&self.files[file], line, offset - start + 1),
+            None => (&self.files[0], 1, 1),
+        }
+    }
+}
@@ -3,6 +3,7 @@ use crate::circify::mem::AllocId;
 use crate::circify::{CirCtx, Embeddable, Typed};
 use crate::front::c::types::*;
 use crate::front::field_list::FieldList;
+use crate::ir::opt::cfold::fold;
 use crate::ir::term::*;
 use rug::Integer;
 use std::fmt::{self, Display, Formatter};
@@ -15,8 +16,14 @@ pub enum CTermData {
     Array(Ty, Option<AllocId>),
     StackPtr(Ty, Term, Option<AllocId>),
     Struct(Ty, FieldList<CTerm>),
+    /// A pointer to a value of this type: an allocation id and an offset into that allocation
+    /// (both 32-bit). The allocation id of a null pointer is [NULL_ALLOC].
+    Ptr(Ty, Term, Term),
 }
 
+/// The allocation id of null pointers. No allocation has this id.
+pub const NULL_ALLOC: AllocId = u32::MAX as AllocId;
+
 impl CTermData {
     pub fn type_(&self) -> Ty {
         match self {
@@ -25,6 +32,7 @@ impl CTermData {
             Self::Array(t, _) => t.clone(),
             Self::StackPtr(t, _o, _) => t.clone(),
             Self::Struct(ty, _) => ty.clone(),
+            Self::Ptr(t, _, _) => Ty::Ptr(32, Box::new(t.clone())),
         }
     }
     /// Get all IR terms inside this value, as a list.
@@ -62,6 +70,10 @@ impl CTermData {
                         output.append(&mut ts);
                     }
                 }
+                CTermData::Ptr(_, alloc, offset) => {
+                    output.push(alloc.clone());
+                    output.push(offset.clone());
+                }
             }
         }
         terms_tail(self, &mut output, ctx);
@@ -91,6 +103,7 @@ impl Display for CTermData {
             CTermData::Array(t, _) => write!(f, "Array({t:#?})"),
             CTermData::StackPtr(t, s, _) => write!(f, "Ptr{s:#?}({t:#?})"),
             CTermData::Struct(t, _) => write!(f, "Struct({t})"),
+            CTermData::Ptr(t, a, o) => write!(f, "Ptr({t}, {a}, {o})"),
         }
     }
 }
@@ -125,6 +138,26 @@ pub fn cterm(data: CTermData) -> CTerm {
     }
 }
 
+/// A pointer to a `ty` at `offset` in the allocation `id`
+pub fn ptr_to(ty: Ty, id: AllocId, offset: Term) -> CTerm {
+    cterm(CTermData::Ptr(ty, bv_lit(id, 32), offset))
+}
+
+/// A null pointer to a `ty`
+pub fn null_ptr(ty: Ty) -> CTerm {
+    ptr_to(ty, NULL_ALLOC, bv_lit(0, 32))
+}
+
+/// Resize the integer `t` (signed if `s`, `w` bits wide) to a 32-bit pointer offset.
+fn to_offset(s: bool, w: usize, t: Term) -> Term {
+    match w.cmp(&32) {
+        std::cmp::Ordering::Equal => t,
+        std::cmp::Ordering::Greater => term![Op::BvExtract(31, 0); t],
+        std::cmp::Ordering::Less if s => term![Op::BvSext(32 - w); t],
+        std::cmp::Ordering::Less => term![Op::BvUext(32 - w); t],
+    }
+}
+
 // pub fn int_resize(from_s: bool, from_w: usize, to_s: bool, to_w: usize, t: Term) -> Term {
 //     if from_w < to_w {
 //         if from_s {
@@ -169,6 +202,9 @@ pub fn cast(to_ty: Option<Ty>, t: CTerm) -> CTerm {
                 term: CTermData::Bool(term![Op::Not; term![Op::Eq; bv_lit(0, w), term.clone()]]),
                 udef: t.udef.clone(),
             },
+            // Only the null pointer constant converts to a pointer.
+            Some(Ty::Ptr(_, p_ty)) if fold(term, &[]) == bv_lit(0, w) => null_ptr(*p_ty),
+            Some(Ty::Ptr(_, _)) => panic!("Cannot cast the non-null integer {} to a pointer", t),
             // P 6.3.1.3.3 of the C11 standard says this is "implementation
             // defined", not "undefined"
             // u   = if toS && toW < fromW
@@ -187,11 +223,8 @@ pub fn cast(to_ty: Option<Ty>, t: CTerm) -> CTerm {
         },
         CTermData::Array(ref ty, id) => match to_ty {
             Some(Ty::Ptr(_, _)) => {
-                let offset = bv_lit(0, 32);
-                CTerm {
-                    term: CTermData::StackPtr(ty.clone(), offset, id),
-                    udef: t.udef,
-                }
+                let id = id.unwrap_or_else(|| panic!("Unknown AllocID: {:#?}", t));
+                ptr_to(ty.clone().inner_ty(), id, bv_lit(0, 32))
             }
             Some(Ty::Array(_, _, _)) => t.clone(),
             _ => panic!("Bad cast from {:#?} to {:?}", ty, to_ty),
@@ -200,14 +233,28 @@ pub fn cast(to_ty: Option<Ty>, t: CTerm) -> CTerm {
             Some(Ty::Struct(_, _)) => t.clone(),
             _ => panic!("Bad cast from {:#?} to {:?}", ty, to_ty),
         },
-        CTermData::StackPtr(ref ty, _, id) => match to_ty {
-            Some(Ty::Ptr(_, _)) => t.clone(),
+        CTermData::StackPtr(ref ty, ref offset, id) => match to_ty {
+            Some(Ty::Ptr(_, _)) => {
+                let id = id.unwrap_or_else(|| panic!("Unknown AllocID: {:#?}", t));
+                ptr_to(ty.clone().inner_ty(), id, offset.clone())
+            }
             Some(Ty::Array(_, _, a_ty)) => CTerm {
                 term: CTermData::Array(*a_ty, id),
                 udef: t.udef,
             },
             _ => panic!("Bad cast from {:#?} to {:?}", ty, to_ty),
         },
+        CTermData::Ptr(_, ref alloc, ref offset) => match to_ty {
+            Some(Ty::Ptr(_, p_ty)) => CTerm {
+                term: CTermData::Ptr(*p_ty, alloc.clone(), offset.clone()),
+                udef: t.udef,
+            },
+            Some(Ty::Bool) => CTerm {
+                term: CTermData::Bool(term![NOT; term![EQ; alloc.clone(), bv_lit(NULL_ALLOC, 32)]]),
+                udef: t.udef.clone(),
+            },
+            _ => panic!("Bad cast from {:#?} to {:?}", ty, to_ty),
+        },
     }
 }
 
@@ -324,6 +371,10 @@ fn wrap_bin_arith(
             term: CTermData::StackPtr(ty, fu(offset, y), aid),
             udef: bool_lit(false),
         }),
+        (CTermData::Ptr(ty, alloc, offset), CTermData::Int(s, w, y), Some(fu), _) => Ok(CTerm {
+            term: CTermData::Ptr(ty, alloc, fu(offset, to_offset(s, w, y))),
+            udef: bool_lit(false),
+        }),
         (x, y, _, _) => Err(format!("Cannot perform op '{name}' on {x} and {y}")),
     }
 }
@@ -333,7 +384,11 @@ fn add_uint(a: Term, b: Term) -> Term {
 }
 
 pub fn add(a: CTerm, b: CTerm) -> Result<CTerm, String> {
-    wrap_bin_arith("+", Some(add_uint), None, a, b)
+    if let (CTermData::Int(..), CTermData::Ptr(..)) = (&a.term, &b.term) {
+        wrap_bin_arith("+", Some(add_uint), None, b, a)
+    } else {
+        wrap_bin_arith("+", Some(add_uint), None, a, b)
+    }
 }
 
 fn sub_uint(a: Term, b: Term) -> Term {
@@ -341,7 +396,14 @@ fn sub_uint(a: Term, b: Term) -> Term {
 }
 
 pub fn sub(a: CTerm, b: CTerm) -> Result<CTerm, String> {
-    wrap_bin_arith("-", Some(sub_uint), None, a, b)
+    match (&a.term, &b.term) {
+        // The distance between two pointers; undefined unless they share an allocation.
+        (CTermData::Ptr(_, a_alloc, a_offset), CTermData::Ptr(_, b_alloc, b_offset)) => Ok(CTerm {
+            term: CTermData::Int(true, 32, sub_uint(a_offset.clone(), b_offset.clone())),
+            udef: term![OR; a.udef.clone(), b.udef.clone(), neq_base(a_alloc.clone(), b_alloc.clone())],
+        }),
+        _ => wrap_bin_arith("-", Some(sub_uint), None, a, b),
+    }
 }
 
 fn mul_uint(a: Term, b: Term) -> Term {
@@ -453,8 +515,27 @@ fn eq_base(a: Term, b: Term) -> Term {
     term![Op::Eq; a, b]
 }
 
+/// If either of `a` and `b` is a pointer, whether they are the same pointer.
+fn ptr_eq(a: &CTerm, b: &CTerm) -> Option<Term> {
+    let (a, b) = match (&a.term, &b.term) {
+        (CTermData::Ptr(..), CTermData::Ptr(..)) => (a.clone(), b.clone()),
+        (CTermData::Ptr(..), _) => (a.clone(), cast(Some(a.type_()), b.clone())),
+        (_, CTermData::Ptr(..)) => (cast(Some(b.type_()), a.clone()), b.clone()),
+        _ => return None,
+    };
+    match (a.term, b.term) {
+        (CTermData::Ptr(_, a_alloc, a_offset), CTermData::Ptr(_, b_alloc, b_offset)) => {
+            Some(term![AND; eq_base(a_alloc, b_alloc), eq_base(a_offset, b_offset)])
+        }
+        _ => unreachable!(),
+    }
+}
+
 pub fn eq(a: CTerm, b: CTerm) -> Result<CTerm, String> {
-    wrap_bin_cmp("==", Some(eq_base), Some(eq_base), a, b)
+    match ptr_eq(&a, &b) {
+        Some(e) => Ok(cterm(CTermData::Bool(e))),
+        None => wrap_bin_cmp("==", Some(eq_base), Some(eq_base), a, b),
+    }
 }
 
 fn neq_base(a: Term, b: Term) -> Term {
@@ -462,7 +543,10 @@ fn neq_base(a: Term, b: Term) -> Term {
 }
 
 pub fn neq(a: CTerm, b: CTerm) -> Result<CTerm, String> {
-    wrap_bin_cmp("!=", Some(neq_base), Some(neq_base), a, b)
+    match ptr_eq(&a, &b) {
+        Some(e) => Ok(cterm(CTermData::Bool(term![NOT; e]))),
+        None => wrap_bin_cmp("!=", Some(neq_base), Some(neq_base), a, b),
+    }
 }
 
 fn ult_uint(a: Term, b: Term) -> Term {
@@ -679,6 +763,18 @@ impl Embeddable for Ct {
                     udef: bool_lit(false),
                 }
             }
+            (CTermData::Ptr(ta, alloc_a, offset_a), CTermData::Ptr(tb, alloc_b, offset_b))
+                if ta == tb =>
+            {
+                Self::T {
+                    term: CTermData::Ptr(
+                        ta,
+                        term![Op::Ite; cond.clone(), alloc_a, alloc_b],
+                        term![Op::Ite; cond, offset_a, offset_b],
+                    ),
+                    udef: bool_lit(false),
+                }
+            }
             (t, f) => panic!("Cannot ITE {} and {}", t, f),
         }
     }
@@ -711,10 +807,7 @@ impl Embeddable for Ct {
                     udef: bool_lit(false),
                 }
             }
-            Ty::Ptr(size, ty) => CTerm {
-                term: CTermData::StackPtr(*ty.clone(), Sort::BitVector(*size).default_term(), None),
-                udef: bool_lit(false),
-            },
+            Ty::Ptr(_, ty) => null_ptr(*ty.clone()),
         }
     }
 }
@@ -1,5 +1,6 @@
 //! C Types
 use crate::circify::CirCtx;
+use crate::front::c::term::null_ptr;
 use crate::front::c::term::CTerm;
 use crate::front::c::term::CTermData;
 use crate::front::field_list::FieldList;
@@ -117,14 +118,7 @@ impl Ty {
                     udef: bool_lit(false),
                 }
             }
-            Self::Ptr(s, ty) => {
-                let mut mem = ctx.mem.borrow_mut();
-                let id = mem.zero_allocate(*s, 32, ty.num_bits());
-                CTerm {
-                    term: CTermData::StackPtr(*ty.clone(), bv_lit(0, *s), Some(id)),
-                    udef: bool_lit(false),
-                }
-            }
+            Self::Ptr(_, ty) => null_ptr(*ty.clone()),
             Self::Struct(_name, fs) => {
                 let fields: Vec<(String, CTerm)> = fs
                     .fields()
@@ -185,6 +179,17 @@ impl Ty {
         }
     }
 
+    /// The size of this type in bytes, as given by `sizeof`
+    pub fn size_of(&self) -> usize {
+        match self {
+            Ty::Void | Ty::Bool => 1,
+            Ty::Int(_, w) => w.div_ceil(8),
+            Ty::Array(s, _, t) => s * t.size_of(),
+            Ty::Ptr(s, _) => s / 8,
+            Ty::Struct(_, fs) => fs.fields().map(|(_, ty)| ty.size_of()).sum(),
+        }
+    }
+
     pub fn num_bits(&self) -> usize {
         match self {
             Ty::Void => 0,
@@ -20,6 +20,15 @@ pub enum ErrorKind {
     #[error("Could not find entry rule '{0}'")]
     /// Could not find the entry rule
     MissingEntry(String),
+    #[error("Rule '{0}' negates '{1}', which depends on '{0}': the program is not stratified")]
+    /// A negated rule call is part of a recursive cycle
+    NotStratified(String, String),
+    #[error("Cannot negate rule '{0}': it has existential variables")]
+    /// Negation-as-failure is only sound for rules without witnesses
+    NegatedExistential(String),
+    #[error("Cannot negate rule '{0}': the recursion limit was reached beneath the negation")]
+    /// A negated call was cut off by the recursion limit, so its result is unknown
+    NegatedRecLimit(String),
     #[error("Circify error: {0}")]
     /// Could not find the entry rule
     Circify(crate::circify::CircError),
@@ -5,7 +5,7 @@ use std::io::Read;
 use std::path::PathBuf;
 use std::str::FromStr;
 
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
 use log::debug;
 use rug::Integer;
 
@@ -36,14 +36,21 @@ struct Gen<'ast> {
     rules: FxHashMap<&'ast str, &'ast ast::Rule_<'ast>>,
     stack_by_fn: FxHashMap<&'ast str, Vec<Option<Integer>>>,
     rec_limit: usize,
+    /// How many negated (or otherwise non-monotone) positions we are currently inside; see
+    /// [rule_calls]
+    negation_depth: usize,
     circ: Circify<term::Datalog>,
+    /// The file being compiled, for the source map
+    file: PathBuf,
 }
 
 impl<'ast> Gen<'ast> {
-    fn new(rec_limit: usize) -> Self {
+    fn new(rec_limit: usize, file: PathBuf) -> Self {
         Self {
             rules: FxHashMap::default(),
             rec_limit,
+            negation_depth: 0,
+            file,
             stack_by_fn: FxHashMap::default(),
             // TODO: values !?
             circ: Circify::new(term::Datalog::new()),
@@ -87,22 +94,55 @@ impl<'ast> Gen<'ast> {
         }
     }
 
+    /// Check that negated rule calls never sit on a recursive cycle, i.e., that the program is
+    /// stratified.
+    fn stratify(&self) -> Result<'ast, ()> {
+        let mut edges: FxHashMap<&'ast str, Vec<(&'ast ast::CallExpression<'ast>, bool)>> =
+            FxHashMap::default();
+        for (name, rule) in &self.rules {
+            let e = edges.entry(*name).or_default();
+            for cond in &rule.conds {
+                for atom in &cond.exprs {
+                    rule_calls(atom, false, e);
+                }
+            }
+        }
+        for (name, calls) in &edges {
+            for (c, negated) in calls {
+                if *negated && reaches(&edges, c.fn_name.value, name) {
+                    return Err(Error::new(
+                        ErrorKind::NotStratified((*name).into(), c.fn_name.value.into()),
+                        c.span,
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn base_ty(ty: &ast::Type<'ast>) -> ty::Ty {
+        ty.array_sizes.iter().fold(
+            match &ty.base {
+                ast::BaseType::Bool(_) => ty::Ty::Bool,
+                ast::BaseType::Field(_) => ty::Ty::Field,
+                ast::BaseType::Uint(u) => {
+                    ty::Ty::Uint(u8::from_str(&u.type_name[1..]).expect("bad uN"))
+                }
+                ast::BaseType::Tuple(t) => {
+                    ty::Ty::Tuple(t.elems.iter().map(Self::base_ty).collect())
+                }
+            },
+            |t, size| {
+                let size = usize::from_str(size.value).expect("bad array size");
+                ty::Ty::Array(size, Box::new(t))
+            },
+        )
+    }
+
     /// Returns (ty, public)
     fn ty(&self, ty: &ast::QualType<'ast>) -> (ty::Ty, bool) {
         (
-            ty.ty.array_sizes.iter().fold(
-                match &ty.ty.base {
-                    ast::BaseType::Bool(_) => ty::Ty::Bool,
-                    ast::BaseType::Field(_) => ty::Ty::Field,
-                    ast::BaseType::Uint(u) => {
-                        ty::Ty::Uint(u8::from_str(&u.type_name[1..]).expect("bad uN"))
-                    }
-                },
-                |t, size| {
-                    let size = usize::from_str(size.value).expect("bad array size");
-                    ty::Ty::Array(size, Box::new(t))
-                },
-            ),
+            Self::base_ty(&ty.ty),
             ty.qualifier
                 .as_ref()
                 .map(|q| match q {
@@ -127,17 +167,47 @@ impl<'ast> Gen<'ast> {
         }
         let r = self.rule_cases(rule)?;
         self.exit_function(name);
+        self.locate(&r.as_bool(), &rule.span, name, None);
         self.circ.assert(r.as_bool());
         Ok(())
     }
 
     fn rule_cases(&mut self, rule: &'ast ast::Rule_) -> Result<'ast, term::T> {
+        if self.negation_depth > 0 {
+            if let Some(c) = rule.conds.iter().find(|c| c.existential.is_some()) {
+                return Err(Error::new(
+                    ErrorKind::NegatedExistential(rule.name.value.into()),
+                    c.span,
+                ));
+            }
+        }
         rule.conds.iter().try_fold(term::bool_lit(false), |x, y| {
             let cond = self.condition(y)?;
+            self.locate(&cond.ir, &y.span, rule.name.value, Some(rule.name.value));
             term::or(&x, &cond).map_err(|e| Error::from(e).with_span(rule.span))
         })
     }
 
+    /// Record that `t` came from `span` in `rule` (and is bound to `name`, if any) in the source
+    /// map.
+    fn locate(&self, t: &Term, span: &ast::Span, rule: &str, name: Option<&str>) {
+        if matches!(t.op(), Op::Const(_)) {
+            return;
+        }
+        let (line, column) = span.start_pos().line_col();
+        let (end_line, end_column) = span.end_pos().line_col();
+        let loc = SourceLoc {
+            file: self.file.to_string_lossy().into_owned(),
+            line,
+            column,
+            end_line,
+            end_column,
+            function: rule.to_owned(),
+            name: name.map(str::to_owned),
+        };
+        self.circ.locate(t.clone(), loc);
+    }
+
     fn condition(&mut self, c: &'ast ast::Condition) -> Result<'ast, term::T> {
         if let Some(decls) = c.existential.as_ref() {
             for d in &decls.declarations {
@@ -147,7 +217,7 @@ impl<'ast> Gen<'ast> {
             }
         }
         c.exprs.iter().try_fold(term::bool_lit(true), |x, y| {
-            let cond = self.expr(y, true)?;
+            let cond = self.expr(y)?;
             term::and(&x, &cond).map_err(|e| Error::from(e).with_span(*y.span()))
         })
     }
@@ -160,27 +230,41 @@ impl<'ast> Gen<'ast> {
     }
 
     /// Generate IR for an expression.
-    ///
-    /// * `top_level` indicates whether this expression is a top-level expression in a condition.
-    fn expr(&mut self, e: &'ast ast::Expression, top_level: bool) -> Result<'ast, term::T> {
+    fn expr(&mut self, e: &'ast ast::Expression) -> Result<'ast, term::T> {
         match e {
             ast::Expression::Binary(ref b) => self.bin_expr(b),
             ast::Expression::Unary(ref u) => self.un_expr(u),
-            ast::Expression::Paren(ref i, _) => self.expr(i, top_level),
+            ast::Expression::Paren(ref i, _) => self.expr(i),
             ast::Expression::Identifier(ref i) => self.ident(i),
             ast::Expression::Literal(ref i) => self.literal(i),
             ast::Expression::Access(ref c) => {
-                let arr = self.ident(&c.arr)?;
-                c.idxs.iter().try_fold(arr, |arr, idx| {
-                    let idx_v = self.expr(idx, false)?;
-                    term::array_idx(&arr, &idx_v).map_err(|err| Error::new(err, *idx.span()))
-                })
+                let base = self.ident(&c.base)?;
+                c.accesses
+                    .iter()
+                    .try_fold(base, |base, access| match access {
+                        ast::Access::Index(i) => {
+                            let idx_v = self.negated_expr(&i.index)?;
+                            term::array_idx(&base, &idx_v).map_err(|err| Error::new(err, i.span))
+                        }
+                        ast::Access::Member(m) => {
+                            let idx = usize::from_str(m.idx.value).expect("bad member index");
+                            term::tuple_field(&base, idx).map_err(|err| Error::new(err, m.span))
+                        }
+                    })
+            }
+            ast::Expression::Tuple(ref t) => {
+                let elems = t
+                    .elems
+                    .iter()
+                    .map(|e| self.negated_expr(e))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(term::tuple(elems))
             }
             ast::Expression::Call(ref c) => {
                 let args = c
                     .args
                     .iter()
-                    .map(|a| self.expr(a, false))
+                    .map(|a| self.negated_expr(a))
                     .collect::<Result<Vec<_>>>()?;
                 match c.fn_name.value {
                     "to_field" => {
@@ -188,11 +272,6 @@ impl<'ast> Gen<'ast> {
                         term::uint_to_field(&args[0]).map_err(|err| Error::new(err, c.span))
                     }
                     name => {
-                        assert!(
-                            top_level,
-                            "Rules must be at the top level {} at {:?}",
-                            name, c.span
-                        );
                         let rule = *self
                             .rules
                             .get(name)
@@ -226,6 +305,8 @@ impl<'ast> Gen<'ast> {
                             let r = self.rule_cases(rule)?;
                             self.exit_function(name);
                             Ok(r)
+                        } else if self.negation_depth > 0 {
+                            Err(Error::new(ErrorKind::NegatedRecLimit(name.into()), c.span))
                         } else {
                             Ok(term::bool_lit(false))
                         }
@@ -256,9 +337,20 @@ impl<'ast> Gen<'ast> {
             }
         }
     }
+    /// Generate IR for `e`, which is in a non-monotone position (see [rule_calls]).
+    fn negated_expr(&mut self, e: &'ast ast::Expression) -> Result<'ast, term::T> {
+        self.negation_depth += 1;
+        let r = self.expr(e);
+        self.negation_depth -= 1;
+        r
+    }
     fn bin_expr(&mut self, e: &'ast ast::BinaryExpression) -> Result<'ast, term::T> {
-        let l = self.expr(&e.left, false)?;
-        let r = self.expr(&e.right, false)?;
+        let (l, r) = match &e.op {
+            ast::BinaryOperator::Or | ast::BinaryOperator::And => {
+                (self.expr(&e.left)?, self.expr(&e.right)?)
+            }
+            _ => (self.negated_expr(&e.left)?, self.negated_expr(&e.right)?),
+        };
         let res = match &e.op {
             ast::BinaryOperator::BitXor => term::bitxor(&l, &r),
             ast::BinaryOperator::BitAnd => term::bitand(&l, &r),
@@ -281,7 +373,7 @@ impl<'ast> Gen<'ast> {
         res.map_err(|err| Error::new(err, e.span))
     }
     fn un_expr(&mut self, e: &'ast ast::UnaryExpression) -> Result<'ast, term::T> {
-        let l = self.expr(&e.expression, false)?;
+        let l = self.negated_expr(&e.expression)?;
         let res = match &e.op {
             ast::UnaryOperator::BitNot(_) => term::bitnot(&l),
             ast::UnaryOperator::Not(_) => term::not(&l),
@@ -335,14 +427,14 @@ impl<'ast> Gen<'ast> {
                                 .circ
                                 .get_value(Loc::local(rule.args[arg_idx].ident.value.to_owned()))?
                                 .unwrap_term();
-                            let actual_arg = self.expr(&c.args[arg_idx], false)?;
+                            let actual_arg = self.expr(&c.args[arg_idx])?;
                             let bug_cond = term::gte(&actual_arg, &formal_arg)?;
                             debug!("Bug if: {}", bug_cond);
                             bad_recursion.push(bug_cond);
                             continue;
                         }
                     }
-                    let force = self.expr(atom, true)?;
+                    let force = self.expr(atom)?;
                     debug!("Force: {}", force);
                     bug_conditions.push(force);
                 }
@@ -372,13 +464,76 @@ impl<'ast> Gen<'ast> {
     }
 }
 
+/// Collect the rule calls in `e` into `calls`, with whether each is negated.
+///
+/// A call is negated if it is beneath any operator other than `&&` and `||` (e.g., `!`, `=`, or
+/// another call), since its rule's result then need not be monotone in the program's facts.
+fn rule_calls<'ast>(
+    e: &'ast ast::Expression<'ast>,
+    negated: bool,
+    calls: &mut Vec<(&'ast ast::CallExpression<'ast>, bool)>,
+) {
+    match e {
+        ast::Expression::Binary(b) => {
+            let negated =
+                negated || !matches!(b.op, ast::BinaryOperator::Or | ast::BinaryOperator::And);
+            rule_calls(&b.left, negated, calls);
+            rule_calls(&b.right, negated, calls);
+        }
+        ast::Expression::Unary(u) => rule_calls(&u.expression, true, calls),
+        ast::Expression::Paren(i, _) => rule_calls(i, negated, calls),
+        ast::Expression::Identifier(_) | ast::Expression::Literal(_) => {}
+        ast::Expression::Access(a) => {
+            for access in &a.accesses {
+                if let ast::Access::Index(i) = access {
+                    rule_calls(&i.index, true, calls);
+                }
+            }
+        }
+        ast::Expression::Tuple(t) => {
+            for e in &t.elems {
+                rule_calls(e, true, calls);
+            }
+        }
+        ast::Expression::Call(c) => {
+            if c.fn_name.value != "to_field" {
+                calls.push((c, negated));
+            }
+            for a in &c.args {
+                rule_calls(a, true, calls);
+            }
+        }
+    }
+}
+
+/// Can rule `from` reach rule `to` in the call graph `edges`?
+fn reaches(
+    edges: &FxHashMap<&str, Vec<(&ast::CallExpression, bool)>>,
+    from: &str,
+    to: &str,
+) -> bool {
+    let mut seen = FxHashSet::default();
+    let mut stack = vec![from];
+    while let Some(r) = stack.pop() {
+        if r == to {
+            return true;
+        }
+        if seen.insert(r) {
+            if let Some(calls) = edges.get(r) {
+                stack.extend(calls.iter().map(|(c, _)| c.fn_name.value));
+            }
+        }
+    }
+    false
+}
+
 /// The Datalog front-end. Implements [FrontEnd].
 pub struct Datalog;
 
 impl FrontEnd for Datalog {
     type Inputs = Inputs;
     fn gen(i: Inputs) -> Computations {
-        let mut f = File::open(i.file).unwrap();
+        let mut f = File::open(&i.file).unwrap();
         let mut buffer = String::new();
         f.read_to_string(&mut buffer).unwrap();
         let ast = parser::parse(&buffer);
@@ -389,13 +544,15 @@ impl FrontEnd for Datalog {
                 panic!("parse error!")
             }
         };
-        let mut g = Gen::new(cfg().datalog.rec_limit);
+        let mut g = Gen::new(cfg().datalog.rec_limit, i.file);
         g.register_rules(&ast);
-        let r = if cfg().datalog.lint_prim_rec {
-            g.lint_rules()
-        } else {
-            g.entry_rule("main")
-        };
+        let r = g.stratify().and_then(|()| {
+            if cfg().datalog.lint_prim_rec {
+                g.lint_rules()
+            } else {
+                g.entry_rule("main")
+            }
+        });
         if let Err(e) = r {
             eprintln!("{e}");
             panic!()
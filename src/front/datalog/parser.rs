@@ -162,6 +162,7 @@ pub mod ast {
         Literal(Literal<'ast>),
         Call(CallExpression<'ast>),
         Access(AccessExpression<'ast>),
+        Tuple(TupleExpression<'ast>),
         Unary(UnaryExpression<'ast>),
         Paren(Box<Expression<'ast>>, Span<'ast>),
     }
@@ -184,11 +185,51 @@ pub mod ast {
         pub span: Span<'ast>,
     }
 
+    #[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+    #[pest_ast(rule(Rule::index))]
+    pub struct IndexAccess<'ast> {
+        pub index: Expression<'ast>,
+        #[pest_ast(outer())]
+        pub span: Span<'ast>,
+    }
+
+    #[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+    #[pest_ast(rule(Rule::member))]
+    pub struct MemberAccess<'ast> {
+        pub idx: DecimalLiteral<'ast>,
+        #[pest_ast(outer())]
+        pub span: Span<'ast>,
+    }
+
+    #[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+    #[pest_ast(rule(Rule::access))]
+    pub enum Access<'ast> {
+        Index(IndexAccess<'ast>),
+        Member(MemberAccess<'ast>),
+    }
+
+    impl<'ast> Access<'ast> {
+        pub fn span(&self) -> &Span<'ast> {
+            match self {
+                Access::Index(i) => &i.span,
+                Access::Member(m) => &m.span,
+            }
+        }
+    }
+
     #[derive(Debug, FromPest, PartialEq, Eq, Clone)]
     #[pest_ast(rule(Rule::access_expr))]
     pub struct AccessExpression<'ast> {
-        pub arr: Ident<'ast>,
-        pub idxs: Vec<Expression<'ast>>,
+        pub base: Ident<'ast>,
+        pub accesses: Vec<Access<'ast>>,
+        #[pest_ast(outer())]
+        pub span: Span<'ast>,
+    }
+
+    #[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+    #[pest_ast(rule(Rule::tuple_expr))]
+    pub struct TupleExpression<'ast> {
+        pub elems: Vec<Expression<'ast>>,
         #[pest_ast(outer())]
         pub span: Span<'ast>,
     }
@@ -233,6 +274,7 @@ pub mod ast {
                 Expression::Unary(u) => &u.span,
                 Expression::Call(u) => &u.span,
                 Expression::Access(u) => &u.span,
+                Expression::Tuple(u) => &u.span,
                 Expression::Paren(_, s) => s,
             }
         }
@@ -356,6 +398,9 @@ pub mod ast {
                     Rule::access_expr => Expression::Access(
                         AccessExpression::from_pest(&mut pair.into_inner()).unwrap(),
                     ),
+                    Rule::tuple_expr => Expression::Tuple(
+                        TupleExpression::from_pest(&mut pair.into_inner()).unwrap(),
+                    ),
                     r => unreachable!("expected `term`, found {:#?}", r),
                 }
             }
@@ -393,12 +438,21 @@ pub mod ast {
         pub span: Span<'ast>,
     }
 
+    #[derive(Debug, FromPest, PartialEq, Eq, Clone)]
+    #[pest_ast(rule(Rule::ty_tuple))]
+    pub struct TypeTuple<'ast> {
+        pub elems: Vec<Type<'ast>>,
+        #[pest_ast(outer())]
+        pub span: Span<'ast>,
+    }
+
     #[derive(Debug, FromPest, PartialEq, Eq, Clone)]
     #[pest_ast(rule(Rule::base_ty))]
     pub enum BaseType<'ast> {
         Uint(TypeUint<'ast>),
         Field(TypeField<'ast>),
         Bool(TypeBool<'ast>),
+        Tuple(TypeTuple<'ast>),
     }
 
     impl<'ast> BaseType<'ast> {
@@ -407,6 +461,7 @@ pub mod ast {
                 BaseType::Uint(p) => &p.span,
                 BaseType::Field(p) => &p.span,
                 BaseType::Bool(p) => &p.span,
+                BaseType::Tuple(p) => &p.span,
             }
         }
     }
@@ -34,6 +34,11 @@ impl T {
             (Ty::Bool, Sort::Bool) | (Ty::Field, Sort::Field(_)) => {}
             (Ty::Uint(w), Sort::BitVector(w2)) if *w as usize == *w2 => {}
             (Ty::Array(l, t), Sort::Array(_, t2, l2)) if l == l2 => Self::check_ty(t2, t),
+            (Ty::Tuple(ts), Sort::Tuple(ts2)) if ts.len() == ts2.len() => {
+                for (t, t2) in ts.iter().zip(ts2.iter()) {
+                    Self::check_ty(t2, t);
+                }
+            }
             _ => panic!("IR sort {} doesn't match datalog type {}", ir, ty),
         }
     }
@@ -87,6 +92,7 @@ impl Ty {
                 Box::new(b.sort()),
                 *n,
             ),
+            Self::Tuple(ts) => Sort::Tuple(ts.iter().map(Ty::sort).collect()),
         }
     }
     fn default_ir_term(&self) -> Term {
@@ -325,7 +331,7 @@ pub fn uint_to_field(s: &T) -> Result<T> {
     }
 }
 
-/// Uint to field
+/// Array indexing
 pub fn array_idx(a: &T, i: &T) -> Result<T> {
     match (&a.ty, &i.ty) {
         (Ty::Array(_, elem_ty), &Ty::Field) => Ok(T::new(
@@ -340,6 +346,25 @@ pub fn array_idx(a: &T, i: &T) -> Result<T> {
     }
 }
 
+/// Tuple construction
+pub fn tuple(elems: Vec<T>) -> T {
+    let ty = Ty::Tuple(elems.iter().map(|e| e.ty.clone()).collect());
+    T::new(
+        term(Op::Tuple, elems.into_iter().map(|e| e.ir).collect()),
+        ty,
+    )
+}
+
+/// Tuple member access
+pub fn tuple_field(t: &T, i: usize) -> Result<T> {
+    match &t.ty {
+        Ty::Tuple(ts) if i < ts.len() => {
+            Ok(T::new(term![Op::Field(i); t.ir.clone()], ts[i].clone()))
+        }
+        _ => Err(ErrorKind::InvalidUnOp(format!(".{i}"), t.clone())),
+    }
+}
+
 /// Datalog lang def
 pub struct Datalog;
 
@@ -13,6 +13,8 @@ pub enum Ty {
     Uint(u8),
     /// array
     Array(usize, Box<Ty>),
+    /// tuple
+    Tuple(Vec<Ty>),
 }
 
 impl Display for Ty {
@@ -22,6 +24,16 @@ impl Display for Ty {
             Ty::Field => write!(f, "field"),
             Ty::Uint(w) => write!(f, "u{w}"),
             Ty::Array(l, t) => write!(f, "{t}[{l}]"),
+            Ty::Tuple(ts) => {
+                write!(f, "(")?;
+                for (i, t) in ts.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{t}")?;
+                }
+                write!(f, ")")
+            }
         }
     }
 }
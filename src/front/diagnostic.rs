@@ -0,0 +1,309 @@
+//! Structured diagnostics for front-ends
+//!
+//! A [Diagnostic] is a problem with an input program: its severity, where it is, a short code
+//! identifying its kind, and any notes or suggestions. Front-ends report them as [Diagnostics]
+//! from [super::FrontEnd::try_gen], and they render with a snippet of the offending source.
+
+use crate::ir::term::SourceLoc;
+
+use std::fmt::{self, Display, Formatter};
+use std::path::PathBuf;
+
+/// How serious a diagnostic is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Extra information
+    Note,
+    /// Suspicious, but compilation can continue
+    Warning,
+    /// Compilation cannot continue
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Note => write!(f, "note"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A position in a source file. Lines and columns count from 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    /// The line
+    pub line: usize,
+    /// The column, in characters
+    pub column: usize,
+}
+
+impl Position {
+    /// Create a position
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+}
+
+/// A range of a source file; `end` is exclusive.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SourceRange {
+    /// The file
+    pub file: PathBuf,
+    /// The first position in the range
+    pub start: Position,
+    /// The position just past the range
+    pub end: Position,
+}
+
+impl SourceRange {
+    /// Create a range
+    pub fn new(file: PathBuf, start: Position, end: Position) -> Self {
+        Self { file, start, end }
+    }
+
+    /// This range as the [SourceLoc] of an IR term made in `function`, bound to the variable
+    /// `name` (if any).
+    pub fn source_loc(&self, function: impl Into<String>, name: Option<String>) -> SourceLoc {
+        SourceLoc {
+            file: self.file.to_string_lossy().into_owned(),
+            line: self.start.line,
+            column: self.start.column,
+            end_line: self.end.line,
+            end_column: self.end.column,
+            function: function.into(),
+            name,
+        }
+    }
+}
+
+impl Display for SourceRange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.file.display(),
+            self.start.line,
+            self.start.column
+        )
+    }
+}
+
+/// A problem with an input program
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// How serious it is
+    pub severity: Severity,
+    /// A short, stable name for this kind of problem (e.g., `type-error`)
+    pub code: &'static str,
+    /// What went wrong
+    pub message: String,
+    /// Where it went wrong, if known
+    pub range: Option<SourceRange>,
+    /// Context for the problem
+    pub notes: Vec<String>,
+    /// Possible fixes
+    pub suggestions: Vec<String>,
+}
+
+impl Diagnostic {
+    /// Create a diagnostic with no location, notes, or suggestions.
+    pub fn new(severity: Severity, code: &'static str, message: impl Display) -> Self {
+        Self {
+            severity,
+            code,
+            message: message.to_string(),
+            range: None,
+            notes: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    /// Create an error
+    pub fn error(code: &'static str, message: impl Display) -> Self {
+        Self::new(Severity::Error, code, message)
+    }
+
+    /// Create a warning
+    pub fn warning(code: &'static str, message: impl Display) -> Self {
+        Self::new(Severity::Warning, code, message)
+    }
+
+    /// Locate this diagnostic
+    pub fn at(mut self, range: SourceRange) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    /// Add a note
+    pub fn note(mut self, note: impl Display) -> Self {
+        self.notes.push(note.to_string());
+        self
+    }
+
+    /// Add a suggestion
+    pub fn suggest(mut self, suggestion: impl Display) -> Self {
+        self.suggestions.push(suggestion.to_string());
+        self
+    }
+
+    /// Render this diagnostic, quoting `source` (the contents of its file) if given.
+    pub fn render(&self, source: Option<&str>) -> String {
+        let mut out = format!("{}[{}]: {}\n", self.severity, self.code, self.message);
+        let gutter = match &self.range {
+            Some(r) => {
+                let lines: Vec<&str> = source.map(|s| s.lines().collect()).unwrap_or_default();
+                let last = r.end.line.max(r.start.line);
+                let gutter = " ".repeat(last.to_string().len());
+                out.push_str(&format!("{gutter}--> {r}\n"));
+                if (1..=lines.len()).contains(&r.start.line) {
+                    out.push_str(&format!("{gutter} |\n"));
+                    for l in r.start.line..=last.min(lines.len()) {
+                        let line = lines[l - 1];
+                        let from = if l == r.start.line {
+                            r.start.column
+                        } else {
+                            line.len() - line.trim_start().len() + 1
+                        };
+                        let to = if l == r.end.line {
+                            r.end.column
+                        } else {
+                            line.chars().count() + 1
+                        };
+                        out.push_str(&format!("{l:>w$} | {line}\n", w = gutter.len()));
+                        out.push_str(&format!(
+                            "{gutter} | {}{}\n",
+                            " ".repeat(from.saturating_sub(1)),
+                            "^".repeat(to.saturating_sub(from).max(1))
+                        ));
+                    }
+                }
+                gutter
+            }
+            None => String::new(),
+        };
+        for n in &self.notes {
+            out.push_str(&format!("{gutter} = note: {n}\n"));
+        }
+        for s in &self.suggestions {
+            out.push_str(&format!("{gutter} = help: {s}\n"));
+        }
+        out
+    }
+}
+
+impl Display for Diagnostic {
+    /// Renders the diagnostic, quoting its file if it can be read.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let source = self
+            .range
+            .as_ref()
+            .and_then(|r| std::fs::read_to_string(&r.file).ok());
+        write!(f, "{}", self.render(source.as_deref()))
+    }
+}
+
+/// The diagnostics from one run of a front-end
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Diagnostics {
+    /// Create an empty list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a diagnostic
+    pub fn push(&mut self, d: Diagnostic) {
+        self.0.push(d);
+    }
+
+    /// Whether there are no diagnostics
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether any diagnostic is an error
+    pub fn has_errors(&self) -> bool {
+        self.0.iter().any(|d| d.severity == Severity::Error)
+    }
+
+    /// Iterate over the diagnostics
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.0.iter()
+    }
+}
+
+impl From<Diagnostic> for Diagnostics {
+    fn from(d: Diagnostic) -> Self {
+        Self(vec![d])
+    }
+}
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for d in &self.0 {
+            writeln!(f, "{d}")?;
+        }
+        let n_errors = self
+            .0
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .count();
+        if n_errors > 0 {
+            write!(
+                f,
+                "{n_errors} error{}",
+                if n_errors == 1 { "" } else { "s" }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let src = "def main(field x) -> field:\n    field y = x + true\n    return y\n";
+        let range = SourceRange::new(
+            "main.zok".into(),
+            Position::new(2, 15),
+            Position::new(2, 23),
+        );
+        let d = Diagnostic::error("type-error", "Cannot perform op '+' on field and bool")
+            .at(range)
+            .note("in function main")
+            .suggest("convert the bool to a field");
+        let expected = "\
+error[type-error]: Cannot perform op '+' on field and bool
+ --> main.zok:2:15
+  |
+2 |     field y = x + true
+  |               ^^^^^^^^
+  = note: in function main
+  = help: convert the bool to a field
+";
+        assert_eq!(d.render(Some(src)), expected);
+    }
+
+    #[test]
+    fn render_without_source() {
+        let d = Diagnostic::warning("unused", "x is unused").at(SourceRange::new(
+            "a.zok".into(),
+            Position::new(10, 1),
+            Position::new(10, 2),
+        ));
+        assert_eq!(
+            d.render(None),
+            "warning[unused]: x is unused\n  --> a.zok:10:1\n"
+        );
+        let ds = Diagnostics::from(d);
+        assert!(!ds.has_errors());
+    }
+}
@@ -4,11 +4,13 @@
 pub mod c;
 #[cfg(all(feature = "smt", feature = "datalog"))]
 pub mod datalog;
+pub mod diagnostic;
 #[cfg(all(feature = "smt", feature = "zok"))]
 pub mod zsharp;
 
 use crate::ir::proof;
 use crate::ir::term::{Computations, PartyId};
+use diagnostic::Diagnostics;
 
 use std::fmt::{self, Display, Formatter};
 
@@ -24,6 +26,13 @@ pub trait FrontEnd {
 
     /// Compile the program to constraints
     fn gen(i: Self::Inputs) -> Computations;
+
+    /// Compile the program to constraints, reporting problems with the program as [Diagnostics].
+    ///
+    /// By default, this defers to [FrontEnd::gen], which aborts on such problems.
+    fn try_gen(i: Self::Inputs) -> Result<Computations, Diagnostics> {
+        Ok(Self::gen(i))
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -0,0 +1,615 @@
+//! Editor support for Z#
+//!
+//! An [Analysis] loads a program (preferring the text of open, possibly unsaved, documents), runs
+//! the front-end's declaration visitor and type checker over it, and indexes the result to answer
+//! an editor's questions: what is wrong, where is this defined, what type is this, and what could
+//! go here. The `zls` example serves these answers over the language server protocol.
+
+use super::parser::{ZLoad, ZStdLib};
+use super::zvisit::{ZVisitorMut, ZVisitorResult};
+use super::{span_range, type_span, ZGen};
+use crate::front::diagnostic::{Diagnostics, Position, SourceRange};
+use crate::front::Mode;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use zokrates_pest_ast as ast;
+
+/// What a name refers to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SymbolKind {
+    /// A function
+    Function,
+    /// A struct
+    Struct,
+    /// A type alias
+    Type,
+    /// A constant
+    Constant,
+    /// A parameter, local variable, loop index, or generic parameter
+    Variable,
+    /// A struct field
+    Field,
+}
+
+/// A named definition
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    /// The name
+    pub name: String,
+    /// What it is
+    pub kind: SymbolKind,
+    /// Where the name is defined
+    pub range: SourceRange,
+    /// A signature (for functions and structs), a definition (for types and constants), or a type
+    /// (for variables and fields)
+    pub detail: String,
+}
+
+/// A completion candidate
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Completion {
+    /// The text to insert
+    pub label: String,
+    /// What it is
+    pub kind: SymbolKind,
+    /// A description; for the standard library, the import that brings it into scope
+    pub detail: String,
+}
+
+/// A variable, visible in `scope` after its definition
+#[derive(Debug)]
+struct Local {
+    symbol: Symbol,
+    scope: SourceRange,
+}
+
+/// The results of checking a Z# program, indexed for editor queries
+#[derive(Debug, Default)]
+pub struct Analysis {
+    overlay: HashMap<PathBuf, String>,
+    diagnostics: Diagnostics,
+    /// top-level definitions, by file
+    symbols: HashMap<PathBuf, HashMap<String, Symbol>>,
+    /// imported names, by file, mapped to their (file, name) of definition
+    imports: HashMap<PathBuf, HashMap<String, (PathBuf, String)>>,
+    /// variables, by file, in source order
+    locals: HashMap<PathBuf, Vec<Local>>,
+    /// struct fields, by (file, struct)
+    fields: HashMap<(PathBuf, String), Vec<Symbol>>,
+    /// literals whose types the type checker inferred, by file
+    literals: HashMap<PathBuf, Vec<(SourceRange, String)>>,
+}
+
+impl Analysis {
+    /// Check the program rooted at `file`, reading the files in `overlay` from it rather than from
+    /// disk.
+    pub fn new(file: &Path, overlay: HashMap<PathBuf, String>) -> Self {
+        let loader = ZLoad::with_overlay(overlay.clone());
+        let mut this = Self {
+            overlay,
+            ..Default::default()
+        };
+        let asts = match loader.load(&file) {
+            Ok(asts) => asts,
+            Err(d) => {
+                this.diagnostics.push(d);
+                return this;
+            }
+        };
+        let mut g = ZGen::new(asts, Mode::Proof, loader.stdlib(), false);
+        // the maps we index are filled in even if checking fails
+        if let Err(ds) = g.visit_files() {
+            this.diagnostics = ds;
+        }
+        this.imports = g.import_map.clone();
+        for (path, f) in &g.asts {
+            this.index_file(path, f);
+        }
+        for (path, fns) in &g.functions {
+            let mut v = LiteralTypes {
+                path,
+                found: Vec::new(),
+            };
+            for f in fns.values() {
+                // the checked definitions carry the inferred literal types
+                let _ = v.visit_function_definition(&mut f.clone());
+            }
+            this.literals.insert(path.clone(), v.found);
+        }
+        this
+    }
+
+    /// The problems with the program
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    /// The definition of the name at `pos` in `file`
+    pub fn definition(&self, file: &Path, pos: Position) -> Option<&Symbol> {
+        let line = self.line(file, pos.line)?;
+        let (name, start) = word_at(&line, pos.column)?;
+        match receiver(&line, start) {
+            Some(chain) => self
+                .member_fields(file, pos, &chain)?
+                .iter()
+                .find(|f| f.name == name),
+            None => self.lookup(file, pos, &name),
+        }
+    }
+
+    /// A description of the thing at `pos` in `file`: its definition, or its type
+    pub fn hover(&self, file: &Path, pos: Position) -> Option<String> {
+        if let Some((_, ty)) = self
+            .literals
+            .get(file)
+            .and_then(|ls| ls.iter().find(|(r, _)| contains(r, pos)))
+        {
+            return Some(ty.clone());
+        }
+        let s = self.definition(file, pos)?;
+        Some(match s.kind {
+            SymbolKind::Variable | SymbolKind::Field => format!("{} {}", s.detail, s.name),
+            _ => s.detail.clone(),
+        })
+    }
+
+    /// What could be written at `pos` in `file`.
+    ///
+    /// After a `.`, these are the fields of the struct before it; otherwise, they are the names in
+    /// scope and `stdlib` (see [stdlib_completions]).
+    pub fn completions(
+        &self,
+        file: &Path,
+        pos: Position,
+        stdlib: &[Completion],
+    ) -> Vec<Completion> {
+        let line = match self.line(file, pos.line) {
+            Some(l) => l,
+            None => return Vec::new(),
+        };
+        // the start of the partial word before the cursor
+        let chars: Vec<char> = line.chars().collect();
+        let mut start = pos.column.min(chars.len() + 1);
+        while start > 1 && is_ident_char(chars[start - 2]) {
+            start -= 1;
+        }
+        if let Some(chain) = receiver(&line, start) {
+            return self
+                .member_fields(file, pos, &chain)
+                .map(|fs| fs.iter().map(completion).collect())
+                .unwrap_or_default();
+        }
+
+        let mut out: Vec<Completion> = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        // innermost and latest definitions first
+        let locals = self.locals.get(file).into_iter().flatten().rev();
+        for l in locals.filter(|l| contains(&l.scope, pos) && l.symbol.range.start <= pos) {
+            if seen.insert(l.symbol.name.clone()) {
+                out.push(completion(&l.symbol));
+            }
+        }
+        let globals = self.symbols.get(file).into_iter().flat_map(|m| m.values());
+        let imported = self.imports.get(file).into_iter().flat_map(|m| {
+            m.iter().filter_map(|(n, (f, o))| {
+                let s = self.symbols.get(f)?.get(o)?;
+                Some(Completion {
+                    label: n.clone(),
+                    ..completion(s)
+                })
+            })
+        });
+        for c in globals.map(completion).chain(imported) {
+            if seen.insert(c.label.clone()) {
+                out.push(c);
+            }
+        }
+        out.extend(stdlib.iter().filter(|c| !seen.contains(&c.label)).cloned());
+        out
+    }
+
+    fn index_file(&mut self, path: &Path, f: &ast::File) {
+        let symbols = self.symbols.entry(path.to_path_buf()).or_default();
+        let mut locals = Vec::new();
+        for d in &f.declarations {
+            let (id, kind, detail) = match d {
+                ast::SymbolDeclaration::Function(f) => {
+                    let scope = span_range(path.to_path_buf(), &f.span);
+                    for g in &f.generics {
+                        locals.push(local(path, g, "u32".to_owned(), &scope));
+                    }
+                    for p in &f.parameters {
+                        locals.push(local(path, &p.id, type_text(&p.ty), &scope));
+                    }
+                    index_stmts(path, &f.statements, &scope, &mut locals);
+                    (&f.id, SymbolKind::Function, first_line(&f.span))
+                }
+                ast::SymbolDeclaration::Struct(s) => {
+                    let fields = s
+                        .fields
+                        .iter()
+                        .map(|fd| Symbol {
+                            name: fd.id.value.clone(),
+                            kind: SymbolKind::Field,
+                            range: span_range(path.to_path_buf(), &fd.id.span),
+                            detail: type_text(&fd.ty),
+                        })
+                        .collect();
+                    self.fields
+                        .insert((path.to_path_buf(), s.id.value.clone()), fields);
+                    (&s.id, SymbolKind::Struct, s.span.as_str().to_owned())
+                }
+                ast::SymbolDeclaration::Type(t) => (&t.id, SymbolKind::Type, first_line(&t.span)),
+                ast::SymbolDeclaration::Constant(c) => {
+                    (&c.id, SymbolKind::Constant, first_line(&c.span))
+                }
+                ast::SymbolDeclaration::Import(_) => continue,
+            };
+            symbols.insert(
+                id.value.clone(),
+                Symbol {
+                    name: id.value.clone(),
+                    kind,
+                    range: span_range(path.to_path_buf(), &id.span),
+                    detail,
+                },
+            );
+        }
+        self.locals.insert(path.to_path_buf(), locals);
+    }
+
+    /// The text of line `n` of `file`
+    fn line(&self, file: &Path, n: usize) -> Option<String> {
+        let text = match self.overlay.get(file) {
+            Some(t) => t.clone(),
+            None => std::fs::read_to_string(file).ok()?,
+        };
+        text.lines().nth(n.checked_sub(1)?).map(ToOwned::to_owned)
+    }
+
+    /// The definition of `name`, as seen from `pos` in `file`
+    fn lookup(&self, file: &Path, pos: Position, name: &str) -> Option<&Symbol> {
+        self.local(file, pos, name)
+            .or_else(|| self.global(file, name))
+    }
+
+    fn local(&self, file: &Path, pos: Position, name: &str) -> Option<&Symbol> {
+        self.locals
+            .get(file)?
+            .iter()
+            .rev()
+            .find(|l| {
+                l.symbol.name == name && contains(&l.scope, pos) && l.symbol.range.start <= pos
+            })
+            .map(|l| &l.symbol)
+    }
+
+    fn global(&self, file: &Path, name: &str) -> Option<&Symbol> {
+        self.symbols
+            .get(file)
+            .and_then(|m| m.get(name))
+            .or_else(|| {
+                let (f, n) = self.imports.get(file)?.get(name)?;
+                self.symbols.get(f)?.get(n)
+            })
+    }
+
+    /// The fields of the struct that `ty`, written in `file`, names (or contains, for arrays)
+    fn struct_fields(&self, file: &Path, ty: &str) -> Option<&Vec<Symbol>> {
+        // `Foo<N>[3]` -> `Foo`
+        let name = ty.split(['<', '[']).next()?.trim();
+        let key = match self.symbols.get(file).and_then(|m| m.get(name)) {
+            Some(_) => (file.to_path_buf(), name.to_owned()),
+            None => self.imports.get(file)?.get(name)?.clone(),
+        };
+        self.fields.get(&key)
+    }
+
+    /// The fields of the struct reached by the member accesses `chain`, e.g., `a.b`
+    fn member_fields(&self, file: &Path, pos: Position, chain: &[String]) -> Option<&Vec<Symbol>> {
+        let var = self.local(file, pos, chain.first()?)?;
+        let mut fields = self.struct_fields(file, &var.detail)?;
+        for name in &chain[1..] {
+            let f = fields.iter().find(|f| &f.name == name)?;
+            fields = self.struct_fields(&f.range.file, &f.detail)?;
+        }
+        Some(fields)
+    }
+}
+
+/// Completions for the functions of the standard library (see [ZStdLib::new])
+pub fn stdlib_completions() -> Vec<Completion> {
+    let stdlib = ZStdLib::new();
+    let mut out = Vec::new();
+    let mut dirs = vec![stdlib.path().to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(es) => es,
+            Err(_) => continue,
+        };
+        for p in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if p.is_dir() {
+                dirs.push(p);
+                continue;
+            }
+            if p.extension().and_then(|e| e.to_str()) != Some("zok") {
+                continue;
+            }
+            let src = match std::fs::read_to_string(&p) {
+                Ok(s) => s,
+                Err(_) => continue,
+            };
+            let file = match ast::generate_ast(&src) {
+                Ok(f) => f,
+                Err(_) => continue,
+            };
+            let import = p
+                .strip_prefix(stdlib.path())
+                .unwrap_or(&p)
+                .with_extension("")
+                .to_string_lossy()
+                .into_owned();
+            for d in &file.declarations {
+                if let ast::SymbolDeclaration::Function(f) = d {
+                    let (label, detail) = if f.id.value == "main" {
+                        let stem = p.file_stem().unwrap().to_string_lossy().into_owned();
+                        (stem, format!("import \"{import}\""))
+                    } else {
+                        let name = f.id.value.clone();
+                        let detail = format!("from \"{import}\" import {name}");
+                        (name, detail)
+                    };
+                    out.push(Completion {
+                        label,
+                        kind: SymbolKind::Function,
+                        detail,
+                    });
+                }
+            }
+        }
+    }
+    out.sort_by(|a, b| (&a.label, &a.detail).cmp(&(&b.label, &b.detail)));
+    out
+}
+
+/// Collects the literal types that the type checker filled in
+struct LiteralTypes<'a> {
+    path: &'a Path,
+    found: Vec<(SourceRange, String)>,
+}
+
+impl<'ast, 'a> ZVisitorMut<'ast> for LiteralTypes<'a> {
+    fn visit_decimal_literal_expression(
+        &mut self,
+        dle: &mut ast::DecimalLiteralExpression<'ast>,
+    ) -> ZVisitorResult {
+        use ast::DecimalSuffix::*;
+        if let Some(s) = &dle.suffix {
+            let ty = match s {
+                U8(_) => "u8",
+                U16(_) => "u16",
+                U32(_) => "u32",
+                U64(_) => "u64",
+                Field(_) => "field",
+            };
+            let range = span_range(self.path.to_path_buf(), &dle.span);
+            self.found.push((range, ty.to_owned()));
+        }
+        Ok(())
+    }
+}
+
+fn index_stmts(path: &Path, stmts: &[ast::Statement], scope: &SourceRange, out: &mut Vec<Local>) {
+    for s in stmts {
+        match s {
+            ast::Statement::Definition(d) => {
+                for l in &d.lhs {
+                    if let ast::TypedIdentifierOrAssignee::TypedIdentifier(ti) = l {
+                        out.push(local(path, &ti.identifier, type_text(&ti.ty), scope));
+                    }
+                }
+            }
+            ast::Statement::Iteration(i) => {
+                let inner = span_range(path.to_path_buf(), &i.span);
+                out.push(local(path, &i.index, type_text(&i.ty), &inner));
+                index_stmts(path, &i.statements, &inner, out);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn local(path: &Path, id: &ast::IdentifierExpression, ty: String, scope: &SourceRange) -> Local {
+    Local {
+        symbol: Symbol {
+            name: id.value.clone(),
+            kind: SymbolKind::Variable,
+            range: span_range(path.to_path_buf(), &id.span),
+            detail: ty,
+        },
+        scope: scope.clone(),
+    }
+}
+
+fn completion(s: &Symbol) -> Completion {
+    Completion {
+        label: s.name.clone(),
+        kind: s.kind,
+        detail: s.detail.clone(),
+    }
+}
+
+fn type_text(ty: &ast::Type) -> String {
+    type_span(ty).as_str().to_owned()
+}
+
+/// The first line of `s`, without a trailing `:`
+fn first_line(s: &ast::Span) -> String {
+    let l = s.as_str().lines().next().unwrap_or_default().trim();
+    l.strip_suffix(':').unwrap_or(l).trim_end().to_owned()
+}
+
+fn contains(r: &SourceRange, pos: Position) -> bool {
+    r.start <= pos && pos < r.end
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// The identifier at (1-based) `column` of `line`, and the column it starts at
+fn word_at(line: &str, column: usize) -> Option<(String, usize)> {
+    let chars: Vec<char> = line.chars().collect();
+    let at = column.checked_sub(1)?;
+    let mut start = at.min(chars.len());
+    while start > 0 && is_ident_char(chars[start - 1]) {
+        start -= 1;
+    }
+    let mut end = at;
+    while end < chars.len() && is_ident_char(chars[end]) {
+        end += 1;
+    }
+    if start >= end || chars[start].is_ascii_digit() {
+        return None;
+    }
+    Some((chars[start..end].iter().collect(), start + 1))
+}
+
+/// If the identifier at (1-based) `column` of `line` is a member access, the names of the
+/// accesses before it; e.g., `["a", "b"]` for `c` in `a.b[i].c`.
+fn receiver(line: &str, column: usize) -> Option<Vec<String>> {
+    let chars: Vec<char> = line.chars().collect();
+    // index just past the receiver
+    let mut i = column.checked_sub(1)?;
+    let mut names = Vec::new();
+    while i > 0 && chars.get(i - 1) == Some(&'.') {
+        i -= 1;
+        // skip array indices
+        while i > 0 && chars[i - 1] == ']' {
+            let mut depth = 0;
+            loop {
+                i = i.checked_sub(1)?;
+                match chars[i] {
+                    ']' => depth += 1,
+                    '[' => depth -= 1,
+                    _ => {}
+                }
+                if depth == 0 {
+                    break;
+                }
+            }
+        }
+        let end = i;
+        while i > 0 && is_ident_char(chars[i - 1]) {
+            i -= 1;
+        }
+        if i == end {
+            return None;
+        }
+        names.push(chars[i..end].iter().collect());
+    }
+    if names.is_empty() {
+        return None;
+    }
+    names.reverse();
+    Some(names)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cfg::cfg_or_default;
+
+    const SRC: &str = "\
+struct Point {
+    field x
+    field y
+}
+
+struct Line {
+    Point a
+    Point b
+}
+
+def norm(Point p) -> field:
+    return p.x * p.x + p.y * p.y
+
+def main(Line l, u32 n) -> field:
+    field s = norm(l.a)
+    for u32 i in 0..n do
+        s = s + 1
+    endfor
+    return s + l.b.y
+";
+
+    fn analyze(src: &str) -> (PathBuf, Analysis) {
+        cfg_or_default();
+        let path = PathBuf::from("/virtual/main.zok");
+        let overlay = HashMap::from([(path.clone(), src.to_owned())]);
+        let a = Analysis::new(&path, overlay);
+        (path, a)
+    }
+
+    #[test]
+    fn definitions() {
+        let (p, a) = analyze(SRC);
+        assert!(a.diagnostics().is_empty(), "{}", a.diagnostics());
+        // `norm` in `field s = norm(l.a)`
+        let d = a.definition(&p, Position::new(15, 16)).unwrap();
+        assert_eq!(d.kind, SymbolKind::Function);
+        assert_eq!(d.range.start, Position::new(11, 5));
+        // `a` in `l.a`
+        let d = a.definition(&p, Position::new(15, 22)).unwrap();
+        assert_eq!(d.kind, SymbolKind::Field);
+        assert_eq!(d.range.start, Position::new(7, 11));
+        // `y` in `l.b.y`
+        let d = a.definition(&p, Position::new(19, 20)).unwrap();
+        assert_eq!(d.range.start, Position::new(3, 11));
+    }
+
+    #[test]
+    fn hovers() {
+        let (p, a) = analyze(SRC);
+        assert_eq!(
+            a.hover(&p, Position::new(17, 9)).as_deref(),
+            Some("field s")
+        );
+        assert_eq!(
+            a.hover(&p, Position::new(15, 16)).as_deref(),
+            Some("def norm(Point p) -> field")
+        );
+        // the inferred type of `1` in `s + 1`
+        assert_eq!(a.hover(&p, Position::new(17, 17)).as_deref(), Some("field"));
+    }
+
+    #[test]
+    fn completes_fields() {
+        let (p, a) = analyze(SRC);
+        // just after `l.b.`
+        let cs = a.completions(&p, Position::new(19, 20), &[]);
+        let labels: Vec<_> = cs.iter().map(|c| c.label.as_str()).collect();
+        assert_eq!(labels, vec!["x", "y"]);
+        // the start of the `return` line
+        let cs = a.completions(&p, Position::new(19, 5), &[]);
+        assert!(cs.iter().any(|c| c.label == "norm"));
+        assert!(cs.iter().any(|c| c.label == "s"));
+        assert!(!cs.iter().any(|c| c.label == "i"));
+    }
+
+    #[test]
+    fn reports_type_errors() {
+        let (_, a) = analyze("def main(field x) -> bool:\n    return x\n");
+        assert!(a.diagnostics().has_errors());
+    }
+
+    #[test]
+    fn receivers() {
+        assert_eq!(
+            receiver("x = a.b[i + v[2]].c", 19),
+            Some(vec!["a".to_owned(), "b".to_owned()])
+        );
+        assert_eq!(receiver("x = c", 5), None);
+        assert_eq!(word_at("x = abc + 1", 6), Some(("abc".to_owned(), 5)));
+    }
+}
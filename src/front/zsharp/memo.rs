@@ -0,0 +1,380 @@
+//! Memoized instantiations of Z# functions
+//!
+//! Embedding a call walks the callee's body, which dominates front-end time when a generic
+//! function is called many times with the same generic values (as in the standard library's hash
+//! functions). Instead, we embed each *instantiation* (a function, its generic values, its
+//! constant arguments, and the sorts of its other arguments) once, with variables standing for the
+//! non-constant arguments, and substitute each call's arguments into the result.
+//!
+//! Optionally, the instantiation becomes a [Computation] of its own, and calls to it become
+//! [Op::Call]s, which [crate::ir::opt::link] inlines later.
+
+use super::term::{const_value, Ty, T};
+use super::ZGen;
+use crate::ir::term::*;
+
+use log::debug;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use zokrates_pest_ast as ast;
+
+/// What determines the result of a call
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub(super) struct InstanceKey {
+    path: PathBuf,
+    name: String,
+    /// generic values, sorted by name
+    generics: Vec<(String, Value)>,
+    /// for each argument, its value (if constant) or its sort
+    args: Vec<Result<Value, Sort>>,
+}
+
+impl InstanceKey {
+    /// The key for a call; `None` if a generic value is not constant.
+    fn new(path: &Path, name: &str, generics: &HashMap<String, T>, args: &[T]) -> Option<Self> {
+        let mut generics = generics
+            .iter()
+            .map(|(n, g)| Some((n.clone(), const_value(&g.term)?)))
+            .collect::<Option<Vec<_>>>()?;
+        generics.sort_by(|a, b| a.0.cmp(&b.0));
+        let args = args
+            .iter()
+            .map(|a| const_value(&a.term).ok_or_else(|| check(&a.term)))
+            .collect();
+        Some(Self {
+            path: path.to_path_buf(),
+            name: name.to_owned(),
+            generics,
+            args,
+        })
+    }
+}
+
+/// An embedded instantiation
+pub(super) struct Instance {
+    /// for each argument, the variable standing for it (`None` for constant arguments)
+    params: Vec<Option<Term>>,
+    /// the return value, in terms of `params`
+    ret: T,
+    /// the assertions made by the body, in terms of `params`
+    assertions: Vec<Term>,
+    /// the callee computation that calls should use, if any
+    callee: Option<(String, Computation)>,
+}
+
+/// The sizes of [ZGen]'s stacks, to unwind to if embedding an instantiation fails
+struct Depths {
+    files: usize,
+    generics: usize,
+    ret_tys: usize,
+    assertions: usize,
+    fns: usize,
+    lhs_ty: Option<Ty>,
+}
+
+impl<'ast> ZGen<'ast> {
+    /// Embed a (non-constant) call using a memoized instantiation.
+    ///
+    /// Returns `None` if the call should be inlined instead: memoization is disabled, assertions
+    /// are isolated, a generic value is not constant, embedding the instantiation failed, or the
+    /// call is a recursive call to the instantiation being embedded.
+    pub(super) fn memoized_call(
+        &self,
+        egv: &[ast::ConstantGenericValue<'ast>],
+        exp_ty: Option<Ty>,
+        generics: &HashMap<String, T>,
+        args: &[T],
+        f_path: &Path,
+        f_name: &str,
+    ) -> Option<T> {
+        if !self.memoize || self.isolate_asserts {
+            return None;
+        }
+        let key = InstanceKey::new(f_path, f_name, generics, args)?;
+        let cached = self.instances.borrow().get(&key).cloned();
+        let inst = match cached {
+            Some(inst) => inst?,
+            None => {
+                // recursive calls to this instantiation (while we embed it) are inlined
+                let n = self.instances.borrow().len();
+                self.instances.borrow_mut().insert(key.clone(), None);
+                let inst = self.instantiate(n, &key, egv, exp_ty, args).map(Rc::new);
+                self.instances.borrow_mut().insert(key, inst.clone());
+                inst?
+            }
+        };
+        Some(self.apply_instance(&inst, args))
+    }
+
+    /// Embed the `n`th instantiation, `key`.
+    fn instantiate(
+        &self,
+        n: usize,
+        key: &InstanceKey,
+        egv: &[ast::ConstantGenericValue<'ast>],
+        exp_ty: Option<Ty>,
+        args: &[T],
+    ) -> Option<Instance> {
+        debug!("Instantiating {} ({})", key.name, key.path.display());
+        let params: Vec<Option<Term>> = key
+            .args
+            .iter()
+            .enumerate()
+            .map(|(i, a)| match a {
+                Ok(_) => None,
+                Err(s) => Some(leaf_term(Op::Var(
+                    format!("{}_inst{n}_arg{i}", key.name),
+                    s.clone(),
+                ))),
+            })
+            .collect();
+        let inst_args = args
+            .iter()
+            .zip(&params)
+            .map(|(a, p)| match p {
+                Some(v) => T::new(a.ty.clone(), v.clone()),
+                None => a.clone(),
+            })
+            .collect();
+
+        let depths = self.depths();
+        let r = self.function_call_impl_::<false>(
+            inst_args,
+            egv,
+            exp_ty,
+            key.path.clone(),
+            key.name.clone(),
+        );
+        let assertions = self.assertions.borrow_mut().split_off(depths.assertions);
+        let ret = match r {
+            Ok(ret) => ret,
+            Err(e) => {
+                debug!("Inlining {} instead: {}", key.name, e);
+                self.unwind(depths);
+                return None;
+            }
+        };
+
+        let callee = if self.emit_calls && params.iter().any(|p| p.is_some()) {
+            let mut c = Computation::new();
+            for p in params.iter().flatten() {
+                if let Op::Var(name, sort) = p.op() {
+                    c.new_var(name, sort.clone(), None, None);
+                }
+            }
+            c.outputs.push(ret.term.clone());
+            c.outputs.push(match assertions.len() {
+                0 => bool_lit(true),
+                1 => assertions[0].clone(),
+                _ => term(AND, assertions.clone()),
+            });
+            Some((format!("{}_inst{n}", key.name), c))
+        } else {
+            None
+        };
+        Some(Instance {
+            params,
+            ret,
+            assertions,
+            callee,
+        })
+    }
+
+    /// Embed a call to `inst` on `args`.
+    fn apply_instance(&self, inst: &Instance, args: &[T]) -> T {
+        if let Some((name, callee)) = &inst.callee {
+            self.callees
+                .borrow_mut()
+                .entry(name.clone())
+                .or_insert_with(|| callee.clone());
+            let call_args = inst
+                .params
+                .iter()
+                .zip(args)
+                .filter_map(|(p, a)| match p.as_ref()?.op() {
+                    Op::Var(name, _) => Some((name.clone(), a.term.clone())),
+                    _ => None,
+                })
+                .collect();
+            let ret_sort = Sort::Tuple(vec![check(&inst.ret.term), Sort::Bool].into());
+            let call = callee
+                .metadata
+                .ordered_call_term(name.clone(), call_args, ret_sort);
+            self.assertions
+                .borrow_mut()
+                .push(term![Op::Field(1); call.clone()]);
+            return T::new(inst.ret.ty.clone(), term![Op::Field(0); call]);
+        }
+
+        let mut subs: TermMap<Term> = TermMap::default();
+        for (p, a) in inst.params.iter().zip(args) {
+            if let Some(p) = p {
+                subs.insert(p.clone(), a.term.clone());
+            }
+        }
+        for a in &inst.assertions {
+            let new_a = extras::substitute_cache(a, &mut subs);
+            let loc = self.circ.borrow().location(a);
+            if let Some(loc) = loc {
+                self.circ.borrow().locate(new_a.clone(), loc);
+            }
+            self.assertions.borrow_mut().push(new_a);
+        }
+        T::new(
+            inst.ret.ty.clone(),
+            extras::substitute_cache(&inst.ret.term, &mut subs),
+        )
+    }
+
+    fn depths(&self) -> Depths {
+        Depths {
+            files: self.file_stack.borrow().len(),
+            generics: self.generics_stack.borrow().len(),
+            ret_tys: self.ret_ty_stack.borrow().len(),
+            assertions: self.assertions.borrow().len(),
+            fns: self.circ.borrow().fn_depth(),
+            lhs_ty: self.lhs_ty.borrow().clone(),
+        }
+    }
+
+    fn unwind(&self, d: Depths) {
+        self.file_stack.borrow_mut().truncate(d.files);
+        self.generics_stack.borrow_mut().truncate(d.generics);
+        self.ret_ty_stack.borrow_mut().truncate(d.ret_tys);
+        self.assertions.borrow_mut().truncate(d.assertions);
+        self.circ.borrow_mut().unwind_fns(d.fns);
+        self.lhs_ty.replace(d.lhs_ty);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::parser::ZLoad;
+    use super::*;
+    use crate::cfg::{cfg, cfg_or_default};
+    use crate::front::Mode;
+    use crate::ir::opt::link::link_all_function_calls;
+    use fxhash::FxHashMap;
+    use rug::Integer;
+
+    /// The result of compiling and evaluating a program
+    #[derive(Debug, PartialEq)]
+    struct Outcome {
+        /// the value of `return`
+        ret: Value,
+        /// whether the assertions hold
+        ok: bool,
+        /// how many instantiations were embedded, and how many had to be inlined instead
+        instances: (usize, usize),
+    }
+
+    /// Compile `src` in proof mode, then evaluate it on `args`.
+    fn run(src: &str, memoize: bool, calls: bool, args: &[(&str, u64)]) -> Result<Outcome, String> {
+        cfg_or_default();
+        let path = PathBuf::from("/virtual/main.zok");
+        let loader = ZLoad::with_overlay(HashMap::from([(path.clone(), src.to_owned())]));
+        let asts = loader.load(&path).map_err(|e| e.to_string())?;
+        let mut g = ZGen::new(asts, Mode::Proof, loader.stdlib(), false);
+        g.memoize = memoize;
+        g.emit_calls = calls;
+        g.visit_files().map_err(|e| e.to_string())?;
+        g.file_stack_push(path);
+        g.generics_stack_push(HashMap::new());
+        g.entry_fn("main").map_err(|e| e.to_string())?;
+        g.generics_stack_pop();
+        g.file_stack_pop();
+        let instances = {
+            let is = g.instances.borrow();
+            let embedded = is.values().filter(|i| i.is_some()).count();
+            (embedded, is.len() - embedded)
+        };
+
+        let mut cs = Computations::new();
+        cs.comps.extend(g.callees.take());
+        let main = g.into_circify().consume().borrow().clone();
+        cs.comps.insert("main".to_owned(), main);
+        link_all_function_calls(&mut cs);
+        let main = cs.get("main");
+        let values: FxHashMap<String, Value> = args
+            .iter()
+            .map(|(name, v)| {
+                let v = match main.metadata.input_sort(name) {
+                    Sort::Field(_) => Value::Field(cfg().field().new_v(*v)),
+                    Sort::BitVector(w) => Value::BitVector(BitVector::new(Integer::from(*v), w)),
+                    s => panic!("unsupported input sort {}", s),
+                };
+                (name.to_string(), v)
+            })
+            .collect();
+        let values = main.precomputes.eval(&values);
+        Ok(Outcome {
+            ret: values.get("return").expect("no return value").clone(),
+            ok: main.outputs.iter().all(|o| eval(o, &values).as_bool()),
+            instances,
+        })
+    }
+
+    #[test]
+    fn stdlib_hash() {
+        let src = "\
+from \"hashes/mimc7/mimc7\" import main as mimc7
+
+def main(private field x, private field k) -> field:
+    return mimc7::<10>(x, k) + mimc7::<10>(k, x) + mimc7::<10>(x, x)
+";
+        let args = [("x", 3), ("k", 5)];
+        let inlined = run(src, false, false, &args).unwrap();
+        let memoized = run(src, true, false, &args).unwrap();
+        assert_eq!(inlined.instances, (0, 0));
+        assert_eq!(memoized.instances, (1, 0));
+        assert_eq!(inlined.ret, memoized.ret);
+        assert!(memoized.ok);
+        let called = run(src, true, true, &args).unwrap();
+        assert_eq!(inlined.ret, called.ret);
+        assert!(called.ok);
+    }
+
+    #[test]
+    fn asserting_call() {
+        let src = "\
+def inv(field x, field y) -> field:
+    assert(x * y == 1)
+    return x + y
+
+def main(private field a, private field b) -> field:
+    return inv(a, b) + inv(b, a)
+";
+        for (args, holds) in [([("a", 1), ("b", 1)], true), ([("a", 2), ("b", 3)], false)] {
+            let inlined = run(src, false, false, &args).unwrap();
+            assert_eq!(inlined.ok, holds);
+            for calls in [false, true] {
+                let memoized = run(src, true, calls, &args).unwrap();
+                assert_eq!(memoized.instances, (1, 0));
+                assert_eq!(inlined.ret, memoized.ret);
+                assert_eq!(inlined.ok, memoized.ok);
+            }
+        }
+    }
+
+    #[test]
+    fn failed_instantiation() {
+        // the loop bound is not constant, so neither the instantiation nor the inlined call can
+        // be embedded
+        let src = "\
+def sum(u32 n) -> u32:
+    u32 s = 0
+    for u32 i in 0..n do
+        s = s + i
+    endfor
+    return s
+
+def main(private u32 x) -> u32:
+    return sum(x)
+";
+        let args = [("x", 4)];
+        let inlined = run(src, false, false, &args).unwrap_err();
+        let memoized = run(src, true, false, &args).unwrap_err();
+        assert_eq!(inlined, memoized);
+    }
+}
@@ -1,9 +1,15 @@
 //! The ZoKrates/Z# front-end
 
+// Errors are [Diagnostic]s, which carry source ranges, notes, and suggestions.
+#![allow(clippy::result_large_err)]
+
+pub mod ide;
+mod memo;
 mod parser;
 mod term;
 pub mod zvisit;
 
+use super::diagnostic::{Diagnostic, Diagnostics, Position, SourceRange};
 use super::{FrontEnd, Mode};
 use crate::cfg::cfg;
 use crate::circify::{CircError, Circify, Loc, Val};
@@ -14,9 +20,10 @@ use crate::ir::term::*;
 use log::{debug, trace, warn};
 use rug::Integer;
 use std::cell::{Cell, RefCell};
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::fmt::Display;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use zokrates_pest_ast as ast;
 
@@ -26,6 +33,36 @@ use zvisit::{ZConstLiteralRewriter, ZGenericInf, ZStatementWalker, ZVisitorMut};
 // garbage collection increment for adaptive GC threshold
 const GC_INC: usize = 32;
 
+// separates a statement's error from the statement's text
+const STMT_CONTEXT: &str = "; context:\n";
+
+// begins the error from a false constant assertion
+const CONST_ASSERT_FAILED: &str = "Const assert failed";
+
+/// Codes for the [Diagnostic]s of the Z# front-end
+pub mod codes {
+    /// The program does not parse
+    pub const PARSE: &str = "parse-error";
+    /// An import is bad
+    pub const IMPORT: &str = "import-error";
+    /// A name is defined twice
+    pub const REDEFINITION: &str = "redefinition";
+    /// The program does not type-check
+    pub const TYPE: &str = "type-error";
+    /// A constant cannot be evaluated
+    pub const CONST: &str = "const-error";
+    /// The entry function is unusable
+    pub const ENTRY: &str = "entry-error";
+    /// An input's visibility is bad
+    pub const VISIBILITY: &str = "visibility-error";
+    /// The program cannot be compiled
+    pub const COMPILE: &str = "compile-error";
+    /// An input to the interpreter is missing or ill-typed
+    pub const INPUT: &str = "input-error";
+    /// An assertion failed while interpreting
+    pub const ASSERT: &str = "assertion-failed";
+}
+
 /// Inputs to the Z# compiler
 pub struct Inputs {
     /// The file to look for `main` in.
@@ -40,42 +77,79 @@ pub struct ZSharpFE;
 impl FrontEnd for ZSharpFE {
     type Inputs = Inputs;
     fn gen(i: Inputs) -> Computations {
+        Self::try_gen(i).unwrap_or_else(panic_with)
+    }
+    fn try_gen(i: Inputs) -> Result<Computations, Diagnostics> {
         debug!(
             "Starting Z# front-end, field: {}",
             Sort::Field(cfg().field().clone())
         );
         let loader = parser::ZLoad::new();
-        let asts = loader.load(&i.file);
+        let asts = loader.load(&i.file)?;
         let mut g = ZGen::new(asts, i.mode, loader.stdlib(), cfg().zsharp.isolate_asserts);
-        g.visit_files();
+        g.visit_files()?;
         g.file_stack_push(i.file);
         g.generics_stack_push(HashMap::new());
-        g.entry_fn("main");
+        g.entry_fn("main")?;
         g.generics_stack_pop();
         g.file_stack_pop();
 
         let mut cs = Computations::new();
+        cs.comps.extend(g.callees.take());
         let main_comp = std::rc::Rc::try_unwrap(g.into_circify().consume())
             .unwrap_or_else(|rc| (*rc).clone())
             .into_inner();
         cs.comps.insert("main".to_string(), main_comp);
-        cs
+        Ok(cs)
     }
 }
 
 impl ZSharpFE {
     /// Execute the Z# front-end interpreter on the supplied file with the supplied inputs
     pub fn interpret(i: Inputs) -> T {
+        Self::try_interpret(i).unwrap_or_else(panic_with)
+    }
+
+    /// Execute the Z# front-end interpreter, reporting problems with the program as
+    /// [Diagnostics].
+    pub fn try_interpret(i: Inputs) -> Result<T, Diagnostics> {
+        Self::interpret_impl(i, None)
+    }
+
+    /// Execute the Z# front-end interpreter, running `main` on arguments from `values`.
+    ///
+    /// `values` maps each parameter to its value, as in the `.pin` and `.vin` files read by
+    /// [crate::ir::term::text::parse_value_map]. If it includes a return value, `main` must return
+    /// it.
+    pub fn interpret_on(i: Inputs, values: &HashMap<String, Value>) -> T {
+        Self::try_interpret_on(i, values).unwrap_or_else(panic_with)
+    }
+
+    /// Like [ZSharpFE::interpret_on], but reporting problems (including failed assertions) as
+    /// [Diagnostics].
+    pub fn try_interpret_on(i: Inputs, values: &HashMap<String, Value>) -> Result<T, Diagnostics> {
+        Self::interpret_impl(i, Some(values))
+    }
+
+    fn interpret_impl(
+        i: Inputs,
+        values: Option<&HashMap<String, Value>>,
+    ) -> Result<T, Diagnostics> {
         let loader = parser::ZLoad::new();
-        let asts = loader.load(&i.file);
+        let asts = loader.load(&i.file)?;
         let mut g = ZGen::new(asts, i.mode, loader.stdlib(), cfg().zsharp.isolate_asserts);
-        g.visit_files();
+        g.visit_files()?;
         g.file_stack_push(i.file);
         g.generics_stack_push(HashMap::new());
-        g.const_entry_fn("main")
+        g.const_entry_fn("main", values).map_err(Diagnostics::from)
     }
 }
 
+/// Panic, showing `ds`. Binaries that would rather exit call the `try_` functions instead.
+fn panic_with<T>(ds: Diagnostics) -> T {
+    panic!("{}", ds)
+}
+
 struct ZGen<'ast> {
     circ: RefCell<Circify<ZSharp>>,
     stdlib: &'ast parser::ZStdLib,
@@ -98,6 +172,14 @@ struct ZGen<'ast> {
     gc_depth_estimate: Cell<usize>,
     assertions: RefCell<Vec<Term>>,
     isolate_asserts: bool,
+    // where the last constant assertion failed
+    failed_assert: RefCell<Option<SourceRange>>,
+    // embedded instantiations of functions; `None` for those that must be inlined
+    instances: RefCell<HashMap<memo::InstanceKey, Option<std::rc::Rc<memo::Instance>>>>,
+    memoize: bool,
+    emit_calls: bool,
+    // computations for instantiations that are called with `Op::Call`
+    callees: RefCell<HashMap<String, Computation>>,
 }
 
 impl<'ast> Drop for ZGen<'ast> {
@@ -111,6 +193,8 @@ impl<'ast> Drop for ZGen<'ast> {
         drop(self.crets_stack.take());
         drop(self.lhs_ty.take());
         drop(self.ret_ty_stack.take());
+        drop(self.instances.take());
+        drop(self.callees.take());
 
         // force garbage collection
         garbage_collect();
@@ -169,14 +253,20 @@ impl<'ast> ZGen<'ast> {
             gc_depth_estimate: Cell::new(2 * GC_INC),
             assertions: Default::default(),
             isolate_asserts,
+            failed_assert: Default::default(),
+            instances: Default::default(),
+            memoize: cfg().zsharp.memoize,
+            emit_calls: cfg().zsharp.calls,
+            callees: Default::default(),
         };
-        this.circ
-            .borrow()
-            .cir_ctx()
-            .cs
-            .borrow_mut()
-            .metadata
-            .add_prover_and_verifier();
+        {
+            let circ = this.circ.borrow();
+            let mut cs = circ.cir_ctx().cs.borrow_mut();
+            match this.mode {
+                Mode::Mpc(n_parties) => cs.metadata.add_mpc_parties(n_parties),
+                _ => cs.metadata.add_prover_and_verifier(),
+            }
+        }
         this
     }
 
@@ -184,16 +274,40 @@ impl<'ast> ZGen<'ast> {
         self.circ.replace(Circify::new(ZSharp::new()))
     }
 
-    /// Unwrap a result with a span-dependent error
-    fn err<E: Display>(&self, e: E, s: &ast::Span) -> ! {
-        println!("Error: {e}");
-        println!("In: {}", self.cur_path().canonicalize().unwrap().display());
-        s.lines().for_each(|l| print!("  {l}"));
-        std::process::exit(1)
+    /// An error at span `s` of the current file
+    fn err<E: Display>(&self, code: &'static str, e: E, s: &ast::Span) -> Diagnostic {
+        Diagnostic::error(code, e).at(self.span_range(s))
+    }
+
+    /// Attach span `s` of the current file to an error
+    fn with_span<T, E: Display>(
+        &self,
+        code: &'static str,
+        r: Result<T, E>,
+        s: &ast::Span,
+    ) -> Result<T, Diagnostic> {
+        r.map_err(|e| self.err(code, e, s))
+    }
+
+    /// An error from statement `s` of the current file.
+    ///
+    /// Errors from nested statements (e.g., in called functions) carry the text of those
+    /// statements as context; that context becomes notes.
+    fn stmt_err(&self, e: String, s: &ast::Span) -> Diagnostic {
+        let mut parts = e.split(STMT_CONTEXT).collect::<Vec<_>>();
+        // the outermost context is usually `s` itself
+        if parts.len() > 1 && parts.last().unwrap().trim() == span_to_string(s).trim() {
+            parts.pop();
+        }
+        let mut d = self.err(codes::COMPILE, parts[0], s);
+        for ctx in &parts[1..] {
+            d = d.note(format!("in `{}`", ctx.trim()));
+        }
+        d
     }
 
-    fn unwrap<T, E: Display>(&self, r: Result<T, E>, s: &ast::Span) -> T {
-        r.unwrap_or_else(|e| self.err(e, s))
+    fn span_range(&self, s: &ast::Span) -> SourceRange {
+        span_range(self.cur_path(), s)
     }
 
     fn builtin_call(f_name: &str, mut args: Vec<T>, mut generics: Vec<T>) -> Result<T, String> {
@@ -523,8 +637,11 @@ impl<'ast> ZGen<'ast> {
             .get(&f_name)
             .ok_or_else(|| format!("No function '{}' attempting fn call", &f_name))?;
         let arg_tys = args.iter().map(|arg| arg.type_().clone());
-        let generics = ZGenericInf::<IS_CNST>::new(self, f, &f_path, &f_name)
-            .unify_generic(egv, exp_ty, arg_tys)?;
+        let generics = ZGenericInf::<IS_CNST>::new(self, f, &f_path, &f_name).unify_generic(
+            egv,
+            exp_ty.clone(),
+            arg_tys,
+        )?;
 
         if self.stdlib.is_embed(&f_path) {
             let mut generics = generics;
@@ -542,8 +659,6 @@ impl<'ast> ZGen<'ast> {
                 .collect::<Result<Vec<_>, _>>()?;
             Self::builtin_call(&f_name, args, generics)
         } else {
-            // XXX(unimpl) multi-return unimplemented
-            assert!(f.returns.len() <= 1);
             if f.generics.len() != generics.len() {
                 return Err(format!(
                     "Wrong number of generic params calling {} (got {}, expected {})",
@@ -560,18 +675,19 @@ impl<'ast> ZGen<'ast> {
                     f.parameters.len()
                 ));
             }
+            if !IS_CNST {
+                if let Some(r) = self.memoized_call(egv, exp_ty, &generics, &args, &f_path, &f_name)
+                {
+                    return Ok(r);
+                }
+            }
 
             let f = f.clone();
             self.file_stack_push(f_path);
             self.generics_stack_push(generics);
             self.ret_ty_stack_push::<IS_CNST>(&f)?;
 
-            // XXX(unimpl) multi-return unimplemented
-            let ret_ty = f
-                .returns
-                .first()
-                .map(|r| self.type_impl_::<IS_CNST>(r))
-                .transpose()?;
+            let ret_ty = self.ret_ty_impl_::<IS_CNST>(&f)?;
             let ret_ty = if IS_CNST {
                 self.cvar_enter_function();
                 ret_ty
@@ -641,59 +757,121 @@ impl<'ast> ZGen<'ast> {
         }
     }
 
-    fn const_entry_fn(&self, n: &str) -> T {
+    fn const_entry_fn(
+        &self,
+        n: &str,
+        values: Option<&HashMap<String, Value>>,
+    ) -> Result<T, Diagnostic> {
         debug!("Const entry: {}", n);
         let (f_file, f_name) = self.deref_import(n);
-        if let Some(f) = self.functions.get(&f_file).and_then(|m| m.get(&f_name)) {
-            if !f.generics.is_empty() {
-                panic!("const_entry_fn cannot be called on a generic function")
-            } else if !f.parameters.is_empty() {
-                panic!("const_entry_fn must be called on a function with zero arguments")
+        let f = self.entry_fn_def(&f_file, &f_name)?;
+        if !f.generics.is_empty() {
+            return Err(self.err(
+                codes::ENTRY,
+                "Entry function cannot be generic when interpreting",
+                &f.span,
+            ));
+        }
+        let args = match values {
+            Some(values) => f
+                .parameters
+                .iter()
+                .map(|p| {
+                    let ty = self.type_(&p.ty)?;
+                    let v = value_from_map(&ty, &p.id.value, values);
+                    self.with_span(codes::INPUT, v, &p.span)
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None if f.parameters.is_empty() => Vec::new(),
+            None => {
+                return Err(self
+                    .err(
+                        codes::ENTRY,
+                        "Entry function cannot have parameters when interpreting",
+                        &f.span,
+                    )
+                    .suggest("supply its arguments in an input file"))
+            }
+        };
+
+        let span = f.span;
+        self.failed_assert.take();
+        let r = self
+            .function_call_impl_::<true>(args, &[][..], None, f_file, f_name)
+            .map_err(|e| {
+                let d = self.stmt_err(e, &span);
+                match self.failed_assert.take() {
+                    Some(range) if d.message.starts_with(CONST_ASSERT_FAILED) => Diagnostic {
+                        code: codes::ASSERT,
+                        range: Some(range),
+                        ..d
+                    },
+                    _ => d,
+                }
+            })?;
+
+        // check the return value, if one is expected
+        let expects_return = |vs: &&HashMap<String, Value>| {
+            vs.keys().any(|k| k == "return" || k.starts_with("return."))
+        };
+        if let Some(values) = values.filter(expects_return) {
+            let expected = value_from_map(&r.ty, "return", values);
+            let expected = self.with_span(codes::INPUT, expected, &span)?;
+            if !const_eq(&r, &expected) {
+                return Err(self.err(
+                    codes::ASSERT,
+                    format!("{n} returned {r}, but {expected} was expected"),
+                    &span,
+                ));
             }
-        } else {
-            panic!(
-                "No function '{:?}//{}' attempting const_entry_fn",
-                &f_file, &f_name
-            );
         }
+        Ok(r)
+    }
 
-        self.function_call_impl_::<true>(Vec::new(), &[][..], None, f_file, f_name)
-            .unwrap_or_else(|e| panic!("const_entry_fn failed: {}", e))
+    fn entry_fn_def(
+        &self,
+        f_file: &Path,
+        f_name: &str,
+    ) -> Result<&ast::FunctionDefinition<'ast>, Diagnostic> {
+        self.functions
+            .get(f_file)
+            .and_then(|m| m.get(f_name))
+            .ok_or_else(|| {
+                Diagnostic::error(
+                    codes::ENTRY,
+                    format!("No function '{f_name}' in {}", f_file.display()),
+                )
+            })
     }
 
-    fn entry_fn(&self, n: &str) {
+    fn entry_fn(&self, n: &str) -> Result<(), Diagnostic> {
         debug!("Entry: {}", n);
         // find the entry function
         let (f_file, f_name) = self.deref_import(n);
-        let f = self
-            .functions
-            .get(&f_file)
-            .unwrap_or_else(|| panic!("No file '{:?}'", &f_file))
-            .get(&f_name)
-            .unwrap_or_else(|| panic!("No function '{}'", &f_name))
-            .clone();
-        // XXX(unimpl) tuple returns not supported
-        assert!(f.returns.len() <= 1);
+        let f = self.entry_fn_def(&f_file, &f_name)?.clone();
         if !f.generics.is_empty() {
-            self.err("Entry function cannot be generic. Try adding a wrapper function that supplies an explicit generic argument.", &f.span);
+            return Err(self
+                .err(codes::ENTRY, "Entry function cannot be generic", &f.span)
+                .suggest("add a wrapper function that supplies explicit generic arguments"));
         }
         // get return type
-        let ret_ty = f.returns.first().map(|r| self.type_(r));
+        let ret_ty = self.with_span(codes::TYPE, self.ret_ty_impl_::<false>(&f), &f.span)?;
         // set up stack frame for entry function
         self.circ_enter_fn(n.to_owned(), ret_ty.clone());
         let mut persistent_arrays: Vec<String> = Vec::new();
         for p in f.parameters.iter() {
-            let ty = self.type_(&p.ty);
+            let ty = self.type_(&p.ty)?;
             debug!("Entry param: {}: {}", p.id.value, ty);
-            let vis = self.interpret_visibility(&p.visibility);
+            let vis = self.interpret_visibility(&p.visibility, &p.span)?;
             if let ZVis::Committed = &vis {
                 persistent_arrays.push(p.id.value.clone());
             }
             let r = self.circ_declare_input(p.id.value.clone(), &ty, vis, None, false);
-            self.unwrap(r, &p.span);
+            self.with_span(codes::COMPILE, r, &p.span)?;
         }
         for s in &f.statements {
-            self.unwrap(self.stmt_impl_::<false>(s), s.span());
+            self.stmt_impl_::<false>(s)
+                .map_err(|e| self.stmt_err(e, s.span()))?;
         }
         for a in persistent_arrays {
             let term = self
@@ -743,7 +921,7 @@ impl<'ast> ZGen<'ast> {
                     );
                     let t = ret_terms.into_iter().next().unwrap();
                     let t_sort = check(&t);
-                    if !matches!(t_sort, Sort::BitVector(_)) {
+                    if !matches!(t_sort, Sort::BitVector(_) | Sort::Field(_)) {
                         panic!("Cannot maximize output of type {}", t_sort);
                     }
                     self.circ.borrow().cir_ctx().cs.borrow_mut().outputs.push(t);
@@ -770,46 +948,66 @@ impl<'ast> ZGen<'ast> {
                 }
             }
         }
+        Ok(())
     }
-    fn interpret_visibility(&self, visibility: &Option<ast::Visibility<'ast>>) -> ZVis {
+    fn interpret_visibility(
+        &self,
+        visibility: &Option<ast::Visibility<'ast>>,
+        param_span: &ast::Span,
+    ) -> Result<ZVis, Diagnostic> {
         match visibility {
-            None | Some(ast::Visibility::Public(_)) => ZVis::Public,
+            None | Some(ast::Visibility::Public(_)) => Ok(ZVis::Public),
             Some(ast::Visibility::Committed(_)) => match self.mode {
-                Mode::Proof => ZVis::Committed,
-                _ => unimplemented!(),
+                Mode::Proof => Ok(ZVis::Committed),
+                _ => Err(self.err(
+                    codes::VISIBILITY,
+                    format!(
+                        "Committed inputs are not supported in a {} circuit",
+                        self.mode
+                    ),
+                    param_span,
+                )),
             },
             Some(ast::Visibility::Private(private)) => match self.mode {
                 Mode::Proof | Mode::Opt | Mode::ProofOfHighValue(_) => {
                     if private.number.is_some() {
-                        self.err(
-                            format!(
-                                "Party number found, but we're generating a {} circuit",
-                                self.mode
-                            ),
-                            &private.span,
-                        );
+                        return Err(self
+                            .err(
+                                codes::VISIBILITY,
+                                format!(
+                                    "Party number found, but we're generating a {} circuit",
+                                    self.mode
+                                ),
+                                &private.span,
+                            )
+                            .suggest("remove the party number"));
                     }
-                    ZVis::Private(PROVER_ID)
+                    Ok(ZVis::Private(PROVER_ID))
                 }
                 Mode::Mpc(n_parties) => {
-                    let num_str = private
-                        .number
-                        .as_ref()
-                        .unwrap_or_else(|| self.err("No party number", &private.span));
-                    let num_val = num_str.value[1..num_str.value.len() - 1]
-                        .parse::<u8>()
-                        .unwrap_or_else(|e| {
-                            self.err(format!("Bad party number: {e}"), &private.span)
-                        });
-                    if num_val <= n_parties {
-                        ZVis::Private(num_val - 1)
+                    let num_str = private.number.as_ref().ok_or_else(|| {
+                        self.err(codes::VISIBILITY, "No party number", &private.span)
+                            .suggest(format!(
+                                "write `private<i>` for party i, numbered 1 to {n_parties}"
+                            ))
+                    })?;
+                    let num_val = self.with_span(
+                        codes::VISIBILITY,
+                        num_str.value[1..num_str.value.len() - 1]
+                            .parse::<u8>()
+                            .map_err(|e| format!("Bad party number: {e}")),
+                        &private.span,
+                    )?;
+                    if (1..=n_parties).contains(&num_val) {
+                        Ok(ZVis::Private(num_val - 1))
                     } else {
-                        self.err(
+                        Err(self.err(
+                            codes::VISIBILITY,
                             format!(
-                                "Party number {num_val} greater than the number of parties ({n_parties})"
+                                "Party number {num_val} out of range for {n_parties} parties (numbered from 1)"
                             ),
                             &private.span,
-                        )
+                        ))
                     }
                 }
             },
@@ -881,11 +1079,10 @@ impl<'ast> ZGen<'ast> {
         {
             Some(v) => Ok(v),
             None if IS_CNST => self.cvar_lookup(&i.value).ok_or_else(|| {
-                format!(
-                    "Undefined const identifier {} in {}",
-                    &i.value,
-                    self.cur_path().canonicalize().unwrap().to_string_lossy()
-                )
+                format!("Undefined const identifier {} in {}", &i.value, {
+                    let p = self.cur_path();
+                    p.canonicalize().unwrap_or(p).to_string_lossy().into_owned()
+                })
             }),
             _ => match self
                 .circ_get_value(Loc::local(i.value.clone()))
@@ -1116,16 +1313,17 @@ impl<'ast> ZGen<'ast> {
 
         match s {
             ast::Statement::Return(r) => {
-                // XXX(unimpl) multi-return unimplemented
-                assert!(r.expressions.len() <= 1);
-                if let Some(e) = r.expressions.first() {
-                    self.set_lhs_ty_ret(r);
-                    let ret = self.expr_impl_::<IS_CNST>(e)?;
-                    self.ret_impl_::<IS_CNST>(Some(ret))
-                } else {
-                    self.ret_impl_::<IS_CNST>(None)
-                }
-                .map_err(|e| format!("{e}"))
+                let rets = r
+                    .expressions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, e)| {
+                        self.set_lhs_ty_ret(r, i);
+                        self.expr_impl_::<IS_CNST>(e)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.ret_impl_::<IS_CNST>(T::from_returns(rets))
+                    .map_err(|e| format!("{e}"))
             }
             ast::Statement::Assertion(e) => {
                 match self.expr_impl_::<true>(&e.expression).and_then(|v| {
@@ -1133,14 +1331,18 @@ impl<'ast> ZGen<'ast> {
                         .ok_or_else(|| "interpreting expr as const bool failed".to_string())
                 }) {
                     Ok(true) => Ok(()),
-                    Ok(false) => Err(format!(
-                        "Const assert failed: {} at\n{}",
-                        e.message
-                            .as_ref()
-                            .map(|m| m.value.as_ref())
-                            .unwrap_or("(no error message given)"),
-                        span_to_string(e.expression.span()),
-                    )),
+                    Ok(false) => Err({
+                        self.failed_assert
+                            .replace(Some(self.span_range(e.expression.span())));
+                        format!(
+                            "{CONST_ASSERT_FAILED}: {} at\n{}",
+                            e.message
+                                .as_ref()
+                                .map(|m| m.value.as_ref())
+                                .unwrap_or("(no error message given)"),
+                            span_to_string(e.expression.span()),
+                        )
+                    }),
                     Err(err) if IS_CNST => Err(format!(
                         "Const assert expression eval failed {} at\n{}",
                         err,
@@ -1148,7 +1350,7 @@ impl<'ast> ZGen<'ast> {
                     )),
                     _ => {
                         let b = bool(self.expr_impl_::<false>(&e.expression)?)?;
-                        self.assert(b);
+                        self.assert(b, &e.span);
                         Ok(())
                     }
                 }
@@ -1200,45 +1402,65 @@ impl<'ast> ZGen<'ast> {
                 Ok(())
             }
             ast::Statement::Definition(d) => {
-                // XXX(unimpl) multi-assignment unimplemented
-                assert!(d.lhs.len() <= 1);
-
                 self.set_lhs_ty_defn::<IS_CNST>(d)?;
                 let e = self.expr_impl_::<IS_CNST>(&d.expression)?;
+                let strict = match &d.expression {
+                    ast::Expression::Unary(u) => matches!(&u.op, ast::UnaryOperator::Strict(_)),
+                    _ => false,
+                };
 
-                if let Some(l) = d.lhs.first() {
-                    match l {
-                        ast::TypedIdentifierOrAssignee::Assignee(l) => {
-                            let strict = match &d.expression {
-                                ast::Expression::Unary(u) => {
-                                    matches!(&u.op, ast::UnaryOperator::Strict(_))
-                                }
-                                _ => false,
-                            };
-                            self.assign_impl_::<IS_CNST>(&l.id.value, &l.accesses[..], e, strict)
-                        }
-                        ast::TypedIdentifierOrAssignee::TypedIdentifier(l) => {
-                            let decl_ty = self.type_impl_::<IS_CNST>(&l.ty)?;
-                            let ty = e.type_();
-                            if &decl_ty != ty {
-                                return Err(format!(
-                                    "Assignment type mismatch: {decl_ty} annotated vs {ty} actual",
-                                ));
-                            }
-                            self.declare_init_impl_::<IS_CNST>(
-                                l.identifier.value.clone(),
-                                decl_ty,
-                                e,
-                            )
+                match &d.lhs[..] {
+                    [] => {
+                        warn!("Statement with no LHS!");
+                        Ok(())
+                    }
+                    [l] => self.def_impl_::<IS_CNST>(l, e, strict),
+                    ls => {
+                        let es = e.unwrap_tuple()?;
+                        if es.len() != ls.len() {
+                            return Err(format!(
+                                "Multi-assignment to {} places from {} values",
+                                ls.len(),
+                                es.len(),
+                            ));
                         }
+                        ls.iter()
+                            .zip(es)
+                            .try_for_each(|(l, e)| self.def_impl_::<IS_CNST>(l, e, strict))
                     }
-                } else {
-                    warn!("Statement with no LHS!");
-                    Ok(())
                 }
             }
         }
-        .map_err(|err| format!("{}; context:\n{}", err, span_to_string(s.span())))
+        .map_err(|err| format!("{}{}{}", err, STMT_CONTEXT, span_to_string(s.span())))
+    }
+
+    fn def_impl_<const IS_CNST: bool>(
+        &self,
+        l: &ast::TypedIdentifierOrAssignee<'ast>,
+        e: T,
+        strict: bool,
+    ) -> Result<(), String> {
+        match l {
+            ast::TypedIdentifierOrAssignee::Assignee(l) => {
+                if !IS_CNST {
+                    self.locate(&e.term, &l.span, Some(&l.id.value));
+                }
+                self.assign_impl_::<IS_CNST>(&l.id.value, &l.accesses[..], e, strict)
+            }
+            ast::TypedIdentifierOrAssignee::TypedIdentifier(l) => {
+                if !IS_CNST {
+                    self.locate(&e.term, &l.span, Some(&l.identifier.value));
+                }
+                let decl_ty = self.type_impl_::<IS_CNST>(&l.ty)?;
+                let ty = e.type_();
+                if &decl_ty != ty {
+                    return Err(format!(
+                        "Assignment type mismatch: {decl_ty} annotated vs {ty} actual",
+                    ));
+                }
+                self.declare_init_impl_::<IS_CNST>(l.identifier.value.clone(), decl_ty, e)
+            }
+        }
     }
 
     fn set_lhs_ty_defn<const IS_CNST: bool>(
@@ -1248,22 +1470,25 @@ impl<'ast> ZGen<'ast> {
         assert!(self.lhs_ty.borrow().is_none()); // starting from nothing...
         if let ast::Expression::Postfix(pfe) = &d.expression {
             if matches!(pfe.accesses.first(), Some(ast::Access::Call(_))) {
-                let ty = d
+                let tys = d
                     .lhs
-                    .first()
+                    .iter()
                     .map(|ty| self.lhs_type::<IS_CNST>(ty))
-                    .transpose()?;
-                self.lhs_ty_put(ty);
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.lhs_ty_put(Ty::from_returns(tys));
             }
         }
         Ok(())
     }
 
-    fn set_lhs_ty_ret(&self, r: &ast::ReturnStatement<'ast>) {
+    fn set_lhs_ty_ret(&self, r: &ast::ReturnStatement<'ast>, idx: usize) {
         assert!(self.lhs_ty.borrow().is_none()); // starting from nothing...
-        if let Some(ast::Expression::Postfix(pfe)) = r.expressions.first() {
+        if let Some(ast::Expression::Postfix(pfe)) = r.expressions.get(idx) {
             if matches!(pfe.accesses.first(), Some(ast::Access::Call(_))) {
-                let ty = self.ret_ty_stack_last();
+                let ty = match self.ret_ty_stack_last() {
+                    Some(Ty::Tuple(tys)) if r.expressions.len() > 1 => tys.get(idx).cloned(),
+                    ty => ty,
+                };
                 self.lhs_ty_put(ty);
             }
         }
@@ -1400,16 +1625,24 @@ impl<'ast> ZGen<'ast> {
         &self,
         fn_def: &ast::FunctionDefinition<'ast>,
     ) -> Result<(), String> {
-        let ty = fn_def
-            .returns
-            .first()
-            .map(|ty| self.type_impl_::<IS_CNST>(ty))
-            .transpose()?
-            .unwrap_or(Ty::Bool);
+        let ty = self.ret_ty_impl_::<IS_CNST>(fn_def)?.unwrap_or(Ty::Bool);
         self.ret_ty_stack.borrow_mut().push(ty);
         Ok(())
     }
 
+    /// The type of the value returned by `fn_def`; a tuple if it has several return types.
+    fn ret_ty_impl_<const IS_CNST: bool>(
+        &self,
+        fn_def: &ast::FunctionDefinition<'ast>,
+    ) -> Result<Option<Ty>, String> {
+        let tys = fn_def
+            .returns
+            .iter()
+            .map(|ty| self.type_impl_::<IS_CNST>(ty))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Ty::from_returns(tys))
+    }
+
     fn ret_ty_stack_pop(&self) {
         self.ret_ty_stack.borrow_mut().pop();
     }
@@ -1427,43 +1660,51 @@ impl<'ast> ZGen<'ast> {
         self.crets_stack.borrow_mut().pop().unwrap()
     }
 
-    fn const_decl_(&mut self, c: &mut ast::ConstantDefinition<'ast>) {
+    fn const_decl_(&mut self, c: &mut ast::ConstantDefinition<'ast>) -> Result<(), Diagnostic> {
         // make sure that this wasn't already an important const name
         if self
             .cur_import_map()
             .map(|m| m.contains_key(&c.id.value))
             .unwrap_or(false)
         {
-            self.err(
+            return Err(self.err(
+                codes::REDEFINITION,
                 format!("Constant {} clashes with import of same name", &c.id.value),
                 &c.span,
-            );
+            ));
         }
 
         // rewrite literals in the const type decl
         let mut v = ZConstLiteralRewriter::new(None);
         v.visit_type(&mut c.ty)
-            .unwrap_or_else(|e| self.err(e.0, &c.span));
-        let ctype = self.unwrap(self.type_impl_::<true>(&c.ty), type_span(&c.ty));
+            .map_err(|e| self.err(codes::TYPE, e.0, &c.span))?;
+        let ctype = self.with_span(
+            codes::TYPE,
+            self.type_impl_::<true>(&c.ty),
+            type_span(&c.ty),
+        )?;
         // handle literal type inference using declared type
         v.replace(Some(ctype));
         v.visit_expression(&mut c.expression)
-            .unwrap_or_else(|e| self.err(e.0, &c.span));
+            .map_err(|e| self.err(codes::TYPE, e.0, &c.span))?;
 
         // evaluate the expression and check the resulting type
-        let value = self
-            .expr_impl_::<true>(&c.expression)
-            .unwrap_or_else(|e| self.err(e, c.expression.span()));
+        let value = self.with_span(
+            codes::CONST,
+            self.expr_impl_::<true>(&c.expression),
+            c.expression.span(),
+        )?;
         let ctype = v.replace(None).unwrap();
         if &ctype != value.type_() {
-            self.err(
+            return Err(self.err(
+                codes::TYPE,
                 format!(
                     "Type mismatch in constant definition: expected {:?}, got {:?}",
                     ctype,
                     value.type_()
                 ),
                 &c.span,
-            );
+            ));
         }
 
         // insert into constant map
@@ -1474,12 +1715,17 @@ impl<'ast> ZGen<'ast> {
             .insert(c.id.value.clone(), (c.ty.clone(), value))
             .is_some()
         {
-            self.err(format!("Constant {} redefined", &c.id.value), &c.span);
+            return Err(self.err(
+                codes::REDEFINITION,
+                format!("Constant {} redefined", &c.id.value),
+                &c.span,
+            ));
         }
+        Ok(())
     }
 
-    fn type_(&self, t: &ast::Type<'ast>) -> Ty {
-        self.unwrap(self.type_impl_::<false>(t), type_span(t))
+    fn type_(&self, t: &ast::Type<'ast>) -> Result<Ty, Diagnostic> {
+        self.with_span(codes::TYPE, self.type_impl_::<false>(t), type_span(t))
     }
 
     fn type_impl_<const IS_CNST: bool>(&self, t: &ast::Type<'ast>) -> Result<Ty, String> {
@@ -1554,15 +1800,15 @@ impl<'ast> ZGen<'ast> {
         }
     }
 
-    fn visit_files(&mut self) {
+    fn visit_files(&mut self) -> Result<(), Diagnostics> {
         // 1. go through includes and return a toposorted visit order for remaining processing
-        let files = self.visit_imports();
+        let files = self.visit_imports()?;
 
         // 2. visit constant, struct, and function defs ; infer types and generics
-        self.visit_declarations(files);
+        self.visit_declarations(files)
     }
 
-    fn visit_imports(&mut self) -> Vec<PathBuf> {
+    fn visit_imports(&mut self) -> Result<Vec<PathBuf>, Diagnostics> {
         use petgraph::algo::toposort;
         use petgraph::graph::{DefaultIx, DiGraph, NodeIndex};
         let asts = std::mem::take(&mut self.asts);
@@ -1570,6 +1816,7 @@ impl<'ast> ZGen<'ast> {
         // we use the graph to toposort the includes and the map to go from PathBuf to NodeIdx
         let mut ig = DiGraph::<PathBuf, ()>::with_capacity(asts.len(), asts.len());
         let mut gn = HashMap::<PathBuf, NodeIndex<DefaultIx>>::with_capacity(asts.len());
+        let mut errs = Diagnostics::new();
 
         for (p, f) in asts.iter() {
             self.file_stack_push(p.to_owned());
@@ -1622,15 +1869,18 @@ impl<'ast> ZGen<'ast> {
                         abs_src_path.display(),
                         dst_names
                     );
-                    src_names
-                        .into_iter()
-                        .zip(dst_names.into_iter())
-                        .for_each(|(sn, dn)| {
-                            if imap.contains_key(&dn) {
-                                self.err(format!("Import {dn} redeclared"), i_span);
+                    for (sn, dn) in src_names.into_iter().zip(dst_names) {
+                        match imap.entry(dn) {
+                            Entry::Occupied(e) => errs.push(self.err(
+                                codes::REDEFINITION,
+                                format!("Import {} redeclared", e.key()),
+                                i_span,
+                            )),
+                            Entry::Vacant(e) => {
+                                e.insert((abs_src_path.clone(), sn));
                             }
-                            assert!(imap.insert(dn, (abs_src_path.clone(), sn)).is_none());
-                        });
+                        }
+                    }
 
                     // add included -> includer edge for later toposort
                     if !gn.contains_key(&abs_src_path) {
@@ -1648,19 +1898,26 @@ impl<'ast> ZGen<'ast> {
         // flatten the import map, i.e., a -> b -> c becomes a -> c
         self.flatten_import_map();
 
-        toposort(&ig, None)
-            .unwrap_or_else(|e| {
-                use petgraph::dot::{Config, Dot};
-                panic!(
-                    "Import graph is cyclic!: {:?}\n{:?}\n",
-                    e,
-                    Dot::with_config(&ig, &[Config::EdgeNoLabel])
-                )
-            })
+        let order = match toposort(&ig, None) {
+            Ok(order) => order,
+            Err(cycle) => {
+                errs.push(
+                    Diagnostic::error(codes::IMPORT, "Import graph is cyclic").note(format!(
+                        "the cycle includes {}",
+                        ig[cycle.node_id()].display()
+                    )),
+                );
+                return Err(errs);
+            }
+        };
+        if !errs.is_empty() {
+            return Err(errs);
+        }
+        Ok(order
             .iter()
             .map(|idx| std::mem::take(ig.node_weight_mut(*idx).unwrap()))
             .filter(|p| self.asts.contains_key(p))
-            .collect()
+            .collect())
     }
 
     fn flatten_import_map(&mut self) {
@@ -1703,123 +1960,153 @@ impl<'ast> ZGen<'ast> {
         self.import_map = new_map;
     }
 
-    fn visit_declarations(&mut self, files: Vec<PathBuf>) {
+    fn visit_declarations(&mut self, files: Vec<PathBuf>) -> Result<(), Diagnostics> {
         let mut t = std::mem::take(&mut self.asts);
         let mut clr = ZConstLiteralRewriter::new(None);
+        let mut errs = Diagnostics::new();
         for p in files {
             self.constants.insert(p.clone(), HashMap::new());
             self.structs_and_tys.insert(p.clone(), HashMap::new());
             self.functions.insert(p.clone(), HashMap::new());
             self.file_stack_push(p.clone());
             for d in t.get_mut(&p).unwrap().declarations.iter_mut() {
-                match d {
-                    ast::SymbolDeclaration::Constant(c) => {
-                        debug!("processing decl: const {} in {}", c.id.value, p.display());
-                        self.const_decl_(c);
-                    }
-                    ast::SymbolDeclaration::Struct(s) => {
-                        debug!("processing decl: struct {} in {}", s.id.value, p.display());
-                        let mut s_ast = s.clone();
-
-                        // rewrite literals in ArrayTypes
-                        clr.visit_struct_definition(&mut s_ast)
-                            .unwrap_or_else(|e| self.err(e.0, &s.span));
-
-                        if self
-                            .structs_and_tys
-                            .get_mut(self.file_stack.borrow().last().unwrap())
-                            .unwrap()
-                            .insert(s.id.value.clone(), Ok(s_ast))
-                            .is_some()
-                        {
-                            self.err(
-                                format!("Struct {} defined over existing name", &s.id.value),
-                                &s.span,
-                            );
-                        }
-                    }
-                    ast::SymbolDeclaration::Type(t) => {
-                        debug!(
-                            "processing decl: type definition {} in {}",
-                            t.id.value,
-                            p.display()
-                        );
-                        let mut t_ast = t.clone();
-
-                        // rewrite literals in ArrayTypes
-                        clr.visit_type_definition(&mut t_ast)
-                            .unwrap_or_else(|e| self.err(e.0, &t.span));
-
-                        if self
-                            .structs_and_tys
-                            .get_mut(self.file_stack.borrow().last().unwrap())
-                            .unwrap()
-                            .insert(t.id.value.clone(), Err(t_ast))
-                            .is_some()
-                        {
-                            self.err(
-                                format!("Type {} defined over existing name", &t.id.value),
-                                &t.span,
-                            );
-                        }
-                    }
-                    ast::SymbolDeclaration::Function(f) => {
-                        debug!("processing decl: fn {} in {}", f.id.value, p.display());
-                        let mut f_ast = f.clone();
-
-                        // rewrite literals in params and returns
-                        let mut v = ZConstLiteralRewriter::new(None);
-                        f_ast
-                            .parameters
-                            .iter_mut()
-                            .try_for_each(|p| v.visit_parameter(p))
-                            .unwrap_or_else(|e| self.err(e.0, &f.span));
-                        if f_ast.returns.len() != 1 {
-                            // XXX(unimpl) functions MUST return exactly 1 value
-                            self.err(
-                                format!(
-                                    "Functions must return exactly 1 value; {} returns {}",
-                                    &f_ast.id.value,
-                                    f_ast.returns.len(),
-                                ),
-                                &f.span,
-                            );
-                        }
-                        f_ast
-                            .returns
-                            .iter_mut()
-                            .try_for_each(|r| v.visit_type(r))
-                            .unwrap_or_else(|e| self.err(e.0, &f.span));
-
-                        // go through stmts typechecking and rewriting literals
-                        let mut sw = ZStatementWalker::new(
-                            f_ast.parameters.as_ref(),
-                            f_ast.returns.as_ref(),
-                            f_ast.generics.as_ref(),
-                            self,
-                        );
-                        f_ast
-                            .statements
-                            .iter_mut()
-                            .try_for_each(|s| sw.visit_statement(s))
-                            .unwrap_or_else(|e| self.err(e.0, &f.span));
-
-                        if self
-                            .functions
-                            .get_mut(self.file_stack.borrow().last().unwrap())
-                            .unwrap()
-                            .insert(f.id.value.clone(), f_ast)
-                            .is_some()
-                        {
-                            self.err(format!("Function {} redefined", &f.id.value), &f.span);
-                        }
-                    }
-                    ast::SymbolDeclaration::Import(_) => (), // already handled in visit_imports
+                // keep going, to report as many errors as we can
+                if let Err(e) = self.visit_declaration(d, &mut clr) {
+                    errs.push(e);
                 }
             }
             self.file_stack_pop();
         }
         self.asts = t;
+        if errs.is_empty() {
+            Ok(())
+        } else {
+            Err(errs)
+        }
+    }
+
+    fn visit_declaration(
+        &mut self,
+        d: &mut ast::SymbolDeclaration<'ast>,
+        clr: &mut ZConstLiteralRewriter,
+    ) -> Result<(), Diagnostic> {
+        let p = self.cur_path();
+        match d {
+            ast::SymbolDeclaration::Constant(c) => {
+                debug!("processing decl: const {} in {}", c.id.value, p.display());
+                self.const_decl_(c)
+            }
+            ast::SymbolDeclaration::Struct(s) => {
+                debug!("processing decl: struct {} in {}", s.id.value, p.display());
+                let mut s_ast = s.clone();
+
+                // rewrite literals in ArrayTypes
+                clr.visit_struct_definition(&mut s_ast)
+                    .map_err(|e| self.err(codes::TYPE, e.0, &s.span))?;
+
+                if self
+                    .structs_and_tys
+                    .get_mut(&p)
+                    .unwrap()
+                    .insert(s.id.value.clone(), Ok(s_ast))
+                    .is_some()
+                {
+                    return Err(self.err(
+                        codes::REDEFINITION,
+                        format!("Struct {} defined over existing name", &s.id.value),
+                        &s.span,
+                    ));
+                }
+                Ok(())
+            }
+            ast::SymbolDeclaration::Type(t) => {
+                debug!(
+                    "processing decl: type definition {} in {}",
+                    t.id.value,
+                    p.display()
+                );
+                let mut t_ast = t.clone();
+
+                // rewrite literals in ArrayTypes
+                clr.visit_type_definition(&mut t_ast)
+                    .map_err(|e| self.err(codes::TYPE, e.0, &t.span))?;
+
+                if self
+                    .structs_and_tys
+                    .get_mut(&p)
+                    .unwrap()
+                    .insert(t.id.value.clone(), Err(t_ast))
+                    .is_some()
+                {
+                    return Err(self.err(
+                        codes::REDEFINITION,
+                        format!("Type {} defined over existing name", &t.id.value),
+                        &t.span,
+                    ));
+                }
+                Ok(())
+            }
+            ast::SymbolDeclaration::Function(f) => {
+                debug!("processing decl: fn {} in {}", f.id.value, p.display());
+                let mut f_ast = f.clone();
+
+                // rewrite literals in params and returns
+                let mut v = ZConstLiteralRewriter::new(None);
+                f_ast
+                    .parameters
+                    .iter_mut()
+                    .try_for_each(|p| v.visit_parameter(p))
+                    .map_err(|e| self.err(codes::TYPE, e.0, &f.span))?;
+                if f_ast.returns.is_empty() {
+                    // XXX(unimpl) functions MUST return at least 1 value
+                    return Err(self.err(
+                        codes::TYPE,
+                        format!(
+                            "Functions must return at least 1 value; {} returns none",
+                            &f_ast.id.value,
+                        ),
+                        &f.span,
+                    ));
+                }
+                f_ast
+                    .returns
+                    .iter_mut()
+                    .try_for_each(|r| v.visit_type(r))
+                    .map_err(|e| self.err(codes::TYPE, e.0, &f.span))?;
+
+                // go through stmts typechecking and rewriting literals
+                let mut sw = ZStatementWalker::new(
+                    f_ast.parameters.as_ref(),
+                    f_ast.returns.as_ref(),
+                    f_ast.generics.as_ref(),
+                    self,
+                );
+                let walked = f_ast.statements.iter_mut().try_for_each(|s| {
+                    let span = *s.span();
+                    sw.visit_statement(s).map_err(|e| (e, span))
+                });
+
+                // define the function even if it is ill-typed, so that callers can find it
+                if self
+                    .functions
+                    .get_mut(&p)
+                    .unwrap()
+                    .insert(f.id.value.clone(), f_ast)
+                    .is_some()
+                {
+                    return Err(self.err(
+                        codes::REDEFINITION,
+                        format!("Function {} redefined", &f.id.value),
+                        &f.span,
+                    ));
+                }
+                walked.map_err(|(e, span)| {
+                    self.err(codes::TYPE, e.0, &span)
+                        .note(format!("in function {}", &f.id.value))
+                })
+            }
+            ast::SymbolDeclaration::Import(_) => Ok(()), // already handled in visit_imports
+        }
     }
 
     fn get_function(&self, fn_id: &str) -> Option<&ast::FunctionDefinition<'ast>> {
@@ -1841,16 +2128,29 @@ impl<'ast> ZGen<'ast> {
             .map(|m| (m.as_ref(), s_path))
     }
 
-    fn assert(&self, asrt: Term) {
+    fn assert(&self, asrt: Term, s: &ast::Span) {
         debug_assert!(matches!(check(&asrt), Sort::Bool));
-        if self.isolate_asserts {
+        let asrt = if self.isolate_asserts {
             let path = self.circ_condition();
-            self.assertions
-                .borrow_mut()
-                .push(term![IMPLIES; path, asrt]);
+            term![IMPLIES; path, asrt]
         } else {
-            self.assertions.borrow_mut().push(asrt);
+            asrt
+        };
+        self.locate(&asrt, s, None);
+        self.assertions.borrow_mut().push(asrt);
+    }
+
+    /// Record that `t` came from `s` (and is bound to `name`, if any) in the source map.
+    fn locate(&self, t: &Term, s: &ast::Span, name: Option<&str>) {
+        if matches!(t.op(), Op::Const(_)) {
+            return;
         }
+        let circ = self.circ.borrow();
+        let function = circ.fn_name().unwrap_or_default().to_owned();
+        let loc = self
+            .span_range(s)
+            .source_loc(function, name.map(str::to_owned));
+        circ.locate(t.clone(), loc);
     }
 
     /*** circify wrapper functions (hides RefCell) ***/
@@ -1940,6 +2240,17 @@ impl<'ast> ZGen<'ast> {
     }
 }
 
+/// The range of `path` that `s` covers
+fn span_range(path: PathBuf, s: &ast::Span) -> SourceRange {
+    let (start_line, start_col) = s.start_pos().line_col();
+    let (end_line, end_col) = s.end_pos().line_col();
+    SourceRange::new(
+        path,
+        Position::new(start_line, start_col),
+        Position::new(end_line, end_col),
+    )
+}
+
 fn span_to_string(span: &ast::Span) -> String {
     span.lines().collect::<String>()
 }
@@ -1960,3 +2271,29 @@ fn type_span<'ast, 'a>(ty: &'a ast::Type<'ast>) -> &'a ast::Span<'ast> {
         },
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cfg::cfg_or_default;
+    use crate::front::diagnostic::Severity;
+
+    #[test]
+    fn reports_every_ill_typed_function() {
+        cfg_or_default();
+        let file =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("scripts/zx_tests/two_type_errors.zxf");
+        let inputs = Inputs {
+            file,
+            mode: Mode::Proof,
+        };
+        let ds = ZSharpFE::try_gen(inputs).expect_err("expected type errors");
+        assert_eq!(ds.0.len(), 2, "{ds}");
+        for (d, (line, function)) in ds.iter().zip([(2, "f"), (6, "main")]) {
+            assert_eq!(d.severity, Severity::Error);
+            assert_eq!(d.code, codes::TYPE);
+            assert_eq!(d.range.as_ref().unwrap().start.line, line, "{d}");
+            assert_eq!(d.notes, vec![format!("in function {function}")]);
+        }
+    }
+}
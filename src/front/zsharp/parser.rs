@@ -9,7 +9,9 @@ use log::debug;
 use std::collections::HashMap;
 use std::env::var_os;
 
+use super::codes;
 use crate::circify::includer::Loader;
+use crate::front::diagnostic::{Diagnostic, Position, SourceRange};
 use std::fs::File;
 use std::io::Read;
 use std::path::{Path, PathBuf};
@@ -77,6 +79,10 @@ impl ZStdLib {
         }
         panic!("Could not find {} from {}", child, parent.display())
     }
+    /// The directory containing the standard library
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
     /// check if this path is the EMBED prototypes path
     pub fn is_embed<P: AsRef<Path>>(&self, p: P) -> bool {
         p.as_ref().starts_with(&self.path)
@@ -89,6 +95,7 @@ impl ZStdLib {
 pub struct ZLoad {
     sources: Arena<String>,
     stdlib: ZStdLib,
+    overlay: HashMap<PathBuf, String>,
 }
 
 impl ZLoad {
@@ -98,6 +105,17 @@ impl ZLoad {
         Self {
             sources: Arena::new(),
             stdlib: ZStdLib::new(),
+            overlay: HashMap::new(),
+        }
+    }
+
+    /// Make a new Z# loader that reads the files in `overlay` from it, rather than from disk.
+    ///
+    /// This lets editors check documents with unsaved changes.
+    pub fn with_overlay(overlay: HashMap<PathBuf, String>) -> Self {
+        Self {
+            overlay,
+            ..Self::new()
         }
     }
 
@@ -105,9 +123,13 @@ impl ZLoad {
     ///
     /// ## Returns
     ///
-    /// Returns a map from file paths to parsed files.
-    pub fn load<P: AsRef<Path>>(&self, p: &P) -> HashMap<PathBuf, ast::File> {
-        self.recursive_load(p).unwrap()
+    /// Returns a map from file paths to parsed files, or a diagnostic for the first file that does
+    /// not parse.
+    pub fn load<P: AsRef<Path>>(
+        &self,
+        p: &P,
+    ) -> Result<HashMap<PathBuf, ast::File<'_>>, Diagnostic> {
+        self.recursive_load(p)
     }
 
     /// Get ref to contained ZStdLib
@@ -117,19 +139,21 @@ impl ZLoad {
 }
 
 impl<'a> Loader for &'a ZLoad {
-    type ParseError = ();
+    type ParseError = Diagnostic;
     type AST = zokrates_pest_ast::File<'a>;
 
     fn parse<P: AsRef<Path>>(&self, p: &P) -> Result<Self::AST, Self::ParseError> {
-        let mut s = String::new();
-        File::open(p).unwrap().read_to_string(&mut s).unwrap();
+        let s = match self.overlay.get(p.as_ref()) {
+            Some(s) => s.clone(),
+            None => {
+                let mut s = String::new();
+                File::open(p).unwrap().read_to_string(&mut s).unwrap();
+                s
+            }
+        };
         debug!("Parsing: {}", p.as_ref().display());
         let s = self.sources.alloc(s);
-        let ast = ast::generate_ast(s);
-        if ast.is_err() {
-            panic!("{}", ast.unwrap_err());
-        }
-        Ok(ast.unwrap())
+        ast::generate_ast(s).map_err(|_| parse_error(s, p.as_ref()))
     }
     fn includes<P: AsRef<Path>>(&self, ast: &Self::AST, p: &P) -> Vec<PathBuf> {
         let mut c = p.as_ref().to_path_buf();
@@ -150,3 +174,34 @@ impl<'a> Loader for &'a ZLoad {
             .collect()
     }
 }
+
+/// Describe why `src`, the contents of `path`, does not parse.
+fn parse_error(src: &str, path: &Path) -> Diagnostic {
+    use pest::error::{ErrorVariant, LineColLocation};
+    // the AST's error hides its location, so we get it from the parser
+    let e = match zokrates_parser::parse(src) {
+        Ok(_) => return Diagnostic::error(codes::PARSE, "Could not build an AST"),
+        Err(e) => e,
+    };
+    let (start, end) = match e.line_col {
+        LineColLocation::Pos((l, c)) => ((l, c), (l, c + 1)),
+        LineColLocation::Span(s, e) => (s, e),
+    };
+    let message = match &e.variant {
+        ErrorVariant::ParsingError { positives, .. } if !positives.is_empty() => {
+            let expected = positives
+                .iter()
+                .map(|r| format!("{r:?}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("Unexpected input; expected one of: {expected}")
+        }
+        ErrorVariant::ParsingError { .. } => "Unexpected input".to_owned(),
+        ErrorVariant::CustomError { message } => message.clone(),
+    };
+    Diagnostic::error(codes::PARSE, message).at(SourceRange::new(
+        path.to_path_buf(),
+        Position::new(start.0, start.1),
+        Position::new(end.0, end.1),
+    ))
+}
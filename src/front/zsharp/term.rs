@@ -1,5 +1,5 @@
 //! Symbolic Z# terms
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{self, Display, Formatter};
 
 use rug::Integer;
@@ -16,6 +16,8 @@ pub enum Ty {
     Bool,
     Field,
     Struct(String, FieldList<Ty>),
+    /// The values returned by a multi-value function
+    Tuple(Vec<Ty>),
     Array(usize, Box<Ty>),
     MutArray(usize),
 }
@@ -33,6 +35,16 @@ impl Display for Ty {
                 }
                 o.finish()
             }
+            Ty::Tuple(tys) => {
+                write!(f, "(")?;
+                for (i, ty) in tys.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{ty}")?;
+                }
+                write!(f, ")")
+            }
             Ty::Array(n, b) => {
                 let mut dims = vec![n];
                 let mut bb = b.as_ref();
@@ -79,6 +91,7 @@ impl Ty {
             Self::Struct(_name, fs) => {
                 Sort::Tuple(fs.fields().map(|(_f_name, f_ty)| f_ty.sort()).collect())
             }
+            Self::Tuple(tys) => Sort::Tuple(tys.iter().map(|ty| ty.sort()).collect()),
         }
     }
     fn default_ir_term(&self) -> Term {
@@ -94,6 +107,14 @@ impl Ty {
     pub fn new_struct<I: IntoIterator<Item = (String, Ty)>>(name: String, fields: I) -> Self {
         Self::Struct(name, FieldList::new(fields.into_iter().collect()))
     }
+    /// The type of the values returned by a function with return types `tys`: nothing, the single
+    /// return type, or a tuple.
+    pub fn from_returns(mut tys: Vec<Ty>) -> Option<Self> {
+        match tys.len() {
+            0 | 1 => tys.pop(),
+            _ => Some(Self::Tuple(tys)),
+        }
+    }
     /// Array value type
     pub fn array_val_ty(&self) -> &Self {
         match self {
@@ -188,6 +209,30 @@ impl T {
         T::new(Ty::Struct(name, field_ty_list), ir_term)
     }
 
+    pub fn new_tuple(elems: Vec<T>) -> T {
+        let (tys, ir_terms): (Vec<_>, Vec<_>) = elems.into_iter().map(|t| (t.ty, t.term)).unzip();
+        T::new(Ty::Tuple(tys), term(Op::Tuple, ir_terms))
+    }
+
+    /// The values returned by a function: nothing, a single value, or a tuple.
+    pub fn from_returns(mut rets: Vec<T>) -> Option<T> {
+        match rets.len() {
+            0 | 1 => rets.pop(),
+            _ => Some(T::new_tuple(rets)),
+        }
+    }
+
+    pub fn unwrap_tuple(self) -> Result<Vec<T>, String> {
+        match &self.ty {
+            Ty::Tuple(tys) => Ok(tys
+                .iter()
+                .enumerate()
+                .map(|(i, ty)| T::new(ty.clone(), term![Op::Field(i); self.term.clone()]))
+                .collect()),
+            _ => Err(format!("{self} is not a tuple")),
+        }
+    }
+
     // XXX(rsw) hrm is there a nicer way to do this?
     pub fn new_field<I>(v: I) -> Self
     where
@@ -241,6 +286,14 @@ impl T {
                 _ => unreachable!(),
             },
             Value::Tuple(vs) => {
+                if let Ty::Tuple(tys) = &self.ty {
+                    write!(f, "(")?;
+                    tys.iter().zip(vs.iter()).try_for_each(|(ty, v)| {
+                        T::new(ty.clone(), leaf_term(Op::Const(v.clone()))).pretty(f)?;
+                        write!(f, ", ")
+                    })?;
+                    return write!(f, ")");
+                }
                 let (n, fl) = if let Ty::Struct(n, fl) = &self.ty {
                     Ok((n, fl))
                 } else {
@@ -601,7 +654,7 @@ pub fn const_val(a: T) -> Result<T, String> {
     }
 }
 
-fn const_value(t: &Term) -> Option<Value> {
+pub fn const_value(t: &Term) -> Option<Value> {
     let folded = constant_fold(t, &[]);
     match &folded.op() {
         Op::Const(v) => Some(v.clone()),
@@ -609,6 +662,58 @@ fn const_value(t: &Term) -> Option<Value> {
     }
 }
 
+/// Whether `a` and `b` are the same constant
+pub fn const_eq(a: &T, b: &T) -> bool {
+    a.ty == b.ty
+        && a.terms().iter().zip(b.terms()).all(|(x, y)| {
+            let x = const_value(x);
+            x.is_some() && x == const_value(&y)
+        })
+}
+
+/// The constant of type `ty` named `name` in `values`.
+///
+/// Compound values are spread over several entries, named as [ZSharp] names the variables of
+/// compound inputs (e.g., `a.0.x` for the field `x` of the first element of `a`).
+pub fn value_from_map(ty: &Ty, name: &str, values: &HashMap<String, Value>) -> Result<T, String> {
+    match ty {
+        Ty::Bool | Ty::Field | Ty::Uint(_) => {
+            let v = values
+                .get(name)
+                .ok_or_else(|| format!("No value for {name}"))?;
+            if v.sort() != ty.sort() {
+                return Err(format!("{name} should be a {ty}, but it is {v}"));
+            }
+            Ok(T::new(ty.clone(), leaf_term(Op::Const(v.clone()))))
+        }
+        Ty::Array(n, elem_ty) => array(
+            (0..*n)
+                .map(|i| value_from_map(elem_ty, &idx_name(name, i), values))
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        Ty::MutArray(n) => array(
+            (0..*n)
+                .map(|i| value_from_map(&Ty::Field, &idx_name(name, i), values))
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        Ty::Struct(n, fs) => Ok(T::new_struct(
+            n.clone(),
+            fs.fields()
+                .map(|(f_name, f_ty)| {
+                    let v = value_from_map(f_ty, &field_name(name, f_name), values)?;
+                    Ok((f_name.clone(), v))
+                })
+                .collect::<Result<Vec<_>, String>>()?,
+        )),
+        Ty::Tuple(tys) => Ok(T::new_tuple(
+            tys.iter()
+                .enumerate()
+                .map(|(i, ty)| value_from_map(ty, &idx_name(name, i), values))
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+    }
+}
+
 pub fn bool(a: T) -> Result<Term, String> {
     match &a.ty {
         Ty::Bool => Ok(a.term),
@@ -1011,6 +1116,22 @@ impl Embeddable for ZSharp {
                     })
                     .collect(),
             ),
+            Ty::Tuple(tys) => {
+                let ps: Vec<Option<T>> = match precompute.map(|p| p.unwrap_tuple()) {
+                    Some(Ok(v)) => v.into_iter().map(Some).collect(),
+                    Some(Err(e)) => panic!("{}", e),
+                    None => std::iter::repeat(None).take(tys.len()).collect(),
+                };
+                T::new_tuple(
+                    tys.iter()
+                        .zip(ps)
+                        .enumerate()
+                        .map(|(i, (ty, p))| {
+                            self.declare_input(ctx, ty, idx_name(&name, i), visibility, p)
+                        })
+                        .collect(),
+                )
+            }
         }
     }
     fn ite(&self, _ctx: &mut CirCtx, cond: Term, t: Self::T, f: Self::T) -> Self::T {
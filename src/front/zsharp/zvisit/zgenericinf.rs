@@ -130,14 +130,25 @@ impl<'ast, 'gen, const IS_CNST: bool> ZGenericInf<'ast, 'gen, IS_CNST> {
             assert!(self.sfx.ends_with(&self.fdef.id.value));
         }
 
-        // 3. unify the return type
-        match (rty, self.fdef.returns.first()) {
-            (Some(rty), Some(ret)) => self.fdef_gen_ty(rty, ret),
-            (Some(rty), None) if rty != Ty::Bool => Err(format!(
+        // 3. unify the return type (a tuple for multi-value returns)
+        let fdef = self.fdef;
+        match (rty, &fdef.returns[..]) {
+            (Some(rty), [ret]) => self.fdef_gen_ty(rty, ret),
+            (Some(rty), []) if rty != Ty::Bool => Err(format!(
                 "Function {} expected implicit Bool ret, but got {}",
-                &self.fdef.id.value, rty
+                &fdef.id.value, rty
+            )),
+            (Some(_), []) => Ok(()),
+            (Some(Ty::Tuple(rtys)), rets) if rtys.len() == rets.len() => rtys
+                .into_iter()
+                .zip(rets.iter())
+                .try_for_each(|(rty, ret)| self.fdef_gen_ty(rty, ret)),
+            (Some(rty), rets) => Err(format!(
+                "Function {} returns {} values, but expected {}",
+                &fdef.id.value,
+                rets.len(),
+                rty
             )),
-            (Some(_), None) => Ok(()),
             (None, _) => Ok(()),
         }?;
         // bracketing invariant
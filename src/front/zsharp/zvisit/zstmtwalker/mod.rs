@@ -94,6 +94,24 @@ impl<'ast, 'ret> ZStatementWalker<'ast, 'ret> {
         call: &mut ast::CallAccess<'ast>,
         rty: Option<&ast::Type<'ast>>,
     ) -> ZResult<ast::Type<'ast>> {
+        self.unify_call_args(fdef, call)?;
+
+        let ret_ty = fdef.returns.first().cloned().unwrap_or({
+            ast::Type::Basic(ast::BasicType::Boolean(ast::BooleanType {
+                span: call.span,
+            }))
+        });
+        if let Some(ty) = rty {
+            self.eq_type(ty, &ret_ty)?;
+        }
+        Ok(ret_ty)
+    }
+
+    fn unify_call_args(
+        &self,
+        fdef: &ast::FunctionDefinition<'ast>,
+        call: &mut ast::CallAccess<'ast>,
+    ) -> ZVisitorResult {
         // basic consistency checks on Call access
         if call.arguments.expressions.len() != fdef.parameters.len() {
             return Err(format!(
@@ -130,17 +148,52 @@ impl<'ast, 'ret> ZStatementWalker<'ast, 'ret> {
             .iter()
             .map(|pty| pty.ty.clone())
             .zip(call.arguments.expressions.iter_mut())
-            .try_for_each(|(pty, arg)| self.unify_expression(pty, arg))?;
+            .try_for_each(|(pty, arg)| self.unify_expression(pty, arg))
+    }
 
-        let ret_ty = fdef.returns.first().cloned().unwrap_or({
-            ast::Type::Basic(ast::BasicType::Boolean(ast::BooleanType {
-                span: call.span,
-            }))
-        });
-        if let Some(ty) = rty {
-            self.eq_type(ty, &ret_ty)?;
+    // unify the values returned by a multi-value fn call with tys
+    fn unify_multi_call(
+        &self,
+        tys: &[ast::Type<'ast>],
+        expr: &mut ast::Expression<'ast>,
+    ) -> ZVisitorResult {
+        let mut rewriter = ZConstLiteralRewriter::new(None);
+        rewriter.visit_expression(expr)?;
+
+        let span = span_to_string(expr.span());
+        let (id, call) = match expr {
+            ast::Expression::Postfix(pf) if pf.accesses.len() == 1 => {
+                match pf.accesses.first_mut() {
+                    Some(ast::Access::Call(ca)) => (&pf.id, ca),
+                    _ => {
+                        return Err(ZVisitorError(format!(
+                            "ZStatementWalker: expected a fn call for {} values:\n{span}",
+                            tys.len(),
+                        )))
+                    }
+                }
+            }
+            _ => {
+                return Err(ZVisitorError(format!(
+                    "ZStatementWalker: expected a fn call for {} values:\n{span}",
+                    tys.len(),
+                )))
+            }
+        };
+
+        let fdef = self.get_function(&id.value)?;
+        if fdef.returns.len() != tys.len() {
+            return Err(ZVisitorError(format!(
+                "ZStatementWalker: fn {} returns {} values, expected {}:\n{span}",
+                &id.value,
+                fdef.returns.len(),
+                tys.len(),
+            )));
         }
-        Ok(ret_ty)
+        self.unify_call_args(fdef, call)?;
+        tys.iter()
+            .zip(fdef.returns.iter())
+            .try_for_each(|(ty, rty)| self.eq_type(ty, rty))
     }
 
     fn get_postfix_ty(
@@ -165,9 +218,9 @@ impl<'ast, 'ret> ZStatementWalker<'ast, 'ret> {
                         &id.value,
                     )))
                 } else if fdef.returns.len() > 1 {
-                    // XXX(unimpl) multiple return types not implemented
+                    // multi-value calls may only be the RHS of a multi-assignment or return
                     Err(ZVisitorError(format!(
-                        "ZStatementWalker: fn {} has multiple returns",
+                        "ZStatementWalker: fn {} returns multiple values; bind them with a multi-assignment",
                         &id.value,
                     )))
                 } else {
@@ -727,21 +780,18 @@ impl<'ast, 'ret> ZStatementWalker<'ast, 'ret> {
 
 impl<'ast, 'ret> ZVisitorMut<'ast> for ZStatementWalker<'ast, 'ret> {
     fn visit_return_statement(&mut self, ret: &mut ast::ReturnStatement<'ast>) -> ZVisitorResult {
-        if self.rets.len() != ret.expressions.len() {
+        if self.rets.len() > 1 && ret.expressions.len() == 1 {
+            // returning the values of a multi-value call
+            self.unify_multi_call(self.rets, &mut ret.expressions[0])?;
+        } else if self.rets.len() != ret.expressions.len() {
             return Err(ZVisitorError(
                 "ZStatementWalker: mismatched return expression/type".to_owned(),
             ));
-        }
-
-        // XXX(unimpl) multi-return statements not supported
-        if self.rets.len() > 1 {
-            return Err(ZVisitorError(
-                "ZStatementWalker: multi-returns not supported".to_owned(),
-            ));
-        }
-
-        if let Some(expr) = ret.expressions.first_mut() {
-            self.unify(self.rets.first().cloned(), expr)?;
+        } else {
+            self.rets
+                .iter()
+                .zip(ret.expressions.iter_mut())
+                .try_for_each(|(ty, expr)| self.unify(Some(ty.clone()), expr))?;
         }
         walk_return_statement(self, ret)
     }
@@ -800,28 +850,25 @@ impl<'ast, 'ret> ZVisitorMut<'ast> for ZStatementWalker<'ast, 'ret> {
             .iter_mut()
             .try_for_each(|l| self.visit_typed_identifier_or_assignee(l))?;
 
-        // unify lhs and rhs
-        // XXX(unimpl) multi-LHS statements not supported
-        if def.lhs.len() > 1 {
-            return Err(ZVisitorError(
-                "ZStatementWalker: multi-LHS assignments not supported".to_owned(),
-            ));
-        }
-        let ty_accs = def
+        // unify lhs and rhs (a multi-value fn call if there are many lhs)
+        let tys = def
             .lhs
-            .first()
+            .iter()
             .map(|tioa| {
                 use ast::TypedIdentifierOrAssignee::*;
                 let (na, acc) = match tioa {
                     Assignee(a) => (&a.id.value, a.accesses.as_ref()),
                     TypedIdentifier(ti) => (&ti.identifier.value, &[][..]),
                 };
-                self.lookup_type_varonly(na).map(|t| t.map(|t| (t, acc)))
+                self.lookup_type_varonly(na)?
+                    .map(|ty| self.walk_accesses(ty, acc, aacc_to_msacc))
+                    .transpose()
             })
-            .transpose()?
-            .flatten();
-        if let Some((ty, accs)) = ty_accs {
-            let ty = self.walk_accesses(ty, accs, aacc_to_msacc)?;
+            .collect::<ZResult<Option<Vec<_>>>>()?
+            .unwrap_or_default();
+        if tys.len() > 1 {
+            self.unify_multi_call(&tys, &mut def.expression)?;
+        } else if let Some(ty) = tys.into_iter().next() {
             self.unify(Some(ty), &mut def.expression)?;
         } else {
             return Err(ZVisitorError(format!(
@@ -50,13 +50,18 @@ impl<'f> Linker<'f> {
     fn link_all(&mut self, name: &str) {
         if !self.cache.contains_key(name) {
             let mut c = self.cs.get(name).clone();
-            for t in c.terms_postorder() {
+            let precomputed: Vec<Term> = c.precomputes.outputs().values().cloned().collect();
+            for t in c
+                .terms_postorder()
+                .chain(PostOrderIter::new(term(Op::Tuple, precomputed)))
+            {
                 if let Op::Call(callee_name, ..) = &t.op() {
                     self.link_all(callee_name);
                 }
             }
 
-            self.traverse(&mut c);
+            // the precomputation may call too
+            self.traverse_full(&mut c, true, true);
             let present = self.cache.insert(name.into(), c);
             assert!(present.is_none());
         }
@@ -308,4 +313,47 @@ mod test {
         let c = cs.get("main").clone();
         assert_eq!(c, expected);
     }
+
+    #[test]
+    fn call_in_precompute() {
+        let mut cs = text::parse_computations(
+            b"
+                (computations
+                    (myxor
+                        (computation
+                            (metadata (parties ) (inputs (a bool) (b bool)) (commitments))
+                            (precompute () () (#t ))
+                            (xor a b)
+                        )
+                    )
+                    (main
+                        (computation
+                            (metadata (parties ) (inputs (a bool) (b bool) (return bool)) (commitments))
+                            (precompute
+                                ((a bool) (b bool))
+                                ((return bool))
+                                (tuple ((field 0) ((call myxor (bool bool) (tuple bool)) a b)))
+                            )
+                            (= return ((field 0) ((call myxor (bool bool) (tuple bool)) a b)))
+                        )
+                    )
+                )",
+        );
+        let expected = text::parse_computation(
+            b"
+                    (computation
+                        (metadata (parties ) (inputs (a bool) (b bool) (return bool)) (commitments))
+                        (precompute
+                            ((a bool) (b bool))
+                            ((return bool))
+                            (tuple ((field 0) (tuple (xor a b))))
+                        )
+                        (= return ((field 0) (tuple (xor a b))))
+                    )
+                ",
+        );
+        link_all_function_calls(&mut cs);
+        let c = cs.get("main").clone();
+        assert_eq!(c, expected);
+    }
 }
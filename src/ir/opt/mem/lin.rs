@@ -4,7 +4,7 @@
 use super::super::visit::RewritePass;
 use crate::ir::term::*;
 
-struct Linearizer;
+pub(super) struct Linearizer;
 
 fn arr_val_to_tup(v: &Value) -> Value {
     match v {
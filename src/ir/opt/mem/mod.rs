@@ -4,6 +4,10 @@
 ///
 /// Replace arrays with tuples, using ITEs to handle variable indexing.
 pub mod lin;
+/// Array elimination for MPC.
+///
+/// Replace arrays with tuples, using linear scans or selection trees to handle variable indexing.
+pub mod mpc;
 /// Oblivious array elimination.
 ///
 /// Replace arrays that are accessed at constant indices with tuples.
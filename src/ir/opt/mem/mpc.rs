@@ -0,0 +1,400 @@
+//! Array elimination for MPC
+//!
+//! As in [super::lin], we replace each array with a tuple. For each access at a variable index,
+//! we choose one of two oblivious strategies:
+//!
+//! * A *linear scan* compares the index to every position, as [super::lin] does.
+//! * A *selection tree* extracts the low `log2(size)` bits of the index. A select is a tree of
+//!   multiplexers, one level per bit. A store finds the written position with a decoder tree (an
+//!   AND per node). There are no per-position comparisons, and the depth is logarithmic.
+//!
+//! We choose the strategy with fewer (estimated) AND gates: the cost of Boolean and Yao sharing.
+//! Accesses at the same index term share the cost of comparing it or extracting its bits.
+//!
+//! Either way, a store builds the new tuple one position at a time, so an access creates `O(size)`
+//! terms. ([super::lin] builds an ITE of whole tuples per position; tuple elimination turns that
+//! into `O(size^2)` terms.) Out-of-bounds accesses are undefined: a linear scan selects the first
+//! value and stores nothing, while a selection tree may read or write some in-bounds position.
+//!
+//! We do not implement square-root ORAM. It makes accesses sub-linear by revealing a (permuted)
+//! position on each access, and then indexing with it. ABY circuits are fixed before they run,
+//! so they cannot index with a revealed value: a fixed circuit must touch every position on each
+//! secret-index access.
+use super::super::visit::RewritePass;
+use super::lin::Linearizer;
+use crate::ir::term::*;
+
+use fxhash::FxHashMap as HashMap;
+
+/// How to access an array at a variable index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Compare the index to each position
+    Linear,
+    /// Decompose the index into bits, and use a tree of multiplexers (or a decoder)
+    Tree,
+}
+
+fn log2_ceil(n: usize) -> usize {
+    n.next_power_of_two().trailing_zeros() as usize
+}
+
+/// The number of bits in a value of sort `s`, for cost estimates.
+fn sort_bits(s: &Sort) -> usize {
+    match s {
+        Sort::Bool => 1,
+        Sort::BitVector(w) => *w,
+        Sort::Array(_, v, n) => n * sort_bits(v),
+        Sort::Tuple(ss) => ss.iter().map(sort_bits).sum(),
+        _ => 32,
+    }
+}
+
+/// Choose a strategy for a select (or store) into an array of `size` values of `value_bits` bits,
+/// at an index of `index_bits` bits that `uses` accesses share.
+pub fn choose(
+    size: usize,
+    index_bits: usize,
+    value_bits: usize,
+    uses: usize,
+    store: bool,
+) -> Strategy {
+    let n = size as f64;
+    let uses = uses.max(1) as f64;
+    // Comparing the index to a constant, or extracting one bit, is an AND tree over its bits.
+    let compare = index_bits.saturating_sub(1) as f64;
+    let mux = value_bits as f64;
+    let bits = log2_ceil(size) as f64 * compare;
+    let (linear, tree) = if store {
+        // The decoder has fewer than `2 * size` ANDs.
+        (
+            n * compare / uses + n * mux,
+            (bits + 2.0 * n) / uses + n * mux,
+        )
+    } else {
+        (
+            (n - 1.0) * compare / uses + (n - 1.0) * mux,
+            bits / uses + (n - 1.0) * mux,
+        )
+    };
+    if tree < linear {
+        Strategy::Tree
+    } else {
+        Strategy::Linear
+    }
+}
+
+struct MpcArrays {
+    lin: Linearizer,
+    /// The number of variable-index accesses at each (original) index term
+    uses: TermMap<usize>,
+    /// Bits of each (rewritten) index, least significant first
+    bits: TermMap<Vec<Term>>,
+    /// Decoder outputs for each (rewritten) index and array size
+    decoders: HashMap<(Term, usize), Vec<Term>>,
+}
+
+fn field(tup: &Term, i: usize) -> Term {
+    if tup.op() == &Op::Tuple {
+        tup.cs()[i].clone()
+    } else {
+        term![Op::Field(i); tup.clone()]
+    }
+}
+
+fn and(a: &Term, b: &Term) -> Term {
+    if a == &bool_lit(true) {
+        b.clone()
+    } else {
+        term![AND; a.clone(), b.clone()]
+    }
+}
+
+impl MpcArrays {
+    fn new(c: &Computation) -> Self {
+        let mut uses = TermMap::default();
+        for t in PostOrderIter::from_roots_and_skips(c.outputs.iter().cloned(), TermSet::default())
+        {
+            if matches!(t.op(), Op::Select | Op::Store) && !t.cs()[1].is_const() {
+                *uses.entry(t.cs()[1].clone()).or_insert(0) += 1;
+            }
+        }
+        Self {
+            lin: Linearizer,
+            uses,
+            bits: TermMap::default(),
+            decoders: HashMap::default(),
+        }
+    }
+
+    fn strategy(&self, orig: &Term, store: bool) -> Strategy {
+        match check(&orig.cs()[0]) {
+            Sort::Array(k, v, size) => match &*k {
+                Sort::BitVector(w) => choose(
+                    size,
+                    *w,
+                    sort_bits(&v),
+                    self.uses.get(&orig.cs()[1]).copied().unwrap_or(1),
+                    store,
+                ),
+                _ => Strategy::Linear,
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    /// The low `n` bits of bit-vector `idx`, least significant first.
+    fn bits(&mut self, idx: &Term, n: usize) -> Vec<Term> {
+        let w = check(idx).as_bv();
+        let bits = self.bits.entry(idx.clone()).or_default();
+        for i in bits.len()..n {
+            bits.push(if i >= w {
+                bool_lit(false)
+            } else {
+                let shifted = if i == 0 {
+                    idx.clone()
+                } else {
+                    term![BV_LSHR; idx.clone(), bv_lit(i, w)]
+                };
+                term![Op::Eq; term![BV_AND; shifted, bv_lit(1, w)], bv_lit(1, w)]
+            });
+        }
+        bits[..n].to_vec()
+    }
+
+    /// For each position below `size`, whether `idx` is that position.
+    fn decoder(&mut self, idx: &Term, size: usize) -> Vec<Term> {
+        let key = (idx.clone(), size);
+        if let Some(hits) = self.decoders.get(&key) {
+            return hits.clone();
+        }
+        let mut hits = vec![bool_lit(true)];
+        for (level, b) in self.bits(idx, log2_ceil(size)).into_iter().enumerate() {
+            let not_b = term![NOT; b.clone()];
+            let len = (hits.len() * 2).min(size);
+            hits = (0..len)
+                .map(|j| {
+                    let lit = if (j >> level) & 1 == 1 { &b } else { &not_b };
+                    and(&hits[j & ((1 << level) - 1)], lit)
+                })
+                .collect();
+        }
+        self.decoders.insert(key, hits.clone());
+        hits
+    }
+
+    fn select(
+        &mut self,
+        tup: &Term,
+        idx: &Term,
+        key_sort: &Sort,
+        size: usize,
+        s: Strategy,
+    ) -> Term {
+        let fields: Vec<Term> = (0..size).map(|i| field(tup, i)).collect();
+        match s {
+            Strategy::Linear => {
+                let mut fields = fields.into_iter();
+                let first = fields.next().unwrap();
+                key_sort.elems_iter().take(size).skip(1).zip(fields).fold(
+                    first,
+                    |acc, (idx_c, field)| term![ITE; term![Op::Eq; idx.clone(), idx_c], field, acc],
+                )
+            }
+            Strategy::Tree => {
+                let mut level = fields;
+                for b in self.bits(idx, log2_ceil(size)) {
+                    level = level
+                        .chunks(2)
+                        .map(|pair| match pair {
+                            [lo, hi] => term![ITE; b.clone(), hi.clone(), lo.clone()],
+                            [x] => x.clone(),
+                            _ => unreachable!(),
+                        })
+                        .collect();
+                }
+                assert_eq!(level.len(), 1);
+                level.pop().unwrap()
+            }
+        }
+    }
+
+    fn store(
+        &mut self,
+        tup: &Term,
+        idx: &Term,
+        val: &Term,
+        key_sort: &Sort,
+        size: usize,
+        s: Strategy,
+    ) -> Term {
+        let hits: Vec<Term> = match s {
+            Strategy::Linear => key_sort
+                .elems_iter()
+                .take(size)
+                .map(|idx_c| term![Op::Eq; idx.clone(), idx_c])
+                .collect(),
+            Strategy::Tree => self.decoder(idx, size),
+        };
+        term(
+            Op::Tuple,
+            hits.into_iter()
+                .enumerate()
+                .map(|(i, hit)| term![ITE; hit, val.clone(), field(tup, i)])
+                .collect(),
+        )
+    }
+}
+
+impl RewritePass for MpcArrays {
+    fn visit<F: Fn() -> Vec<Term>>(
+        &mut self,
+        computation: &mut Computation,
+        orig: &Term,
+        rewritten_children: F,
+    ) -> Option<Term> {
+        match orig.op() {
+            Op::Select | Op::Store if !orig.cs()[1].is_const() => {
+                let cs = rewritten_children();
+                let (key_sort, size) = match check(&orig.cs()[0]) {
+                    Sort::Array(k, _, size) => (k, size),
+                    _ => unreachable!(),
+                };
+                assert!(size > 0);
+                let store = orig.op() == &Op::Store;
+                let s = self.strategy(orig, store);
+                Some(if store {
+                    self.store(&cs[0], &cs[1], &cs[2], &key_sort, size, s)
+                } else {
+                    self.select(&cs[0], &cs[1], &key_sort, size, s)
+                })
+            }
+            _ => self.lin.visit(computation, orig, rewritten_children),
+        }
+    }
+}
+
+/// Eliminate arrays, using linear scans or selection trees. See module documentation.
+pub fn lower_arrays(c: &mut Computation) {
+    let mut pass = MpcArrays::new(c);
+    pass.traverse(c);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rug::Integer;
+
+    fn array_free(t: &Term) -> bool {
+        PostOrderIter::new(t.clone()).all(|c| !matches!(check(&c), Sort::Array(..)))
+    }
+
+    fn bv(i: usize, w: usize) -> Value {
+        Value::BitVector(BitVector::new(Integer::from(i), w))
+    }
+
+    /// Check `c` against its lowering, at all in-bounds values of inputs `a`, `b`, and `c`.
+    fn check_lowering(c: &Computation, size: usize, width: usize) {
+        let mut lowered = c.clone();
+        lower_arrays(&mut lowered);
+        assert!(lowered.outputs.iter().all(array_free));
+        for a in 0..size {
+            for b in 0..size {
+                for i in 0..size {
+                    let inputs: HashMap<String, Value> = vec![
+                        ("a".to_owned(), bv(a, width)),
+                        ("b".to_owned(), bv(b, width)),
+                        ("c".to_owned(), bv(i, width)),
+                    ]
+                    .into_iter()
+                    .collect();
+                    assert_eq!(c.eval_all(&inputs), lowered.eval_all(&inputs));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn choices() {
+        assert_eq!(Strategy::Linear, choose(2, 32, 32, 1, false));
+        assert_eq!(Strategy::Tree, choose(1000, 32, 32, 1, false));
+        assert_eq!(Strategy::Tree, choose(1000, 32, 32, 1, true));
+        // A 1-bit index is as cheap to compare as to decompose.
+        assert_eq!(Strategy::Linear, choose(2, 1, 32, 1, true));
+    }
+
+    #[test]
+    fn stores_then_select() {
+        for size in [1, 2, 3, 5, 8] {
+            let c = text::parse_computation(
+                format!(
+                    "
+                (computation
+                    (metadata (parties ) (inputs (a (bv 8)) (b (bv 8)) (c (bv 8))) (commitments))
+                    (precompute () () (#t ))
+                    (let
+                        (
+                            (arr (#a (bv 8) #x07 {size} ()))
+                            (store_1 (store arr a #x01))
+                            (store_2 (store store_1 b (bvadd a #x02)))
+                        )
+                        (select (ite (bvult a b) store_1 store_2) c)
+                    )
+                )
+            "
+                )
+                .as_bytes(),
+            );
+            check_lowering(&c, size, 8);
+        }
+    }
+
+    #[test]
+    fn shared_index() {
+        let c = text::parse_computation(
+            b"
+            (computation
+                (metadata (parties ) (inputs (a (bv 4)) (b (bv 4)) (c (bv 4))) (commitments))
+                (precompute () () (#t ))
+                (let
+                    (
+                        (arr (#a (bv 4) #x0 6 ()))
+                        (store_1 (store arr c a))
+                        (store_2 (store store_1 b (select store_1 c)))
+                    )
+                    (bvadd (select store_2 c) (select store_2 a))
+                )
+            )
+        ",
+        );
+        check_lowering(&c, 6, 4);
+    }
+
+    #[test]
+    fn store_is_linear_in_size() {
+        let size = 1024;
+        let mut c = text::parse_computation(
+            format!(
+                "
+            (computation
+                (metadata (parties ) (inputs (a (bv 32)) (b (bv 32))) (commitments))
+                (precompute () () (#t ))
+                (let
+                    (
+                        (arr (#a (bv 32) #x00000000 {size} ()))
+                        (store_1 (store arr a #x00000001))
+                        (store_2 (store store_1 b #x00000002))
+                    )
+                    (select store_2 a)
+                )
+            )
+        "
+            )
+            .as_bytes(),
+        );
+        lower_arrays(&mut c);
+        crate::ir::opt::tuple::eliminate_tuples(&mut c);
+        let terms = PostOrderIter::new(c.outputs[0].clone()).count();
+        assert!(terms < 10 * size, "{} terms", terms);
+    }
+}
@@ -36,6 +36,13 @@ pub enum Opt {
     Obliv,
     /// Replace arrays with linear scans
     LinearScan,
+    /// Replace arrays with linear scans or selection trees, whichever is cheaper for MPC (there is
+    /// no ORAM strategy).
+    ///
+    /// A selection tree only reads the low `log2(size)` bits of an index, so an out-of-bounds
+    /// access can alias an in-bounds position: a select may return that position's value, and a
+    /// store may overwrite it. Only use this pass on programs whose indices stay in bounds.
+    MpcArrays,
     /// Extract top-level ANDs as distinct outputs
     FlattenAssertions,
     /// Find outputs like `(= variable term)`, and substitute out `variable`
@@ -63,6 +70,12 @@ pub fn opt<I: IntoIterator<Item = Opt>>(mut cs: Computations, optimizations: I)
         }
 
         for (_, c) in cs.comps.iter_mut() {
+            // to carry source locations across the pass
+            let old_outputs = if c.metadata.source_map().is_empty() {
+                None
+            } else {
+                Some(c.outputs.clone())
+            };
             match i.clone() {
                 Opt::ParseCondStores => {
                     cstore::parse(c);
@@ -91,6 +104,9 @@ pub fn opt<I: IntoIterator<Item = Opt>>(mut cs: Computations, optimizations: I)
                 Opt::LinearScan => {
                     mem::lin::linearize(c);
                 }
+                Opt::MpcArrays => {
+                    mem::mpc::lower_arrays(c);
+                }
                 Opt::FlattenAssertions => {
                     let mut new_outputs = Vec::new();
                     for a in std::mem::take(&mut c.outputs) {
@@ -135,6 +151,11 @@ pub fn opt<I: IntoIterator<Item = Opt>>(mut cs: Computations, optimizations: I)
                     chall::skolemize_challenges(c);
                 }
             }
+            if let Some(old_outputs) = old_outputs {
+                c.metadata
+                    .source_map_mut()
+                    .realign(&old_outputs, &c.outputs);
+            }
             debug!("After {:?}: {} outputs", i, c.outputs.len());
             trace!("After {:?}: {}", i, c.outputs[0]);
             //debug!("After {:?}: {}", i, Letified(cs.outputs[0].clone()));
@@ -149,7 +149,8 @@ impl BitVector {
     /// arithmetic right shift
     pub fn ashr(mut self, other: &Self) -> Self {
         assert_eq!(self.width, other.width);
-        let n = other.uint.to_u32().unwrap();
+        // shifting by the width or more leaves only copies of the sign bit
+        let n = std::cmp::min(other.uint.to_u32().unwrap(), self.width as u32);
         let b = self.uint.get_bit(self.width as u32 - 1);
         self.uint >>= n;
         for i in 0..n {
@@ -42,11 +42,13 @@ pub mod fmt;
 pub mod lin;
 pub mod precomp;
 pub mod serde_mods;
+pub mod source;
 pub mod text;
 pub mod ty;
 
 pub use bv::BitVector;
 pub use ext::ExtOp;
+pub use source::{SourceLoc, SourceMap};
 pub use ty::{check, check_rec, TypeError, TypeErrorReason};
 
 #[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -1790,6 +1792,8 @@ pub struct ComputationMetadata {
     ///
     /// Each commitment is a vector of variables.
     commitments: Vec<Vec<String>>,
+    /// Where assertions and named terms came from in the source program.
+    source_map: SourceMap,
 }
 
 impl ComputationMetadata {
@@ -1799,6 +1803,18 @@ impl ComputationMetadata {
         self.party_ids.len() as u8 - 1
     }
 
+    /// Add `n` MPC parties, named `party0`, `party1`, ..., with ids `0..n`.
+    pub fn add_mpc_parties(&mut self, n: u8) {
+        for i in 0..n {
+            assert_eq!(self.add_party(format!("party{i}")), i);
+        }
+    }
+
+    /// The number of parties.
+    pub fn num_parties(&self) -> usize {
+        self.party_ids.len()
+    }
+
     /// Add a new input to the computation, visible to `party`, or public if `party` is [None].
     pub fn new_input(&mut self, name: String, party: Option<PartyId>, sort: Sort) {
         let var_md = VariableMetadata {
@@ -2030,6 +2046,16 @@ impl ComputationMetadata {
 
         term(Op::Call(name, ordered_sorts, ret_sort), ordered_args)
     }
+
+    /// Where assertions and named terms came from in the source program.
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    /// Where assertions and named terms came from in the source program (mutable).
+    pub fn source_map_mut(&mut self) -> &mut SourceMap {
+        &mut self.source_map
+    }
 }
 
 /// A structured collection of variables that indicates the round structure: e.g., orderings,
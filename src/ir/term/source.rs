@@ -0,0 +1,189 @@
+//! Source maps: where terms came from in the front-end program
+//!
+//! Front-ends record the file, span, and function that produced each assertion and each term
+//! bound to a source variable. The map is keyed by [Term], so it survives a rewrite only for the
+//! terms that the rewrite leaves alone; [SourceMap::realign] carries it (best-effort) across
+//! passes that preserve the shape of the outputs.
+
+use super::*;
+
+/// Where in the source program a term came from. Lines and columns count from 1.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SourceLoc {
+    /// The file
+    pub file: String,
+    /// The first line of the span
+    pub line: usize,
+    /// The first column of the span
+    pub column: usize,
+    /// The last line of the span
+    pub end_line: usize,
+    /// The column just past the span
+    pub end_column: usize,
+    /// The function that the span is in
+    pub function: String,
+    /// The source variable that the term is bound to; `None` for assertions.
+    pub name: Option<String>,
+}
+
+impl Display for SourceLoc {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        if let Some(n) = &self.name {
+            write!(f, "{n} at ")?;
+        }
+        write!(
+            f,
+            "{}:{}:{} in {}",
+            self.file, self.line, self.column, self.function
+        )
+    }
+}
+
+/// A map from terms to the source locations that produced them
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    locs: TermMap<SourceLoc>,
+}
+
+impl SourceMap {
+    /// Record that `t` came from `loc`. If `t` already has a location, it is kept.
+    pub fn insert(&mut self, t: Term, loc: SourceLoc) {
+        self.locs.entry(t).or_insert(loc);
+    }
+
+    /// Where `t` came from
+    pub fn get(&self, t: &Term) -> Option<&SourceLoc> {
+        self.locs.get(t)
+    }
+
+    /// The number of located terms
+    pub fn len(&self) -> usize {
+        self.locs.len()
+    }
+
+    /// Whether no term is located
+    pub fn is_empty(&self) -> bool {
+        self.locs.is_empty()
+    }
+
+    /// Iterate over the located terms
+    pub fn iter(&self) -> impl Iterator<Item = (&Term, &SourceLoc)> {
+        self.locs.iter()
+    }
+
+    /// Iterate over the located assertions
+    pub fn assertions(&self) -> impl Iterator<Item = (&Term, &SourceLoc)> {
+        self.locs.iter().filter(|(_, l)| l.name.is_none())
+    }
+
+    /// Carry locations across a pass that rewrote the outputs `old` to `new`.
+    ///
+    /// Where the two have the same shape (the same operator kinds and arities), a located old
+    /// term gives its location to the new term in the same position. Locations of terms that are
+    /// no longer in `new` are dropped.
+    pub fn realign(&mut self, old: &[Term], new: &[Term]) {
+        if self.locs.is_empty() {
+            return;
+        }
+        if old.len() == new.len() {
+            let mut seen = TermSet::default();
+            let mut moved = Vec::new();
+            let mut stack: Vec<(Term, Term)> =
+                old.iter().cloned().zip(new.iter().cloned()).collect();
+            while let Some((o, n)) = stack.pop() {
+                if o == n || !seen.insert(o.clone()) {
+                    continue;
+                }
+                if let Some(loc) = self.locs.get(&o) {
+                    moved.push((n.clone(), loc.clone()));
+                }
+                if o.cs().len() == n.cs().len()
+                    && std::mem::discriminant(o.op()) == std::mem::discriminant(n.op())
+                {
+                    stack.extend(o.cs().iter().cloned().zip(n.cs().iter().cloned()));
+                }
+            }
+            for (t, loc) in moved {
+                self.insert(t, loc);
+            }
+        }
+        self.prune(new);
+    }
+
+    /// Drop the locations of terms that are not in `roots`.
+    pub fn prune(&mut self, roots: &[Term]) {
+        let live: TermSet =
+            PostOrderIter::from_roots_and_skips(roots.iter().cloned(), Default::default())
+                .collect();
+        self.locs.retain(|t, _| live.contains(t));
+    }
+}
+
+impl Serialize for SourceMap {
+    fn serialize<S>(&self, ser: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let (terms, locs): (Vec<Term>, Vec<SourceLoc>) = self
+            .locs
+            .iter()
+            .map(|(t, l)| (t.clone(), l.clone()))
+            .unzip();
+        (term(Op::Tuple, terms), locs).serialize(ser)
+    }
+}
+
+impl<'de> Deserialize<'de> for SourceMap {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (tuple, locs): (Term, Vec<SourceLoc>) = Deserialize::deserialize(de)?;
+        Ok(Self {
+            locs: tuple.cs().iter().cloned().zip(locs).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::term;
+
+    fn loc(line: usize) -> SourceLoc {
+        SourceLoc {
+            file: "a.zok".into(),
+            line,
+            column: 5,
+            end_line: line,
+            end_column: 10,
+            function: "main".into(),
+            name: None,
+        }
+    }
+
+    #[test]
+    fn realign_follows_rewrites() {
+        let x = leaf_term(Op::Var("x".into(), Sort::Bool));
+        let y = leaf_term(Op::Var("y".into(), Sort::Bool));
+        let a0 = term![NOT; term![AND; x.clone(), bool_lit(true)]];
+        let a1 = term![OR; x.clone(), y.clone()];
+        let mut m = SourceMap::default();
+        m.insert(a0.clone(), loc(1));
+        m.insert(a1.clone(), loc(2));
+        let old = vec![term![AND; a0, a1.clone()]];
+        // as if constant folding simplified the first assertion
+        let b0 = term![NOT; x];
+        let new = vec![term![AND; b0.clone(), a1.clone()]];
+        m.realign(&old, &new);
+        assert_eq!(m.len(), 2);
+        assert_eq!(m.get(&b0), Some(&loc(1)));
+        assert_eq!(m.get(&a1), Some(&loc(2)));
+
+        // the second assertion is gone
+        m.realign(&new, std::slice::from_ref(&b0));
+        assert_eq!(m.len(), 1);
+        assert_eq!(m.get(&b0), Some(&loc(1)));
+        assert!(m.get(&y).is_none());
+    }
+}
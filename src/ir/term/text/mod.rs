@@ -15,7 +15,7 @@
 //!   * `I`: integer (arbitrary-precision)
 //!   * `X`: identifier
 //!     * regex: `[^()0-9#; \t\n\f][^(); \t\n\f#]*`
-//!   * Computation `C`: `(computation M P ARRAYS T)`
+//!   * Computation `C`: `(computation M P ARRAYS SOURCES T)`
 //!     * Metadata `M`: `(metadata PARTIES INPUTS COMMITMENTS)`
 //!       * PARTIES is `(parties X1 .. Xn)`
 //!       * INPUTS is `(inputs INPUT1 .. INPUTn)`
@@ -33,6 +33,10 @@
 //!         * X is the name of the inital state
 //!         * S is the size
 //!         * T is the state (final)
+//!     * SOURCES (optional): `(source_map LOCATED*)`:
+//!       * LOCATED is `((FILE LINE COL END_LINE END_COL FUNCTION [NAME]) T)`
+//!         * FILE, FUNCTION, and NAME are identifiers; the rest are naturals
+//!         * NAME is the source variable bound to T; it is absent for assertions
 //!   * Sort `S`:
 //!     * `bool`
 //!     * `f32`
@@ -723,13 +727,13 @@ impl<'src> IrInterp<'src> {
     pub fn computation(&mut self, tt: &TokTree<'src>) -> Computation {
         let tts = self.unwrap_prefix_list(tt, "computation");
         assert!(tts.len() >= 3);
-        let (metadata, input_names) = self.metadata(&tts[0]);
+        let (mut metadata, input_names) = self.metadata(&tts[0]);
         let precomputes = self.precompute(&tts[1]);
         let mut persistent_arrays = Vec::new();
-        let mut skip_one = false;
-        if let List(tts_inner) = &tts[2] {
+        let mut skip = 2;
+        if let Some(List(tts_inner)) = tts.get(skip) {
             if tts_inner[0] == Leaf(Token::Ident, b"persistent_arrays") {
-                skip_one = true;
+                skip += 1;
                 for tti in tts_inner.iter().skip(1) {
                     let ttis = self.unwrap_list(tti, "persistent_arrays");
                     let id = self.ident_string(&ttis[0]);
@@ -739,11 +743,18 @@ impl<'src> IrInterp<'src> {
                 }
             }
         }
-        let mut iter = tts.iter().skip(2);
-        if skip_one {
-            iter.next();
+        if let Some(List(tts_inner)) = tts.get(skip) {
+            if tts_inner[0] == Leaf(Token::Ident, b"source_map") {
+                skip += 1;
+                for tti in tts_inner.iter().skip(1) {
+                    let ttis = self.unwrap_list(tti, "source_map");
+                    let loc = self.source_loc(&ttis[0]);
+                    let term = self.term(&ttis[1]);
+                    metadata.source_map_mut().insert(term, loc);
+                }
+            }
         }
-        let outputs = iter.map(|tti| self.term(tti)).collect();
+        let outputs = tts.iter().skip(skip).map(|tti| self.term(tti)).collect();
         self.unbind(input_names);
         Computation {
             outputs,
@@ -753,6 +764,24 @@ impl<'src> IrInterp<'src> {
         }
     }
 
+    fn source_loc(&self, tt: &TokTree<'src>) -> SourceLoc {
+        let tts = self.unwrap_list(tt, "source location");
+        assert!(
+            tts.len() == 6 || tts.len() == 7,
+            "Expected source location, found {}",
+            tt
+        );
+        SourceLoc {
+            file: self.ident_string(&tts[0]),
+            line: self.usize(&tts[1]),
+            column: self.usize(&tts[2]),
+            end_line: self.usize(&tts[3]),
+            end_column: self.usize(&tts[4]),
+            function: self.ident_string(&tts[5]),
+            name: tts.get(6).map(|n| self.ident_string(n)),
+        }
+    }
+
     /// Parse a computation set.
     pub fn computations(&mut self, tt: &TokTree<'src>) -> Computations {
         if let List(tts) = tt {
@@ -906,6 +935,24 @@ pub fn serialize_computation(c: &Computation) -> String {
         }
         writeln!(&mut out, "\n)").unwrap();
     }
+    if !c.metadata.source_map().is_empty() {
+        writeln!(&mut out, "(source_map").unwrap();
+        let mut locs: Vec<_> = c.metadata.source_map().iter().collect();
+        locs.sort_by(|(t0, l0), (t1, l1)| l0.cmp(l1).then(t0.cmp(t1)));
+        for (t, l) in locs {
+            write!(
+                &mut out,
+                "  (({} {} {} {} {} {}",
+                l.file, l.line, l.column, l.end_line, l.end_column, l.function
+            )
+            .unwrap();
+            if let Some(n) = &l.name {
+                write!(&mut out, " {n}").unwrap();
+            }
+            writeln!(&mut out, ") {})", serialize_term(t)).unwrap();
+        }
+        writeln!(&mut out, "\n)").unwrap();
+    }
     for o in &c.outputs {
         writeln!(&mut out, "\n  {}", serialize_term(o)).unwrap();
     }
@@ -1120,6 +1167,10 @@ mod test {
                     ((a bool))
                     (tuple (not (and c d)))
                 )
+                (source_map
+                    ((main.zok 3 5 3 12 main) (not b))
+                    ((main.zok 2 5 2 10 main x) ((field 1) A))
+                )
                 (let (
                         (B ((update 1) A b))
                 ) (xor ((field 1) B)
@@ -1127,6 +1178,8 @@ mod test {
             )",
         );
         assert_eq!(c.metadata.vars.len(), 4);
+        assert_eq!(c.metadata.source_map().len(), 2);
+        assert_eq!(c.metadata.source_map().assertions().count(), 1);
         assert!(!c.metadata.is_input_public("a"));
         assert!(c.metadata.is_input_public("b"));
         assert!(c.metadata.is_input_public("A"));
@@ -0,0 +1,446 @@
+//! ILP-based sharing assignment
+//!
+//! Loosely based on ["Efficient MPC via Program Analysis: A Framework for Efficient Optimal
+//! Mixing"](https://dl.acm.org/doi/pdf/10.1145/3319535.3339818) by Ishaq, Muhammad and Milanova,
+//! Ana L. and Zikas, Vassilis.
+//!
+//! Our actual ILP is as follows:
+//!
+//! Let `s`, `t` denote terms, and `a`, `b` denote protocols.
+//!
+//! Let `T[t, a]` be a binary variable indicating whether term `t` is evaluated using protocol `a`.
+//! Let `C[t, a, b]` be a binary variable indicating whether term `t` needs to be converted from
+//! `a` to `b`.
+//!
+//! Since each term is evaluated using one protocol,
+//!
+//! `forall t. 1 = \sum_a T[t, a]             (1)`
+//!
+//! Sometimes conversions are needed
+//!
+//! `forall t a b. forall s in Uses(t). C[t, a, b] >= T[t, a] + T[s, b] - 1     (2)`
+//!
+//! The constraint (2) is intendend to encode
+//!
+//! `forall t a b. C[t, a, b] = OR_(s in Uses(t)) T[t, a] AND T[s, b]`
+//!
+//! It does this well because (a) the system is SAT and (b) our objective is a linear combination
+//! of all variables (term and conversion) scaled by their cost. In trying to minimize that, `C`
+//! will be set to the smallest value possible (0) if either of the variables on the right of (2)
+//! are 0.  If they are both 1 (for ANY `s`), then it must be 1.
+//!
+//! If rounds have a cost (see [CostModel]), we also bound the depth. Let `D[t]` be a continuous
+//! variable bounding the round in which `t` is ready, `R[t] = \sum_a rounds(t, a) T[t, a]` the
+//! rounds of `t`'s own operation, and `D` a continuous variable bounding the depth.
+//!
+//! `forall t. forall s in Args(t). D[t] >= D[s] + R[t]     (3)`
+//!
+//! `forall t a b. forall s in Args(t). D[t] >= D[s] + R[t] + rounds(a, b) (T[s, a] + T[t, b] - 1)  (4)`
+//!
+//! `forall t. D >= D[t] >= R[t]     (5)`
+//!
+//! (4) charges the rounds of converting `s` from `a` to `b` when `s` uses `a` and `t` uses `b`.
+//! The objective adds the round cost times `D`.
+
+use fxhash::{FxHashMap, FxHashSet};
+
+use super::partition::TermGraph;
+use super::{ShareType, SharingMap, SHARE_TYPES};
+use crate::ir::term::*;
+use crate::target::aby::assignment::{get_cost_model, CostModel};
+
+use crate::target::ilp::{geq, variable, Expression, Ilp, Variable};
+
+#[cfg(test)]
+use std::env::var;
+
+/// Uses an ILP to assign...
+pub fn assign(c: &Computation, cm: &str) -> SharingMap {
+    let costs = get_cost_model(cm);
+    build_ilp(c, &costs)
+}
+
+fn build_ilp(c: &Computation, costs: &CostModel) -> SharingMap {
+    let graph = TermGraph::new(c);
+    let all: Vec<usize> = (0..graph.len()).collect();
+    build_region_ilp(&graph, &all, &SharingMap::default(), costs)
+}
+
+/// Build and solve the ILP for the terms of `region`.
+///
+/// Terms outside the region with an entry in `fixed` keep that sharing: conversions between them
+/// and the region are costed. Edges to other terms are ignored. For depth, fixed terms are ready
+/// at the start.
+pub(super) fn build_region_ilp(
+    graph: &TermGraph,
+    region: &[usize],
+    fixed: &SharingMap,
+    costs: &CostModel,
+) -> SharingMap {
+    let in_region: FxHashSet<usize> = region.iter().copied().collect();
+    let is_fixed = |i: &usize| !in_region.contains(i) && fixed.contains_key(graph.term(*i));
+    let mut def_uses: FxHashSet<(usize, usize)> = FxHashSet::default();
+    for i in region {
+        for d in graph.operands(*i) {
+            if in_region.contains(d) || is_fixed(d) {
+                def_uses.insert((*d, *i));
+            }
+        }
+        for u in graph.uses(*i) {
+            if is_fixed(u) {
+                def_uses.insert((*i, *u));
+            }
+        }
+    }
+    let mut term_vars: FxHashMap<(usize, ShareType), (Variable, f64, String)> =
+        FxHashMap::default();
+    let mut conv_vars: FxHashMap<(usize, ShareType, ShareType), (Variable, f64)> =
+        FxHashMap::default();
+    let mut ilp = Ilp::new();
+
+    // build variables for all term assignments
+    for i in region {
+        let t = graph.term(*i);
+        let mut vars = vec![];
+        match &t.op() {
+            Op::Var(..)
+            | Op::Const(_)
+            | Op::Call(..)
+            | Op::Field(_)
+            | Op::Update(..)
+            | Op::Tuple => {
+                for ty in &SHARE_TYPES {
+                    let name = format!("t_{}_{}", i, ty.char());
+                    let v = ilp.new_variable(variable().binary(), name.clone());
+                    term_vars.insert((*i, *ty), (v, 0.0, name));
+                    vars.push(v);
+                }
+            }
+            Op::Select | Op::Store => {
+                panic!("Requires def-use-graph, tests should not have secret indices.")
+            }
+            _ => {
+                if let Some(costs) = costs.ops.get(t.op()) {
+                    for (ty, cost) in costs {
+                        let name = format!("t_{}_{}", i, ty.char());
+                        let v = ilp.new_variable(variable().binary(), name.clone());
+                        term_vars.insert((*i, *ty), (v, *cost, name));
+                        vars.push(v);
+                    }
+                } else {
+                    panic!("No cost for op {}", &t.op())
+                }
+            }
+        }
+        // Sum of assignments is at least 1.
+        ilp.new_constraint(geq(
+            vars.into_iter()
+                .fold((0.0).into(), |acc: Expression, v| acc + v),
+            1.0,
+        ));
+    }
+
+    // The indicator for term `i` using `ty`: a variable, or a constant for fixed terms.
+    let assigned = |i: usize, ty: ShareType| -> Option<Expression> {
+        if in_region.contains(&i) {
+            term_vars
+                .get(&(i, ty))
+                .map(|(v, _, _)| Expression::from(*v))
+        } else {
+            (fixed.get(graph.term(i)) == Some(&ty)).then_some(Expression::from(1.0))
+        }
+    };
+
+    // build variables for all conversions assignments
+    for (def, use_) in &def_uses {
+        for from_ty in &SHARE_TYPES {
+            for to_ty in &SHARE_TYPES {
+                // if def can be from_ty, and use can be to_ty
+                if assigned(*def, *from_ty).is_some()
+                    && assigned(*use_, *to_ty).is_some()
+                    && from_ty != to_ty
+                    && !conv_vars.contains_key(&(*def, *from_ty, *to_ty))
+                {
+                    let v = ilp.new_variable(
+                        variable().binary(),
+                        format!("c_{}_{}2{}", def, from_ty.char(), to_ty.char()),
+                    );
+                    conv_vars.insert(
+                        (*def, *from_ty, *to_ty),
+                        (v, *costs.conversions.get(&(*from_ty, *to_ty)).unwrap()),
+                    );
+                }
+            }
+        }
+    }
+
+    for (def, use_) in &def_uses {
+        for from_ty in &SHARE_TYPES {
+            for to_ty in &SHARE_TYPES {
+                if let (Some(c), Some(t_from), Some(t_to)) = (
+                    conv_vars.get(&(*def, *from_ty, *to_ty)),
+                    assigned(*def, *from_ty),
+                    assigned(*use_, *to_ty),
+                ) {
+                    // c[term i from pi to pi'] >= t[term j with pi'] + t[term i with pi] - 1
+                    ilp.new_constraint(geq(c.0, t_from + t_to - 1.0));
+                }
+            }
+        }
+    }
+
+    let mut depth_cost: Expression = 0.0.into();
+    if costs.round_cost > 0.0 {
+        let depth = ilp.new_variable(variable().min(0.0), "depth".into());
+        let ready: FxHashMap<usize, Variable> = region
+            .iter()
+            .map(|i| (*i, ilp.new_variable(variable().min(0.0), format!("d_{i}"))))
+            .collect();
+        let op_rounds = |i: usize| -> Expression {
+            SHARE_TYPES
+                .iter()
+                .filter_map(|ty| term_vars.get(&(i, *ty)).map(|(v, _, _)| (*v, *ty)))
+                .fold(0.0.into(), |acc: Expression, (v, ty)| {
+                    acc + v * costs.op_rounds(graph.term(i).op(), ty)
+                })
+        };
+        // (5)
+        for i in region {
+            ilp.new_constraint(geq(ready[i], op_rounds(*i)));
+            ilp.new_constraint(geq(depth, ready[i]));
+        }
+        for (def, use_) in &def_uses {
+            let use_ready = match ready.get(use_) {
+                Some(v) => v,
+                None => continue,
+            };
+            let def_ready = ready
+                .get(def)
+                .map(|v| Expression::from(*v))
+                .unwrap_or_else(|| 0.0.into());
+            let base = def_ready + op_rounds(*use_);
+            // (3)
+            ilp.new_constraint(geq(*use_ready, base.clone()));
+            for from_ty in &SHARE_TYPES {
+                for to_ty in &SHARE_TYPES {
+                    let rounds = costs.conversion_rounds(*from_ty, *to_ty);
+                    if rounds <= 0.0 {
+                        continue;
+                    }
+                    if let (Some(t_from), Some(t_to)) =
+                        (assigned(*def, *from_ty), assigned(*use_, *to_ty))
+                    {
+                        // (4)
+                        ilp.new_constraint(geq(
+                            *use_ready,
+                            base.clone() + (t_from + t_to - 1.0) * rounds,
+                        ));
+                    }
+                }
+            }
+        }
+        depth_cost = depth * costs.round_cost;
+    }
+
+    ilp.maximize(
+        -(conv_vars
+            .values()
+            .map(|(a, b)| (a, b))
+            .chain(term_vars.values().map(|(a, b, _)| (a, b)))
+            .fold(0.0.into(), |acc: Expression, (v, cost)| acc + *v * *cost)
+            + depth_cost),
+    );
+
+    let (_opt, solution) = ilp.default_solve().unwrap();
+
+    let mut assignment = TermMap::default();
+    for ((i, ty), (_, _, var_name)) in &term_vars {
+        if solution.get(var_name).unwrap() == &1.0 {
+            assignment.insert(graph.term(*i).clone(), *ty);
+        }
+    }
+    assignment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cost_model() {
+        let p = format!(
+            "{}/third_party/opa/adapted_costs.json",
+            var("CARGO_MANIFEST_DIR").expect("Could not find env var CARGO_MANIFEST_DIR")
+        );
+        let c = CostModel::from_opa_cost_file(&p);
+        // random checks from the file...
+        assert_eq!(
+            &1127.0,
+            c.ops.get(&BV_MUL).unwrap().get(&ShareType::Yao).unwrap()
+        );
+        assert_eq!(
+            &1731.0,
+            c.ops
+                .get(&BV_MUL)
+                .unwrap()
+                .get(&ShareType::Boolean)
+                .unwrap()
+        );
+        assert_eq!(
+            &7.0,
+            c.ops
+                .get(&BV_XOR)
+                .unwrap()
+                .get(&ShareType::Boolean)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn mul1_bv_opt() {
+        let p = format!(
+            "{}/third_party/opa/adapted_costs.json",
+            var("CARGO_MANIFEST_DIR").expect("Could not find env var CARGO_MANIFEST_DIR")
+        );
+        let costs = CostModel::from_opa_cost_file(&p);
+        let cs = Computation {
+            outputs: vec![term![BV_MUL;
+                leaf_term(Op::Var("a".to_owned(), Sort::BitVector(32))),
+                leaf_term(Op::Var("b".to_owned(), Sort::BitVector(32)))
+            ]],
+            ..Default::default()
+        };
+        let _assignment = build_ilp(&cs, &costs);
+    }
+
+    #[test]
+    fn huge_mul_then_eq() {
+        let p = format!(
+            "{}/third_party/opa/adapted_costs.json",
+            var("CARGO_MANIFEST_DIR").expect("Could not find env var CARGO_MANIFEST_DIR")
+        );
+        let costs = CostModel::from_opa_cost_file(&p);
+        let cs = Computation {
+            outputs: vec![term![Op::Eq;
+                term![BV_MUL;
+                leaf_term(Op::Var("a".to_owned(), Sort::BitVector(32))),
+                term![BV_MUL;
+                leaf_term(Op::Var("a".to_owned(), Sort::BitVector(32))),
+                term![BV_MUL;
+                leaf_term(Op::Var("a".to_owned(), Sort::BitVector(32))),
+                term![BV_MUL;
+                leaf_term(Op::Var("a".to_owned(), Sort::BitVector(32))),
+                term![BV_MUL;
+                leaf_term(Op::Var("a".to_owned(), Sort::BitVector(32))),
+                term![BV_MUL;
+                leaf_term(Op::Var("a".to_owned(), Sort::BitVector(32))),
+                term![BV_MUL;
+                leaf_term(Op::Var("a".to_owned(), Sort::BitVector(32))),
+                leaf_term(Op::Var("a".to_owned(), Sort::BitVector(32)))
+            ]
+            ]
+            ]
+            ]
+            ]
+            ]
+            ],
+            leaf_term(Op::Var("a".to_owned(), Sort::BitVector(32)))
+            ]],
+            ..Default::default()
+        };
+        let assignment = build_ilp(&cs, &costs);
+        // Big enough to do the math with arith
+        assert_eq!(
+            &ShareType::Arithmetic,
+            assignment.get(&cs.outputs[0].cs()[0]).unwrap()
+        );
+        // Then convert to boolean
+        assert_eq!(&ShareType::Boolean, assignment.get(&cs.outputs[0]).unwrap());
+    }
+
+    #[test]
+    fn big_mul_then_eq() {
+        let p = format!(
+            "{}/third_party/opa/adapted_costs.json",
+            var("CARGO_MANIFEST_DIR").expect("Could not find env var CARGO_MANIFEST_DIR")
+        );
+        let costs = CostModel::from_opa_cost_file(&p);
+        let cs = Computation {
+            outputs: vec![term![Op::Eq;
+                term![BV_MUL;
+                leaf_term(Op::Var("a".to_owned(), Sort::BitVector(32))),
+                term![BV_MUL;
+                leaf_term(Op::Var("a".to_owned(), Sort::BitVector(32))),
+                term![BV_MUL;
+                leaf_term(Op::Var("a".to_owned(), Sort::BitVector(32))),
+                term![BV_MUL;
+                leaf_term(Op::Var("a".to_owned(), Sort::BitVector(32))),
+                leaf_term(Op::Var("a".to_owned(), Sort::BitVector(32)))
+            ]
+            ]
+            ]
+            ],
+            leaf_term(Op::Var("a".to_owned(), Sort::BitVector(32)))
+            ]],
+            ..Default::default()
+        };
+        let assignment = build_ilp(&cs, &costs);
+        // All yao
+        assert_eq!(
+            &ShareType::Yao,
+            assignment.get(&cs.outputs[0].cs()[0]).unwrap()
+        );
+        assert_eq!(&ShareType::Yao, assignment.get(&cs.outputs[0]).unwrap());
+    }
+
+    #[test]
+    fn parse_default_rounds() {
+        let p = format!(
+            "{}/third_party/opa/adapted_costs.json",
+            var("CARGO_MANIFEST_DIR").expect("Could not find env var CARGO_MANIFEST_DIR")
+        );
+        let c = CostModel::from_opa_cost_file(&p);
+        assert_eq!(1.0, c.op_rounds(&BV_MUL, ShareType::Arithmetic));
+        assert_eq!(0.0, c.op_rounds(&BV_MUL, ShareType::Yao));
+        assert_eq!(5.0, c.op_rounds(&BV_ADD, ShareType::Boolean));
+        assert_eq!(0.0, c.op_rounds(&BV_XOR, ShareType::Boolean));
+        assert_eq!(
+            5.0,
+            c.conversion_rounds(ShareType::Arithmetic, ShareType::Boolean)
+        );
+        assert_eq!(0.0, c.conversion_rounds(ShareType::Yao, ShareType::Yao));
+        assert_eq!(0.0, c.round_cost);
+    }
+
+    #[test]
+    fn huge_mul_then_eq_expensive_rounds() {
+        let p = format!(
+            "{}/third_party/opa/adapted_costs.json",
+            var("CARGO_MANIFEST_DIR").expect("Could not find env var CARGO_MANIFEST_DIR")
+        );
+        let mut costs = CostModel::from_opa_cost_file(&p);
+        let a = leaf_term(Op::Var("a".to_owned(), Sort::BitVector(32)));
+        let mut prod = a.clone();
+        for _ in 0..7 {
+            prod = term![BV_MUL; a.clone(), prod];
+        }
+        let cs = Computation {
+            outputs: vec![term![Op::Eq; prod, a]],
+            ..Default::default()
+        };
+        let assignment = build_ilp(&cs, &costs);
+        assert_eq!(
+            &ShareType::Arithmetic,
+            assignment.get(&cs.outputs[0].cs()[0]).unwrap()
+        );
+        assert!(costs.assignment_cost(&cs, &assignment).rounds > 0.0);
+
+        // Over a slow network, constant-round Yao is worth its extra work.
+        costs.round_cost = 1e5;
+        let assignment = build_ilp(&cs, &costs);
+        for t in PostOrderIter::new(cs.outputs[0].clone()) {
+            assert_eq!(&ShareType::Yao, assignment.get(&t).unwrap());
+        }
+        assert_eq!(0.0, costs.assignment_cost(&cs, &assignment).rounds);
+    }
+}
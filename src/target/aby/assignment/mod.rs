@@ -0,0 +1,451 @@
+//! Machinery for assigning operations to sharing schemes
+use crate::ir::term::*;
+use fxhash::{FxHashMap, FxHashSet};
+use serde_json::Value;
+use std::{env::var, fs::File, path::Path};
+
+#[cfg(feature = "lp")]
+pub mod ilp;
+pub mod partition;
+
+/// The sharing scheme used for an operation
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum ShareType {
+    /// Arithmetic sharing (additive mod `Z_(2^l)`)
+    Arithmetic,
+    /// Boolean sharing (additive mod `Z_2`)
+    Boolean,
+    /// Yao sharing (one party holds `k_a`, `k_b`, other knows the `{k_a, k_b} <-> {0, 1}` mapping)
+    Yao,
+}
+
+/// List of share types.
+pub const SHARE_TYPES: [ShareType; 3] = [ShareType::Arithmetic, ShareType::Boolean, ShareType::Yao];
+
+impl ShareType {
+    /// Output associated char for each ShareType
+    pub fn char(&self) -> char {
+        match self {
+            ShareType::Arithmetic => 'a',
+            ShareType::Boolean => 'b',
+            ShareType::Yao => 'y',
+        }
+    }
+}
+
+/// A map from terms (operations or inputs) to sharing schemes they use
+pub type SharingMap = TermMap<ShareType>;
+
+/// The cost of a sharing assignment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AssignmentCost {
+    /// Total cost of operations and conversions
+    pub work: f64,
+    /// Number of rounds on the critical path
+    pub rounds: f64,
+}
+
+/// A cost model for ABY operations and share conversions
+///
+/// Besides its cost, each operation and conversion has a *depth*: the number of communication
+/// rounds it takes. For Boolean sharing, that is the AND-depth of its circuit; for Arithmetic
+/// sharing, its multiplicative depth. Yao sharing takes a constant number of rounds, which we
+/// count as zero per operation.
+#[derive(Debug)]
+pub struct CostModel {
+    /// Conversion costs: maps (from, to) pairs to cost
+    conversions: FxHashMap<(ShareType, ShareType), f64>,
+
+    /// Operator costs: maps (op, type) to cost
+    ops: FxHashMap<Op, FxHashMap<ShareType, f64>>,
+
+    /// Conversion depths: maps (from, to) pairs to rounds
+    conversion_rounds: FxHashMap<(ShareType, ShareType), f64>,
+
+    /// Operator depths: maps (op, type) to rounds
+    op_rounds: FxHashMap<Op, FxHashMap<ShareType, f64>>,
+
+    /// The cost of one round. Assignments minimize the total cost, plus this times the depth.
+    pub round_cost: f64,
+}
+
+/// The depth of a 32-bit operation or conversion (named as in OPA cost files), for cost files that
+/// do not give one.
+///
+/// Boolean depths assume ABY's depth-optimized circuits: a parallel-prefix adder has AND-depth
+/// `log2(32) = 5`.
+fn default_rounds(name: &str, share: Option<ShareType>) -> f64 {
+    use ShareType::*;
+    match (name, share) {
+        (_, Some(Yao)) => 0.0,
+        ("mul", Some(Arithmetic)) => 1.0,
+        (_, Some(Arithmetic)) => 0.0,
+        ("xor", _) => 0.0,
+        ("and" | "or" | "&&" | "||" | "mux", _) => 1.0,
+        ("add" | "sub" | "ge" | "le" | "gt" | "lt" | "eq" | "ne" | "shl" | "shr", _) => 5.0,
+        ("mul", _) => 10.0,
+        ("div" | "rem", _) => 160.0,
+        ("a2b", _) => 5.0,
+        ("y2b", _) => 0.0,
+        ("b2a" | "b2y" | "a2y" | "y2a", _) => 1.0,
+        _ => panic!("No default depth for operator {}", name),
+    }
+}
+
+impl CostModel {
+    /// Create a cost model from an OPA json file, like [this](https://github.com/ishaq/OPA/blob/d613c15ff715fa62c03e37b673548f94c16bfe0d/solver/sample-costs.json)
+    ///
+    /// Depths may be given in a `rounds` entry: a number for a conversion (`"a2b": {"32": 335,
+    /// "rounds": 5}`) or an object of share types for an operator (`"add": {..., "rounds": {"a": 0,
+    /// "b": 5}}`). Missing depths get defaults. The round cost is zero.
+    pub fn from_opa_cost_file(p: &impl AsRef<Path>) -> CostModel {
+        use ShareType::*;
+        let get_cost_opt =
+            |share_name: &str, obj: &serde_json::map::Map<String, Value>| -> Option<f64> {
+                let o = obj.get(share_name)?;
+                Some(
+                    o.get("32")
+                        .unwrap_or_else(|| panic!("Missing op '32' entry in {:#?}", o))
+                        .as_f64()
+                        .expect("not a number"),
+                )
+            };
+        let get_cost = |op_name: &str, obj: &serde_json::map::Map<String, Value>| -> f64 {
+            let o = obj
+                .get(op_name)
+                .unwrap_or_else(|| panic!("Missing op {} in {:#?}", op_name, obj));
+            Some(
+                o.get("32")
+                    .unwrap_or_else(|| panic!("Missing op '32' entry in {:#?}", o))
+                    .as_f64()
+                    .expect("not a number"),
+            )
+            .unwrap()
+        };
+        let get_rounds = |op_name: &str, share: Option<(ShareType, &str)>, obj: &Value| -> f64 {
+            let rounds = obj.get("rounds");
+            let rounds = match share {
+                Some((_, share_name)) => rounds.and_then(|r| r.get(share_name)),
+                None => rounds,
+            };
+            rounds
+                .map(|r| r.as_f64().expect("rounds are not a number"))
+                .unwrap_or_else(|| default_rounds(op_name, share.map(|s| s.0)))
+        };
+        let mut conversions = FxHashMap::default();
+        let mut ops = FxHashMap::default();
+        let mut conversion_rounds = FxHashMap::default();
+        let mut op_rounds = FxHashMap::default();
+        let f = File::open(p).expect("Missing file");
+        let json: Value = serde_json::from_reader(f).expect("Bad JSON");
+        let costs = json.as_object().unwrap();
+        // conversions
+        for (name, from, to) in [
+            ("a2b", Arithmetic, Boolean),
+            ("b2a", Boolean, Arithmetic),
+            ("y2b", Yao, Boolean),
+            ("b2y", Boolean, Yao),
+            ("y2a", Yao, Arithmetic),
+            ("a2y", Arithmetic, Yao),
+        ] {
+            conversions.insert((from, to), get_cost(name, costs));
+            conversion_rounds.insert((from, to), get_rounds(name, None, &costs[name]));
+        }
+
+        let ops_from_name = |name: &str| {
+            match name {
+                // assume comparisions are unsigned
+                "ge" => vec![BV_UGE],
+                "le" => vec![BV_ULE],
+                "gt" => vec![BV_UGT],
+                "lt" => vec![BV_ULT],
+                // assume n-ary ops apply to BVs
+                "add" => vec![BV_ADD],
+                "mul" => vec![BV_MUL],
+                "and" => vec![BV_AND],
+                "or" => vec![BV_OR],
+                "xor" => vec![BV_XOR],
+                // assume eq applies to BVs
+                "eq" => vec![Op::Eq],
+                "shl" => vec![BV_SHL],
+                // assume shr is logical, not arithmetic
+                "shr" => vec![BV_LSHR],
+                "sub" => vec![BV_SUB],
+                "mux" => vec![ITE],
+                "ne" => vec![Op::Not, Op::Eq],
+                "div" => vec![BV_UDIV],
+                "rem" => vec![BV_UREM],
+                // added to pass test case
+                "&&" => vec![AND],
+                "||" => vec![OR],
+                _ => panic!("Unknown operator name: {}", name),
+            }
+        };
+        for (op_name, cost) in costs {
+            // HACK: assumes the presence of 2 partitions names into conversion and otherwise.
+            if !op_name.contains('2') {
+                for op in ops_from_name(op_name) {
+                    for (share_type, share_name) in &[(Arithmetic, "a"), (Boolean, "b"), (Yao, "y")]
+                    {
+                        if let Some(c) = get_cost_opt(share_name, cost.as_object().unwrap()) {
+                            ops.entry(op.clone())
+                                .or_insert_with(FxHashMap::default)
+                                .insert(*share_type, c);
+                            op_rounds
+                                .entry(op.clone())
+                                .or_insert_with(FxHashMap::default)
+                                .insert(
+                                    *share_type,
+                                    get_rounds(op_name, Some((*share_type, share_name)), cost),
+                                );
+                        }
+                    }
+                }
+            }
+        }
+        CostModel {
+            conversions,
+            ops,
+            conversion_rounds,
+            op_rounds,
+            round_cost: 0.0,
+        }
+    }
+
+    /// The depth of `op` in sharing `ty`, or zero if the model does not know the op.
+    pub fn op_rounds(&self, op: &Op, ty: ShareType) -> f64 {
+        self.op_rounds
+            .get(op)
+            .and_then(|r| r.get(&ty))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// The depth of converting from `from` to `to`.
+    pub fn conversion_rounds(&self, from: ShareType, to: ShareType) -> f64 {
+        if from == to {
+            0.0
+        } else {
+            self.conversion_rounds[&(from, to)]
+        }
+    }
+
+    /// The costs of `op` in each sharing it supports, with each depth charged at the round cost.
+    ///
+    /// Greedy assignments use this. It over-charges operations that run in parallel, but steers
+    /// them away from deep sharings when rounds are expensive.
+    fn op_costs(&self, op: &Op) -> Option<FxHashMap<ShareType, f64>> {
+        self.ops.get(op).map(|costs| {
+            costs
+                .iter()
+                .map(|(ty, c)| (*ty, c + self.round_cost * self.op_rounds(op, *ty)))
+                .collect()
+        })
+    }
+}
+
+impl CostModel {
+    /// The work and depth of `assignment`, which must assign every term of `c`.
+    ///
+    /// As in the ILP, a term converted to some sharing pays for that conversion once, however many
+    /// uses need it.
+    pub fn assignment_cost(&self, c: &Computation, assignment: &SharingMap) -> AssignmentCost {
+        let mut work = 0.0;
+        let mut converted: FxHashSet<(Term, ShareType)> = FxHashSet::default();
+        let mut ready: TermMap<f64> = TermMap::default();
+        for t in PostOrderIter::from_roots_and_skips(c.outputs.iter().cloned(), TermSet::default())
+        {
+            let ty = assignment[&t];
+            work += self
+                .ops
+                .get(t.op())
+                .and_then(|c| c.get(&ty))
+                .copied()
+                .unwrap_or(0.0);
+            let mut start: f64 = 0.0;
+            for s in t.cs() {
+                let from = assignment[s];
+                if from != ty && converted.insert((s.clone(), ty)) {
+                    work += self.conversions[&(from, ty)];
+                }
+                start = start.max(ready[s] + self.conversion_rounds(from, ty));
+            }
+            ready.insert(t.clone(), start + self.op_rounds(t.op(), ty));
+        }
+        AssignmentCost {
+            work,
+            rounds: c.outputs.iter().map(|o| ready[o]).fold(0.0, f64::max),
+        }
+    }
+}
+
+/// The work and depth of `assignment` under the named cost model.
+pub fn assignment_cost(c: &Computation, assignment: &SharingMap, cm: &str) -> AssignmentCost {
+    get_cost_model(cm).assignment_cost(c, assignment)
+}
+
+/// Load the named cost model, with the round cost from the CirC configuration (if it is set).
+fn get_cost_model(cm: &str) -> CostModel {
+    let base_dir = match cm {
+        "opa" => "opa",
+        "hycc" => "hycc",
+        _ => panic!("Unknown cost model type: {}", cm),
+    };
+    let p = format!(
+        "{}/third_party/{}/adapted_costs.json",
+        var("CARGO_MANIFEST_DIR").expect("Could not find env var CARGO_MANIFEST_DIR"),
+        base_dir
+    );
+    let mut costs = CostModel::from_opa_cost_file(&p);
+    if crate::cfg::is_cfg_set() {
+        costs.round_cost = crate::cfg::cfg().mpc.round_cost;
+    }
+    costs
+}
+
+/// Assigns boolean sharing to all terms
+pub fn assign_all_boolean(c: &Computation, _cm: &str) -> SharingMap {
+    c.outputs
+        .iter()
+        .flat_map(|output| {
+            PostOrderIter::new(output.clone()).map(|term| (term, ShareType::Boolean))
+        })
+        .collect()
+}
+
+/// Assigns Yao sharing to all terms
+pub fn assign_all_yao(c: &Computation, _cm: &str) -> SharingMap {
+    c.outputs
+        .iter()
+        .flat_map(|output| PostOrderIter::new(output.clone()).map(|term| (term, ShareType::Yao)))
+        .collect()
+}
+
+/// Assign greedy Arithmetic and Boolean sharings based on cost model
+pub fn assign_arithmetic_and_boolean(c: &Computation, cm: &str) -> SharingMap {
+    let cost_model = get_cost_model(cm);
+    c.outputs
+        .iter()
+        .flat_map(|output| {
+            PostOrderIter::new(output.clone()).map(|term| {
+                (
+                    term.clone(),
+                    if let Some(costs) = cost_model.op_costs(term.op()) {
+                        let mut min_ty: ShareType = ShareType::Boolean;
+                        let mut min_cost: f64 = costs[&min_ty];
+                        for ty in &[ShareType::Arithmetic] {
+                            if let Some(c) = costs.get(ty) {
+                                if *c < min_cost {
+                                    min_ty = *ty;
+                                    min_cost = *c;
+                                }
+                            }
+                        }
+                        min_ty
+                    } else {
+                        ShareType::Boolean
+                    },
+                )
+            })
+        })
+        .collect()
+}
+
+/// Assign greedy Arithmetic and yao sharings based on cost model
+pub fn assign_arithmetic_and_yao(c: &Computation, cm: &str) -> SharingMap {
+    let cost_model = get_cost_model(cm);
+    c.outputs
+        .iter()
+        .flat_map(|output| {
+            PostOrderIter::new(output.clone()).map(|term| {
+                (
+                    term.clone(),
+                    if let Some(costs) = cost_model.op_costs(term.op()) {
+                        let mut min_ty: ShareType = ShareType::Yao;
+                        let mut min_cost: f64 = costs[&min_ty];
+                        for ty in &[ShareType::Arithmetic] {
+                            if let Some(c) = costs.get(ty) {
+                                if *c < min_cost {
+                                    min_ty = *ty;
+                                    min_cost = *c;
+                                }
+                            }
+                        }
+                        min_ty
+                    } else {
+                        ShareType::Yao
+                    },
+                )
+            })
+        })
+        .collect()
+}
+
+/// Assign all greedy sharings based on cost model
+pub fn assign_greedy(c: &Computation, cm: &str) -> SharingMap {
+    let cost_model = get_cost_model(cm);
+    c.outputs
+        .iter()
+        .flat_map(|output| {
+            PostOrderIter::new(output.clone()).map(|term| {
+                (
+                    term.clone(),
+                    if let Some(costs) = cost_model.op_costs(term.op()) {
+                        let mut min_ty: ShareType = ShareType::Yao;
+                        let mut min_cost: f64 = costs[&min_ty];
+                        for ty in &[ShareType::Arithmetic, ShareType::Boolean] {
+                            if let Some(c) = costs.get(ty) {
+                                if *c < min_cost {
+                                    min_ty = *ty;
+                                    min_cost = *c;
+                                }
+                            }
+                        }
+                        min_ty
+                    } else {
+                        ShareType::Boolean
+                    },
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn assignment_cost() {
+        let p = format!(
+            "{}/third_party/opa/adapted_costs.json",
+            var("CARGO_MANIFEST_DIR").expect("Could not find env var CARGO_MANIFEST_DIR")
+        );
+        let costs = CostModel::from_opa_cost_file(&p);
+        let a = leaf_term(Op::Var("a".to_owned(), Sort::BitVector(32)));
+        let b = leaf_term(Op::Var("b".to_owned(), Sort::BitVector(32)));
+        let c = leaf_term(Op::Var("c".to_owned(), Sort::BitVector(32)));
+        let mul = term![BV_MUL; a.clone(), b.clone()];
+        let eq = term![Op::Eq; mul.clone(), c.clone()];
+        let cs = Computation {
+            outputs: vec![eq.clone()],
+            ..Default::default()
+        };
+        let assignment: SharingMap = vec![
+            (a, ShareType::Arithmetic),
+            (b, ShareType::Arithmetic),
+            (c, ShareType::Boolean),
+            (mul, ShareType::Arithmetic),
+            (eq, ShareType::Boolean),
+        ]
+        .into_iter()
+        .collect();
+        let cost = costs.assignment_cost(&cs, &assignment);
+        // mul (1 round), a2b (5 rounds), eq (5 rounds)
+        assert_eq!(11.0, cost.rounds);
+        let expected = costs.ops[&BV_MUL][&ShareType::Arithmetic]
+            + costs.conversions[&(ShareType::Arithmetic, ShareType::Boolean)]
+            + costs.ops[&Op::Eq][&ShareType::Boolean];
+        assert_eq!(expected, cost.work);
+    }
+}
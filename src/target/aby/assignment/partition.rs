@@ -0,0 +1,543 @@
+//! Partition-based sharing assignment
+//!
+//! Solving one ILP over the whole term graph (see [super::ilp]) does not scale past a few thousand
+//! terms. Instead, we split the term graph into regions of bounded size, and assign each region on
+//! its own.
+//!
+//! Regions are assigned one at a time, roughly in topological order. When a region is assigned,
+//! the sharing of every neighboring term in an earlier region is fixed, and the region's
+//! assignment pays for the conversions to and from those fixed terms. Edges to regions that are
+//! not yet assigned are paid for when those regions are.
+//!
+//! The partitioner is [KaHIP](https://github.com/KaHIP/KaHIP) (feature `kahip`; binary found
+//! through `KAHIP_SOURCE`) or [KaHyPar](https://github.com/kahypar/kahypar) (feature `kahypar`;
+//! binary found through `KAHYPAR_SOURCE`). If neither is enabled, or the binary fails, we fall back
+//! to an in-tree partitioner.
+
+use super::{get_cost_model, CostModel, ShareType, SharingMap, SHARE_TYPES};
+use crate::ir::term::*;
+
+use fxhash::FxHashMap;
+use log::debug;
+#[cfg(any(feature = "kahip", feature = "kahypar"))]
+use log::warn;
+
+#[cfg(any(feature = "kahip", feature = "kahypar"))]
+use std::{
+    env::var,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+/// The default maximum number of terms in a region.
+pub const DEFAULT_REGION_SIZE: usize = 1000;
+
+/// Allowed imbalance of the in-tree partitioner, as a fraction of the target region size.
+const IMBALANCE: f64 = 0.1;
+
+/// Refinement passes of the in-tree partitioner.
+const REFINE_PASSES: usize = 8;
+
+/// The term graph of a computation.
+///
+/// Terms are numbered in post-order, so operands come before their uses.
+pub struct TermGraph {
+    terms: Vec<Term>,
+    operands: Vec<Vec<usize>>,
+    uses: Vec<Vec<usize>>,
+}
+
+impl TermGraph {
+    /// Build the term graph of `c`'s outputs.
+    pub fn new(c: &Computation) -> Self {
+        let terms: Vec<Term> =
+            PostOrderIter::from_roots_and_skips(c.outputs.iter().cloned(), TermSet::default())
+                .collect();
+        let index: TermMap<usize> = terms
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (t.clone(), i))
+            .collect();
+        let operands: Vec<Vec<usize>> = terms
+            .iter()
+            .map(|t| {
+                let mut cs: Vec<usize> = t.cs().iter().map(|c| index[c]).collect();
+                cs.sort_unstable();
+                cs.dedup();
+                cs
+            })
+            .collect();
+        let mut uses = vec![Vec::new(); terms.len()];
+        for (u, cs) in operands.iter().enumerate() {
+            for d in cs {
+                uses[*d].push(u);
+            }
+        }
+        Self {
+            terms,
+            operands,
+            uses,
+        }
+    }
+
+    /// The number of terms.
+    pub fn len(&self) -> usize {
+        self.terms.len()
+    }
+
+    /// Whether there are no terms.
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// The term numbered `i`.
+    pub fn term(&self, i: usize) -> &Term {
+        &self.terms[i]
+    }
+
+    /// The (distinct) operands of term `i`.
+    pub fn operands(&self, i: usize) -> &[usize] {
+        &self.operands[i]
+    }
+
+    /// The (distinct) uses of term `i`.
+    pub fn uses(&self, i: usize) -> &[usize] {
+        &self.uses[i]
+    }
+
+    fn neighbors(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        self.operands[i].iter().chain(&self.uses[i]).copied()
+    }
+
+    #[cfg(feature = "kahip")]
+    fn num_edges(&self) -> usize {
+        self.operands.iter().map(|cs| cs.len()).sum()
+    }
+}
+
+/// A graph partitioner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Partitioner {
+    /// The in-tree partitioner: chunks of the post-order, refined by greedy moves
+    InTree,
+    #[cfg(feature = "kahip")]
+    /// KaHIP's `kaffpa`, on the term graph
+    KaHIP,
+    #[cfg(feature = "kahypar")]
+    /// KaHyPar, on the hypergraph with one edge per term and its uses
+    KaHyPar,
+}
+
+impl Default for Partitioner {
+    #[allow(unreachable_code)]
+    fn default() -> Self {
+        #[cfg(feature = "kahip")]
+        return Partitioner::KaHIP;
+        #[cfg(feature = "kahypar")]
+        return Partitioner::KaHyPar;
+        Partitioner::InTree
+    }
+}
+
+/// How each region is assigned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionSolver {
+    #[cfg(feature = "lp")]
+    /// The ILP from [super::ilp]
+    Ilp,
+    /// Choose the cheapest sharing for each term, given its operands and fixed neighbors
+    Greedy,
+}
+
+/// Partition `graph` into (at most) `num_parts` parts. Returns the part of each term.
+pub fn partition(graph: &TermGraph, num_parts: usize, partitioner: Partitioner) -> Vec<usize> {
+    if num_parts <= 1 {
+        return vec![0; graph.len()];
+    }
+    #[cfg(any(feature = "kahip", feature = "kahypar"))]
+    let or_in_tree = |r: io::Result<Vec<usize>>| {
+        r.unwrap_or_else(|e| {
+            warn!("{partitioner:?} failed ({e}), using the in-tree partitioner");
+            partition_in_tree(graph, num_parts)
+        })
+    };
+    match partitioner {
+        Partitioner::InTree => partition_in_tree(graph, num_parts),
+        #[cfg(feature = "kahip")]
+        Partitioner::KaHIP => or_in_tree(partition_kahip(graph, num_parts)),
+        #[cfg(feature = "kahypar")]
+        Partitioner::KaHyPar => or_in_tree(partition_kahypar(graph, num_parts)),
+    }
+}
+
+/// Start from contiguous chunks of the post-order (which keep operands near their uses), then
+/// repeatedly move each term to the part holding most of its neighbors.
+fn partition_in_tree(graph: &TermGraph, num_parts: usize) -> Vec<usize> {
+    let n = graph.len();
+    let target = n.div_ceil(num_parts).max(1);
+    let max_size = target + (target as f64 * IMBALANCE) as usize;
+    let mut parts: Vec<usize> = (0..n).map(|i| i / target).collect();
+    let mut sizes = vec![0; num_parts];
+    for p in &parts {
+        sizes[*p] += 1;
+    }
+    for pass in 0..REFINE_PASSES {
+        let mut moves = 0;
+        for i in 0..n {
+            let from = parts[i];
+            if sizes[from] <= 1 {
+                continue;
+            }
+            let mut counts: FxHashMap<usize, usize> = FxHashMap::default();
+            for j in graph.neighbors(i) {
+                *counts.entry(parts[j]).or_default() += 1;
+            }
+            let here = counts.get(&from).copied().unwrap_or(0);
+            let best = counts
+                .into_iter()
+                .filter(|(p, _)| *p != from && sizes[*p] < max_size)
+                .max_by_key(|(p, count)| (*count, std::cmp::Reverse(*p)));
+            if let Some((to, count)) = best {
+                if count > here {
+                    parts[i] = to;
+                    sizes[from] -= 1;
+                    sizes[to] += 1;
+                    moves += 1;
+                }
+            }
+        }
+        debug!("Partition refinement pass {pass}: {moves} moves");
+        if moves == 0 {
+            break;
+        }
+    }
+    parts
+}
+
+/// A fresh scratch directory for partitioner input and output.
+#[cfg(any(feature = "kahip", feature = "kahypar"))]
+fn scratch_dir() -> io::Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("circ_partition_{}", std::process::id()));
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+#[cfg(any(feature = "kahip", feature = "kahypar"))]
+fn source_dir(key: &str) -> io::Result<String> {
+    var(key).map_err(|e| io::Error::new(io::ErrorKind::NotFound, format!("{key}: {e}")))
+}
+
+#[cfg(any(feature = "kahip", feature = "kahypar"))]
+fn run(cmd: &mut Command) -> io::Result<()> {
+    let status = cmd.stdout(Stdio::null()).status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("{cmd:?} exited with {status}")))
+    }
+}
+
+/// Read a partition file: one part number per line, one line per vertex.
+#[cfg(any(feature = "kahip", feature = "kahypar"))]
+fn read_partition(path: &Path, graph: &TermGraph, num_parts: usize) -> io::Result<Vec<usize>> {
+    let bad = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+    let mut parts = Vec::with_capacity(graph.len());
+    for line in BufReader::new(fs::File::open(path)?).lines() {
+        let line = line?;
+        let p: usize = line
+            .trim()
+            .parse()
+            .map_err(|e| bad(format!("bad part {line:?}: {e}")))?;
+        if p >= num_parts {
+            return Err(bad(format!("part {p} out of range")));
+        }
+        parts.push(p);
+    }
+    if parts.len() != graph.len() {
+        return Err(bad(format!(
+            "{} parts for {} vertices",
+            parts.len(),
+            graph.len()
+        )));
+    }
+    Ok(parts)
+}
+
+/// Write `graph` in METIS format: a header, then the (1-based) neighbors of each vertex.
+#[cfg(feature = "kahip")]
+fn write_metis(graph: &TermGraph, path: &Path) -> io::Result<()> {
+    let mut f = io::BufWriter::new(fs::File::create(path)?);
+    writeln!(f, "{} {}", graph.len(), graph.num_edges())?;
+    for i in 0..graph.len() {
+        let ns: Vec<String> = graph.neighbors(i).map(|j| (j + 1).to_string()).collect();
+        writeln!(f, "{}", ns.join(" "))?;
+    }
+    f.flush()
+}
+
+#[cfg(feature = "kahip")]
+fn partition_kahip(graph: &TermGraph, num_parts: usize) -> io::Result<Vec<usize>> {
+    let source = source_dir("KAHIP_SOURCE")?;
+    let dir = scratch_dir()?;
+    let graph_path = dir.join("graph.metis");
+    let part_path = dir.join("graph.part");
+    write_metis(graph, &graph_path)?;
+    run(Command::new(format!("{source}/deploy/kaffpa"))
+        .arg(&graph_path)
+        .arg(format!("--k={num_parts}"))
+        .arg("--preconfiguration=fast")
+        .arg(format!("--output_filename={}", part_path.display())))?;
+    let parts = read_partition(&part_path, graph, num_parts);
+    fs::remove_dir_all(&dir)?;
+    parts
+}
+
+/// Write the hypergraph of `graph` in hMETIS format: a header, then one hyperedge per term with
+/// uses, holding the (1-based) term and its uses.
+#[cfg(feature = "kahypar")]
+fn write_hmetis(graph: &TermGraph, path: &Path) -> io::Result<()> {
+    let mut f = io::BufWriter::new(fs::File::create(path)?);
+    let defs: Vec<usize> = (0..graph.len())
+        .filter(|i| !graph.uses(*i).is_empty())
+        .collect();
+    writeln!(f, "{} {}", defs.len(), graph.len())?;
+    for d in defs {
+        let pins: Vec<String> = std::iter::once(d)
+            .chain(graph.uses(d).iter().copied())
+            .map(|j| (j + 1).to_string())
+            .collect();
+        writeln!(f, "{}", pins.join(" "))?;
+    }
+    f.flush()
+}
+
+#[cfg(feature = "kahypar")]
+fn partition_kahypar(graph: &TermGraph, num_parts: usize) -> io::Result<Vec<usize>> {
+    let source = source_dir("KAHYPAR_SOURCE")?;
+    let dir = scratch_dir()?;
+    let graph_path = dir.join("graph.hgr");
+    write_hmetis(graph, &graph_path)?;
+    run(
+        Command::new(format!("{source}/build/kahypar/application/KaHyPar"))
+            .arg("-h")
+            .arg(&graph_path)
+            .arg(format!("-k{num_parts}"))
+            .arg(format!("-e{IMBALANCE}"))
+            .args(["-o", "km1", "-m", "direct", "-w", "true", "-p"])
+            .arg(format!("{source}/config/km1_kKaHyPar_sea20.ini")),
+    )?;
+    // KaHyPar names its output after the input, the parameters, and the seed.
+    let part_path = fs::read_dir(&dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .find(|p| p.to_string_lossy().contains("graph.hgr.part"))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no KaHyPar output"))?;
+    let parts = read_partition(&part_path, graph, num_parts);
+    fs::remove_dir_all(&dir)?;
+    parts
+}
+
+/// Assign sharings by partitioning `c` into regions of at most (about) `region_size` terms, and
+/// assigning each region with `solver`.
+pub fn assign_partitioned(
+    c: &Computation,
+    cm: &str,
+    region_size: usize,
+    solver: RegionSolver,
+) -> SharingMap {
+    let costs = get_cost_model(cm);
+    let graph = TermGraph::new(c);
+    let num_parts = graph.len().div_ceil(region_size.max(1)).max(1);
+    let parts = partition(&graph, num_parts, Partitioner::default());
+    assign_regions(&graph, &parts, num_parts, &costs, solver)
+}
+
+fn assign_regions(
+    graph: &TermGraph,
+    parts: &[usize],
+    num_parts: usize,
+    costs: &CostModel,
+    solver: RegionSolver,
+) -> SharingMap {
+    let mut regions: Vec<Vec<usize>> = vec![Vec::new(); num_parts];
+    for (i, p) in parts.iter().enumerate() {
+        regions[*p].push(i);
+    }
+    regions.retain(|r| !r.is_empty());
+    // Regions whose terms come early in the post-order tend to hold the operands of later ones.
+    regions.sort_by_key(|r| r.iter().sum::<usize>() / r.len());
+    debug!(
+        "Assigning {} terms in {} regions",
+        graph.len(),
+        regions.len()
+    );
+    let mut assignment = SharingMap::default();
+    for region in &regions {
+        let region_assignment = match solver {
+            #[cfg(feature = "lp")]
+            RegionSolver::Ilp => super::ilp::build_region_ilp(graph, region, &assignment, costs),
+            RegionSolver::Greedy => assign_region_greedy(graph, region, &assignment, costs),
+        };
+        assignment.extend(region_assignment);
+    }
+    assignment
+}
+
+/// Whether `op` only moves data, and so is free in any sharing.
+fn is_free(op: &Op) -> bool {
+    matches!(
+        op,
+        Op::Var(..) | Op::Const(_) | Op::Call(..) | Op::Field(_) | Op::Update(..) | Op::Tuple
+    )
+}
+
+/// The sharings term `t` may use, and their costs (depth included).
+///
+/// Ops with no cost entry are boolean, as in [super::assign_greedy].
+fn candidates(t: &Term, costs: &CostModel) -> Vec<(ShareType, f64)> {
+    match t.op() {
+        op if is_free(op) => SHARE_TYPES.iter().map(|ty| (*ty, 0.0)).collect(),
+        op => match costs.op_costs(op) {
+            Some(c) => SHARE_TYPES
+                .iter()
+                .filter_map(|ty| c.get(ty).map(|cost| (*ty, *cost)))
+                .collect(),
+            None => vec![(ShareType::Boolean, 0.0)],
+        },
+    }
+}
+
+fn assign_region_greedy(
+    graph: &TermGraph,
+    region: &[usize],
+    fixed: &SharingMap,
+    costs: &CostModel,
+) -> SharingMap {
+    let conversion = |from: ShareType, to: ShareType| {
+        if from == to {
+            0.0
+        } else {
+            costs.conversions[&(from, to)] + costs.round_cost * costs.conversion_rounds(from, to)
+        }
+    };
+    let mut assignment = SharingMap::default();
+    // Pick the cheapest sharing for term `i`, given the sharings of its assigned neighbors.
+    let choose = |i: usize, assignment: &SharingMap| -> ShareType {
+        let known = |j: usize| {
+            let t = graph.term(j);
+            assignment.get(t).or_else(|| fixed.get(t)).copied()
+        };
+        candidates(graph.term(i), costs)
+            .into_iter()
+            .map(|(ty, cost)| {
+                let ins: f64 = graph
+                    .operands(i)
+                    .iter()
+                    .filter_map(|j| known(*j))
+                    .map(|from| conversion(from, ty))
+                    .sum();
+                let outs: f64 = graph
+                    .uses(i)
+                    .iter()
+                    .filter_map(|j| known(*j))
+                    .map(|to| conversion(ty, to))
+                    .sum();
+                (ty, cost + ins + outs)
+            })
+            .fold(
+                None,
+                |best: Option<(ShareType, f64)>, (ty, cost)| match best {
+                    Some((_, best_cost)) if best_cost <= cost => best,
+                    _ => Some((ty, cost)),
+                },
+            )
+            .unwrap()
+            .0
+    };
+    for i in region {
+        let ty = choose(*i, &assignment);
+        assignment.insert(graph.term(*i).clone(), ty);
+    }
+    // Free terms (e.g., inputs) were chosen before their uses; choose again, knowing them.
+    for i in region {
+        let t = graph.term(*i);
+        if is_free(t.op()) {
+            let ty = choose(*i, &assignment);
+            assignment.insert(t.clone(), ty);
+        }
+    }
+    assignment
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// `a * (a * (a * ... a)) == a`, with `n` multiplications.
+    fn mul_chain(n: usize) -> Computation {
+        let a = leaf_term(Op::Var("a".to_owned(), Sort::BitVector(32)));
+        let mut t = a.clone();
+        for _ in 0..n {
+            t = term![BV_MUL; a.clone(), t];
+        }
+        Computation {
+            outputs: vec![term![Op::Eq; t, a]],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn in_tree_partition_is_balanced() {
+        let cs = mul_chain(40);
+        let graph = TermGraph::new(&cs);
+        let parts = partition(&graph, 4, Partitioner::InTree);
+        assert_eq!(parts.len(), graph.len());
+        let mut sizes = [0; 4];
+        for p in &parts {
+            sizes[*p] += 1;
+        }
+        let target = graph.len().div_ceil(4);
+        for s in sizes {
+            assert!(s > 0);
+            assert!(s <= target + (target as f64 * IMBALANCE) as usize);
+        }
+    }
+
+    #[test]
+    fn greedy_regions_assign_everything() {
+        let cs = mul_chain(20);
+        let assignment = assign_partitioned(&cs, "opa", 5, RegionSolver::Greedy);
+        let graph = TermGraph::new(&cs);
+        for i in 0..graph.len() {
+            assert!(assignment.contains_key(graph.term(i)));
+        }
+    }
+
+    #[cfg(feature = "lp")]
+    #[test]
+    fn one_region_matches_ilp() {
+        let cs = mul_chain(7);
+        let whole = super::super::ilp::assign(&cs, "opa");
+        let regions = assign_partitioned(&cs, "opa", DEFAULT_REGION_SIZE, RegionSolver::Ilp);
+        assert_eq!(
+            whole.get(&cs.outputs[0].cs()[0]),
+            regions.get(&cs.outputs[0].cs()[0])
+        );
+        assert_eq!(whole.get(&cs.outputs[0]), regions.get(&cs.outputs[0]));
+    }
+
+    #[cfg(feature = "lp")]
+    #[test]
+    fn ilp_regions_assign_everything() {
+        let cs = mul_chain(20);
+        let assignment = assign_partitioned(&cs, "opa", 5, RegionSolver::Ilp);
+        let graph = TermGraph::new(&cs);
+        for i in 0..graph.len() {
+            assert!(assignment.contains_key(graph.term(i)));
+        }
+    }
+}
@@ -0,0 +1,561 @@
+//! An in-process simulator for ABY bytecode
+//!
+//! Runs the `*_bytecode`, `const` and `share_map` files written by [super::trans::to_aby] with
+//! plain (unshared) semantics, as if all parties were one process. Every share holds a flat
+//! vector of 32-bit words: scalars are one word, and arrays and tuples are their scalars in order.
+//!
+//! Along the way, it counts the gates evaluated in each sharing and the conversions between
+//! sharings that the ABY interpreter would perform: each gate runs in the sharing of its output,
+//! and each input share in a different sharing is converted (once per call).
+
+use super::assignment::ShareType;
+use super::utils::get_path;
+use crate::ir::term::*;
+
+use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+/// An error in reading or simulating bytecode
+pub enum SimError {
+    #[error("Could not read bytecode: {0}")]
+    /// Reading a file failed
+    Io(#[from] std::io::Error),
+    #[error("Bad line {1} in {0}: {2}")]
+    /// Malformed line: file, line number, reason
+    Parse(String, usize, String),
+    #[error("Share {0} is used before it is defined")]
+    /// Undefined share
+    Undefined(i32),
+    #[error("Share {0} is not in the share map")]
+    /// Share without a sharing
+    NoShareType(i32),
+    #[error("No value for input '{0}'")]
+    /// Missing input
+    MissingInput(String),
+    #[error("No bytecode for computation '{0}'")]
+    /// Missing computation
+    MissingComputation(String),
+    #[error("Index {0} is out of bounds for a vector of length {1}")]
+    /// Out-of-bounds index
+    OutOfBounds(usize, usize),
+    #[error("Expected {0} output words, but got {1}")]
+    /// Output count mismatch
+    OutputCount(usize, usize),
+    #[error("Output word {0} is {2}, but should be {1}")]
+    /// Output mismatch: word index, expected, actual
+    Mismatch(usize, u32, u32),
+}
+
+/// An operand of a bytecode line
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Arg {
+    Share(i32),
+    Lit(i64),
+    Name(String),
+}
+
+/// A bytecode line: `<#inputs> <#outputs> <inputs...> <outputs...> <op>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Line {
+    op: String,
+    args: Vec<Arg>,
+    outs: Vec<i32>,
+}
+
+impl Line {
+    fn parse(file: &str, number: usize, text: &str) -> Result<Self, SimError> {
+        let err = |msg: &str| SimError::Parse(file.to_owned(), number, msg.to_owned());
+        let tokens: Vec<&str> = text.split_whitespace().collect();
+        if tokens.len() < 3 {
+            return Err(err("too few tokens"));
+        }
+        let n_in: usize = tokens[0].parse().map_err(|_| err("bad input count"))?;
+        let n_out: usize = tokens[1].parse().map_err(|_| err("bad output count"))?;
+        if tokens.len() != n_in + n_out + 3 {
+            return Err(err("wrong number of tokens"));
+        }
+        let op = tokens[tokens.len() - 1].to_owned();
+        let ins = &tokens[2..2 + n_in];
+        let args = ins
+            .iter()
+            .enumerate()
+            .map(|(i, tok)| {
+                let lit = || tok.parse().map(Arg::Lit).map_err(|_| err("bad literal"));
+                let share = || tok.parse().map(Arg::Share).map_err(|_| err("bad share"));
+                match (op.as_str(), i) {
+                    ("IN", 0) => Ok(Arg::Name(tok.to_string())),
+                    ("IN", _) | ("CONS", _) => lit(),
+                    ("SHL" | "LSHR" | "FIELD" | "SELECT_CONS" | "STORE_CONS" | "UPDATE", 1)
+                    | ("FIELD_VEC", 1 | 2) => lit(),
+                    _ => share(),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let outs = tokens[2 + n_in..2 + n_in + n_out]
+            .iter()
+            .map(|tok| tok.parse().map_err(|_| err("bad share")))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { op, args, outs })
+    }
+
+    fn out(&self) -> Option<i32> {
+        self.outs.first().copied()
+    }
+
+    fn share_args(&self) -> impl Iterator<Item = i32> + '_ {
+        self.args.iter().filter_map(|a| match a {
+            Arg::Share(s) => Some(*s),
+            _ => None,
+        })
+    }
+}
+
+/// Bytecode, constants, and share map for a set of computations
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    bytecode: HashMap<String, Vec<Line>>,
+    consts: HashMap<i32, Vec<u32>>,
+    share_map: HashMap<i32, ShareType>,
+}
+
+/// The non-empty lines of `text`, with (1-based) line numbers.
+fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty())
+        .map(|(i, l)| (i + 1, l))
+}
+
+impl Program {
+    /// Parse a program from the text of its files. `bytecode` maps computation names to the text
+    /// of their bytecode files.
+    pub fn parse<'a>(
+        bytecode: impl IntoIterator<Item = (&'a str, &'a str)>,
+        consts: &str,
+        share_map: &str,
+    ) -> Result<Self, SimError> {
+        let mut program = Program::default();
+        for (number, line) in lines(share_map) {
+            let err = |msg: &str| SimError::Parse("share_map".into(), number, msg.into());
+            let mut tokens = line.split_whitespace();
+            let share: i32 = tokens
+                .next()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| err("bad share"))?;
+            let ty = match tokens.next() {
+                Some("a") => ShareType::Arithmetic,
+                Some("b") => ShareType::Boolean,
+                Some("y") => ShareType::Yao,
+                _ => return Err(err("bad sharing")),
+            };
+            program.share_map.insert(share, ty);
+        }
+        for (number, text) in lines(consts) {
+            let line = Line::parse("const", number, text)?;
+            let value = match (line.op.as_str(), &line.args[..]) {
+                ("CONS", [Arg::Lit(v), Arg::Lit(_)]) => vec![*v as u32],
+                ("CONS_ARRAY" | "CONS_TUPLE", _) => {
+                    let mut words = Vec::new();
+                    for s in line.share_args() {
+                        words.extend(program.consts.get(&s).ok_or(SimError::Undefined(s))?);
+                    }
+                    words
+                }
+                _ => {
+                    return Err(SimError::Parse(
+                        "const".into(),
+                        number,
+                        format!("unknown op {}", line.op),
+                    ))
+                }
+            };
+            for s in &line.outs {
+                program.consts.insert(*s, value.clone());
+            }
+        }
+        for (name, text) in bytecode {
+            let file = format!("{name}_bytecode");
+            let code = lines(text)
+                .map(|(number, l)| Line::parse(&file, number, l))
+                .collect::<Result<Vec<_>, _>>()?;
+            program.bytecode.insert(name.to_owned(), code);
+        }
+        Ok(program)
+    }
+
+    /// Read the files that [super::trans::to_aby] wrote for the source file `path` and language
+    /// `lang`, for computations `comps`.
+    pub fn read<'a>(
+        path: &Path,
+        lang: &str,
+        comps: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Self, SimError> {
+        let read = |t: &str| fs::read_to_string(get_path(path, lang, t, false));
+        let bytecode = comps
+            .into_iter()
+            .map(|name| Ok((name, read(&format!("{name}_bytecode"))?)))
+            .collect::<Result<Vec<_>, std::io::Error>>()?;
+        Program::parse(
+            bytecode.iter().map(|(n, t)| (*n, t.as_str())),
+            &read("const")?,
+            &read("share_map")?,
+        )
+    }
+}
+
+/// Gate and conversion counts from a simulation
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /// Gates evaluated, by sharing and op
+    pub gates: HashMap<(ShareType, String), usize>,
+    /// Conversions performed, by (from, to) sharing
+    pub conversions: HashMap<(ShareType, ShareType), usize>,
+}
+
+impl Report {
+    /// The number of gates evaluated in sharing `ty`.
+    pub fn gates_in(&self, ty: ShareType) -> usize {
+        self.gates
+            .iter()
+            .filter(|((t, _), _)| *t == ty)
+            .map(|(_, n)| n)
+            .sum()
+    }
+
+    /// The number of conversions from `from` to `to`.
+    pub fn conversions(&self, from: ShareType, to: ShareType) -> usize {
+        self.conversions.get(&(from, to)).copied().unwrap_or(0)
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut gates: Vec<_> = self
+            .gates
+            .iter()
+            .map(|((ty, op), n)| (ty.char(), op, n))
+            .collect();
+        gates.sort();
+        for (ty, op, n) in gates {
+            writeln!(f, "{ty} {op}: {n}")?;
+        }
+        let mut convs: Vec<_> = self
+            .conversions
+            .iter()
+            .map(|((from, to), n)| (from.char(), to.char(), n))
+            .collect();
+        convs.sort();
+        for (from, to, n) in convs {
+            writeln!(f, "{from}2{to}: {n}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Flatten `v` into words, with a mask for the bits of each word that are meaningful.
+fn flatten(v: &Value, out: &mut Vec<(u32, u32)>) {
+    match v {
+        Value::Bool(b) => out.push((*b as u32, 1)),
+        Value::BitVector(bv) => {
+            let mask = if bv.width() >= 32 {
+                u32::MAX
+            } else {
+                (1 << bv.width()) - 1
+            };
+            out.push((bv.uint().to_u32_wrapping() & mask, mask));
+        }
+        Value::Array(arr) => {
+            for k in arr.key_sort.elems_iter_values().take(arr.size) {
+                flatten(&arr.select(&k), out);
+            }
+        }
+        Value::Tuple(vs) => {
+            for v in vs.iter() {
+                flatten(v, out);
+            }
+        }
+        _ => panic!("Unsupported value in ABY simulation: {}", v),
+    }
+}
+
+fn words(v: &Value) -> Vec<u32> {
+    let mut out = Vec::new();
+    flatten(v, &mut out);
+    out.into_iter().map(|(w, _)| w).collect()
+}
+
+/// Simulates a [Program], accumulating a [Report].
+pub struct Simulator<'a> {
+    program: &'a Program,
+    report: Report,
+}
+
+/// The source of a computation's inputs: given input `i`, named `n`, its value
+type InputSource<'s> = dyn FnMut(usize, &str) -> Result<Vec<u32>, SimError> + 's;
+
+impl<'a> Simulator<'a> {
+    /// Create a simulator for `program`.
+    pub fn new(program: &'a Program) -> Self {
+        Self {
+            program,
+            report: Report::default(),
+        }
+    }
+
+    /// The counts so far.
+    pub fn report(&self) -> &Report {
+        &self.report
+    }
+
+    fn share_type(&self, s: i32) -> Result<ShareType, SimError> {
+        self.program
+            .share_map
+            .get(&s)
+            .copied()
+            .ok_or(SimError::NoShareType(s))
+    }
+
+    /// Run computation `name`, taking inputs (by name) from `inputs`. Returns the output words.
+    pub fn run(
+        &mut self,
+        name: &str,
+        inputs: &HashMap<String, Value>,
+    ) -> Result<Vec<u32>, SimError> {
+        self.call(name, &mut |_, input| {
+            inputs
+                .get(input)
+                .map(words)
+                .ok_or_else(|| SimError::MissingInput(input.to_owned()))
+        })
+    }
+
+    /// Run computation `name`, taking the value of input `i`, named `n`, from `input(i, n)`.
+    fn call(&mut self, name: &str, input: &mut InputSource<'_>) -> Result<Vec<u32>, SimError> {
+        let program = self.program;
+        let code = program
+            .bytecode
+            .get(name)
+            .ok_or_else(|| SimError::MissingComputation(name.to_owned()))?;
+        let mut env: HashMap<i32, Vec<u32>> = HashMap::default();
+        let mut converted: HashSet<(i32, ShareType)> = HashSet::default();
+        let mut outputs = Vec::new();
+        let mut n_inputs = 0;
+        for line in code {
+            if line.op == "IN" {
+                if let Arg::Name(n) = &line.args[0] {
+                    let v = input(n_inputs, n)?;
+                    if let Some(s) = line.out() {
+                        env.insert(s, v);
+                    }
+                }
+                n_inputs += 1;
+                continue;
+            }
+            // Look up share arguments, counting conversions into the gate's sharing.
+            let gate_ty = line.out().map(|s| self.share_type(s)).transpose()?;
+            let mut args: Vec<&Vec<u32>> = Vec::new();
+            for s in line.share_args() {
+                let v = match env.get(&s).or_else(|| program.consts.get(&s)) {
+                    Some(v) => v,
+                    None => return Err(SimError::Undefined(s)),
+                };
+                args.push(v);
+                if let Some(to) = gate_ty {
+                    let from = self.share_type(s)?;
+                    if from != to && converted.insert((s, to)) {
+                        *self.report.conversions.entry((from, to)).or_default() += 1;
+                    }
+                }
+            }
+            let lit = |i: usize| match &line.args[i] {
+                Arg::Lit(l) => *l as usize,
+                _ => unreachable!(),
+            };
+            let at = |v: &Vec<u32>, i: usize| -> Result<u32, SimError> {
+                v.get(i).copied().ok_or(SimError::OutOfBounds(i, v.len()))
+            };
+            let bin = |f: fn(u32, u32) -> u32| vec![f(args[0][0], args[1][0])];
+            let value = match line.op.as_str() {
+                "OUT" => {
+                    outputs.extend(args[0]);
+                    continue;
+                }
+                "ADD" => bin(u32::wrapping_add),
+                "SUB" => bin(u32::wrapping_sub),
+                "MUL" => bin(u32::wrapping_mul),
+                // As in the IR: division by zero gives all ones, and remainder the dividend.
+                "DIV" => bin(|a, b| a.checked_div(b).unwrap_or(u32::MAX)),
+                "REM" => bin(|a, b| a.checked_rem(b).unwrap_or(a)),
+                "AND" => bin(|a, b| a & b),
+                "OR" => bin(|a, b| a | b),
+                "XOR" => bin(|a, b| a ^ b),
+                "EQ" => bin(|a, b| (a == b) as u32),
+                "GT" => bin(|a, b| (a > b) as u32),
+                "LT" => bin(|a, b| (a < b) as u32),
+                "GE" => bin(|a, b| (a >= b) as u32),
+                "LE" => bin(|a, b| (a <= b) as u32),
+                "NOT" => vec![args[0][0] ^ 1],
+                "SHL" => vec![args[0][0].checked_shl(lit(1) as u32).unwrap_or(0)],
+                "LSHR" => vec![args[0][0].checked_shr(lit(1) as u32).unwrap_or(0)],
+                "MUX" => {
+                    if args[0][0] != 0 {
+                        args[1].clone()
+                    } else {
+                        args[2].clone()
+                    }
+                }
+                "FIELD" | "SELECT_CONS" => vec![at(args[0], lit(1))?],
+                "SELECT" => vec![at(args[0], args[1][0] as usize)?],
+                "FIELD_VEC" => {
+                    let (offset, len) = (lit(1), lit(2));
+                    if offset + len > args[0].len() {
+                        return Err(SimError::OutOfBounds(offset + len, args[0].len()));
+                    }
+                    args[0][offset..offset + len].to_vec()
+                }
+                "STORE" | "STORE_CONS" | "UPDATE" => {
+                    let (idx, value) = match line.op.as_str() {
+                        "STORE" => (args[1][0] as usize, args[2]),
+                        _ => (lit(1), args[1]),
+                    };
+                    let mut v = args[0].clone();
+                    at(&v, idx)?;
+                    v[idx] = value[0];
+                    v
+                }
+                "TUPLE" => args.iter().flat_map(|a| a.iter().copied()).collect(),
+                op if op.starts_with("CALL(") && op.ends_with(')') => {
+                    let callee = &op[5..op.len() - 1];
+                    let args: Vec<Vec<u32>> = args.iter().map(|a| a.to_vec()).collect();
+                    self.call(callee, &mut |i, n| {
+                        args.get(i)
+                            .cloned()
+                            .ok_or_else(|| SimError::MissingInput(n.to_owned()))
+                    })?
+                }
+                op => {
+                    return Err(SimError::Parse(
+                        format!("{name}_bytecode"),
+                        0,
+                        format!("unknown op {op}"),
+                    ))
+                }
+            };
+            if let Some(ty) = gate_ty {
+                *self.report.gates.entry((ty, line.op.clone())).or_default() += 1;
+            }
+            for s in &line.outs {
+                env.insert(*s, value.clone());
+            }
+        }
+        Ok(outputs)
+    }
+}
+
+/// Simulate the `main` computation of `program` on `inputs`, and check its outputs against
+/// evaluating `cs` directly. Returns the gate and conversion counts.
+pub fn check(
+    cs: &Computations,
+    program: &Program,
+    inputs: &HashMap<String, Value>,
+) -> Result<Report, SimError> {
+    let main = cs
+        .comps
+        .get("main")
+        .ok_or_else(|| SimError::MissingComputation("main".into()))?;
+    let mut expected = Vec::new();
+    for v in main.eval_all(inputs) {
+        flatten(&v, &mut expected);
+    }
+    let mut sim = Simulator::new(program);
+    let actual = sim.run("main", inputs)?;
+    if actual.len() != expected.len() {
+        return Err(SimError::OutputCount(expected.len(), actual.len()));
+    }
+    for (i, ((e, mask), a)) in expected.into_iter().zip(actual).enumerate() {
+        if e != a & mask {
+            return Err(SimError::Mismatch(i, e, a & mask));
+        }
+    }
+    Ok(sim.report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::trans::to_aby;
+    use super::*;
+    use crate::ir::term::text::{parse_computation, parse_value_map};
+
+    /// Lower `ir` (the `main` computation) to ABY with selection scheme `ss`, then check it on
+    /// `inputs` in the simulator.
+    fn lower_and_check(test: &str, ir: &str, ss: &str, inputs: &str) -> Report {
+        let mut cs = Computations::new();
+        cs.comps
+            .insert("main".into(), parse_computation(ir.as_bytes()));
+        let path = format!("{test}.ir");
+        let path = Path::new(&path);
+        to_aby(cs.clone(), path, "sim", "opa", ss);
+        let program = Program::read(path, "sim", ["main"]).unwrap();
+        let result = check(&cs, &program, &parse_value_map(inputs.as_bytes()));
+        fs::remove_dir_all(format!("scripts/aby_tests/tests/{test}_sim")).unwrap();
+        result.unwrap()
+    }
+
+    const ADD_MUL: &str = "
+        (computation
+            (metadata (parties A B) (inputs (a (bv 32) (party 0)) (b (bv 32) (party 1))) (commitments))
+            (precompute () () (#t ))
+            (ite (bvult a b) (bvmul a b) (bvadd a b))
+        )
+    ";
+
+    #[test]
+    fn all_boolean() {
+        for inputs in [
+            "(let ((a #x00000003) (b #x00000005)) false)",
+            "(let ((a #x00000007) (b #x00000005)) false)",
+        ] {
+            let report = lower_and_check("sim_all_boolean", ADD_MUL, "b", inputs);
+            assert_eq!(report.gates_in(ShareType::Boolean), 4);
+            assert_eq!(report.gates_in(ShareType::Yao), 0);
+            assert!(report.conversions.is_empty());
+        }
+    }
+
+    #[test]
+    fn greedy_conversions() {
+        let inputs = "(let ((a #x00000003) (b #x00000005)) false)";
+        let report = lower_and_check("sim_greedy", ADD_MUL, "greedy", inputs);
+        // MUL and ADD are arithmetic, LT and MUX are Yao, and inputs are boolean.
+        assert_eq!(report.gates_in(ShareType::Arithmetic), 2);
+        assert_eq!(report.gates_in(ShareType::Yao), 2);
+        assert_eq!(
+            report.conversions(ShareType::Boolean, ShareType::Arithmetic),
+            2
+        );
+        assert_eq!(report.conversions(ShareType::Boolean, ShareType::Yao), 2);
+        assert_eq!(report.conversions(ShareType::Arithmetic, ShareType::Yao), 2);
+    }
+
+    #[test]
+    fn hand_written() {
+        let program = Program::parse(
+            [(
+                "main",
+                "2 1 a 0 0 IN\n2 1 a 0 1 IN\n2 1 0 1 2 ADD\n1 0 2 OUT\n",
+            )],
+            "",
+            "0 b\n1 b\n2 b\n",
+        );
+        // `a` is read twice, so this program computes `a + a`.
+        let program = program.unwrap();
+        let mut sim = Simulator::new(&program);
+        let inputs = parse_value_map(b"(let ((a #x00000003)) false)");
+        assert_eq!(sim.run("main", &inputs).unwrap(), vec![6]);
+        assert_eq!(sim.report().gates_in(ShareType::Boolean), 1);
+    }
+}
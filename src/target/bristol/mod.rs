@@ -0,0 +1,319 @@
+//! Bristol Fashion boolean circuits
+//!
+//! [Bristol Fashion](https://nigelsmart.github.io/MPC-Circuits/) is the netlist format read by
+//! garbled-circuit toolchains such as EMP-toolkit and MP-SPDZ. A circuit is a list of XOR, AND,
+//! and INV gates (and EQ, which fixes a wire to a constant) over numbered wires. The input wires
+//! come first, split into input values, and the output wires come last, split into output values.
+//!
+//! We use one input value per party (holding that party's inputs, in metadata order), followed by
+//! one for the public inputs, if there are any. There is one output value per output of the
+//! computation. Within a value, each IR input or output is a run of bits, least significant first.
+
+use crate::ir::term::*;
+
+use fxhash::FxHashMap as HashMap;
+use rug::Integer;
+use std::fmt::{self, Display, Formatter};
+use thiserror::Error;
+
+pub mod trans;
+
+/// A gate. Outputs are last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Gate {
+    /// `out = a ^ b`
+    Xor(usize, usize, usize),
+    /// `out = a & b`
+    And(usize, usize, usize),
+    /// `out = !a`
+    Inv(usize, usize),
+    /// `out = c`
+    Eq(bool, usize),
+}
+
+impl Gate {
+    /// The output wire.
+    pub fn output(&self) -> usize {
+        match self {
+            Gate::Xor(_, _, o) | Gate::And(_, _, o) | Gate::Inv(_, o) | Gate::Eq(_, o) => *o,
+        }
+    }
+
+    /// The input wires.
+    pub fn inputs(&self) -> Vec<usize> {
+        match self {
+            Gate::Xor(a, b, _) | Gate::And(a, b, _) => vec![*a, *b],
+            Gate::Inv(a, _) => vec![*a],
+            Gate::Eq(..) => vec![],
+        }
+    }
+
+    fn map_wires(&self, f: impl Fn(usize) -> usize) -> Self {
+        match self {
+            Gate::Xor(a, b, o) => Gate::Xor(f(*a), f(*b), f(*o)),
+            Gate::And(a, b, o) => Gate::And(f(*a), f(*b), f(*o)),
+            Gate::Inv(a, o) => Gate::Inv(f(*a), f(*o)),
+            Gate::Eq(c, o) => Gate::Eq(*c, f(*o)),
+        }
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+/// An error in reading a Bristol Fashion circuit
+pub enum BristolError {
+    #[error("Bad Bristol Fashion circuit, line {0}: {1}")]
+    /// Malformed line
+    Parse(usize, String),
+}
+
+/// A Bristol Fashion netlist
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Netlist {
+    /// The number of wires
+    pub num_wires: usize,
+    /// The number of bits in each input value
+    pub inputs: Vec<usize>,
+    /// The number of bits in each output value
+    pub outputs: Vec<usize>,
+    /// The gates, in evaluation order
+    pub gates: Vec<Gate>,
+}
+
+impl Netlist {
+    /// The number of AND gates: the cost of garbling.
+    pub fn and_count(&self) -> usize {
+        self.gates
+            .iter()
+            .filter(|g| matches!(g, Gate::And(..)))
+            .count()
+    }
+
+    /// Evaluate the netlist on input values, each a vector of bits.
+    pub fn eval(&self, inputs: &[Vec<bool>]) -> Vec<Vec<bool>> {
+        assert_eq!(
+            inputs.len(),
+            self.inputs.len(),
+            "Wrong number of input values"
+        );
+        let mut wires: Vec<Option<bool>> = vec![None; self.num_wires];
+        let mut next = 0;
+        for (value, size) in inputs.iter().zip(&self.inputs) {
+            assert_eq!(value.len(), *size, "Wrong input value size");
+            for b in value {
+                wires[next] = Some(*b);
+                next += 1;
+            }
+        }
+        let get = |wires: &[Option<bool>], w: usize| {
+            wires[w].unwrap_or_else(|| panic!("Wire {} is used before it is set", w))
+        };
+        for g in &self.gates {
+            let v = match g {
+                Gate::Xor(a, b, _) => get(&wires, *a) ^ get(&wires, *b),
+                Gate::And(a, b, _) => get(&wires, *a) & get(&wires, *b),
+                Gate::Inv(a, _) => !get(&wires, *a),
+                Gate::Eq(c, _) => *c,
+            };
+            wires[g.output()] = Some(v);
+        }
+        let mut next = self.num_wires - self.outputs.iter().sum::<usize>();
+        self.outputs
+            .iter()
+            .map(|size| {
+                let value = (next..next + size).map(|w| get(&wires, w)).collect();
+                next += size;
+                value
+            })
+            .collect()
+    }
+
+    /// Parse a netlist in Bristol Fashion.
+    pub fn parse(text: &str) -> Result<Self, BristolError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, l)| (i + 1, l.split_whitespace().collect::<Vec<_>>()))
+            .filter(|(_, l)| !l.is_empty());
+        let mut next_line = |what: &str| {
+            lines
+                .next()
+                .ok_or_else(|| BristolError::Parse(0, format!("missing {what}")))
+        };
+        let num = |line: usize, tok: &str| {
+            tok.parse::<usize>()
+                .map_err(|_| BristolError::Parse(line, format!("bad number '{tok}'")))
+        };
+        let sizes = |(line, toks): (usize, Vec<&str>)| -> Result<Vec<usize>, BristolError> {
+            let sizes = toks
+                .iter()
+                .map(|t| num(line, t))
+                .collect::<Result<Vec<_>, _>>()?;
+            if sizes.is_empty() || sizes[0] + 1 != sizes.len() {
+                return Err(BristolError::Parse(line, "bad value sizes".into()));
+            }
+            Ok(sizes[1..].to_vec())
+        };
+        let (line, header) = next_line("header")?;
+        if header.len() != 2 {
+            return Err(BristolError::Parse(line, "bad header".into()));
+        }
+        let num_gates = num(line, header[0])?;
+        let num_wires = num(line, header[1])?;
+        let inputs = sizes(next_line("inputs")?)?;
+        let outputs = sizes(next_line("outputs")?)?;
+        let mut gates = Vec::with_capacity(num_gates);
+        for (line, toks) in lines {
+            let ws = toks[..toks.len() - 1]
+                .iter()
+                .map(|t| num(line, t))
+                .collect::<Result<Vec<_>, _>>()?;
+            let gate = match (toks[toks.len() - 1], &ws[..]) {
+                ("XOR", [2, 1, a, b, o]) => Gate::Xor(*a, *b, *o),
+                ("AND", [2, 1, a, b, o]) => Gate::And(*a, *b, *o),
+                ("INV", [1, 1, a, o]) => Gate::Inv(*a, *o),
+                ("EQ", [1, 1, c, o]) if *c <= 1 => Gate::Eq(*c == 1, *o),
+                _ => return Err(BristolError::Parse(line, "bad gate".into())),
+            };
+            if gate
+                .inputs()
+                .into_iter()
+                .chain(Some(gate.output()))
+                .any(|w| w >= num_wires)
+            {
+                return Err(BristolError::Parse(line, "wire out of range".into()));
+            }
+            gates.push(gate);
+        }
+        if gates.len() != num_gates {
+            return Err(BristolError::Parse(
+                0,
+                format!("expected {} gates, found {}", num_gates, gates.len()),
+            ));
+        }
+        Ok(Netlist {
+            num_wires,
+            inputs,
+            outputs,
+            gates,
+        })
+    }
+}
+
+impl Display for Netlist {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let sizes = |s: &[usize]| {
+            std::iter::once(s.len())
+                .chain(s.iter().copied())
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        writeln!(f, "{} {}", self.gates.len(), self.num_wires)?;
+        writeln!(f, "{}", sizes(&self.inputs))?;
+        writeln!(f, "{}", sizes(&self.outputs))?;
+        writeln!(f)?;
+        for g in &self.gates {
+            match g {
+                Gate::Xor(a, b, o) => writeln!(f, "2 1 {a} {b} {o} XOR")?,
+                Gate::And(a, b, o) => writeln!(f, "2 1 {a} {b} {o} AND")?,
+                Gate::Inv(a, o) => writeln!(f, "1 1 {a} {o} INV")?,
+                Gate::Eq(c, o) => writeln!(f, "1 1 {} {o} EQ", *c as u8)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An input value: the inputs of a party (or the public inputs), in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputGroup {
+    /// The party, or [None] for public inputs
+    pub party: Option<PartyId>,
+    /// The inputs
+    pub vars: Vec<(String, Sort)>,
+}
+
+/// A netlist, and the IR inputs and outputs its values hold
+#[derive(Debug, Clone)]
+pub struct Circuit {
+    /// The netlist
+    pub netlist: Netlist,
+    /// The input values
+    pub inputs: Vec<InputGroup>,
+    /// The sort of each output value
+    pub outputs: Vec<Sort>,
+}
+
+fn sort_bits(s: &Sort) -> usize {
+    match s {
+        Sort::Bool => 1,
+        Sort::BitVector(w) => *w,
+        _ => panic!(
+            "Bristol Fashion circuits only hold booleans and bit-vectors, not {}",
+            s
+        ),
+    }
+}
+
+fn value_bits(v: &Value, out: &mut Vec<bool>) {
+    match v {
+        Value::Bool(b) => out.push(*b),
+        Value::BitVector(bv) => {
+            out.extend((0..bv.width()).map(|i| bv.uint().get_bit(i as u32)));
+        }
+        _ => panic!(
+            "Bristol Fashion circuits only hold booleans and bit-vectors, not {}",
+            v
+        ),
+    }
+}
+
+fn bits_value(s: &Sort, bits: &[bool]) -> Value {
+    match s {
+        Sort::Bool => Value::Bool(bits[0]),
+        Sort::BitVector(w) => {
+            let mut i = Integer::from(0);
+            for (j, b) in bits.iter().enumerate() {
+                i.set_bit(j as u32, *b);
+            }
+            Value::BitVector(BitVector::new(i, *w))
+        }
+        _ => unreachable!(),
+    }
+}
+
+impl Circuit {
+    /// Encode IR input values as netlist input values.
+    pub fn encode_inputs(&self, values: &HashMap<String, Value>) -> Vec<Vec<bool>> {
+        self.inputs
+            .iter()
+            .map(|group| {
+                let mut bits = Vec::new();
+                for (name, _) in &group.vars {
+                    let v = values
+                        .get(name)
+                        .unwrap_or_else(|| panic!("Missing input {}", name));
+                    value_bits(v, &mut bits);
+                }
+                bits
+            })
+            .collect()
+    }
+
+    /// Decode netlist output values as IR values.
+    pub fn decode_outputs(&self, outputs: &[Vec<bool>]) -> Vec<Value> {
+        self.outputs
+            .iter()
+            .zip(outputs)
+            .map(|(s, bits)| {
+                assert_eq!(sort_bits(s), bits.len());
+                bits_value(s, bits)
+            })
+            .collect()
+    }
+
+    /// Evaluate the netlist on IR input values.
+    pub fn eval(&self, values: &HashMap<String, Value>) -> Vec<Value> {
+        self.decode_outputs(&self.netlist.eval(&self.encode_inputs(values)))
+    }
+}
@@ -0,0 +1,743 @@
+//! Lowering IR to Bristol Fashion
+//!
+//! The computation must hold only booleans and bit-vectors: tuples, arrays, and fields should be
+//! eliminated first (e.g., by `Opt::Tuple`, `Opt::Obliv`, and `Opt::MpcArrays`).
+//!
+//! Garbling cost is the number of AND gates, so we try to use few of them:
+//! * constants are propagated, and gates are hashed structurally;
+//! * OR, MUX, and majority each use one AND;
+//! * adders (and so subtraction and comparison) use one AND per bit;
+//! * gates that no output depends on are removed.
+
+use super::{Circuit, Gate, InputGroup, Netlist};
+use crate::ir::term::*;
+use crate::target::bitsize;
+
+use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
+use log::debug;
+
+/// A bit: a constant or a wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Bit {
+    Const(bool),
+    Wire(usize),
+}
+
+struct ToBristol {
+    num_wires: usize,
+    gates: Vec<Gate>,
+    ands: HashMap<(usize, usize), usize>,
+    xors: HashMap<(usize, usize), usize>,
+    /// Maps each inverted wire to its inverse, in both directions.
+    invs: HashMap<usize, usize>,
+    cache: TermMap<Vec<Bit>>,
+}
+
+impl ToBristol {
+    fn new() -> Self {
+        Self {
+            num_wires: 0,
+            gates: Vec::new(),
+            ands: HashMap::default(),
+            xors: HashMap::default(),
+            invs: HashMap::default(),
+            cache: TermMap::default(),
+        }
+    }
+
+    fn fresh(&mut self) -> usize {
+        self.num_wires += 1;
+        self.num_wires - 1
+    }
+
+    fn gate(&mut self, g: impl FnOnce(usize) -> Gate) -> usize {
+        let o = self.fresh();
+        self.gates.push(g(o));
+        o
+    }
+
+    fn not(&mut self, a: Bit) -> Bit {
+        match a {
+            Bit::Const(c) => Bit::Const(!c),
+            Bit::Wire(a) => Bit::Wire(match self.invs.get(&a) {
+                Some(o) => *o,
+                None => {
+                    let o = self.gate(|o| Gate::Inv(a, o));
+                    self.invs.insert(a, o);
+                    self.invs.insert(o, a);
+                    o
+                }
+            }),
+        }
+    }
+
+    fn xor(&mut self, a: Bit, b: Bit) -> Bit {
+        match (a, b) {
+            (Bit::Const(a), Bit::Const(b)) => Bit::Const(a ^ b),
+            (Bit::Const(false), x) | (x, Bit::Const(false)) => x,
+            (Bit::Const(true), x) | (x, Bit::Const(true)) => self.not(x),
+            (Bit::Wire(a), Bit::Wire(b)) if a == b => Bit::Const(false),
+            (Bit::Wire(a), Bit::Wire(b)) if self.invs.get(&a) == Some(&b) => Bit::Const(true),
+            (Bit::Wire(a), Bit::Wire(b)) => {
+                let key = (a.min(b), a.max(b));
+                Bit::Wire(match self.xors.get(&key) {
+                    Some(o) => *o,
+                    None => {
+                        let o = self.gate(|o| Gate::Xor(key.0, key.1, o));
+                        self.xors.insert(key, o);
+                        o
+                    }
+                })
+            }
+        }
+    }
+
+    fn and(&mut self, a: Bit, b: Bit) -> Bit {
+        match (a, b) {
+            (Bit::Const(false), _) | (_, Bit::Const(false)) => Bit::Const(false),
+            (Bit::Const(true), x) | (x, Bit::Const(true)) => x,
+            (Bit::Wire(a), Bit::Wire(b)) if a == b => Bit::Wire(a),
+            (Bit::Wire(a), Bit::Wire(b)) if self.invs.get(&a) == Some(&b) => Bit::Const(false),
+            (Bit::Wire(a), Bit::Wire(b)) => {
+                let key = (a.min(b), a.max(b));
+                Bit::Wire(match self.ands.get(&key) {
+                    Some(o) => *o,
+                    None => {
+                        let o = self.gate(|o| Gate::And(key.0, key.1, o));
+                        self.ands.insert(key, o);
+                        o
+                    }
+                })
+            }
+        }
+    }
+
+    /// `a | b == a ^ b ^ (a & b)`
+    fn or(&mut self, a: Bit, b: Bit) -> Bit {
+        let x = self.xor(a, b);
+        let n = self.and(a, b);
+        self.xor(x, n)
+    }
+
+    /// `s ? t : f == f ^ (s & (t ^ f))`
+    fn mux(&mut self, s: Bit, t: Bit, f: Bit) -> Bit {
+        let d = self.xor(t, f);
+        let m = self.and(s, d);
+        self.xor(f, m)
+    }
+
+    /// `maj(a, b, c) == a ^ ((a ^ b) & (a ^ c))`
+    fn maj(&mut self, a: Bit, b: Bit, c: Bit) -> Bit {
+        let ab = self.xor(a, b);
+        let ac = self.xor(a, c);
+        let m = self.and(ab, ac);
+        self.xor(a, m)
+    }
+
+    fn and_all(&mut self, bits: &[Bit]) -> Bit {
+        match bits {
+            [] => Bit::Const(true),
+            [b] => *b,
+            _ => {
+                let (l, r) = bits.split_at(bits.len() / 2);
+                let l = self.and_all(l);
+                let r = self.and_all(r);
+                self.and(l, r)
+            }
+        }
+    }
+
+    fn or_all(&mut self, bits: &[Bit]) -> Bit {
+        let nots: Vec<Bit> = bits.iter().map(|b| self.not(*b)).collect();
+        let none = self.and_all(&nots);
+        self.not(none)
+    }
+
+    fn nots(&mut self, a: &[Bit]) -> Vec<Bit> {
+        a.iter().map(|b| self.not(*b)).collect()
+    }
+
+    /// Ripple-carry addition of `a`, `b`, and `carry`. Returns the sum, truncated to the width of
+    /// `a`, and the carry out (only built if `want_carry`).
+    fn adder(&mut self, a: &[Bit], b: &[Bit], mut carry: Bit, want_carry: bool) -> (Vec<Bit>, Bit) {
+        assert_eq!(a.len(), b.len());
+        let mut sum = Vec::with_capacity(a.len());
+        for i in 0..a.len() {
+            let ab = self.xor(a[i], b[i]);
+            sum.push(self.xor(ab, carry));
+            if i + 1 < a.len() || want_carry {
+                carry = self.maj(a[i], b[i], carry);
+            }
+        }
+        (sum, carry)
+    }
+
+    fn add(&mut self, a: &[Bit], b: &[Bit]) -> Vec<Bit> {
+        self.adder(a, b, Bit::Const(false), false).0
+    }
+
+    /// Returns `a - b`, and whether `a >= b` (unsigned).
+    fn sub(&mut self, a: &[Bit], b: &[Bit], want_ge: bool) -> (Vec<Bit>, Bit) {
+        let nb = self.nots(b);
+        self.adder(a, &nb, Bit::Const(true), want_ge)
+    }
+
+    fn neg(&mut self, a: &[Bit]) -> Vec<Bit> {
+        let zero = vec![Bit::Const(false); a.len()];
+        self.sub(&zero, a, false).0
+    }
+
+    /// Unsigned `a < b`
+    fn ult(&mut self, a: &[Bit], b: &[Bit]) -> Bit {
+        let nb = self.nots(b);
+        // a - b borrows iff a < b
+        let ge = self.carry(a, &nb, Bit::Const(true));
+        self.not(ge)
+    }
+
+    /// The carry out of `a + b + carry`, without the sum.
+    fn carry(&mut self, a: &[Bit], b: &[Bit], mut carry: Bit) -> Bit {
+        for (a, b) in a.iter().zip(b) {
+            carry = self.maj(*a, *b, carry);
+        }
+        carry
+    }
+
+    /// Signed `a < b`: flipping the sign bits makes the order unsigned.
+    fn slt(&mut self, a: &[Bit], b: &[Bit]) -> Bit {
+        let mut a = a.to_vec();
+        let mut b = b.to_vec();
+        let n = a.len();
+        a[n - 1] = self.not(a[n - 1]);
+        b[n - 1] = self.not(b[n - 1]);
+        self.ult(&a, &b)
+    }
+
+    fn eq(&mut self, a: &[Bit], b: &[Bit]) -> Bit {
+        let eqs: Vec<Bit> = a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| {
+                let x = self.xor(*a, *b);
+                self.not(x)
+            })
+            .collect();
+        self.and_all(&eqs)
+    }
+
+    fn mux_bits(&mut self, s: Bit, t: &[Bit], f: &[Bit]) -> Vec<Bit> {
+        t.iter().zip(f).map(|(t, f)| self.mux(s, *t, *f)).collect()
+    }
+
+    /// Schoolbook multiplication, truncated to the width of `a`.
+    fn mul(&mut self, a: &[Bit], b: &[Bit]) -> Vec<Bit> {
+        let n = a.len();
+        let mut acc = vec![Bit::Const(false); n];
+        for i in 0..n {
+            let partial: Vec<Bit> = (0..n - i).map(|j| self.and(a[j], b[i])).collect();
+            let sum = self.add(&acc[i..], &partial);
+            acc[i..].copy_from_slice(&sum);
+        }
+        acc
+    }
+
+    /// Restoring division. Returns the quotient and remainder. As in the IR, dividing by zero
+    /// gives a quotient of all ones and a remainder of `a`.
+    fn divrem(&mut self, a: &[Bit], b: &[Bit]) -> (Vec<Bit>, Vec<Bit>) {
+        let n = a.len();
+        let mut q = vec![Bit::Const(false); n];
+        let mut r = vec![Bit::Const(false); n];
+        let mut b_ext = b.to_vec();
+        b_ext.push(Bit::Const(false));
+        for i in (0..n).rev() {
+            // r_ext = 2r + a[i], in n+1 bits
+            let mut r_ext = vec![a[i]];
+            r_ext.extend_from_slice(&r);
+            let (diff, ge) = self.sub(&r_ext, &b_ext, true);
+            q[i] = ge;
+            r = self.mux_bits(ge, &diff[..n], &r_ext[..n]);
+        }
+        (q, r)
+    }
+
+    /// Shift `a` by `amount`: left if `left`, otherwise right, filling with `fill`.
+    fn shift(&mut self, a: &[Bit], amount: &[Bit], left: bool, fill: Bit) -> Vec<Bit> {
+        let n = a.len();
+        let mut bits = a.to_vec();
+        for (i, s) in amount.iter().enumerate() {
+            let k = if i < bitsize(n) { (1 << i).min(n) } else { n };
+            let shifted: Vec<Bit> = (0..n)
+                .map(|j| match left {
+                    true if j >= k => bits[j - k],
+                    false if j + k < n => bits[j + k],
+                    _ => fill,
+                })
+                .collect();
+            bits = self.mux_bits(*s, &shifted, &bits);
+        }
+        bits
+    }
+
+    fn get_bool(&self, t: &Term) -> Bit {
+        let bits = self
+            .cache
+            .get(t)
+            .unwrap_or_else(|| panic!("Missing wire for {}", t));
+        assert_eq!(bits.len(), 1);
+        bits[0]
+    }
+
+    fn get_bv(&self, t: &Term) -> &[Bit] {
+        self.cache
+            .get(t)
+            .unwrap_or_else(|| panic!("Missing wires for {}", t))
+    }
+
+    fn embed(&mut self, t: Term) {
+        for c in PostOrderIter::new(t) {
+            if self.cache.contains_key(&c) {
+                continue;
+            }
+            let bits = match check(&c) {
+                Sort::Bool => vec![self.embed_bool(&c)],
+                Sort::BitVector(_) => self.embed_bv(&c),
+                s => panic!("Unsupported sort in Bristol back-end: {}", s),
+            };
+            self.cache.insert(c, bits);
+        }
+    }
+
+    fn embed_bool(&mut self, t: &Term) -> Bit {
+        match t.op() {
+            Op::Var(..) => panic!("Unknown input {}", t),
+            Op::Const(Value::Bool(b)) => Bit::Const(*b),
+            Op::Eq => match check(&t.cs()[0]) {
+                Sort::Bool => {
+                    let a = self.get_bool(&t.cs()[0]);
+                    let b = self.get_bool(&t.cs()[1]);
+                    let x = self.xor(a, b);
+                    self.not(x)
+                }
+                _ => {
+                    let a = self.get_bv(&t.cs()[0]).to_vec();
+                    let b = self.get_bv(&t.cs()[1]).to_vec();
+                    self.eq(&a, &b)
+                }
+            },
+            Op::Ite => {
+                let s = self.get_bool(&t.cs()[0]);
+                let a = self.get_bool(&t.cs()[1]);
+                let b = self.get_bool(&t.cs()[2]);
+                self.mux(s, a, b)
+            }
+            Op::Not => {
+                let a = self.get_bool(&t.cs()[0]);
+                self.not(a)
+            }
+            Op::Implies => {
+                let a = self.get_bool(&t.cs()[0]);
+                let b = self.get_bool(&t.cs()[1]);
+                let na = self.not(a);
+                self.or(na, b)
+            }
+            Op::BoolNaryOp(o) => {
+                let args: Vec<Bit> = t.cs().iter().map(|c| self.get_bool(c)).collect();
+                match o {
+                    BoolNaryOp::And => self.and_all(&args),
+                    BoolNaryOp::Or => self.or_all(&args),
+                    BoolNaryOp::Xor => args
+                        .into_iter()
+                        .fold(Bit::Const(false), |acc, b| self.xor(acc, b)),
+                }
+            }
+            Op::BoolMaj => {
+                let a = self.get_bool(&t.cs()[0]);
+                let b = self.get_bool(&t.cs()[1]);
+                let c = self.get_bool(&t.cs()[2]);
+                self.maj(a, b, c)
+            }
+            Op::BvBit(i) => self.get_bv(&t.cs()[0])[*i],
+            Op::BvBinPred(o) => {
+                let a = self.get_bv(&t.cs()[0]).to_vec();
+                let b = self.get_bv(&t.cs()[1]).to_vec();
+                let (lt, swap, negate) = match o {
+                    BvBinPred::Ult => (false, false, false),
+                    BvBinPred::Ugt => (false, true, false),
+                    BvBinPred::Uge => (false, false, true),
+                    BvBinPred::Ule => (false, true, true),
+                    BvBinPred::Slt => (true, false, false),
+                    BvBinPred::Sgt => (true, true, false),
+                    BvBinPred::Sge => (true, false, true),
+                    BvBinPred::Sle => (true, true, true),
+                };
+                let (a, b) = if swap { (b, a) } else { (a, b) };
+                let r = if lt {
+                    self.slt(&a, &b)
+                } else {
+                    self.ult(&a, &b)
+                };
+                if negate {
+                    self.not(r)
+                } else {
+                    r
+                }
+            }
+            _ => panic!("Unsupported boolean op in Bristol back-end: {}", t.op()),
+        }
+    }
+
+    fn embed_bv(&mut self, t: &Term) -> Vec<Bit> {
+        match t.op() {
+            Op::Var(..) => panic!("Unknown input {}", t),
+            Op::Const(Value::BitVector(b)) => (0..b.width())
+                .map(|i| Bit::Const(b.uint().get_bit(i as u32)))
+                .collect(),
+            Op::Ite => {
+                let s = self.get_bool(&t.cs()[0]);
+                let a = self.get_bv(&t.cs()[1]).to_vec();
+                let b = self.get_bv(&t.cs()[2]).to_vec();
+                self.mux_bits(s, &a, &b)
+            }
+            Op::BvUnOp(o) => {
+                let a = self.get_bv(&t.cs()[0]).to_vec();
+                match o {
+                    BvUnOp::Not => self.nots(&a),
+                    BvUnOp::Neg => self.neg(&a),
+                }
+            }
+            Op::BvUext(n) => {
+                let mut bits = self.get_bv(&t.cs()[0]).to_vec();
+                bits.extend(std::iter::repeat(Bit::Const(false)).take(*n));
+                bits
+            }
+            Op::BvSext(n) => {
+                let mut bits = self.get_bv(&t.cs()[0]).to_vec();
+                let msb = *bits.last().unwrap();
+                bits.extend(std::iter::repeat(msb).take(*n));
+                bits
+            }
+            Op::BoolToBv => vec![self.get_bool(&t.cs()[0])],
+            Op::BvConcat => t
+                .cs()
+                .iter()
+                .rev()
+                .flat_map(|c| self.get_bv(c).to_vec())
+                .collect(),
+            // inclusive!
+            Op::BvExtract(high, low) => self.get_bv(&t.cs()[0])[*low..=*high].to_vec(),
+            Op::BvNaryOp(o) => {
+                let args: Vec<Vec<Bit>> = t.cs().iter().map(|c| self.get_bv(c).to_vec()).collect();
+                let (first, rest) = args.split_first().unwrap();
+                rest.iter().fold(first.clone(), |acc, b| match o {
+                    BvNaryOp::And => acc.iter().zip(b).map(|(x, y)| self.and(*x, *y)).collect(),
+                    BvNaryOp::Or => acc.iter().zip(b).map(|(x, y)| self.or(*x, *y)).collect(),
+                    BvNaryOp::Xor => acc.iter().zip(b).map(|(x, y)| self.xor(*x, *y)).collect(),
+                    BvNaryOp::Add => self.add(&acc, b),
+                    BvNaryOp::Mul => self.mul(&acc, b),
+                })
+            }
+            Op::BvBinOp(o) => {
+                let a = self.get_bv(&t.cs()[0]).to_vec();
+                let b = self.get_bv(&t.cs()[1]).to_vec();
+                match o {
+                    BvBinOp::Sub => self.sub(&a, &b, false).0,
+                    BvBinOp::Udiv => self.divrem(&a, &b).0,
+                    BvBinOp::Urem => self.divrem(&a, &b).1,
+                    BvBinOp::Shl => self.shift(&a, &b, true, Bit::Const(false)),
+                    BvBinOp::Lshr => self.shift(&a, &b, false, Bit::Const(false)),
+                    BvBinOp::Ashr => {
+                        let msb = *a.last().unwrap();
+                        self.shift(&a, &b, false, msb)
+                    }
+                }
+            }
+            _ => panic!("Unsupported bit-vector op in Bristol back-end: {}", t.op()),
+        }
+    }
+
+    /// Make a wire that holds `b` and is the output of its own gate.
+    fn output_wire(&mut self, b: Bit, first_input: Option<usize>) -> usize {
+        match b {
+            Bit::Wire(w) => {
+                let n = self.not(Bit::Wire(w));
+                let n = match n {
+                    Bit::Wire(n) => n,
+                    Bit::Const(_) => unreachable!(),
+                };
+                self.gate(|o| Gate::Inv(n, o))
+            }
+            // Build constants from an input wire when we can: not every tool supports EQ.
+            Bit::Const(c) => match first_input {
+                Some(i) => {
+                    let zero = self.gate(|o| Gate::Xor(i, i, o));
+                    if c {
+                        self.gate(|o| Gate::Inv(zero, o))
+                    } else {
+                        zero
+                    }
+                }
+                None => self.gate(|o| Gate::Eq(c, o)),
+            },
+        }
+    }
+
+    /// Remove dead gates, and renumber wires so that the outputs are last.
+    fn finish(mut self, num_inputs: usize, output_bits: Vec<Vec<Bit>>) -> (Netlist, Vec<usize>) {
+        let first_input = (num_inputs > 0).then_some(0);
+        let gate_outputs: HashSet<usize> = self.gates.iter().map(|g| g.output()).collect();
+        let mut seen: HashSet<usize> = HashSet::default();
+        let mut outputs: Vec<usize> = Vec::new();
+        for bit in output_bits.iter().flatten() {
+            let w = match bit {
+                Bit::Wire(w) if gate_outputs.contains(w) && seen.insert(*w) => *w,
+                b => self.output_wire(*b, first_input),
+            };
+            outputs.push(w);
+        }
+        // Dead gate elimination
+        let mut live: HashSet<usize> = outputs.iter().copied().collect();
+        let mut gates: Vec<Gate> = Vec::new();
+        for g in self.gates.iter().rev() {
+            if live.contains(&g.output()) {
+                live.extend(g.inputs());
+                gates.push(*g);
+            }
+        }
+        gates.reverse();
+        // Renumbering: inputs, then internal wires, then outputs.
+        let mut renumber: HashMap<usize, usize> = (0..num_inputs).map(|i| (i, i)).collect();
+        let output_set: HashSet<usize> = outputs.iter().copied().collect();
+        for g in &gates {
+            if !output_set.contains(&g.output()) {
+                let n = renumber.len();
+                renumber.insert(g.output(), n);
+            }
+        }
+        let first_output = renumber.len();
+        for (i, o) in outputs.iter().enumerate() {
+            renumber.insert(*o, first_output + i);
+        }
+        let gates: Vec<Gate> = gates
+            .iter()
+            .map(|g| g.map_wires(|w| renumber[&w]))
+            .collect();
+        let netlist = Netlist {
+            num_wires: first_output + outputs.len(),
+            inputs: Vec::new(),
+            outputs: output_bits.iter().map(|o| o.len()).collect(),
+            gates,
+        };
+        (netlist, outputs)
+    }
+}
+
+/// Lower a boolean/bit-vector computation to a Bristol Fashion circuit.
+pub fn to_bristol(cs: &Computation) -> Circuit {
+    let md = &cs.metadata;
+    let mut converter = ToBristol::new();
+
+    // Input wires, grouped by party, then public. Inputs may name parties the metadata does not.
+    let names = md.ordered_input_names();
+    let num_parties = names
+        .iter()
+        .filter_map(|n| md.get_input_visibility(n))
+        .map(|p| p as usize + 1)
+        .chain(Some(md.num_parties()))
+        .max()
+        .unwrap();
+    let mut groups: Vec<InputGroup> = (0..num_parties)
+        .map(|p| InputGroup {
+            party: Some(p as PartyId),
+            vars: Vec::new(),
+        })
+        .collect();
+    let mut public = InputGroup {
+        party: None,
+        vars: Vec::new(),
+    };
+    for name in names {
+        let sort = md.input_sort(&name);
+        match md.get_input_visibility(&name) {
+            Some(p) => groups[p as usize].vars.push((name, sort)),
+            None => public.vars.push((name, sort)),
+        }
+    }
+    if !public.vars.is_empty() {
+        groups.push(public);
+    }
+    let mut input_sizes = Vec::new();
+    for group in &groups {
+        let mut size = 0;
+        for (name, sort) in &group.vars {
+            let width = super::sort_bits(sort);
+            let bits = (0..width).map(|_| Bit::Wire(converter.fresh())).collect();
+            converter
+                .cache
+                .insert(leaf_term(Op::Var(name.clone(), sort.clone())), bits);
+            size += width;
+        }
+        input_sizes.push(size);
+    }
+    let num_inputs = converter.num_wires;
+
+    let mut output_bits = Vec::new();
+    for o in &cs.outputs {
+        converter.embed(o.clone());
+        output_bits.push(converter.get_bv(o).to_vec());
+    }
+    let (mut netlist, _) = converter.finish(num_inputs, output_bits);
+    netlist.inputs = input_sizes;
+    debug!(
+        "Bristol circuit: {} gates, {} ANDs",
+        netlist.gates.len(),
+        netlist.and_count()
+    );
+    Circuit {
+        netlist,
+        inputs: groups,
+        outputs: cs.outputs.iter().map(check).collect(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::proof::Constraints;
+    use crate::ir::term::dist::test::PureBool;
+    use crate::ir::term::dist::PureBoolDist;
+    use crate::ir::term::text::{parse_computation, parse_value_map};
+    use quickcheck_macros::quickcheck;
+    use rand::distributions::Distribution;
+    use rand::{Rng, SeedableRng};
+
+    fn check_against_ir(cs: &Computation, values: &HashMap<String, Value>) -> Circuit {
+        let circuit = to_bristol(cs);
+        assert_eq!(circuit.eval(values), cs.eval_all(values));
+        // The written netlist reads back the same.
+        let text = format!("{}", circuit.netlist);
+        assert_eq!(Netlist::parse(&text).unwrap(), circuit.netlist);
+        circuit
+    }
+
+    #[quickcheck]
+    fn random_pure_bool(PureBool(t, values): PureBool) {
+        let cs = Computation::from_constraint_system_parts(vec![t], Vec::new());
+        check_against_ir(&cs, &values);
+    }
+
+    #[test]
+    fn random_bool_opt() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let d = PureBoolDist(8);
+        for _ in 0..50 {
+            let t = d.sample(&mut rng);
+            let values: HashMap<String, Value> = PostOrderIter::new(t.clone())
+                .filter_map(|c| match c.op() {
+                    Op::Var(n, _) => Some((n.clone(), Value::Bool(rng.gen()))),
+                    _ => None,
+                })
+                .collect();
+            let cs = Computation::from_constraint_system_parts(vec![t], Vec::new());
+            check_against_ir(&cs, &values);
+        }
+    }
+
+    const ARITH: &str = "
+        (computation
+            (metadata (parties A B) (inputs (a (bv 8) (party 0)) (b (bv 8) (party 1)) (c (bv 8))) (commitments))
+            (precompute () () (#t ))
+            (tuple
+                (bvadd a b)
+                (bvsub a b)
+                (bvmul a b c)
+                (bvudiv a b)
+                (bvurem a b)
+                (bvshl a c)
+                (bvlshr a c)
+                (bvashr a c)
+                (bvneg a)
+                (concat a ((extract 3 0) b))
+                ((sext 4) a)
+                (bvult a b)
+                (bvslt a b)
+                (bvuge a b)
+                (bvsle a b)
+                (= a b)
+                (ite (bvugt a b) a b)
+            )
+        )
+    ";
+
+    fn arith_cs() -> Computation {
+        let mut cs = parse_computation(ARITH.as_bytes());
+        crate::ir::opt::tuple::eliminate_tuples(&mut cs);
+        cs
+    }
+
+    #[test]
+    fn arith() {
+        let cs = arith_cs();
+        for (a, b, c) in [(3, 5, 1), (200, 7, 3), (9, 0, 9), (128, 255, 0), (0, 0, 0)] {
+            let values = parse_value_map(
+                format!("(let ((a #b{a:08b}) (b #b{b:08b}) (c #b{c:08b})) false)").as_bytes(),
+            );
+            check_against_ir(&cs, &values);
+        }
+    }
+
+    #[test]
+    fn input_groups() {
+        let circuit = to_bristol(&arith_cs());
+        assert_eq!(circuit.netlist.inputs, vec![8, 8, 8]);
+        assert_eq!(circuit.inputs[0].party, Some(0));
+        assert_eq!(circuit.inputs[1].party, Some(1));
+        assert_eq!(circuit.inputs[2].party, None);
+    }
+
+    #[test]
+    fn and_counts() {
+        let cs = parse_computation(
+            b"
+            (computation
+                (metadata (parties A B) (inputs (a (bv 8) (party 0)) (b (bv 8) (party 1)) (s bool (party 0))) (commitments))
+                (precompute () () (#t ))
+                (tuple (bvadd a b) (bvult a b) (ite s a b) (maj s (bvult a b) (= a b)))
+            )
+        ",
+        );
+        let mut cs = cs;
+        crate::ir::opt::tuple::eliminate_tuples(&mut cs);
+        let separate = |i: usize| {
+            let mut c = cs.clone();
+            c.outputs = vec![cs.outputs[i].clone()];
+            to_bristol(&c).netlist.and_count()
+        };
+        // One AND per bit of carry: the top carry of an 8-bit add is unneeded.
+        assert_eq!(separate(0), 7);
+        assert_eq!(separate(1), 8);
+        // One AND per bit for a MUX.
+        assert_eq!(separate(2), 8);
+        // Comparison (8), equality (7), majority (1)
+        assert_eq!(separate(3), 16);
+    }
+
+    #[test]
+    fn constant_and_repeated_outputs() {
+        let cs = parse_computation(
+            b"
+            (computation
+                (metadata (parties A) (inputs (a bool (party 0))) (commitments))
+                (precompute () () (#t ))
+                (tuple true a a (not a) (and a (not a)))
+            )
+        ",
+        );
+        let mut cs = cs;
+        crate::ir::opt::tuple::eliminate_tuples(&mut cs);
+        for a in ["true", "false"] {
+            let values = parse_value_map(format!("(let ((a {a})) false)").as_bytes());
+            let circuit = check_against_ir(&cs, &values);
+            assert_eq!(circuit.netlist.and_count(), 0);
+        }
+    }
+}
@@ -0,0 +1,491 @@
+//! A small branch-and-bound ILP solver
+//!
+//! LP relaxations are solved with a dense, two-phase simplex method. We branch on the most
+//! fractional integer variable, depth-first, pruning nodes whose relaxation is no better than the
+//! best integer solution so far. This is enough for the small ILPs in tests and examples; large
+//! instances deserve a real solver (see [super::IlpSolver]).
+
+use super::{Ilp, IlpUnsat};
+use fxhash::FxHashMap as HashMap;
+use good_lp::{Expression, IntoAffineExpression, Variable};
+use log::{debug, warn};
+use std::time::{Duration, Instant};
+
+/// Tolerance for pivoting and optimality
+const EPS: f64 = 1e-9;
+/// Tolerance for feasibility and integrality
+const INT_EPS: f64 = 1e-6;
+/// After this many pivots, switch from Dantzig's rule to Bland's rule, which cannot cycle
+const BLAND_AFTER: usize = 1000;
+
+/// A linear function: coefficients of variables (by index), and a constant
+type Linear = (Vec<(usize, f64)>, f64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RowKind {
+    Le,
+    Ge,
+    Eq,
+}
+
+/// An LP over non-negative variables: maximize `obj . y`, subject to `rows`.
+struct Lp {
+    num_vars: usize,
+    obj: Vec<f64>,
+    /// Rows `a . y (<=|>=|=) b`
+    rows: Vec<(Vec<f64>, RowKind, f64)>,
+}
+
+enum LpResult {
+    Optimal(Vec<f64>),
+    Infeasible,
+    Unbounded,
+}
+
+struct Tableau {
+    /// Each row holds its coefficients, and then its right-hand side
+    rows: Vec<Vec<f64>>,
+    /// The basic variable of each row
+    basis: Vec<usize>,
+    num_cols: usize,
+}
+
+impl Tableau {
+    fn pivot(&mut self, r: usize, c: usize) {
+        let p = self.rows[r][c];
+        for x in self.rows[r].iter_mut() {
+            *x /= p;
+        }
+        let pivot_row = self.rows[r].clone();
+        for (i, row) in self.rows.iter_mut().enumerate() {
+            let f = row[c];
+            if i != r && f != 0.0 {
+                for (x, p) in row.iter_mut().zip(&pivot_row) {
+                    *x -= f * p;
+                }
+            }
+        }
+        self.basis[r] = c;
+    }
+
+    /// Maximize `obj`, only letting columns satisfying `allowed` enter the basis. Returns false if
+    /// the objective is unbounded.
+    fn optimize(&mut self, obj: &[f64], allowed: impl Fn(usize) -> bool) -> bool {
+        let mut iters = 0;
+        loop {
+            let bland = iters >= BLAND_AFTER;
+            let mut entering: Option<(usize, f64)> = None;
+            for j in (0..self.num_cols).filter(|j| allowed(*j)) {
+                let reduced = obj[j]
+                    - self
+                        .rows
+                        .iter()
+                        .zip(&self.basis)
+                        .map(|(row, b)| obj[*b] * row[j])
+                        .sum::<f64>();
+                let better = match entering {
+                    None => true,
+                    Some((_, best)) => !bland && reduced > best,
+                };
+                if reduced > EPS && better {
+                    entering = Some((j, reduced));
+                }
+            }
+            let c = match entering {
+                Some((c, _)) => c,
+                None => return true,
+            };
+            let mut leaving: Option<(usize, f64)> = None;
+            for (i, row) in self.rows.iter().enumerate() {
+                if row[c] > EPS {
+                    let ratio = row[self.num_cols] / row[c];
+                    let better = match leaving {
+                        None => true,
+                        Some((l, best)) => {
+                            ratio < best - EPS
+                                || (ratio <= best + EPS && self.basis[i] < self.basis[l])
+                        }
+                    };
+                    if better {
+                        leaving = Some((i, ratio));
+                    }
+                }
+            }
+            match leaving {
+                Some((r, _)) => self.pivot(r, c),
+                None => return false,
+            }
+            iters += 1;
+        }
+    }
+}
+
+impl Lp {
+    fn solve(&self) -> LpResult {
+        use RowKind::*;
+        let n = self.num_vars;
+        // Make the right-hand sides non-negative, and scale each row so that its largest
+        // coefficient is 1. Without scaling, the round-off from big-M rows can make a feasible LP
+        // look infeasible.
+        let rows: Vec<(Vec<f64>, RowKind, f64)> = self
+            .rows
+            .iter()
+            .map(|(a, k, b)| {
+                let scale = a.iter().fold(0.0f64, |m, x| m.max(x.abs()));
+                let scale = if scale > 0.0 { scale } else { 1.0 };
+                let (scale, k) = if *b < 0.0 {
+                    let k = match k {
+                        Le => Ge,
+                        Ge => Le,
+                        Eq => Eq,
+                    };
+                    (-scale, k)
+                } else {
+                    (scale, *k)
+                };
+                (a.iter().map(|x| x / scale).collect(), k, b / scale)
+            })
+            .collect();
+        // Columns: the variables, then slacks (for inequalities), then artificials (for rows
+        // without an obvious basic variable).
+        let num_slack = rows.iter().filter(|r| r.1 != Eq).count();
+        let num_art = rows.iter().filter(|r| r.1 != Le).count();
+        let first_art = n + num_slack;
+        let num_cols = first_art + num_art;
+        let mut t = Tableau {
+            rows: Vec::new(),
+            basis: Vec::new(),
+            num_cols,
+        };
+        let (mut slack, mut art) = (n, first_art);
+        for (mut row, k, b) in rows {
+            row.resize(num_cols + 1, 0.0);
+            row[num_cols] = b;
+            if k != Eq {
+                row[slack] = if k == Le { 1.0 } else { -1.0 };
+                slack += 1;
+            }
+            if k == Le {
+                t.basis.push(slack - 1);
+            } else {
+                row[art] = 1.0;
+                t.basis.push(art);
+                art += 1;
+            }
+            t.rows.push(row);
+        }
+
+        // Phase 1: drive the artificial variables to zero.
+        if num_art > 0 {
+            let obj: Vec<f64> = (0..num_cols)
+                .map(|j| if j >= first_art { -1.0 } else { 0.0 })
+                .collect();
+            t.optimize(&obj, |_| true);
+            let infeasibility: f64 = t
+                .rows
+                .iter()
+                .zip(&t.basis)
+                .filter(|(_, b)| **b >= first_art)
+                .map(|(row, _)| row[num_cols])
+                .sum();
+            if infeasibility > INT_EPS {
+                return LpResult::Infeasible;
+            }
+            // Pivot the (zero) artificial variables that remain basic out of the basis. If a row
+            // has no other non-zero coefficients, it is redundant.
+            let mut i = 0;
+            while i < t.rows.len() {
+                if t.basis[i] >= first_art {
+                    match (0..first_art).find(|j| t.rows[i][*j].abs() > EPS) {
+                        Some(j) => t.pivot(i, j),
+                        None => {
+                            t.rows.remove(i);
+                            t.basis.remove(i);
+                            continue;
+                        }
+                    }
+                }
+                i += 1;
+            }
+        }
+
+        // Phase 2: optimize the real objective.
+        let mut obj = self.obj.clone();
+        obj.resize(num_cols, 0.0);
+        if !t.optimize(&obj, |j| j < first_art) {
+            return LpResult::Unbounded;
+        }
+        let mut y = vec![0.0; n];
+        for (row, b) in t.rows.iter().zip(&t.basis) {
+            if *b < n {
+                y[*b] = row[num_cols];
+            }
+        }
+        LpResult::Optimal(y)
+    }
+}
+
+/// Solve the LP relaxation of an ILP, with variable bounds `bounds`.
+fn relax(bounds: &[(f64, f64)], objective: &Linear, constraints: &[(Linear, bool)]) -> LpResult {
+    if bounds.iter().any(|(lo, hi)| lo > hi) {
+        return LpResult::Infeasible;
+    }
+    // Express each variable as an offset plus a combination of non-negative LP variables.
+    let mut num_vars = 0;
+    let mut bound_rows = Vec::new();
+    let subst: Vec<(f64, Vec<(usize, f64)>)> = bounds
+        .iter()
+        .map(|(lo, hi)| {
+            let k = num_vars;
+            match (lo.is_finite(), hi.is_finite()) {
+                (true, bounded) => {
+                    num_vars += 1;
+                    if bounded {
+                        bound_rows.push((k, hi - lo));
+                    }
+                    (*lo, vec![(k, 1.0)])
+                }
+                (false, true) => {
+                    num_vars += 1;
+                    (*hi, vec![(k, -1.0)])
+                }
+                (false, false) => {
+                    num_vars += 2;
+                    (0.0, vec![(k, 1.0), (k + 1, -1.0)])
+                }
+            }
+        })
+        .collect();
+    let substitute = |(coeffs, constant): &Linear| -> (Vec<f64>, f64) {
+        let mut row = vec![0.0; num_vars];
+        let mut c = *constant;
+        for (i, a) in coeffs {
+            let (offset, terms) = &subst[*i];
+            c += a * offset;
+            for (k, s) in terms {
+                row[*k] += a * s;
+            }
+        }
+        (row, c)
+    };
+
+    let (obj, _) = substitute(objective);
+    let mut rows = Vec::new();
+    for (k, ub) in bound_rows {
+        let mut row = vec![0.0; num_vars];
+        row[k] = 1.0;
+        rows.push((row, RowKind::Le, ub));
+    }
+    for (c, is_equality) in constraints {
+        let (row, constant) = substitute(c);
+        let kind = if *is_equality {
+            RowKind::Eq
+        } else {
+            RowKind::Le
+        };
+        rows.push((row, kind, -constant));
+    }
+    match (Lp {
+        num_vars,
+        obj,
+        rows,
+    })
+    .solve()
+    {
+        LpResult::Optimal(y) => LpResult::Optimal(
+            subst
+                .iter()
+                .map(|(offset, terms)| offset + terms.iter().map(|(k, s)| s * y[*k]).sum::<f64>())
+                .collect(),
+        ),
+        r => r,
+    }
+}
+
+fn eval((coeffs, constant): &Linear, x: &[f64]) -> f64 {
+    constant + coeffs.iter().map(|(i, a)| a * x[*i]).sum::<f64>()
+}
+
+/// Solve `ilp` by branch-and-bound. If `time_limit` passes, return the best solution found so far
+/// (if any).
+pub(super) fn solve(
+    ilp: &Ilp,
+    time_limit: Option<Duration>,
+) -> Result<(f64, HashMap<String, f64>), IlpUnsat> {
+    let start = Instant::now();
+    let index: HashMap<Variable, usize> = ilp
+        .var_defs
+        .iter()
+        .enumerate()
+        .map(|(i, (v, _, _))| (*v, i))
+        .collect();
+    let linear = |e: &Expression| -> Linear {
+        (
+            e.clone()
+                .linear_coefficients()
+                .map(|(v, c)| (index[&v], c))
+                .collect(),
+            e.constant(),
+        )
+    };
+    let objective = linear(&ilp.maximize);
+    let constraints: Vec<(Linear, bool)> = ilp
+        .constraints
+        .iter()
+        .map(|c| (linear(&c.expr), c.is_equality))
+        .collect();
+    let integer: Vec<bool> = ilp.var_defs.iter().map(|(_, _, d)| d.integer).collect();
+    let root: Vec<(f64, f64)> = ilp
+        .var_defs
+        .iter()
+        .map(|(_, _, d)| {
+            if d.integer {
+                (d.min.ceil(), d.max.floor())
+            } else {
+                (d.min, d.max)
+            }
+        })
+        .collect();
+
+    let mut best: Option<(f64, Vec<f64>)> = None;
+    let mut stack = vec![root];
+    let mut nodes = 0;
+    let mut timed_out = false;
+    while let Some(bounds) = stack.pop() {
+        if matches!(time_limit, Some(limit) if start.elapsed() > limit) {
+            timed_out = true;
+            break;
+        }
+        nodes += 1;
+        let x = match relax(&bounds, &objective, &constraints) {
+            LpResult::Optimal(x) => x,
+            LpResult::Infeasible => continue,
+            // If any relaxation is unbounded, so is the root relaxation.
+            LpResult::Unbounded => return Err(IlpUnsat::Unbounded),
+        };
+        if matches!(&best, Some((b, _)) if eval(&objective, &x) <= b + INT_EPS) {
+            continue;
+        }
+        let branch = (0..x.len())
+            .filter(|i| integer[*i])
+            .map(|i| (i, (x[i] - x[i].round()).abs()))
+            .filter(|(_, frac)| *frac > INT_EPS)
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+        match branch {
+            None => {
+                let x: Vec<f64> = x
+                    .iter()
+                    .zip(&integer)
+                    .map(|(v, int)| if *int { v.round() } else { *v })
+                    .collect();
+                best = Some((eval(&objective, &x), x));
+            }
+            Some((i, _)) => {
+                let (lo, hi) = bounds[i];
+                let mut down = bounds.clone();
+                down[i] = (lo, x[i].floor());
+                let mut up = bounds;
+                up[i] = (x[i].ceil(), hi);
+                // Explore the nearer side first.
+                if x[i] - x[i].floor() < 0.5 {
+                    stack.push(up);
+                    stack.push(down);
+                } else {
+                    stack.push(down);
+                    stack.push(up);
+                }
+            }
+        }
+    }
+    debug!("Branch-and-bound explored {} nodes", nodes);
+
+    match best {
+        Some((value, x)) => {
+            if timed_out {
+                warn!("ILP time limit reached; the solution may not be optimal");
+            }
+            Ok((
+                value,
+                ilp.var_defs
+                    .iter()
+                    .zip(x)
+                    .map(|((_, name, _), v)| (name.clone(), v))
+                    .collect(),
+            ))
+        }
+        None if timed_out => Err(IlpUnsat::TimeLimit),
+        None => Err(IlpUnsat::Infeasible),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn lp(obj: &[f64], rows: &[(&[f64], RowKind, f64)]) -> LpResult {
+        Lp {
+            num_vars: obj.len(),
+            obj: obj.to_vec(),
+            rows: rows.iter().map(|(a, k, b)| (a.to_vec(), *k, *b)).collect(),
+        }
+        .solve()
+    }
+
+    fn assert_optimal(r: LpResult, expected: &[f64]) {
+        match r {
+            LpResult::Optimal(y) => {
+                assert_eq!(y.len(), expected.len());
+                for (a, b) in y.iter().zip(expected) {
+                    assert!((a - b).abs() < INT_EPS, "{:?} != {:?}", y, expected);
+                }
+            }
+            LpResult::Infeasible => panic!("infeasible"),
+            LpResult::Unbounded => panic!("unbounded"),
+        }
+    }
+
+    #[test]
+    fn simplex() {
+        use RowKind::*;
+        // max 3x + 2y, x + y <= 4, x + 3y <= 6, x <= 3
+        assert_optimal(
+            lp(
+                &[3.0, 2.0],
+                &[
+                    (&[1.0, 1.0], Le, 4.0),
+                    (&[1.0, 3.0], Le, 6.0),
+                    (&[1.0, 0.0], Le, 3.0),
+                ],
+            ),
+            &[3.0, 1.0],
+        );
+        // max -x - y, x + y >= 2, x - y = 1 (needs phase 1)
+        assert_optimal(
+            lp(
+                &[-1.0, -1.0],
+                &[(&[1.0, 1.0], Ge, 2.0), (&[1.0, -1.0], Eq, 1.0)],
+            ),
+            &[1.5, 0.5],
+        );
+        // redundant equalities
+        assert_optimal(
+            lp(
+                &[1.0, 0.0],
+                &[
+                    (&[1.0, 1.0], Eq, 2.0),
+                    (&[2.0, 2.0], Eq, 4.0),
+                    (&[0.0, 1.0], Ge, 1.0),
+                ],
+            ),
+            &[1.0, 1.0],
+        );
+        assert!(matches!(
+            lp(&[1.0], &[(&[1.0], Ge, 2.0), (&[1.0], Le, 1.0)]),
+            LpResult::Infeasible
+        ));
+        assert!(matches!(
+            lp(&[1.0, 1.0], &[(&[1.0, -1.0], Le, 1.0)]),
+            LpResult::Unbounded
+        ));
+    }
+}
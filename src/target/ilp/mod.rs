@@ -0,0 +1,480 @@
+//! Mixed ILP backend
+//!
+//! An [Ilp] can be solved by several solvers (see [IlpSolver]): CBC (linked in, and the default),
+//! an external solver binary (given an LP file), or our own branch-and-bound solver, which needs
+//! nothing outside CirC but is only suitable for small instances.
+
+mod bnb;
+pub mod trans;
+
+use crate::ir::term::*;
+use fxhash::FxHashMap as HashMap;
+use good_lp::{
+    solvers::lp_solvers::{CbcSolver, GlpkSolver, LpSolver},
+    IntoAffineExpression,
+};
+pub(crate) use good_lp::{
+    Expression, ProblemVariables, ResolutionError, Solution, Solver, SolverModel, Variable,
+};
+use log::debug;
+use std::fmt::{self, Debug, Formatter};
+use std::io::{self, Write};
+use std::time::Duration;
+use thiserror::Error;
+
+/// The definition of a variable: its bounds, and whether it is an integer. Build with
+/// [variable].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VarDef {
+    min: f64,
+    max: f64,
+    integer: bool,
+}
+
+/// A continuous, unbounded variable. Constrain it with the methods of [VarDef].
+pub fn variable() -> VarDef {
+    VarDef {
+        min: f64::NEG_INFINITY,
+        max: f64::INFINITY,
+        integer: false,
+    }
+}
+
+impl VarDef {
+    /// Make the variable an integer.
+    pub fn integer(mut self) -> Self {
+        self.integer = true;
+        self
+    }
+    /// Make the variable an integer in `[0, 1]`.
+    pub fn binary(self) -> Self {
+        self.integer().min(0).max(1)
+    }
+    /// Set the lower bound.
+    pub fn min(mut self, min: impl Into<f64>) -> Self {
+        self.min = min.into();
+        self
+    }
+    /// Set the upper bound.
+    pub fn max(mut self, max: impl Into<f64>) -> Self {
+        self.max = max.into();
+        self
+    }
+    fn to_good_lp(self, name: &str) -> good_lp::VariableDefinition {
+        let defn = good_lp::variable().name(name).min(self.min).max(self.max);
+        if self.integer {
+            defn.integer()
+        } else {
+            defn
+        }
+    }
+}
+
+/// A linear constraint. Build with [leq], [geq], or [equal].
+#[derive(Debug, Clone)]
+pub struct Constraint {
+    /// Constrained to be at most zero (or zero, for equalities)
+    expr: Expression,
+    is_equality: bool,
+}
+
+/// The constraint `lhs <= rhs`
+pub fn leq(lhs: impl Into<Expression>, rhs: impl Into<Expression>) -> Constraint {
+    Constraint {
+        expr: lhs.into() - rhs.into(),
+        is_equality: false,
+    }
+}
+
+/// The constraint `lhs >= rhs`
+pub fn geq(lhs: impl Into<Expression>, rhs: impl Into<Expression>) -> Constraint {
+    leq(rhs, lhs)
+}
+
+/// The constraint `lhs = rhs`
+pub fn equal(lhs: impl Into<Expression>, rhs: impl Into<Expression>) -> Constraint {
+    Constraint {
+        expr: lhs.into() - rhs.into(),
+        is_equality: true,
+    }
+}
+
+impl Constraint {
+    fn to_good_lp(&self) -> good_lp::Constraint {
+        if self.is_equality {
+            self.expr.clone().eq(0.0)
+        } else {
+            self.expr.clone() << 0.0
+        }
+    }
+}
+
+/// An integer linear program
+pub struct Ilp {
+    /// Map from names to variables
+    pub var_names: HashMap<String, Variable>,
+    /// The variables
+    variables: ProblemVariables,
+    /// The variable definitions, in order of creation
+    var_defs: Vec<(Variable, String, VarDef)>,
+    /// The constraints
+    constraints: Vec<Constraint>,
+    /// The optimization objective (to maximize)
+    maximize: Expression,
+}
+
+impl Debug for Ilp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ilp")
+            .field("var_names", &self.var_names)
+            .field("constraints", &self.constraints)
+            .field("maximize", &self.maximize)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Ilp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An ILP solver
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IlpSolver {
+    /// CBC, linked in
+    #[default]
+    Cbc,
+    /// The `cbc` binary, given an LP file
+    CbcBin,
+    /// The `glpsol` binary, given an LP file
+    Glpk,
+    /// Our branch-and-bound solver, with an optional time limit
+    BranchAndBound(Option<Duration>),
+}
+
+impl IlpSolver {
+    /// The solver chosen by the CirC configuration (or the default, if it is not set).
+    pub fn from_cfg() -> Self {
+        if !crate::cfg::is_cfg_set() {
+            return Self::default();
+        }
+        let opt = &crate::cfg::cfg().ilp;
+        let time_limit = (opt.time_limit > 0).then(|| Duration::from_secs(opt.time_limit));
+        match opt.solver {
+            circ_opt::IlpSolver::Cbc => IlpSolver::Cbc,
+            circ_opt::IlpSolver::CbcBin => IlpSolver::CbcBin,
+            circ_opt::IlpSolver::Glpk => IlpSolver::Glpk,
+            circ_opt::IlpSolver::Bnb => IlpSolver::BranchAndBound(time_limit),
+        }
+    }
+}
+
+impl Ilp {
+    /// Create an empty ILP
+    pub fn new() -> Self {
+        Self {
+            var_names: HashMap::default(),
+            variables: ProblemVariables::new(),
+            var_defs: Vec::new(),
+            constraints: Vec::new(),
+            maximize: Expression::from(0),
+        }
+    }
+    /// Create a new variable. `defn` can specify bounds, etc. See [VarDef], which can be built
+    /// using [variable()].
+    pub fn new_variable(&mut self, defn: VarDef, name: String) -> Variable {
+        let v = self.variables.add(defn.to_good_lp(&name));
+        self.var_names.insert(name.clone(), v);
+        debug!("Variable: {} -> {:?}", name, v);
+        self.var_defs.push((v, name, defn));
+        v
+    }
+    /// Add a constraint.
+    pub fn new_constraint(&mut self, c: Constraint) {
+        debug!("Constraint: {:?}", c);
+        self.constraints.push(c);
+    }
+    /// Add a constraint.
+    pub fn new_constraints(&mut self, c: impl IntoIterator<Item = Constraint>) {
+        self.constraints.extend(c);
+    }
+    /// Get constraints
+    pub fn constraints(&self) -> &[Constraint] {
+        &self.constraints
+    }
+    /// Set maximization objective
+    pub fn maximize(&mut self, e: Expression) {
+        self.maximize = e;
+    }
+    /// Solve, using the [good_lp] solver `s`.
+    pub fn solve<M: SolverModel<Error = ResolutionError>, S: Solver<Model = M>>(
+        self,
+        s: S,
+    ) -> Result<(f64, HashMap<String, f64>), IlpUnsat> {
+        let max = self.maximize.clone();
+        let mut prob = self.variables.maximise(self.maximize).using(s);
+        for c in &self.constraints {
+            prob = prob.with(c.to_good_lp());
+        }
+        match prob.solve() {
+            Ok(s) => Ok((
+                s.eval(max),
+                self.var_names
+                    .into_iter()
+                    .map(|(name, v)| (name, s.value(v)))
+                    .collect(),
+            )),
+            Err(ResolutionError::Unbounded) => Err(IlpUnsat::Unbounded),
+            Err(ResolutionError::Infeasible) => Err(IlpUnsat::Infeasible),
+            Err(e) => panic!("Error in solving: {}", e),
+        }
+    }
+    /// Solve, using `solver`.
+    pub fn solve_with(self, solver: IlpSolver) -> Result<(f64, HashMap<String, f64>), IlpUnsat> {
+        debug!("Solving with {:?}", solver);
+        match solver {
+            IlpSolver::Cbc => self.solve(good_lp::default_solver),
+            IlpSolver::CbcBin => self.solve(LpSolver(CbcSolver::new())),
+            IlpSolver::Glpk => self.solve(LpSolver(GlpkSolver::new())),
+            IlpSolver::BranchAndBound(time_limit) => bnb::solve(&self, time_limit),
+        }
+    }
+    /// Solve, using the solver chosen by the CirC configuration (see [IlpSolver::from_cfg]).
+    pub fn default_solve(self) -> Result<(f64, HashMap<String, f64>), IlpUnsat> {
+        self.solve_with(IlpSolver::from_cfg())
+    }
+
+    /// Write this ILP in the (CPLEX) LP file format, for external solvers.
+    pub fn write_lp(&self, w: &mut impl Write) -> io::Result<()> {
+        let names: HashMap<Variable, &str> = self
+            .var_defs
+            .iter()
+            .map(|(v, name, _)| (*v, name.as_str()))
+            .collect();
+        let fmt_expr = |e: &Expression| -> String {
+            let mut coeffs: Vec<(&str, f64)> = e
+                .clone()
+                .linear_coefficients()
+                .filter(|(_, c)| *c != 0.0)
+                .map(|(v, c)| (names[&v], c))
+                .collect();
+            coeffs.sort_by(|a, b| a.0.cmp(b.0));
+            if coeffs.is_empty() {
+                return "0".into();
+            }
+            coeffs
+                .iter()
+                .map(|(v, c)| format!("{c:+} {v}"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        writeln!(w, "Maximize")?;
+        let constant = self.maximize.constant();
+        if constant != 0.0 {
+            writeln!(w, " obj: {} {constant:+}", fmt_expr(&self.maximize))?;
+        } else {
+            writeln!(w, " obj: {}", fmt_expr(&self.maximize))?;
+        }
+        writeln!(w, "Subject To")?;
+        for (i, c) in self.constraints.iter().enumerate() {
+            let op = if c.is_equality { "=" } else { "<=" };
+            // (adding zero turns -0 into 0)
+            let rhs = -c.expr.constant() + 0.0;
+            writeln!(w, " c{i}: {} {op} {rhs}", fmt_expr(&c.expr))?;
+        }
+        writeln!(w, "Bounds")?;
+        for (_, name, defn) in &self.var_defs {
+            match (defn.min.is_finite(), defn.max.is_finite()) {
+                (false, false) => writeln!(w, " {name} free")?,
+                (true, false) => writeln!(w, " {name} >= {}", defn.min)?,
+                (false, true) => writeln!(w, " -inf <= {name} <= {}", defn.max)?,
+                (true, true) => writeln!(w, " {} <= {name} <= {}", defn.min, defn.max)?,
+            }
+        }
+        let integers: Vec<&str> = self
+            .var_defs
+            .iter()
+            .filter(|(_, _, defn)| defn.integer)
+            .map(|(_, name, _)| name.as_str())
+            .collect();
+        if !integers.is_empty() {
+            writeln!(w, "General")?;
+            writeln!(w, " {}", integers.join(" "))?;
+        }
+        writeln!(w, "End")
+    }
+}
+
+/// Convert an ILP assignment to an IR assignment.
+pub fn assignment_to_values(
+    assignment: &HashMap<String, f64>,
+    inputs: &HashMap<String, Sort>,
+) -> HashMap<String, Value> {
+    assignment
+        .iter()
+        .filter_map(|(name, v)| match inputs.get(name) {
+            Some(Sort::BitVector(n)) => Some((
+                name.clone(),
+                Value::BitVector(BitVector::new((v.round() as u64).into(), *n)),
+            )),
+            Some(Sort::Bool) => Some((name.clone(), Value::Bool(v.round() != 0.0))),
+            Some(Sort::Int) => Some((name.clone(), Value::Int((v.round() as i64).into()))),
+            Some(Sort::Field(f)) => Some((name.clone(), Value::Field(f.new_v(v.round() as i64)))),
+            Some(s) => unimplemented!(
+                "Cannot reconstruct value of sort {} (var {}) from ILP output",
+                s,
+                name
+            ),
+            None => None,
+        })
+        .collect()
+}
+
+/// Why the ILP could not be solved
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum IlpUnsat {
+    /// The objective can be arbitrarily maximized
+    #[error("the ILP is unbounded")]
+    Unbounded,
+    /// No solutions to the constraints
+    #[error("the ILP is infeasible")]
+    Infeasible,
+    /// The solver ran out of time before finding any solution
+    #[error("the ILP solver ran out of time")]
+    TimeLimit,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use good_lp::solvers::lp_solvers::SolverTrait;
+
+    #[test]
+    fn simple() {
+        use good_lp::default_solver;
+        let mut vars = ProblemVariables::new();
+        let a = vars.add(good_lp::variable().name("a").binary());
+        let b = vars.add(good_lp::variable().name("b").integer().max(10));
+        let c = vars.add(good_lp::variable().name("c").max(10));
+        let solution = vars
+            .maximise(a + b + c)
+            .using(default_solver)
+            .with((a + b) << 30.0)
+            .solve()
+            .unwrap();
+        assert_eq!(solution.value(a), 1.0);
+        assert_eq!(solution.value(b), 10.0);
+        assert_eq!(solution.value(c), 10.0);
+    }
+
+    fn test_solver<S: SolverTrait + Clone>(s: S) {
+        let mut vars = ProblemVariables::new();
+        let a = vars.add(good_lp::variable().name("a").binary());
+        let b = vars.add(good_lp::variable().name("b").integer().max(10));
+        let c = vars.add(good_lp::variable().name("c").max(10));
+        let solution = vars
+            .maximise(a + b + c)
+            .using(good_lp::solvers::lp_solvers::LpSolver(s))
+            .with((a + b) << 30.0)
+            .solve()
+            .unwrap();
+        assert_eq!(solution.value(a), 1.0);
+        assert_eq!(solution.value(b), 10.0);
+        assert_eq!(solution.value(c), 10.0);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_cbc() {
+        test_solver(good_lp::solvers::lp_solvers::CbcSolver::new());
+    }
+    #[test]
+    #[ignore]
+    fn test_glpk() {
+        test_solver(good_lp::solvers::lp_solvers::GlpkSolver::new());
+    }
+
+    fn our_ilp() -> Ilp {
+        let mut vars = Ilp::new();
+        let a = vars.new_variable(variable().binary(), "a".into());
+        let b = vars.new_variable(variable().integer().max(10), "b".into());
+        let c = vars.new_variable(variable().max(10), "c".into());
+        vars.maximize(a + b + c);
+        vars.new_constraint(leq(a, 5.0));
+        vars.new_constraint(leq(b, 5.0));
+        vars.new_constraint(leq(c, 2.0));
+        vars
+    }
+
+    fn test_solver_our_ilp(s: IlpSolver) {
+        let (_max, solution) = our_ilp().solve_with(s).unwrap();
+        assert_eq!(solution.get("a").unwrap(), &1.0);
+        assert_eq!(solution.get("b").unwrap(), &5.0);
+        assert_eq!(solution.get("c").unwrap(), &2.0);
+    }
+
+    #[test]
+    fn test_our_ilp_with_default_solver() {
+        test_solver_our_ilp(IlpSolver::Cbc)
+    }
+
+    #[test]
+    fn test_our_ilp_with_bnb() {
+        test_solver_our_ilp(IlpSolver::BranchAndBound(None))
+    }
+
+    #[test]
+    fn bnb_outcomes() {
+        // x + y <= 3.5, 2x - y >= -1, maximize x + 2y over integers: x = 1, y = 2.
+        let mut ilp = Ilp::new();
+        let x = ilp.new_variable(variable().integer().min(0), "x".into());
+        let y = ilp.new_variable(variable().integer().min(0), "y".into());
+        ilp.new_constraint(leq(x + y, 3.5));
+        ilp.new_constraint(geq(2 * x - y, -1));
+        ilp.maximize(x + 2 * y);
+        let (max, solution) = ilp.solve_with(IlpSolver::BranchAndBound(None)).unwrap();
+        assert_eq!(max, 5.0);
+        assert_eq!(solution["x"], 1.0);
+        assert_eq!(solution["y"], 2.0);
+
+        // 2x = 1 has no integer solution
+        let mut ilp = Ilp::new();
+        let x = ilp.new_variable(variable().integer(), "x".into());
+        ilp.new_constraint(equal(2 * x, 1));
+        assert_eq!(
+            ilp.solve_with(IlpSolver::BranchAndBound(None)),
+            Err(IlpUnsat::Infeasible)
+        );
+
+        let mut ilp = Ilp::new();
+        let x = ilp.new_variable(variable().integer().min(0), "x".into());
+        ilp.maximize(x.into());
+        assert_eq!(
+            ilp.solve_with(IlpSolver::BranchAndBound(None)),
+            Err(IlpUnsat::Unbounded)
+        );
+    }
+
+    #[test]
+    fn lp_file() {
+        let mut out = Vec::new();
+        our_ilp().write_lp(&mut out).unwrap();
+        let expected = "\
+Maximize
+ obj: +1 a +1 b +1 c
+Subject To
+ c0: +1 a <= 5
+ c1: +1 b <= 5
+ c2: +1 c <= 2
+Bounds
+ 0 <= a <= 1
+ -inf <= b <= 10
+ -inf <= c <= 10
+General
+ a b
+End
+";
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+}
@@ -0,0 +1,464 @@
+//! Plonkish constraint tables
+//!
+//! A [PlonkTable] has three advice (witness) columns, `a`, `b`, and `c`, and one instance (public
+//! input) column, `pi`. Each row also has fixed columns, set when the circuit is compiled:
+//!
+//! * `q_l`, `q_r`, `q_o`, `q_m`, `q_c`: field-valued selectors for the arithmetic gate
+//! * `q_bool`, `q_xor`, `q_range`: boolean selectors for the custom gates
+//!
+//! Every row must satisfy every gate:
+//!
+//! * arithmetic: `q_l*a + q_r*b + q_o*c + q_m*a*b + q_c - pi = 0`
+//! * boolean, if `q_bool`: `a*(a - 1) = 0`
+//! * XOR, if `q_xor`: `a + b - 2*a*b - c = 0` (the XOR of `a` and `b`, when they are bits)
+//! * range, if `q_range`: `b*(b - 1) = 0` and `2*a + b - c = 0` (one step of a running sum
+//!   that consumes the bits of a value, most significant first)
+//!
+//! The instance column holds the public inputs in its first [PlonkTable::num_public] rows, and is
+//! zero elsewhere. Each copy constraint is a class of cells that must all hold the same value.
+//!
+//! ## Serialized form
+//!
+//! [PlonkTable], [ProverData], and [VerifierData] implement [serde::Serialize], and are written
+//! with `bincode` by the `circ` example. The table has these fields, in order:
+//!
+//! * `field`: the prime field ([circ_fields::FieldT])
+//! * `num_public`: the number of public inputs
+//! * `rows`: the fixed columns, as one [Row] per row
+//! * `copies`: the copy constraints, each a list of [Cell]s
+//!
+//! [ProverData] adds the witness layout, which names the [Wire] that fills each advice cell (empty
+//! cells hold zero), and a [StagedWitComp] that computes wire values from the prover's inputs.
+//! [VerifierData] has a [StagedWitComp] that computes the instance column from the verifier's
+//! inputs.
+
+use circ_fields::{FieldT, FieldV};
+use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::ir::term::*;
+use crate::target::r1cs::wit_comp::{StagedWitComp, StagedWitCompEvaluator};
+
+pub mod trans;
+
+/// A witness value, which fills one or more advice cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Wire(usize);
+
+/// An advice column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Column {
+    /// The left input
+    A,
+    /// The right input
+    B,
+    /// The output
+    C,
+}
+
+impl Column {
+    const ALL: [Column; 3] = [Column::A, Column::B, Column::C];
+}
+
+/// An advice cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Cell {
+    /// The column
+    pub column: Column,
+    /// The row
+    pub row: usize,
+}
+
+/// The fixed columns of one row.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Row {
+    /// Coefficient of `a`
+    pub q_l: FieldV,
+    /// Coefficient of `b`
+    pub q_r: FieldV,
+    /// Coefficient of `c`
+    pub q_o: FieldV,
+    /// Coefficient of `a*b`
+    pub q_m: FieldV,
+    /// Constant term
+    pub q_c: FieldV,
+    /// Enables the boolean gate
+    pub q_bool: bool,
+    /// Enables the XOR gate
+    pub q_xor: bool,
+    /// Enables the range gate
+    pub q_range: bool,
+}
+
+impl Row {
+    /// A row with no gates enabled.
+    pub fn new(field: &FieldT) -> Self {
+        Self {
+            q_l: field.zero(),
+            q_r: field.zero(),
+            q_o: field.zero(),
+            q_m: field.zero(),
+            q_c: field.zero(),
+            q_bool: false,
+            q_xor: false,
+            q_range: false,
+        }
+    }
+
+    /// Check this row's gates against the values of its cells and its public input.
+    fn check(&self, row: usize, [a, b, c]: &[FieldV; 3], pi: &FieldV) -> Result<(), MockFailure> {
+        let arith = self.q_l.clone() * a
+            + &(self.q_r.clone() * b)
+            + &(self.q_o.clone() * c)
+            + &(self.q_m.clone() * a * b)
+            + &self.q_c
+            - pi;
+        if !arith.is_zero() {
+            return Err(MockFailure::Gate { gate: "arith", row });
+        }
+        let one = a.ty().new_v(1);
+        let two = a.ty().new_v(2);
+        if self.q_bool && !(a.clone() * &(a.clone() - &one)).is_zero() {
+            return Err(MockFailure::Gate { gate: "bool", row });
+        }
+        if self.q_xor && !(a.clone() + b - &(two.clone() * a * b) - c).is_zero() {
+            return Err(MockFailure::Gate { gate: "xor", row });
+        }
+        if self.q_range
+            && (!(b.clone() * &(b.clone() - &one)).is_zero() || !(two * a + b - c).is_zero())
+        {
+            return Err(MockFailure::Gate { gate: "range", row });
+        }
+        Ok(())
+    }
+}
+
+/// A way in which an assignment fails to satisfy a [PlonkTable].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum MockFailure {
+    #[error("the {gate} gate does not hold at row {row}")]
+    /// A gate does not hold
+    Gate {
+        /// The gate's name
+        gate: &'static str,
+        /// The row
+        row: usize,
+    },
+    #[error("copy constraint violated: {0:?} and {1:?} differ")]
+    /// Two cells in one copy class differ
+    Copy(Cell, Cell),
+    #[error("expected {expected} public inputs, but got {actual}")]
+    /// The instance has the wrong length
+    InstanceLength {
+        /// The number of public inputs in the table
+        expected: usize,
+        /// The number given
+        actual: usize,
+    },
+}
+
+/// A Plonkish constraint table. See the [module documentation](self).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlonkTable {
+    field: FieldT,
+    num_public: usize,
+    rows: Vec<Row>,
+    copies: Vec<Vec<Cell>>,
+}
+
+impl PlonkTable {
+    /// The field
+    pub fn field(&self) -> &FieldT {
+        &self.field
+    }
+
+    /// The number of public inputs, which occupy the first rows of the instance column.
+    pub fn num_public(&self) -> usize {
+        self.num_public
+    }
+
+    /// The fixed columns, row by row.
+    pub fn rows(&self) -> &[Row] {
+        &self.rows
+    }
+
+    /// The copy constraints.
+    pub fn copies(&self) -> &[Vec<Cell>] {
+        &self.copies
+    }
+
+    /// Check that `advice` (one triple of cell values per row) and `instance` satisfy this table.
+    pub fn check(&self, advice: &[[FieldV; 3]], instance: &[FieldV]) -> Result<(), MockFailure> {
+        assert_eq!(advice.len(), self.rows.len());
+        if instance.len() != self.num_public {
+            return Err(MockFailure::InstanceLength {
+                expected: self.num_public,
+                actual: instance.len(),
+            });
+        }
+        let zero = self.field.zero();
+        for (i, (row, cells)) in self.rows.iter().zip(advice).enumerate() {
+            row.check(i, cells, instance.get(i).unwrap_or(&zero))?;
+        }
+        let value = |cell: &Cell| &advice[cell.row][cell.column as usize];
+        for class in &self.copies {
+            let first = &class[0];
+            if let Some(other) = class.iter().find(|c| value(c) != value(first)) {
+                return Err(MockFailure::Copy(*first, *other));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A Plonkish system under construction.
+///
+/// Each [Wire] has a name and a term that computes its value. Rows are added with
+/// [Plonk::gate]; [Plonk::equate] asserts that two wires are equal (which becomes a copy
+/// constraint).
+pub struct Plonk {
+    field: FieldT,
+    names: Vec<String>,
+    terms: Vec<Term>,
+    public: Vec<Wire>,
+    rows: Vec<Row>,
+    layout: Vec<[Option<Wire>; 3]>,
+    /// Union-find forest over wires, for equalities.
+    parent: Vec<usize>,
+}
+
+impl Plonk {
+    /// Make an empty system.
+    pub fn new(field: FieldT) -> Self {
+        Self {
+            field,
+            names: Vec::new(),
+            terms: Vec::new(),
+            public: Vec::new(),
+            rows: Vec::new(),
+            layout: Vec::new(),
+            parent: Vec::new(),
+        }
+    }
+
+    /// The field
+    pub fn field(&self) -> &FieldT {
+        &self.field
+    }
+
+    /// Add a wire named `name`, whose value is computed by `term`.
+    pub fn add_wire(&mut self, name: String, term: Term) -> Wire {
+        debug_assert!(matches!(check(&term), Sort::Field(_)));
+        let w = Wire(self.names.len());
+        self.names.push(name);
+        self.terms.push(term);
+        self.parent.push(w.0);
+        w
+    }
+
+    /// Make `w` a public input. Public inputs are numbered in the order they are added.
+    pub fn add_public(&mut self, w: Wire) {
+        self.public.push(w);
+    }
+
+    /// The term that computes `w`.
+    pub fn term(&self, w: Wire) -> &Term {
+        &self.terms[w.0]
+    }
+
+    /// Add a row, filling its advice cells with `cells`.
+    pub fn gate(&mut self, row: Row, cells: [Option<Wire>; 3]) {
+        self.rows.push(row);
+        self.layout.push(cells);
+    }
+
+    /// Assert that `a` and `b` are equal.
+    pub fn equate(&mut self, a: Wire, b: Wire) {
+        let a = self.find(a.0);
+        let b = self.find(b.0);
+        self.parent[a] = b;
+    }
+
+    fn find(&mut self, mut w: usize) -> usize {
+        while self.parent[w] != w {
+            self.parent[w] = self.parent[self.parent[w]];
+            w = self.parent[w];
+        }
+        w
+    }
+
+    /// The number of rows, including those for public inputs.
+    pub fn num_rows(&self) -> usize {
+        self.public.len() + self.rows.len()
+    }
+
+    /// The number of wires.
+    pub fn num_wires(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Lay out the table, with public input rows first, and derive the copy constraints.
+    fn table(&mut self) -> (PlonkTable, Vec<[Option<Wire>; 3]>) {
+        let mut rows = Vec::new();
+        let mut layout = Vec::new();
+        for p in &self.public {
+            let mut row = Row::new(&self.field);
+            row.q_l = self.field.new_v(1);
+            rows.push(row);
+            layout.push([Some(*p), None, None]);
+        }
+        rows.append(&mut self.rows);
+        layout.append(&mut self.layout);
+        let mut classes: HashMap<usize, Vec<Cell>> = HashMap::default();
+        for (row, cells) in layout.iter().enumerate() {
+            for (column, w) in Column::ALL.iter().zip(cells) {
+                if let Some(w) = w {
+                    let root = self.find(w.0);
+                    classes.entry(root).or_default().push(Cell {
+                        column: *column,
+                        row,
+                    });
+                }
+            }
+        }
+        let mut copies: Vec<Vec<Cell>> = classes.into_values().filter(|c| c.len() > 1).collect();
+        copies.sort();
+        let table = PlonkTable {
+            field: self.field.clone(),
+            num_public: self.public.len(),
+            rows,
+            copies,
+        };
+        (table, layout)
+    }
+
+    /// Add the wires of this system to the precomputation, as outputs.
+    fn extend_precomputation(&self, precompute: &mut precomp::PreComp, public_only: bool) {
+        let public: HashSet<Wire> = self.public.iter().cloned().collect();
+        for (i, (name, term)) in self.names.iter().zip(&self.terms).enumerate() {
+            if (!public_only || public.contains(&Wire(i)))
+                && !precompute.outputs().contains_key(name)
+            {
+                precompute.add_output(name.clone(), term.clone());
+            }
+        }
+    }
+
+    /// Split this system into prover and verifier information.
+    pub fn finalize(mut self, cs: &Computation) -> (ProverData, VerifierData) {
+        let verifier_precompute = {
+            let mut precompute = cs.precomputes.clone();
+            self.extend_precomputation(&mut precompute, true);
+            precompute.restrict_to_inputs(cs.metadata.get_inputs_for_party(None));
+            let mut outputs = precompute.flatten();
+            let terms: Vec<Term> = self
+                .public
+                .iter()
+                .map(|w| outputs.remove(&self.names[w.0]).unwrap())
+                .collect();
+            let mut comp = StagedWitComp::default();
+            comp.add_stage(free_vars(&terms), terms);
+            comp
+        };
+        let prover_precompute = {
+            let mut precompute = cs.precomputes.clone();
+            self.extend_precomputation(&mut precompute, false);
+            let mut outputs = precompute.flatten();
+            let terms: Vec<Term> = self
+                .names
+                .iter()
+                .map(|n| outputs.remove(n).unwrap())
+                .collect();
+            let mut comp = StagedWitComp::default();
+            comp.add_stage(free_vars(&terms), terms);
+            comp
+        };
+        let (table, layout) = self.table();
+        (
+            ProverData {
+                table,
+                layout,
+                precompute: prover_precompute,
+            },
+            VerifierData {
+                precompute: verifier_precompute,
+            },
+        )
+    }
+}
+
+/// The free variables of `terms`, with their sorts.
+fn free_vars(terms: &[Term]) -> HashMap<String, Sort> {
+    PostOrderIter::from_roots_and_skips(terms.iter().cloned(), Default::default())
+        .filter_map(|t| {
+            if let Op::Var(n, s) = t.op() {
+                Some((n.clone(), s.clone()))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Data that a prover needs: the table, and how to fill its advice columns.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProverData {
+    /// The table
+    pub table: PlonkTable,
+    /// For each row, the wires in its `a`, `b`, and `c` cells.
+    pub layout: Vec<[Option<Wire>; 3]>,
+    /// Computes the value of every wire, in order.
+    pub precompute: StagedWitComp,
+}
+
+impl ProverData {
+    /// Compute the advice columns from the prover's inputs.
+    pub fn advice(&self, inputs: &HashMap<String, Value>) -> Vec<[FieldV; 3]> {
+        let mut eval = StagedWitCompEvaluator::new(&self.precompute);
+        let wires: Vec<FieldV> = eval
+            .eval_stage(inputs.clone())
+            .into_iter()
+            .map(|v| v.as_pf().clone())
+            .collect();
+        let zero = self.table.field.zero();
+        self.layout
+            .iter()
+            .map(|cells| {
+                let value =
+                    |c: &Option<Wire>| c.map_or_else(|| zero.clone(), |w| wires[w.0].clone());
+                [value(&cells[0]), value(&cells[1]), value(&cells[2])]
+            })
+            .collect()
+    }
+}
+
+/// Data that a verifier needs: how to compute the public inputs.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifierData {
+    /// Computes the public inputs, in order.
+    pub precompute: StagedWitComp,
+}
+
+impl VerifierData {
+    /// Compute the instance column from the verifier's inputs.
+    pub fn instance(&self, inputs: &HashMap<String, Value>) -> Vec<FieldV> {
+        let mut eval = StagedWitCompEvaluator::new(&self.precompute);
+        eval.eval_stage(inputs.clone())
+            .into_iter()
+            .map(|v| v.as_pf().clone())
+            .collect()
+    }
+}
+
+/// Check a witness against the table, without making a proof.
+///
+/// `witness` holds the prover's inputs, and `instance` holds the verifier's.
+pub fn mock_prove(
+    pd: &ProverData,
+    vd: &VerifierData,
+    witness: &HashMap<String, Value>,
+    instance: &HashMap<String, Value>,
+) -> Result<(), MockFailure> {
+    pd.table.check(&pd.advice(witness), &vd.instance(instance))
+}
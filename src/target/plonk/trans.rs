@@ -0,0 +1,1011 @@
+//! Lowering IR to a Plonkish table
+//!
+//! Booleans and field elements are each one wire. A bit-vector is kept as its bits (least
+//! significant first), its unsigned value, or both; either is built from the other on demand.
+//! Bits are created by the range gate, and bitwise logic uses the boolean and XOR gates.
+
+use super::{Plonk, Row, Wire};
+use crate::cfg::CircCfg;
+use crate::ir::term::*;
+use crate::target::bitsize;
+
+use circ_fields::{FieldT, FieldV};
+use circ_opt::FieldDivByZero;
+use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
+use log::debug;
+use rug::Integer;
+
+struct BvEntry {
+    width: usize,
+    /// Empty if not yet created.
+    uint: Option<Wire>,
+    /// Empty if not yet created.
+    bits: Option<Vec<Wire>>,
+}
+
+impl BvEntry {
+    fn from_bits(bits: Vec<Wire>) -> Self {
+        Self {
+            width: bits.len(),
+            uint: None,
+            bits: Some(bits),
+        }
+    }
+
+    fn from_uint(uint: Wire, width: usize) -> Self {
+        Self {
+            width,
+            uint: Some(uint),
+            bits: None,
+        }
+    }
+}
+
+enum EmbeddedTerm {
+    Bv(BvEntry),
+    Bool(Wire),
+    Field(Wire),
+}
+
+struct ToPlonk<'cfg> {
+    plonk: Plonk,
+    cache: TermMap<EmbeddedTerm>,
+    consts: HashMap<FieldV, Wire>,
+    next_idx: usize,
+    cfg: &'cfg CircCfg,
+    field: FieldT,
+    used_vars: HashSet<String>,
+}
+
+impl<'cfg> ToPlonk<'cfg> {
+    fn new(cfg: &'cfg CircCfg, used_vars: HashSet<String>) -> Self {
+        let field = cfg.field().clone();
+        debug!("Starting Plonk back-end, field: {}", field);
+        Self {
+            plonk: Plonk::new(field.clone()),
+            cache: TermMap::default(),
+            consts: HashMap::default(),
+            next_idx: 0,
+            cfg,
+            field,
+            used_vars,
+        }
+    }
+
+    /// Get a new wire, with name dependent on `ctx`, whose value is computed by `comp`.
+    fn fresh<D: std::fmt::Display + ?Sized>(&mut self, ctx: &D, comp: Term) -> Wire {
+        let n = format!("{ctx}_n{}", self.next_idx);
+        self.next_idx += 1;
+        debug!("fresh: {n:?}");
+        self.plonk.add_wire(n, comp)
+    }
+
+    fn term(&self, w: Wire) -> Term {
+        self.plonk.term(w).clone()
+    }
+
+    fn v(&self, i: impl Into<Integer>) -> FieldV {
+        self.field.new_v(i.into())
+    }
+
+    fn row(&self) -> Row {
+        Row::new(&self.field)
+    }
+
+    fn field_bits(&self) -> usize {
+        self.field.modulus().significant_bits() as usize
+    }
+
+    /// A wire fixed to `v`. Each constant gets one wire.
+    fn constant(&mut self, v: FieldV) -> Wire {
+        if let Some(w) = self.consts.get(&v) {
+            return *w;
+        }
+        let w = self.fresh("const", pf_lit(v.clone()));
+        let mut row = self.row();
+        row.q_l = self.v(1);
+        row.q_c = -v.clone();
+        self.plonk.gate(row, [Some(w), None, None]);
+        self.consts.insert(v, w);
+        w
+    }
+
+    fn bit_const(&mut self, b: bool) -> Wire {
+        let v = self.v(b as u8);
+        self.constant(v)
+    }
+
+    /// Returns `ca*a + cb*b + k`.
+    fn affine(&mut self, ca: FieldV, a: Wire, cb: FieldV, b: Wire, k: FieldV) -> Wire {
+        let comp = term![PF_ADD;
+            term![PF_MUL; pf_lit(ca.clone()), self.term(a)],
+            term![PF_MUL; pf_lit(cb.clone()), self.term(b)],
+            pf_lit(k.clone())];
+        let out = self.fresh("lin", comp);
+        let mut row = self.row();
+        row.q_l = ca;
+        row.q_r = cb;
+        row.q_o = -self.v(1);
+        row.q_c = k;
+        self.plonk.gate(row, [Some(a), Some(b), Some(out)]);
+        out
+    }
+
+    /// Returns `ca*a + k`.
+    fn affine1(&mut self, ca: FieldV, a: Wire, k: FieldV) -> Wire {
+        let comp =
+            term![PF_ADD; term![PF_MUL; pf_lit(ca.clone()), self.term(a)], pf_lit(k.clone())];
+        let out = self.fresh("lin", comp);
+        let mut row = self.row();
+        row.q_l = ca;
+        row.q_o = -self.v(1);
+        row.q_c = k;
+        self.plonk.gate(row, [Some(a), None, Some(out)]);
+        out
+    }
+
+    fn add(&mut self, a: Wire, b: Wire) -> Wire {
+        self.affine(self.v(1), a, self.v(1), b, self.v(0))
+    }
+
+    fn sub(&mut self, a: Wire, b: Wire) -> Wire {
+        self.affine(self.v(1), a, self.v(-1), b, self.v(0))
+    }
+
+    fn mul(&mut self, a: Wire, b: Wire) -> Wire {
+        let out = self.fresh("mul", term![PF_MUL; self.term(a), self.term(b)]);
+        let mut row = self.row();
+        row.q_m = self.v(1);
+        row.q_o = -self.v(1);
+        self.plonk.gate(row, [Some(a), Some(b), Some(out)]);
+        out
+    }
+
+    /// Given a bit-valued `c`, returns `t` if `c`, else `f`.
+    fn ite(&mut self, c: Wire, t: Wire, f: Wire) -> Wire {
+        if t == f {
+            return t;
+        }
+        let d = self.sub(t, f);
+        let m = self.mul(c, d);
+        self.add(m, f)
+    }
+
+    /// Enforce `x` to be bit-valued.
+    fn enforce_bit(&mut self, x: Wire) {
+        let mut row = self.row();
+        row.q_bool = true;
+        self.plonk.gate(row, [Some(x), None, None]);
+    }
+
+    fn not(&mut self, a: Wire) -> Wire {
+        self.affine1(self.v(-1), a, self.v(1))
+    }
+
+    fn and(&mut self, a: Wire, b: Wire) -> Wire {
+        self.mul(a, b)
+    }
+
+    fn or(&mut self, a: Wire, b: Wire) -> Wire {
+        let (ta, tb) = (self.term(a), self.term(b));
+        let comp = term![PF_ADD; ta.clone(), tb.clone(), term![PF_NEG; term![PF_MUL; ta, tb]]];
+        let out = self.fresh("or", comp);
+        let mut row = self.row();
+        row.q_l = self.v(1);
+        row.q_r = self.v(1);
+        row.q_m = -self.v(1);
+        row.q_o = -self.v(1);
+        self.plonk.gate(row, [Some(a), Some(b), Some(out)]);
+        out
+    }
+
+    fn xor(&mut self, a: Wire, b: Wire) -> Wire {
+        let (ta, tb) = (self.term(a), self.term(b));
+        let comp = term![PF_ADD; ta.clone(), tb.clone(),
+            term![PF_MUL; pf_lit(self.v(-2)), term![PF_MUL; ta, tb]]];
+        let out = self.fresh("xor", comp);
+        let mut row = self.row();
+        row.q_xor = true;
+        self.plonk.gate(row, [Some(a), Some(b), Some(out)]);
+        out
+    }
+
+    /// Returns a bit indicating whether `x` is zero, and a wire holding the inverse of `x` (or zero).
+    #[allow(clippy::wrong_self_convention)]
+    fn is_zero_with_inv(&mut self, x: Wire) -> (Wire, Wire) {
+        let tx = self.term(x);
+        let eqz = term![Op::Eq; tx.clone(), pf_lit(self.v(0))];
+        // m * x - 1 + is_zero == 0
+        // is_zero * x == 0
+        let m = self.fresh(
+            "is_zero_inv",
+            term![Op::Ite; eqz.clone(), pf_lit(self.v(0)), term![PF_RECIP; tx]],
+        );
+        let is_zero = self.fresh(
+            "is_zero",
+            term![Op::Ite; eqz, pf_lit(self.v(1)), pf_lit(self.v(0))],
+        );
+        let mut row = self.row();
+        row.q_m = self.v(1);
+        row.q_o = self.v(1);
+        row.q_c = -self.v(1);
+        self.plonk.gate(row, [Some(m), Some(x), Some(is_zero)]);
+        self.assert_product_zero(is_zero, x);
+        (is_zero, m)
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    fn is_zero(&mut self, x: Wire) -> Wire {
+        self.is_zero_with_inv(x).0
+    }
+
+    /// Enforce `a * b == 0`.
+    fn assert_product_zero(&mut self, a: Wire, b: Wire) {
+        let mut row = self.row();
+        row.q_m = self.v(1);
+        self.plonk.gate(row, [Some(a), Some(b), None]);
+    }
+
+    /// Given wire `x`, returns `n` wires which are its bits, with the LSB at index 0.
+    /// Constrains `x` to fit in `n` bits.
+    fn range<D: std::fmt::Display + ?Sized>(&mut self, d: &D, x: Wire, n: usize) -> Vec<Wire> {
+        let x_bv = term![Op::PfToBv(n); self.term(x)];
+        let mut acc = self.bit_const(false);
+        let mut bits = Vec::new();
+        for i in (0..n).rev() {
+            let bit = self.fresh(
+                &format!("{d}_b{i}"),
+                term![Op::Ite; term![Op::BvBit(i); x_bv.clone()], pf_lit(self.v(1)), pf_lit(self.v(0))],
+            );
+            let next = self.fresh(
+                &format!("{d}_acc"),
+                term![PF_ADD; term![PF_MUL; pf_lit(self.v(2)), self.term(acc)], self.term(bit)],
+            );
+            let mut row = self.row();
+            row.q_range = true;
+            self.plonk.gate(row, [Some(acc), Some(bit), Some(next)]);
+            bits.push(bit);
+            acc = next;
+        }
+        self.plonk.equate(acc, x);
+        bits.reverse();
+        bits
+    }
+
+    /// Given bits (LSB first), returns a wire holding their unsigned value.
+    fn debitify(&mut self, bits: &[Wire]) -> Wire {
+        match bits.split_last() {
+            None => self.bit_const(false),
+            Some((msb, rest)) => rest.iter().rev().fold(*msb, |acc, b| {
+                self.affine(self.v(2), acc, self.v(1), *b, self.v(0))
+            }),
+        }
+    }
+
+    fn embed_var(&mut self, var: &Term, public: bool) {
+        assert!(
+            !self.cache.contains_key(var),
+            "already have var {}",
+            var.op()
+        );
+        if !self.used_vars.contains(var.as_var_name()) {
+            return;
+        }
+        let e = match var.op() {
+            Op::Var(name, Sort::Bool) => {
+                let comp = term![Op::Ite; var.clone(), pf_lit(self.v(1)), pf_lit(self.v(0))];
+                let w = self.fresh(name, comp);
+                if public {
+                    self.plonk.add_public(w);
+                } else {
+                    self.enforce_bit(w);
+                }
+                EmbeddedTerm::Bool(w)
+            }
+            Op::Var(name, Sort::BitVector(n_bits)) => {
+                let w = self.fresh(name, term![Op::UbvToPf(self.field.clone()); var.clone()]);
+                let mut entry = BvEntry::from_uint(w, *n_bits);
+                if public {
+                    self.plonk.add_public(w);
+                } else {
+                    entry.bits = Some(self.range(name, w, *n_bits));
+                }
+                EmbeddedTerm::Bv(entry)
+            }
+            Op::Var(name, Sort::Field(f)) => {
+                assert_eq!(f, &self.field);
+                let w = self.fresh(name, var.clone());
+                if public {
+                    self.plonk.add_public(w);
+                }
+                EmbeddedTerm::Field(w)
+            }
+            o => unreachable!("Unhandled variable operator {}", o),
+        };
+        self.cache.insert(var.clone(), e);
+    }
+
+    fn embed(&mut self, t: &Term) {
+        if self.cache.contains_key(t) {
+            return;
+        }
+        debug!("Embed op: {}", t.op());
+        let e = match check(t) {
+            Sort::Bool => EmbeddedTerm::Bool(self.embed_bool(t)),
+            Sort::BitVector(n) => EmbeddedTerm::Bv(self.embed_bv(t, n)),
+            Sort::Field(_) => EmbeddedTerm::Field(self.embed_pf(t)),
+            s => panic!("Unsupported sort in embed: {:?}", s),
+        };
+        self.cache.insert(t.clone(), e);
+    }
+
+    fn get_bool(&self, t: &Term) -> Wire {
+        match self
+            .cache
+            .get(t)
+            .unwrap_or_else(|| panic!("Missing wire for {:?}", t))
+        {
+            EmbeddedTerm::Bool(b) => *b,
+            _ => panic!("Non-boolean for {:?}", t),
+        }
+    }
+
+    fn get_pf(&self, t: &Term) -> Wire {
+        match self
+            .cache
+            .get(t)
+            .unwrap_or_else(|| panic!("Missing wire for {:?}", t))
+        {
+            EmbeddedTerm::Field(f) => *f,
+            _ => panic!("Non-field for {:?}", t),
+        }
+    }
+
+    fn get_bv(&mut self, t: &Term) -> &mut BvEntry {
+        match self
+            .cache
+            .get_mut(t)
+            .unwrap_or_else(|| panic!("Missing wire for {:?}", t))
+        {
+            EmbeddedTerm::Bv(b) => b,
+            _ => panic!("Non-bv for {:?}", t),
+        }
+    }
+
+    fn bv_has_bits(&self, t: &Term) -> bool {
+        matches!(self.cache.get(t), Some(EmbeddedTerm::Bv(b)) if b.bits.is_some())
+    }
+
+    fn bv_has_uint(&self, t: &Term) -> bool {
+        matches!(self.cache.get(t), Some(EmbeddedTerm::Bv(b)) if b.uint.is_some())
+    }
+
+    fn get_bv_uint(&mut self, t: &Term) -> Wire {
+        let entry = self.get_bv(t);
+        if let Some(uint) = entry.uint {
+            return uint;
+        }
+        let bits = entry.bits.clone().unwrap();
+        let uint = self.debitify(&bits);
+        self.get_bv(t).uint = Some(uint);
+        uint
+    }
+
+    fn get_bv_bits(&mut self, t: &Term) -> Vec<Wire> {
+        let entry = self.get_bv(t);
+        if let Some(bits) = &entry.bits {
+            return bits.clone();
+        }
+        let (uint, width) = (entry.uint.unwrap(), entry.width);
+        let bits = self.range("getbits", uint, width);
+        self.get_bv(t).bits = Some(bits.clone());
+        bits
+    }
+
+    /// The two's complement value of `t`, as a field element.
+    fn get_bv_signed_int(&mut self, t: &Term) -> Wire {
+        let bits = self.get_bv_bits(t);
+        let uint = self.get_bv_uint(t);
+        let shift = -self.v(Integer::from(1) << bits.len());
+        self.affine(self.v(1), uint, shift, *bits.last().unwrap(), self.v(0))
+    }
+
+    fn embed_eq(&mut self, a: &Term, b: &Term) -> Wire {
+        match check(a) {
+            Sort::Bool => {
+                let a = self.get_bool(a);
+                let b = self.get_bool(b);
+                let x = self.xor(a, b);
+                self.not(x)
+            }
+            Sort::BitVector(_) => {
+                let a = self.get_bv_uint(a);
+                let b = self.get_bv_uint(b);
+                let diff = self.sub(a, b);
+                self.is_zero(diff)
+            }
+            Sort::Field(_) => {
+                let a = self.get_pf(a);
+                let b = self.get_pf(b);
+                let diff = self.sub(a, b);
+                self.is_zero(diff)
+            }
+            s => panic!("Unimplemented sort for Eq: {:?}", s),
+        }
+    }
+
+    fn embed_bool(&mut self, c: &Term) -> Wire {
+        match c.op() {
+            Op::Var(..) => panic!("call embed_var instead"),
+            Op::Const(Value::Bool(b)) => self.bit_const(*b),
+            Op::Eq => self.embed_eq(&c.cs()[0], &c.cs()[1]),
+            Op::Ite => {
+                let a = self.get_bool(&c.cs()[0]);
+                let b = self.get_bool(&c.cs()[1]);
+                let c = self.get_bool(&c.cs()[2]);
+                self.ite(a, b, c)
+            }
+            Op::BoolMaj => {
+                let a = self.get_bool(&c.cs()[0]);
+                let b = self.get_bool(&c.cs()[1]);
+                let c = self.get_bool(&c.cs()[2]);
+                // maj(a, b, c) = ab + c(a xor b), and the two summands are never both one.
+                let ab = self.and(a, b);
+                let a_xor_b = self.xor(a, b);
+                let c_and_xor = self.and(c, a_xor_b);
+                self.add(ab, c_and_xor)
+            }
+            Op::Not => {
+                let a = self.get_bool(&c.cs()[0]);
+                self.not(a)
+            }
+            Op::Implies => {
+                let a = self.get_bool(&c.cs()[0]);
+                let b = self.get_bool(&c.cs()[1]);
+                let not_a = self.not(a);
+                self.or(not_a, b)
+            }
+            Op::BoolNaryOp(o) => {
+                let args: Vec<Wire> = c.cs().iter().map(|c| self.get_bool(c)).collect();
+                let mut args = args.into_iter();
+                let first = args.next().expect("empty boolean operator");
+                args.fold(first, |acc, a| match o {
+                    BoolNaryOp::And => self.and(acc, a),
+                    BoolNaryOp::Or => self.or(acc, a),
+                    BoolNaryOp::Xor => self.xor(acc, a),
+                })
+            }
+            Op::BvBit(i) => self.get_bv_bits(&c.cs()[0])[*i],
+            Op::BvBinPred(o) => {
+                let n = check(&c.cs()[0]).as_bv();
+                let (a, b) = (&c.cs()[0], &c.cs()[1]);
+                use BvBinPred::*;
+                match o {
+                    Sge => self.bv_cmp(n, true, false, a, b),
+                    Sgt => self.bv_cmp(n, true, true, a, b),
+                    Uge => self.bv_cmp(n, false, false, a, b),
+                    Ugt => self.bv_cmp(n, false, true, a, b),
+                    Sle => self.bv_cmp(n, true, false, b, a),
+                    Slt => self.bv_cmp(n, true, true, b, a),
+                    Ule => self.bv_cmp(n, false, false, b, a),
+                    Ult => self.bv_cmp(n, false, true, b, a),
+                }
+            }
+            // we trust that this is zero or one
+            Op::PfToBoolTrusted => self.get_pf(&c.cs()[0]),
+            _ => panic!("Non-boolean in embed_bool: {}", c),
+        }
+    }
+
+    /// Given a and b such that -2^n < a - b < 2^n, returns whether a >= b (or a > b if `strict` is
+    /// set).
+    fn bv_greater(&mut self, a: Wire, b: Wire, n: usize, strict: bool) -> Wire {
+        let shift = self.v((Integer::from(1) << n) - strict as u8);
+        let sum = self.affine(self.v(1), a, self.v(-1), b, shift);
+        self.range("cmp", sum, n + 1).pop().unwrap()
+    }
+
+    /// Returns whether `a` is (`strict`ly) (`signed`ly) greater than `b`.
+    /// Assumes they are each `w`-bit bit-vectors.
+    fn bv_cmp(&mut self, w: usize, signed: bool, strict: bool, a: &Term, b: &Term) -> Wire {
+        if w + 2 < self.field_bits() {
+            let (a, b) = if signed {
+                (self.get_bv_signed_int(a), self.get_bv_signed_int(b))
+            } else {
+                (self.get_bv_uint(a), self.get_bv_uint(b))
+            };
+            self.bv_greater(a, b, w, strict)
+        } else {
+            assert!(
+                !signed,
+                "Cannot perform signed comparisons on huge bit-vectors"
+            );
+            let xs = self.get_bv_bits(a);
+            let ys = self.get_bv_bits(b);
+            let mut acc = self.bit_const(!strict);
+            for (x, y) in xs.into_iter().zip(ys) {
+                let x_xor_y = self.xor(x, y);
+                let eq = self.not(x_xor_y);
+                let eq_and_acc = self.and(eq, acc);
+                let not_y = self.not(y);
+                let x_gt_y = self.and(x, not_y);
+                acc = self.or(x_gt_y, eq_and_acc);
+            }
+            acc
+        }
+    }
+
+    fn embed_bv(&mut self, bv: &Term, n: usize) -> BvEntry {
+        match bv.op() {
+            Op::Var(..) => panic!("call embed_var instead"),
+            Op::Const(Value::BitVector(b)) => BvEntry::from_bits(
+                (0..b.width())
+                    .map(|i| self.bit_const(b.uint().get_bit(i as u32)))
+                    .collect(),
+            ),
+            Op::Ite => {
+                let c = self.get_bool(&bv.cs()[0]);
+                let t = self.get_bv_uint(&bv.cs()[1]);
+                let f = self.get_bv_uint(&bv.cs()[2]);
+                BvEntry::from_uint(self.ite(c, t, f), n)
+            }
+            Op::BvUnOp(BvUnOp::Not) => {
+                if self.bv_has_uint(&bv.cs()[0]) {
+                    // !x = 2^n - 1 - x
+                    let x = self.get_bv_uint(&bv.cs()[0]);
+                    let max = self.v((Integer::from(1) << n) - 1);
+                    BvEntry::from_uint(self.affine1(self.v(-1), x, max), n)
+                } else {
+                    let bits = self.get_bv_bits(&bv.cs()[0]);
+                    BvEntry::from_bits(bits.into_iter().map(|b| self.not(b)).collect())
+                }
+            }
+            Op::BvUnOp(BvUnOp::Neg) => {
+                let x = self.get_bv_uint(&bv.cs()[0]);
+                // (2^n - x) is right, unless x == 0
+                let almost_neg_x = self.affine1(self.v(-1), x, self.v(Integer::from(1) << n));
+                let is_zero = self.is_zero(x);
+                let is_nonzero = self.not(is_zero);
+                BvEntry::from_uint(self.mul(is_nonzero, almost_neg_x), n)
+            }
+            Op::BvUext(extra_n) => {
+                if self.bv_has_bits(&bv.cs()[0]) {
+                    let mut bits = self.get_bv_bits(&bv.cs()[0]);
+                    let zero = self.bit_const(false);
+                    bits.extend(std::iter::repeat(zero).take(*extra_n));
+                    BvEntry::from_bits(bits)
+                } else {
+                    BvEntry::from_uint(self.get_bv_uint(&bv.cs()[0]), n)
+                }
+            }
+            Op::BvSext(extra_n) => {
+                let mut bits = self.get_bv_bits(&bv.cs()[0]);
+                let msb = *bits.last().expect("sign ext empty");
+                bits.extend(std::iter::repeat(msb).take(*extra_n));
+                BvEntry::from_bits(bits)
+            }
+            Op::PfToBv(nbits) => {
+                let x = self.get_pf(&bv.cs()[0]);
+                let bits = self.range("pf2bv", x, *nbits);
+                BvEntry {
+                    width: *nbits,
+                    uint: Some(x),
+                    bits: Some(bits),
+                }
+            }
+            Op::BoolToBv => BvEntry::from_bits(vec![self.get_bool(&bv.cs()[0])]),
+            Op::BvNaryOp(o) => match o {
+                BvNaryOp::Xor | BvNaryOp::Or | BvNaryOp::And => {
+                    let bits_by_bv: Vec<Vec<Wire>> =
+                        bv.cs().iter().map(|c| self.get_bv_bits(c)).collect();
+                    let bits = (0..n)
+                        .map(|i| {
+                            let mut column = bits_by_bv.iter().map(|bits| bits[i]);
+                            let first = column.next().expect("empty bit-vector operator");
+                            column.fold(first, |acc, b| match o {
+                                BvNaryOp::And => self.and(acc, b),
+                                BvNaryOp::Or => self.or(acc, b),
+                                BvNaryOp::Xor => self.xor(acc, b),
+                                _ => unreachable!(),
+                            })
+                        })
+                        .collect();
+                    BvEntry::from_bits(bits)
+                }
+                BvNaryOp::Add | BvNaryOp::Mul => {
+                    let values: Vec<Wire> = bv.cs().iter().map(|c| self.get_bv_uint(c)).collect();
+                    let mut values = values.into_iter();
+                    let first = values.next().expect("empty bit-vector operator");
+                    let f_width = self.field_bits() - 1;
+                    let mut bits = match o {
+                        BvNaryOp::Add => {
+                            let sum = values.fold(first, |s, v| self.add(s, v));
+                            let extra_width = bitsize(bv.cs().len().saturating_sub(1));
+                            self.range("arith", sum, n + extra_width)
+                        }
+                        BvNaryOp::Mul if bv.cs().len() * n < f_width => {
+                            let product = values.fold(first, |p, v| self.mul(p, v));
+                            self.range("arith", product, bv.cs().len() * n)
+                        }
+                        BvNaryOp::Mul => {
+                            let mut bits = self.get_bv_bits(&bv.cs()[0]);
+                            let mut acc = first;
+                            for v in values {
+                                let p = self.mul(acc, v);
+                                bits = self.range("binMul", p, 2 * n);
+                                bits.truncate(n);
+                                acc = self.debitify(&bits);
+                            }
+                            bits
+                        }
+                        _ => unreachable!(),
+                    };
+                    bits.truncate(n);
+                    BvEntry::from_bits(bits)
+                }
+            },
+            Op::BvBinOp(o) => match o {
+                BvBinOp::Sub => {
+                    let a = self.get_bv_uint(&bv.cs()[0]);
+                    let b = self.get_bv_uint(&bv.cs()[1]);
+                    let shift = self.v(Integer::from(1) << n);
+                    let sum = self.affine(self.v(1), a, self.v(-1), b, shift);
+                    let mut bits = self.range("sub", sum, n + 1);
+                    bits.truncate(n);
+                    BvEntry::from_bits(bits)
+                }
+                BvBinOp::Udiv | BvBinOp::Urem => {
+                    let a = self.get_bv_uint(&bv.cs()[0]);
+                    let b = self.get_bv_uint(&bv.cs()[1]);
+                    let a_bv_term = term![Op::PfToBv(n); self.term(a)];
+                    let b_bv_term = term![Op::PfToBv(n); self.term(b)];
+                    let to_pf = Op::UbvToPf(self.field.clone());
+                    let q_term =
+                        term![to_pf.clone(); term![BV_UDIV; a_bv_term.clone(), b_bv_term.clone()]];
+                    let r_term = term![to_pf; term![BV_UREM; a_bv_term, b_bv_term]];
+                    let q = self.fresh("div_q", q_term);
+                    let r = self.fresh("div_r", r_term);
+                    let qb = self.range("div_q", q, n);
+                    let rb = self.range("div_r", r, n);
+                    // q * b + r == a
+                    let qb_prod = self.mul(q, b);
+                    let mut row = self.row();
+                    row.q_l = self.v(1);
+                    row.q_r = self.v(1);
+                    row.q_o = -self.v(1);
+                    self.plonk.gate(row, [Some(qb_prod), Some(r), Some(a)]);
+                    // b == 0 -> q == M, and b != 0 -> r < b.
+                    // Since we don't care about b == 0, we require q == M or r < b.
+                    let r_ge_b = self.bv_greater(r, b, n, false);
+                    let max = self.v((Integer::from(1) << n) - 1);
+                    let q_minus_max = self.affine1(self.v(1), q, -max);
+                    let q_eq_max = self.is_zero(q_minus_max);
+                    let q_ne_max = self.not(q_eq_max);
+                    self.assert_product_zero(r_ge_b, q_ne_max);
+                    let (uint, bits) = match o {
+                        BvBinOp::Udiv => (q, qb),
+                        BvBinOp::Urem => (r, rb),
+                        _ => unreachable!(),
+                    };
+                    BvEntry {
+                        width: n,
+                        uint: Some(uint),
+                        bits: Some(bits),
+                    }
+                }
+                // Shift cases: a barrel shifter over the bits
+                _ => {
+                    let mut bits = self.get_bv_bits(&bv.cs()[0]);
+                    let amount = self.get_bv_bits(&bv.cs()[1]);
+                    let fill = match o {
+                        BvBinOp::Ashr => *bits.last().unwrap(),
+                        _ => self.bit_const(false),
+                    };
+                    for (i, s) in amount.into_iter().enumerate() {
+                        let k = if i < bitsize(n) { (1 << i).min(n) } else { n };
+                        let shifted: Vec<Wire> = (0..n)
+                            .map(|j| match o {
+                                BvBinOp::Shl if j >= k => bits[j - k],
+                                BvBinOp::Shl => fill,
+                                _ if j + k < n => bits[j + k],
+                                _ => fill,
+                            })
+                            .collect();
+                        bits = shifted
+                            .into_iter()
+                            .zip(bits)
+                            .map(|(t, f)| self.ite(s, t, f))
+                            .collect();
+                    }
+                    BvEntry::from_bits(bits)
+                }
+            },
+            Op::BvConcat => {
+                let mut bits = Vec::new();
+                for c in bv.cs().iter().rev() {
+                    bits.extend(self.get_bv_bits(c));
+                }
+                BvEntry::from_bits(bits)
+            }
+            // inclusive!
+            Op::BvExtract(high, low) => {
+                BvEntry::from_bits(self.get_bv_bits(&bv.cs()[0])[*low..=*high].to_vec())
+            }
+            _ => panic!("Non-bv in embed_bv: {}", bv),
+        }
+    }
+
+    fn embed_pf(&mut self, c: &Term) -> Wire {
+        match c.op() {
+            Op::Var(..) => panic!("call embed_var instead"),
+            Op::Const(Value::Field(r)) => self.constant(r.as_ty_ref(&self.field)),
+            Op::Ite => {
+                let cond = self.get_bool(&c.cs()[0]);
+                let t = self.get_pf(&c.cs()[1]);
+                let f = self.get_pf(&c.cs()[2]);
+                self.ite(cond, t, f)
+            }
+            Op::PfNaryOp(o) => {
+                let args: Vec<Wire> = c.cs().iter().map(|c| self.get_pf(c)).collect();
+                let mut args = args.into_iter();
+                let first = args.next().expect("empty field operator");
+                match o {
+                    PfNaryOp::Add => args.fold(first, |a, b| self.add(a, b)),
+                    PfNaryOp::Mul => args.fold(first, |a, b| self.mul(a, b)),
+                }
+            }
+            Op::UbvToPf(_) => self.get_bv_uint(&c.cs()[0]),
+            Op::PfUnOp(PfUnOp::Neg) => {
+                let x = self.get_pf(&c.cs()[0]);
+                self.affine1(self.v(-1), x, self.v(0))
+            }
+            Op::PfUnOp(PfUnOp::Recip) => {
+                let x = self.get_pf(&c.cs()[0]);
+                match self.cfg.r1cs.div_by_zero {
+                    FieldDivByZero::Incomplete => {
+                        // ix = 1
+                        let inv_x = self.fresh("recip", term![PF_RECIP; self.term(x)]);
+                        let mut row = self.row();
+                        row.q_m = self.v(1);
+                        row.q_c = -self.v(1);
+                        self.plonk.gate(row, [Some(x), Some(inv_x), None]);
+                        inv_x
+                    }
+                    FieldDivByZero::NonDet => {
+                        // ixx = x
+                        let x2 = self.mul(x, x);
+                        let inv_x = self.fresh("recip", term![PF_RECIP; self.term(x)]);
+                        let mut row = self.row();
+                        row.q_m = self.v(1);
+                        row.q_o = -self.v(1);
+                        self.plonk.gate(row, [Some(x2), Some(inv_x), Some(x)]);
+                        inv_x
+                    }
+                    FieldDivByZero::Zero => {
+                        // ix = 1 - z
+                        // zx = 0
+                        // zi = 0
+                        let (z, i) = self.is_zero_with_inv(x);
+                        self.assert_product_zero(z, i);
+                        i
+                    }
+                }
+            }
+            _ => panic!("Non-field in embed_pf: {}", c),
+        }
+    }
+
+    /// Assert that bit-valued `t` holds.
+    fn assert(&mut self, t: &Term) {
+        debug!("Assert: {}", t);
+        if t.op() == &Op::Eq {
+            let (a, b) = (&t.cs()[0], &t.cs()[1]);
+            match check(a) {
+                Sort::Bool => {
+                    let (a, b) = (self.get_bool(a), self.get_bool(b));
+                    self.plonk.equate(a, b);
+                }
+                Sort::BitVector(_) if self.bv_has_bits(a) && self.bv_has_bits(b) => {
+                    let a_bits = self.get_bv_bits(a);
+                    let b_bits = self.get_bv_bits(b);
+                    for (a, b) in a_bits.into_iter().zip(b_bits) {
+                        self.plonk.equate(a, b);
+                    }
+                }
+                Sort::BitVector(_) => {
+                    let (a, b) = (self.get_bv_uint(a), self.get_bv_uint(b));
+                    self.plonk.equate(a, b);
+                }
+                Sort::Field(_) => {
+                    let (a, b) = (self.get_pf(a), self.get_pf(b));
+                    self.plonk.equate(a, b);
+                }
+                s => panic!("Unimplemented sort for Eq: {:?}", s),
+            }
+        } else if t.op() == &AND {
+            for c in t.cs() {
+                self.assert(c);
+            }
+        } else if let Op::PfFitsInBits(n) = t.op() {
+            let x = self.get_pf(&t.cs()[0]);
+            self.range("fits", x, *n);
+        } else {
+            let b = self.get_bool(t);
+            let one = self.bit_const(true);
+            self.plonk.equate(b, one);
+        }
+    }
+}
+
+/// Collect the terms that asserting `t` needs embedded.
+fn assertion_operands(t: &Term, out: &mut Vec<Term>) {
+    if t.op() == &Op::Eq || matches!(t.op(), Op::PfFitsInBits(_)) {
+        out.extend(t.cs().iter().cloned());
+    } else if t.op() == &AND {
+        for c in t.cs() {
+            assertion_operands(c, out);
+        }
+    } else {
+        out.push(t.clone());
+    }
+}
+
+/// Convert this (IR) constraint system `cs` to a Plonkish system.
+///
+/// Committed witnesses and interactive rounds are not supported.
+pub fn to_plonk(cs: &Computation, cfg: &CircCfg) -> Plonk {
+    let used_vars = extras::free_variables(term(Op::Tuple, cs.outputs.clone()));
+    let mut converter = ToPlonk::new(cfg, used_vars);
+    let vars = cs.metadata.interactive_vars();
+    assert!(
+        vars.committed_wit_vecs.is_empty() && vars.rounds.is_empty(),
+        "The Plonk backend does not support committed witnesses or interaction"
+    );
+    for i in &vars.instances {
+        converter.embed_var(i, true);
+    }
+    for w in &vars.final_witnesses {
+        converter.embed_var(w, false);
+    }
+    let mut operands = Vec::new();
+    for c in &cs.outputs {
+        assertion_operands(c, &mut operands);
+    }
+    for t in PostOrderIter::from_roots_and_skips(operands, Default::default()) {
+        converter.embed(&t);
+    }
+    for c in &cs.outputs {
+        converter.assert(c);
+    }
+    debug!(
+        "Plonk table: {} rows, {} wires",
+        converter.plonk.num_rows(),
+        converter.plonk.num_wires()
+    );
+    converter.plonk
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::ir::proof::Constraints;
+    use crate::ir::term::dist::test::*;
+    use crate::target::plonk::{mock_prove, MockFailure};
+
+    use quickcheck_macros::quickcheck;
+
+    fn init() {
+        let _ = env_logger::builder().is_test(true).try_init();
+    }
+
+    /// Lower `cs` and check it against `values`, which must include every input.
+    fn check_values(cs: &Computation, values: &HashMap<String, Value>) -> Result<(), MockFailure> {
+        let plonk = to_plonk(cs, &CircCfg::default());
+        let (pd, vd) = plonk.finalize(cs);
+        mock_prove(&pd, &vd, values, values)
+    }
+
+    #[quickcheck]
+    fn random_pure_bool(PureBool(t, values): PureBool) {
+        let t = if eval(&t, &values).as_bool() {
+            t
+        } else {
+            term![Op::Not; t]
+        };
+        let cs = Computation::from_constraint_system_parts(vec![t], Vec::new());
+        check_values(&cs, &values).unwrap();
+    }
+
+    #[quickcheck]
+    fn random_bool(ArbitraryTermEnv(t, values): ArbitraryTermEnv) {
+        let v = eval(&t, &values);
+        let t = term![Op::Eq; t, leaf_term(Op::Const(v))];
+        let mut cs = Computation::from_constraint_system_parts(vec![t], Vec::new());
+        crate::ir::opt::scalarize_vars::scalarize_inputs(&mut cs);
+        crate::ir::opt::tuple::eliminate_tuples(&mut cs);
+        check_values(&cs, &values).unwrap();
+    }
+
+    #[test]
+    fn public_input() {
+        init();
+        let cs = text::parse_computation(
+            b"
+            (computation
+                (metadata (parties P) (inputs (x (bv 8)) (y (bv 8) (party 0))) (commitments))
+                (precompute () () (#t ))
+                (= (bvmul x y) #x0c)
+            )
+        ",
+        );
+        let values = text::parse_value_map(b"(let ((x #x03) (y #x04)) false)");
+        let instance = text::parse_value_map(b"(let ((x #x03)) false)");
+        let (pd, vd) = to_plonk(&cs, &CircCfg::default()).finalize(&cs);
+        assert_eq!(pd.table.num_public(), 1);
+        mock_prove(&pd, &vd, &values, &instance).unwrap();
+        let wrong_instance = text::parse_value_map(b"(let ((x #x05)) false)");
+        assert!(mock_prove(&pd, &vd, &values, &wrong_instance).is_err());
+    }
+
+    #[test]
+    fn custom_gates() {
+        init();
+        let cs = text::parse_computation(
+            b"
+            (computation
+                (metadata (parties P) (inputs (a bool (party 0)) (b bool (party 0))) (commitments))
+                (precompute () () (#t ))
+                (xor a b)
+            )
+        ",
+        );
+        let (pd, _) = to_plonk(&cs, &CircCfg::default()).finalize(&cs);
+        let rows = pd.table.rows();
+        assert_eq!(rows.iter().filter(|r| r.q_bool).count(), 2);
+        assert_eq!(rows.iter().filter(|r| r.q_xor).count(), 1);
+        assert!(!rows.iter().any(|r| r.q_range));
+    }
+
+    #[test]
+    fn bad_witness() {
+        init();
+        let cs = text::parse_computation(
+            b"
+            (computation
+                (metadata (parties P) (inputs (x (bv 4) (party 0))) (commitments))
+                (precompute () () (#t ))
+                (bvult x #x3)
+            )
+        ",
+        );
+        let good = text::parse_value_map(b"(let ((x #x2)) false)");
+        let bad = text::parse_value_map(b"(let ((x #x7)) false)");
+        assert_eq!(check_values(&cs, &good), Ok(()));
+        assert!(matches!(
+            check_values(&cs, &bad),
+            Err(MockFailure::Copy(..))
+        ));
+    }
+
+    #[test]
+    fn serialize_table() {
+        let cs = text::parse_computation(
+            b"
+            (computation
+                (metadata (parties P) (inputs (x (mod 17)) (y (mod 17) (party 0))) (commitments))
+                (precompute () () (#t ))
+                (= (* x y) #f6m17)
+            )
+        ",
+        );
+        let mut opt = crate::cfg::CircOpt::default();
+        opt.field.custom_modulus = "17".into();
+        let (pd, _) = to_plonk(&cs, &CircCfg::from(opt)).finalize(&cs);
+        let bytes = bincode::serialize(&pd).unwrap();
+        let pd2: crate::target::plonk::ProverData = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(pd.table.rows(), pd2.table.rows());
+        assert_eq!(pd.table.copies(), pd2.table.copies());
+        assert_eq!(pd.layout, pd2.layout);
+    }
+}
@@ -0,0 +1,470 @@
+//! Solidity verifiers for [Bellman](super::Bellman) Groth16 proofs.
+//!
+//! The generated contract checks proofs on-chain using the BLS12-381 precompiles of EIP-2537:
+//! * `0x0c`: G1 multi-scalar multiplication, used to fold the public inputs into the key
+//! * `0x0f`: the pairing check
+//!
+//! Points are encoded as the precompiles expect: each base-field element is a 64-byte big-endian
+//! word (16 bytes of zero padding, then 48 bytes), a G1 point is `x || y`, and a G2 point is
+//! `x.c0 || x.c1 || y.c0 || y.c1`. The point at infinity is all zeros.
+//!
+//! The contract's entry point is `verifyProof(bytes proof, uint256[] input)`, where
+//! * `proof` is `A || B || C` (512 bytes) and
+//! * `input` is the R1CS instance, in the order produced by [VerifierData::eval].
+//!
+//! To keep the contract free of base-field arithmetic, we negate `beta`, `gamma`, and `delta`
+//! off-chain, so the contract checks
+//! `e(A, B) * e(alpha, -beta) * e(vk_x, -gamma) * e(C, -delta) == 1`.
+
+use fxhash::FxHashMap as HashMap;
+use group::prime::PrimeCurveAffine;
+use group::UncompressedEncoding;
+use pairing::Engine;
+use rug::integer::Order;
+use rug::Integer;
+use std::fmt::Write;
+use std::path::Path;
+
+use super::super::proof::{deserialize_from_file, value_map_from_path};
+use super::super::VerifierData;
+use super::{get_modulus, Proof, VerifyingKey};
+use crate::ir::term::Value;
+
+/// Bytes in a (zkcrypto) serialized BLS12-381 base-field element.
+const FP_BYTES: usize = 48;
+/// Bytes in an EIP-2537 base-field element.
+const EVM_FP_BYTES: usize = 64;
+/// Bytes in an EIP-2537 G1 point.
+const EVM_G1_BYTES: usize = 2 * EVM_FP_BYTES;
+/// Bytes in an EIP-2537 G2 point.
+const EVM_G2_BYTES: usize = 4 * EVM_FP_BYTES;
+/// Bytes in an encoded proof: `A || B || C`.
+pub const PROOF_BYTES: usize = 2 * EVM_G1_BYTES + EVM_G2_BYTES;
+/// The selector of `verifyProof(bytes,uint256[])`: the first four bytes of its keccak256 hash.
+pub const VERIFY_PROOF_SELECTOR: [u8; 4] = [0x1e, 0x8e, 0x1e, 0x13];
+
+/// zkcrypto's uncompressed encoding puts the compression, infinity, and sort flags in the top
+/// three bits of the first byte.
+const FLAG_MASK: u8 = 0x1f;
+
+fn evm_fp(fp: &[u8]) -> impl Iterator<Item = u8> + '_ {
+    assert_eq!(fp.len(), FP_BYTES);
+    std::iter::repeat(0)
+        .take(EVM_FP_BYTES - FP_BYTES)
+        .chain(fp.iter().copied())
+}
+
+/// Encode a G1 point for EIP-2537.
+///
+/// Assumes the zkcrypto BLS12-381 uncompressed encoding: `x || y`, big-endian, flags in `x`.
+fn g1_to_evm<E: Engine>(p: &E::G1Affine) -> Vec<u8> {
+    if bool::from(p.is_identity()) {
+        return vec![0; EVM_G1_BYTES];
+    }
+    let mut raw = p.to_uncompressed();
+    let raw = raw.as_mut();
+    assert_eq!(raw.len(), 2 * FP_BYTES, "not a BLS12-381 G1 point");
+    raw[0] &= FLAG_MASK;
+    raw.chunks(FP_BYTES).flat_map(evm_fp).collect()
+}
+
+/// Encode a G2 point for EIP-2537.
+///
+/// Assumes the zkcrypto BLS12-381 uncompressed encoding: `x.c1 || x.c0 || y.c1 || y.c0`,
+/// big-endian, flags in `x.c1`. EIP-2537 puts `c0` first.
+fn g2_to_evm<E: Engine>(p: &E::G2Affine) -> Vec<u8> {
+    if bool::from(p.is_identity()) {
+        return vec![0; EVM_G2_BYTES];
+    }
+    let mut raw = p.to_uncompressed();
+    let raw = raw.as_mut();
+    assert_eq!(raw.len(), 4 * FP_BYTES, "not a BLS12-381 G2 point");
+    raw[0] &= FLAG_MASK;
+    let fps: Vec<&[u8]> = raw.chunks(FP_BYTES).collect();
+    [fps[1], fps[0], fps[3], fps[2]]
+        .iter()
+        .flat_map(|fp| evm_fp(fp))
+        .collect()
+}
+
+/// Encode a non-negative integer as a 32-byte big-endian EVM word.
+fn int_to_word(i: &Integer) -> [u8; 32] {
+    let digits = i.to_digits::<u8>(Order::Msf);
+    assert!(digits.len() <= 32, "{i} does not fit in a word");
+    let mut word = [0u8; 32];
+    word[32 - digits.len()..].copy_from_slice(&digits);
+    word
+}
+
+fn usize_to_word(u: usize) -> [u8; 32] {
+    int_to_word(&Integer::from(u))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(2 * bytes.len());
+    for b in bytes {
+        write!(&mut s, "{b:02x}").unwrap();
+    }
+    s
+}
+
+/// A Groth16 verifying key, encoded for the EVM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SolidityVerifier {
+    scalar_modulus: Integer,
+    alpha_g1: Vec<u8>,
+    neg_beta_g2: Vec<u8>,
+    neg_gamma_g2: Vec<u8>,
+    neg_delta_g2: Vec<u8>,
+    /// One point for the constant 1, then one per instance variable.
+    ic: Vec<Vec<u8>>,
+}
+
+impl SolidityVerifier {
+    /// Encode a verifying key.
+    pub fn new<E: Engine>(vk: &VerifyingKey<E>) -> Self {
+        let gvk = &vk.1;
+        Self {
+            scalar_modulus: get_modulus::<E::Fr>(),
+            alpha_g1: g1_to_evm::<E>(&gvk.alpha_g1),
+            neg_beta_g2: g2_to_evm::<E>(&-gvk.beta_g2),
+            neg_gamma_g2: g2_to_evm::<E>(&-gvk.gamma_g2),
+            neg_delta_g2: g2_to_evm::<E>(&-gvk.delta_g2),
+            ic: gvk.ic.iter().map(g1_to_evm::<E>).collect(),
+        }
+    }
+
+    /// The number of instance variables (field elements) the contract expects.
+    pub fn num_inputs(&self) -> usize {
+        self.ic.len() - 1
+    }
+
+    /// The source of a self-contained Solidity contract, named `name`, that verifies proofs.
+    pub fn contract(&self, name: &str) -> String {
+        format!(
+            r#"// SPDX-License-Identifier: MIT
+// Generated by CirC. Do not edit.
+pragma solidity ^0.8.4;
+
+/// A Groth16 verifier over BLS12-381, using the EIP-2537 precompiles.
+contract {name} {{
+    /// The scalar field modulus.
+    uint256 constant R = 0x{r};
+    uint256 constant NUM_INPUTS = {num_inputs};
+    uint256 constant G1_BYTES = {g1_bytes};
+    uint256 constant G2_BYTES = {g2_bytes};
+    uint256 constant PROOF_BYTES = {proof_bytes};
+
+    bytes constant ALPHA_G1 = hex"{alpha}";
+    bytes constant NEG_BETA_G2 = hex"{neg_beta}";
+    bytes constant NEG_GAMMA_G2 = hex"{neg_gamma}";
+    bytes constant NEG_DELTA_G2 = hex"{neg_delta}";
+    /// NUM_INPUTS + 1 G1 points: one for the constant 1, then one per input.
+    bytes constant IC = hex"{ic}";
+
+    /// `proof` is A (G1), B (G2), C (G1), in EIP-2537 encoding.
+    /// `input` is the R1CS instance; each entry must be less than R.
+    function verifyProof(bytes calldata proof, uint256[] calldata input) public view returns (bool) {{
+        require(proof.length == PROOF_BYTES, "bad proof length");
+        require(input.length == NUM_INPUTS, "bad input length");
+
+        // vk_x = IC[0] + sum_i input[i] * IC[i + 1], as one MSM.
+        bytes memory ic = IC;
+        bytes memory msmInput = new bytes((NUM_INPUTS + 1) * (G1_BYTES + 32));
+        for (uint256 i = 0; i <= NUM_INPUTS; i++) {{
+            uint256 s = 1;
+            if (i > 0) {{
+                s = input[i - 1];
+                require(s < R, "input not in the scalar field");
+            }}
+            assembly {{
+                let src := add(add(ic, 32), mul(i, {g1_bytes}))
+                let dst := add(add(msmInput, 32), mul(i, {msm_stride}))
+                mstore(dst, mload(src))
+                mstore(add(dst, 32), mload(add(src, 32)))
+                mstore(add(dst, 64), mload(add(src, 64)))
+                mstore(add(dst, 96), mload(add(src, 96)))
+                mstore(add(dst, {g1_bytes}), s)
+            }}
+        }}
+        (bool ok, bytes memory vkX) = address(0x0c).staticcall(msmInput);
+        require(ok && vkX.length == G1_BYTES, "G1 MSM failed");
+
+        // e(A, B) * e(alpha, -beta) * e(vk_x, -gamma) * e(C, -delta) == 1
+        bytes memory pairingInput = bytes.concat(
+            proof[0:G1_BYTES + G2_BYTES],
+            ALPHA_G1,
+            NEG_BETA_G2,
+            vkX,
+            NEG_GAMMA_G2,
+            proof[G1_BYTES + G2_BYTES:PROOF_BYTES],
+            NEG_DELTA_G2
+        );
+        bytes memory out;
+        (ok, out) = address(0x0f).staticcall(pairingInput);
+        return ok && out.length == 32 && abi.decode(out, (uint256)) == 1;
+    }}
+}}
+"#,
+            r = self.scalar_modulus.to_string_radix(16),
+            num_inputs = self.num_inputs(),
+            g1_bytes = EVM_G1_BYTES,
+            g2_bytes = EVM_G2_BYTES,
+            msm_stride = EVM_G1_BYTES + 32,
+            proof_bytes = PROOF_BYTES,
+            alpha = hex(&self.alpha_g1),
+            neg_beta = hex(&self.neg_beta_g2),
+            neg_gamma = hex(&self.neg_gamma_g2),
+            neg_delta = hex(&self.neg_delta_g2),
+            ic = hex(&self.ic.concat()),
+        )
+    }
+}
+
+/// Encode a proof as `A || B || C`.
+pub fn encode_proof<E: Engine>(pf: &Proof<E>) -> Vec<u8> {
+    let mut out = g1_to_evm::<E>(&pf.0.a);
+    out.extend(g2_to_evm::<E>(&pf.0.b));
+    out.extend(g1_to_evm::<E>(&pf.0.c));
+    debug_assert_eq!(out.len(), PROOF_BYTES);
+    out
+}
+
+/// Encode the R1CS instance for `inst` (a verifier input map) as EVM words.
+pub fn encode_instance(v_data: &VerifierData, inst: &HashMap<String, Value>) -> Vec<[u8; 32]> {
+    v_data
+        .eval(inst)
+        .into_iter()
+        .map(|f| int_to_word(&f.i()))
+        .collect()
+}
+
+/// ABI-encoded calldata for `verifyProof(proof, input)`.
+pub fn encode_calldata<E: Engine>(
+    vk: &VerifyingKey<E>,
+    inst: &HashMap<String, Value>,
+    pf: &Proof<E>,
+) -> Vec<u8> {
+    let proof = encode_proof(pf);
+    let input = encode_instance(&vk.0, inst);
+    let mut out = VERIFY_PROOF_SELECTOR.to_vec();
+    // head: offsets of the two dynamic arguments
+    out.extend(usize_to_word(2 * 32));
+    out.extend(usize_to_word(2 * 32 + 32 + PROOF_BYTES));
+    // tail: `proof` (already word-aligned), then `input`
+    out.extend(usize_to_word(PROOF_BYTES));
+    out.extend(proof);
+    out.extend(usize_to_word(input.len()));
+    for word in input {
+        out.extend(word);
+    }
+    out
+}
+
+/// Read a verifying key and write a verifier contract.
+pub fn write_contract_fs<E: Engine>(
+    vk_path: impl AsRef<Path>,
+    contract_path: impl AsRef<Path>,
+    name: &str,
+) -> std::io::Result<()> {
+    let vk: VerifyingKey<E> = deserialize_from_file(vk_path)?;
+    std::fs::write(contract_path, SolidityVerifier::new(&vk).contract(name))
+}
+
+/// Read a verifying key, an instance, and a proof; write hex calldata for `verifyProof`.
+pub fn write_calldata_fs<E: Engine>(
+    vk_path: impl AsRef<Path>,
+    instance_path: impl AsRef<Path>,
+    pf_path: impl AsRef<Path>,
+    calldata_path: impl AsRef<Path>,
+) -> std::io::Result<()> {
+    let vk: VerifyingKey<E> = deserialize_from_file(vk_path)?;
+    let inst = value_map_from_path(instance_path)?;
+    let pf: Proof<E> = deserialize_from_file(pf_path)?;
+    std::fs::write(
+        calldata_path,
+        format!("0x{}\n", hex(&encode_calldata(&vk, &inst, &pf))),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::super::proof::ProofSystem;
+    use super::super::super::trans::to_r1cs;
+    use super::super::Bellman;
+    use super::*;
+    use crate::cfg::CircCfg;
+    use crate::ir::term::text;
+    use bls12_381::{
+        multi_miller_loop, Bls12, G1Affine, G1Projective, G2Affine, G2Prepared, Gt, Scalar,
+    };
+
+    fn word(bytes: &[u8], i: usize) -> &[u8] {
+        &bytes[32 * i..32 * (i + 1)]
+    }
+
+    fn word_to_usize(w: &[u8]) -> usize {
+        Integer::from_digits(w, Order::Msf).to_usize().unwrap()
+    }
+
+    fn g1_from_evm(b: &[u8]) -> G1Affine {
+        assert_eq!(b.len(), EVM_G1_BYTES);
+        if b.iter().all(|x| *x == 0) {
+            return G1Affine::identity();
+        }
+        let mut raw = [0u8; 2 * FP_BYTES];
+        for (i, fp) in b.chunks(EVM_FP_BYTES).enumerate() {
+            assert!(fp[..EVM_FP_BYTES - FP_BYTES].iter().all(|x| *x == 0));
+            raw[i * FP_BYTES..(i + 1) * FP_BYTES].copy_from_slice(&fp[EVM_FP_BYTES - FP_BYTES..]);
+        }
+        G1Affine::from_uncompressed(&raw).unwrap()
+    }
+
+    fn g2_from_evm(b: &[u8]) -> G2Affine {
+        assert_eq!(b.len(), EVM_G2_BYTES);
+        if b.iter().all(|x| *x == 0) {
+            return G2Affine::identity();
+        }
+        let mut raw = [0u8; 4 * FP_BYTES];
+        // undo the c0/c1 swap
+        for (i, fp) in b.chunks(EVM_FP_BYTES).enumerate() {
+            let j = i ^ 1;
+            raw[j * FP_BYTES..(j + 1) * FP_BYTES].copy_from_slice(&fp[EVM_FP_BYTES - FP_BYTES..]);
+        }
+        G2Affine::from_uncompressed(&raw).unwrap()
+    }
+
+    /// A model of the generated contract: decode `calldata` and run the contract's checks.
+    fn contract_accepts(v: &SolidityVerifier, calldata: &[u8]) -> bool {
+        assert_eq!(&calldata[..4], &VERIFY_PROOF_SELECTOR);
+        let args = &calldata[4..];
+        let proof_off = word_to_usize(word(args, 0));
+        let input_off = word_to_usize(word(args, 1));
+        let proof_len = word_to_usize(&args[proof_off..proof_off + 32]);
+        if proof_len != PROOF_BYTES {
+            return false;
+        }
+        let proof = &args[proof_off + 32..proof_off + 32 + proof_len];
+        let input_len = word_to_usize(&args[input_off..input_off + 32]);
+        if input_len != v.num_inputs() {
+            return false;
+        }
+        let inputs = &args[input_off + 32..input_off + 32 + 32 * input_len];
+
+        let mut vk_x = G1Projective::from(g1_from_evm(&v.ic[0]));
+        for (i, ic) in v.ic[1..].iter().enumerate() {
+            let s = Integer::from_digits(word(inputs, i), Order::Msf);
+            if s >= v.scalar_modulus {
+                return false;
+            }
+            let mut le = [0u8; 32];
+            le.copy_from_slice(word(inputs, i));
+            le.reverse();
+            let s = Scalar::from_bytes(&le).unwrap();
+            vk_x += G1Projective::from(g1_from_evm(ic)) * s;
+        }
+        let a = g1_from_evm(&proof[..EVM_G1_BYTES]);
+        let b = g2_from_evm(&proof[EVM_G1_BYTES..EVM_G1_BYTES + EVM_G2_BYTES]);
+        let c = g1_from_evm(&proof[EVM_G1_BYTES + EVM_G2_BYTES..]);
+        let alpha = g1_from_evm(&v.alpha_g1);
+        let vk_x = G1Affine::from(vk_x);
+        let neg_beta = G2Prepared::from(g2_from_evm(&v.neg_beta_g2));
+        let neg_gamma = G2Prepared::from(g2_from_evm(&v.neg_gamma_g2));
+        let neg_delta = G2Prepared::from(g2_from_evm(&v.neg_delta_g2));
+        let b = G2Prepared::from(b);
+        let result = multi_miller_loop(&[
+            (&a, &b),
+            (&alpha, &neg_beta),
+            (&vk_x, &neg_gamma),
+            (&c, &neg_delta),
+        ])
+        .final_exponentiation();
+        result == Gt::identity()
+    }
+
+    #[test]
+    fn calldata_matches_bellman() {
+        let cs = text::parse_computation(
+            b"
+            (computation
+                (metadata
+                    (parties P)
+                    (inputs
+                        (a (mod 52435875175126190479447740508185965837690552500527637822603658699938581184513) (party 0))
+                        (b (mod 52435875175126190479447740508185965837690552500527637822603658699938581184513) (party 0))
+                        (c (mod 52435875175126190479447740508185965837690552500527637822603658699938581184513))
+                        (d (mod 52435875175126190479447740508185965837690552500527637822603658699938581184513))
+                    )
+                    (commitments)
+                )
+                (precompute () () (tuple))
+                (and
+                    (= (* a b) c)
+                    (= (+ a b) d)
+                )
+            )",
+        );
+        let p_input = text::parse_value_map(
+            b"
+            (set_default_modulus 52435875175126190479447740508185965837690552500527637822603658699938581184513
+            (let (
+                (a #f3)
+                (b #f5)
+                (c #f15)
+                (d #f8)
+            ) false))",
+        );
+        let v_input = text::parse_value_map(
+            b"
+            (set_default_modulus 52435875175126190479447740508185965837690552500527637822603658699938581184513
+            (let (
+                (c #f15)
+                (d #f8)
+            ) false))",
+        );
+        let bad_v_input = text::parse_value_map(
+            b"
+            (set_default_modulus 52435875175126190479447740508185965837690552500527637822603658699938581184513
+            (let (
+                (c #f15)
+                (d #f9)
+            ) false))",
+        );
+        let r1cs = to_r1cs(&cs, &CircCfg::default());
+        let (p_data, v_data) = r1cs.finalize(&cs);
+        let (pk, vk) = Bellman::<Bls12>::setup(p_data, v_data);
+        let pf = Bellman::<Bls12>::prove(&pk, &p_input);
+        let verifier = SolidityVerifier::new(&vk);
+        assert_eq!(verifier.num_inputs(), 2);
+
+        let contract = verifier.contract("Verifier");
+        assert!(contract.contains("contract Verifier {"));
+        assert!(contract.contains(&hex(&verifier.ic.concat())));
+        assert!(contract.contains(&hex(&verifier.neg_delta_g2)));
+
+        for inst in [&v_input, &bad_v_input] {
+            let calldata = encode_calldata(&vk, inst, &pf);
+            assert_eq!(calldata.len(), 4 + 32 * 4 + PROOF_BYTES + 32 * 2);
+            assert_eq!(
+                contract_accepts(&verifier, &calldata),
+                Bellman::<Bls12>::verify(&vk, inst, &pf)
+            );
+        }
+        assert!(contract_accepts(
+            &verifier,
+            &encode_calldata(&vk, &v_input, &pf)
+        ));
+    }
+
+    #[test]
+    fn point_encoding_roundtrip() {
+        let g1 = G1Affine::from(G1Projective::generator() * Scalar::from(7));
+        let g2 = G2Affine::generator();
+        assert_eq!(g1_from_evm(&g1_to_evm::<Bls12>(&g1)), g1);
+        assert_eq!(g2_from_evm(&g2_to_evm::<Bls12>(&g2)), g2);
+        assert_eq!(
+            g1_to_evm::<Bls12>(&G1Affine::identity()),
+            vec![0; EVM_G1_BYTES]
+        );
+    }
+}
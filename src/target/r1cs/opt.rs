@@ -0,0 +1,576 @@
+//! Optimizations over R1CS
+use fxhash::{FxHashMap as HashMap, FxHashSet as HashSet};
+use log::debug;
+
+use std::cmp::Reverse;
+use std::collections::hash_map::Entry;
+use std::collections::BinaryHeap;
+
+use super::*;
+use crate::cfg::CircCfg;
+use crate::util::once::OnceQueue;
+
+struct LinReducer {
+    r1cs: R1cs,
+    uses: HashMap<Var, HashSet<usize>>,
+    queue: OnceQueue<usize>,
+    /// The maximum size LC (number of non-constant monomials)
+    /// that will be used for propagation
+    lc_size_thresh: usize,
+    stats: LinReductionStats,
+}
+
+/// Counts of variables eliminated by [reduce_linearities_with_stats].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LinReductionStats {
+    /// Eliminated by substituting linear constraints under the size threshold
+    pub thresh_elims: usize,
+    /// Eliminated by Gaussian elimination
+    pub gauss_elims: usize,
+}
+
+impl LinReducer {
+    fn new(mut r1cs: R1cs, lc_size_thresh: usize) -> Self {
+        let uses = LinReducer::gen_uses(&r1cs);
+        let queue = (0..r1cs.constraints.len()).collect::<OnceQueue<usize>>();
+        for c in &mut r1cs.constraints {
+            normalize(c);
+        }
+        Self {
+            r1cs,
+            uses,
+            queue,
+            lc_size_thresh,
+            stats: Default::default(),
+        }
+    }
+
+    // generate a new uses hash
+    fn gen_uses(r1cs: &R1cs) -> HashMap<Var, HashSet<usize>> {
+        let mut uses: HashMap<Var, HashSet<usize>> =
+            HashMap::with_capacity_and_hasher(r1cs.num_vars(), Default::default());
+        let mut add = |i: usize, y: &Lc| {
+            for x in y.monomials.keys() {
+                uses.get_mut(x).map(|m| m.insert(i)).or_else(|| {
+                    let mut m: HashSet<usize> = Default::default();
+                    m.insert(i);
+                    uses.insert(*x, m);
+                    None
+                });
+            }
+        };
+        for (i, (a, b, c)) in r1cs.constraints.iter().enumerate() {
+            add(i, a);
+            add(i, b);
+            add(i, c);
+        }
+        uses
+    }
+
+    /// Substitute `val` for `var` in constraint with id `con_id`.
+    /// Updates uses conservatively (not precisely)
+    /// Returns whether a sub happened.
+    fn sub_in(&mut self, var: Var, val: &Lc, con_id: usize) -> bool {
+        let (a, b, c) = &mut self.r1cs.constraints[con_id];
+        let uses = &mut self.uses;
+        let mut do_in = |a: &mut Lc| {
+            if let Some(sc) = a.monomials.remove(&var) {
+                assert_eq!(&a.modulus, &val.modulus);
+                a.constant += sc.clone() * &val.constant;
+                let tot = a.monomials.len() + val.monomials.len();
+                if tot > a.monomials.capacity() {
+                    a.monomials.reserve(tot - a.monomials.capacity());
+                }
+                for (i, v) in &val.monomials {
+                    match a.monomials.entry(*i) {
+                        Entry::Occupied(mut e) => {
+                            let m = e.get_mut();
+                            *m += sc.clone() * v;
+                            if e.get().is_zero() {
+                                uses.get_mut(i).unwrap().remove(&con_id);
+                                e.remove_entry();
+                            }
+                        }
+                        Entry::Vacant(e) => {
+                            e.insert(sc.clone() * v);
+                            uses.get_mut(i).unwrap().insert(con_id);
+                        }
+                    }
+                }
+                true
+            } else {
+                false
+            }
+        };
+        let change_a = do_in(a);
+        let change_b = do_in(b);
+        let change_c = do_in(c);
+        let change = change_a || change_b || change_c;
+        self.uses.get_mut(&var).unwrap().remove(&con_id);
+        if change {
+            normalize(&mut self.r1cs.constraints[con_id]);
+        }
+        change
+    }
+
+    fn clear_constraint(&mut self, i: usize) {
+        for v in self.r1cs.constraints[i].0.monomials.keys() {
+            self.uses.get_mut(v).unwrap().remove(&i);
+        }
+        self.r1cs.constraints[i].0.clear();
+        for v in self.r1cs.constraints[i].1.monomials.keys() {
+            self.uses.get_mut(v).unwrap().remove(&i);
+        }
+        self.r1cs.constraints[i].1.clear();
+        for v in self.r1cs.constraints[i].2.monomials.keys() {
+            self.uses.get_mut(v).unwrap().remove(&i);
+        }
+        self.r1cs.constraints[i].2.clear();
+    }
+
+    /// Eliminate `var`, which constraint `con_id` sets to `lc`.
+    ///
+    /// Returns the constraints that changed.
+    fn eliminate(&mut self, var: Var, lc: &Lc, con_id: usize) -> Vec<usize> {
+        debug!(
+            "Elim: {} -> {}",
+            self.r1cs.idx_to_sig.get_fwd(&var).unwrap(),
+            self.r1cs.format_lc(lc)
+        );
+        self.clear_constraint(con_id);
+        let mut changed = Vec::new();
+        for use_id in self.uses[&var].clone() {
+            if self.sub_in(var, lc, use_id) {
+                changed.push(use_id);
+            }
+        }
+        self.r1cs.idx_to_sig.remove_fwd(&var);
+        self.r1cs.terms.remove(&var);
+        debug_assert_eq!(0, self.uses[&var].len());
+        changed
+    }
+
+    fn is_linear(&self, con_id: usize) -> bool {
+        let (a, b, _) = &self.r1cs.constraints[con_id];
+        a.is_zero() || b.is_zero()
+    }
+
+    fn run(&mut self) {
+        while let Some(con_id) = self.queue.pop() {
+            if let Some((var, lc)) = as_linear_sub(&self.r1cs.constraints[con_id], &self.r1cs) {
+                if lc.monomials.len() < self.lc_size_thresh {
+                    for use_id in self.eliminate(var, &lc, con_id) {
+                        if self.is_linear(use_id) {
+                            self.queue.push(use_id);
+                        }
+                    }
+                    self.stats.thresh_elims += 1;
+                }
+            }
+        }
+    }
+
+    /// The variables that linear constraint `con_id` could be solved for, with the Markowitz
+    /// estimate of the fill-in of eliminating each.
+    fn pivot_candidates(&self, con_id: usize) -> Vec<(usize, Var)> {
+        if !self.is_linear(con_id) {
+            return Vec::new();
+        }
+        let row = &self.r1cs.constraints[con_id].2.monomials;
+        row.keys()
+            .filter(|v| self.r1cs.can_eliminate(**v))
+            .map(|v| {
+                let other_uses = self.uses[v].len().saturating_sub(1);
+                ((row.len() - 1) * other_uses, *v)
+            })
+            .collect()
+    }
+
+    /// Sparse Gaussian elimination over all linear constraints.
+    ///
+    /// Pivots are chosen greedily by (Markowitz) fill-in estimate, and pivots whose estimate
+    /// exceeds `max_fill` are never taken. Estimates are maintained lazily: a popped pivot is
+    /// re-scored, and re-queued if its estimate grew.
+    fn gauss(&mut self, max_fill: usize) {
+        let mut heap: BinaryHeap<Reverse<(usize, usize, Var)>> = BinaryHeap::new();
+        for con_id in 0..self.r1cs.constraints.len() {
+            for (fill, var) in self.pivot_candidates(con_id) {
+                if fill <= max_fill {
+                    heap.push(Reverse((fill, con_id, var)));
+                }
+            }
+        }
+        while let Some(Reverse((fill, con_id, var))) = heap.pop() {
+            let current = self
+                .pivot_candidates(con_id)
+                .into_iter()
+                .find(|(_, v)| *v == var);
+            match current {
+                None => continue,
+                Some((new_fill, _)) if new_fill > fill => {
+                    if new_fill <= max_fill {
+                        heap.push(Reverse((new_fill, con_id, var)));
+                    }
+                    continue;
+                }
+                Some(_) => {}
+            }
+            let lc = solve_for(&self.r1cs.constraints[con_id].2, var);
+            for use_id in self.eliminate(var, &lc, con_id) {
+                for (fill, var) in self.pivot_candidates(use_id) {
+                    if fill <= max_fill {
+                        heap.push(Reverse((fill, use_id, var)));
+                    }
+                }
+            }
+            self.stats.gauss_elims += 1;
+        }
+    }
+
+    fn finish(mut self) -> (R1cs, LinReductionStats) {
+        self.r1cs.constraints.retain(|c| !constantly_true(c));
+        (self.r1cs, self.stats)
+    }
+}
+
+/// Given that `c` is zero, solve for `var`.
+fn solve_for(c: &Lc, var: Var) -> Lc {
+    let mut lc = c.clone();
+    let v = lc.monomials.remove(&var).unwrap();
+    lc *= v.recip();
+    -lc
+}
+
+fn as_linear_sub((a, b, c): &(Lc, Lc, Lc), r1cs: &R1cs) -> Option<(Var, Lc)> {
+    if a.is_zero() || b.is_zero() {
+        c.monomials
+            .keys()
+            .find(|i| r1cs.can_eliminate(**i))
+            .map(|i| (*i, solve_for(c, *i)))
+    } else {
+        None
+    }
+}
+
+fn normalize((a, b, c): &mut (Lc, Lc, Lc)) {
+    match (a.as_const(), b.as_const()) {
+        (Some(ac), _) => {
+            *c -= &(b.take() * ac);
+            a.clear();
+        }
+        (_, Some(bc)) => {
+            *c -= &(a.take() * bc);
+            b.clear();
+        }
+        _ => {}
+    }
+}
+
+fn constantly_true((a, b, c): &(Lc, Lc, Lc)) -> bool {
+    match (a.as_const(), b.as_const(), c.as_const()) {
+        (Some(x), Some(y), Some(z)) => (x.clone() * y - z).is_zero(),
+        _ => false,
+    }
+}
+
+/// Attempt to shrink this system by reducing linearities.
+///
+/// ## Configuration (from `cfg.r1cs`)
+///
+///   * `lc_elim_thresh`: only LCs with fewer non-constant monomials than this are used for
+///     propagation.
+///   * `lc_elim_gauss`: whether to follow up with Gaussian elimination over all linear
+///     constraints, pivoting only when the fill-in estimate is at most `lc_elim_gauss_fill`.
+pub fn reduce_linearities(r1cs: R1cs, cfg: &CircCfg) -> R1cs {
+    let (r1cs, stats) = reduce_linearities_with_stats(r1cs, cfg);
+    debug!("Linear reduction: {stats:?}");
+    r1cs
+}
+
+/// Like [reduce_linearities], but also report how many variables were eliminated, and how.
+pub fn reduce_linearities_with_stats(r1cs: R1cs, cfg: &CircCfg) -> (R1cs, LinReductionStats) {
+    let mut reducer = LinReducer::new(r1cs, cfg.r1cs.lc_elim_thresh);
+    reducer.run();
+    if cfg.r1cs.lc_elim_gauss {
+        reducer.gauss(cfg.r1cs.lc_elim_gauss_fill);
+    }
+    reducer.finish()
+}
+
+/// A linear combination as sorted monomials and a constant.
+type CanonicalLc = (Vec<(Var, FieldV)>, FieldV);
+
+/// A canonical, hashable form of a linear combination, scaled so that the coefficient of its
+/// least variable is one. Returns the form and the factor it was scaled by.
+fn canonical_lc(lc: &Lc) -> Option<(CanonicalLc, FieldV)> {
+    let lead = lc.monomials.keys().min()?;
+    let scale = lc.monomials[lead].clone().recip();
+    let mut monomials: Vec<(Var, FieldV)> = lc
+        .monomials
+        .iter()
+        .map(|(v, c)| (*v, c.clone() * &scale))
+        .collect();
+    monomials.sort();
+    Some(((monomials, lc.constant.clone() * &scale), scale))
+}
+
+/// A canonical form of a constraint, equal for constraints that are scalar multiples of each other
+/// (treating `A * B` and `B * A` as the same).
+#[derive(PartialEq, Eq, Hash)]
+enum CanonicalConstraint {
+    Linear(CanonicalLc),
+    Quadratic(CanonicalLc, CanonicalLc, CanonicalLc),
+}
+
+fn canonical_constraint((a, b, c): &(Lc, Lc, Lc)) -> Option<CanonicalConstraint> {
+    if a.is_zero() || b.is_zero() {
+        Some(CanonicalConstraint::Linear(canonical_lc(c)?.0))
+    } else {
+        let (a, a_scale) = canonical_lc(a)?;
+        let (b, b_scale) = canonical_lc(b)?;
+        let mut c = c.clone();
+        c *= a_scale * &b_scale;
+        let c = (
+            {
+                let mut m: Vec<(Var, FieldV)> = c.monomials.into_iter().collect();
+                m.sort();
+                m
+            },
+            c.constant,
+        );
+        let (a, b) = if a <= b { (a, b) } else { (b, a) };
+        Some(CanonicalConstraint::Quadratic(a, b, c))
+    }
+}
+
+/// Remove redundancy left over after lowering and [reduce_linearities]:
+/// * constraints that are scalar multiples of an earlier constraint (up to swapping `A` and `B`)
+/// * final-round witness variables that appear in no constraint (and their terms)
+///
+/// Instances, committed witnesses, round witnesses, and challenges are always kept, so the round
+/// structure is unchanged. Variables are renumbered compactly when the system is finalized.
+pub fn remove_redundancies(mut r1cs: R1cs) -> R1cs {
+    for c in &mut r1cs.constraints {
+        normalize(c);
+    }
+    let n_constraints = r1cs.constraints.len();
+    let mut seen: HashSet<CanonicalConstraint> = Default::default();
+    r1cs.constraints.retain(|c| {
+        !constantly_true(c)
+            && match canonical_constraint(c) {
+                Some(canon) => seen.insert(canon),
+                None => true,
+            }
+    });
+    let mut used: HashSet<Var> = Default::default();
+    for (a, b, c) in &r1cs.constraints {
+        used.extend(
+            a.monomials
+                .keys()
+                .chain(b.monomials.keys())
+                .chain(c.monomials.keys()),
+        );
+    }
+    let dead: Vec<Var> = r1cs
+        .terms
+        .keys()
+        .filter(|v| r1cs.can_eliminate(**v) && !used.contains(*v))
+        .copied()
+        .collect();
+    for v in &dead {
+        r1cs.idx_to_sig.remove_fwd(v);
+        r1cs.terms.remove(v);
+    }
+    debug!(
+        "Removed {} redundant constraints and {} dead variables",
+        n_constraints - r1cs.constraints.len(),
+        dead.len()
+    );
+    r1cs
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    use fxhash::FxHashMap;
+    use quickcheck::{Arbitrary, Gen};
+    use quickcheck_macros::quickcheck;
+    use rand::SeedableRng;
+
+    #[derive(Clone, Debug)]
+    pub struct SatR1cs(R1cs, FxHashMap<String, Value>);
+
+    impl Arbitrary for SatR1cs {
+        fn arbitrary(g: &mut Gen) -> Self {
+            let m = 101;
+            let field = FieldT::from(Integer::from(m));
+            let n_vars = g.size() + 1;
+            let vars: Vec<_> = (0..n_vars).map(|i| format!("v{i}")).collect();
+            let mut values: FxHashMap<String, Value> = Default::default();
+            let mut var_values: FxHashMap<Var, FieldV> = Default::default();
+            let mut r1cs = R1cs::new(field.clone(), Default::default());
+            let mut rng = rand::rngs::StdRng::seed_from_u64(u64::arbitrary(g));
+            for v in &vars {
+                let var = r1cs.add_var(
+                    v.clone(),
+                    leaf_term(Op::Var(v.clone(), Sort::Field(field.clone()))),
+                    VarType::FinalWit,
+                );
+                let val = field.random_v(&mut rng);
+                var_values.insert(var, val.clone());
+                values.insert(v.into(), Value::Field(val));
+            }
+            for _ in 0..(2 * g.size()) {
+                let ac: isize = <isize as Arbitrary>::arbitrary(g) % m;
+                let a = if Arbitrary::arbitrary(g) {
+                    r1cs.signal_lc(g.choose(&vars[..]).unwrap())
+                } else {
+                    r1cs.zero()
+                } + ac;
+                let bc: isize = <isize as Arbitrary>::arbitrary(g) % m;
+                let b = if Arbitrary::arbitrary(g) {
+                    r1cs.signal_lc(g.choose(&vars[..]).unwrap())
+                } else {
+                    r1cs.zero()
+                } + bc;
+                let cc: isize = <isize as Arbitrary>::arbitrary(g) % m;
+                let mut c = if Arbitrary::arbitrary(g) {
+                    r1cs.signal_lc(g.choose(&vars[..]).unwrap())
+                } else {
+                    r1cs.zero()
+                } + cc;
+                let off = r1cs.eval(&a, &var_values) * r1cs.eval(&b, &var_values)
+                    - r1cs.eval(&c, &var_values);
+                c += &off;
+                r1cs.constraint(a, b, c);
+            }
+            SatR1cs(r1cs, values)
+        }
+        fn shrink(&self) -> Box<dyn Iterator<Item = Self>> {
+            let c = self.clone();
+            Box::new((0..self.0.constraints.len()).rev().map(move |i| {
+                let mut this = c.clone();
+                this.0.constraints.truncate(i);
+                this
+            }))
+        }
+    }
+
+    #[quickcheck]
+    fn random(SatR1cs(r1cs, values): SatR1cs) {
+        let r1cs2 = reduce_linearities(r1cs, &CircCfg::default());
+        r1cs2.check_all(&values);
+    }
+
+    fn gauss_cfg(lc_elim_thresh: usize) -> CircCfg {
+        let mut opt = crate::cfg::CircOpt::default();
+        opt.r1cs.lc_elim_thresh = lc_elim_thresh;
+        opt.r1cs.lc_elim_gauss = true;
+        CircCfg::from(opt)
+    }
+
+    #[quickcheck]
+    fn random_gauss(SatR1cs(r1cs, values): SatR1cs) {
+        let r1cs2 = reduce_linearities(r1cs, &gauss_cfg(1));
+        r1cs2.check_all(&values);
+    }
+
+    #[test]
+    fn gauss_solves_jointly() {
+        let field = FieldT::from(Integer::from(101));
+        let mut r1cs = R1cs::new(field.clone(), Default::default());
+        let mut values: FxHashMap<String, Value> = Default::default();
+        for (name, val, ty) in [
+            ("x", 2, VarType::FinalWit),
+            ("y", 1, VarType::FinalWit),
+            ("z", 2, VarType::FinalWit),
+            ("w", 3, VarType::Inst),
+        ] {
+            r1cs.add_var(
+                name.into(),
+                leaf_term(Op::Var(name.into(), Sort::Field(field.clone()))),
+                ty,
+            );
+            values.insert(name.into(), Value::Field(field.new_v(val)));
+        }
+        // x + y = w
+        let c = r1cs.signal_lc("x") + &r1cs.signal_lc("y") - &r1cs.signal_lc("w");
+        r1cs.constraint(r1cs.zero(), r1cs.zero(), c);
+        // x - y = 1
+        let c = r1cs.signal_lc("x") - &r1cs.signal_lc("y") - 1;
+        r1cs.constraint(r1cs.zero(), r1cs.zero(), c);
+        // x * y = z
+        r1cs.constraint(
+            r1cs.signal_lc("x"),
+            r1cs.signal_lc("y"),
+            r1cs.signal_lc("z"),
+        );
+        r1cs.check_all(&values);
+
+        // Every linear constraint has two monomials: too big for a threshold of 1.
+        let (thresh_only, stats) = reduce_linearities_with_stats(r1cs.clone(), &{
+            let mut opt = crate::cfg::CircOpt::default();
+            opt.r1cs.lc_elim_thresh = 1;
+            CircCfg::from(opt)
+        });
+        assert_eq!(stats.thresh_elims + stats.gauss_elims, 0);
+        assert_eq!(thresh_only.constraints().len(), 3);
+
+        let (gauss, stats) = reduce_linearities_with_stats(r1cs, &gauss_cfg(1));
+        assert_eq!(stats.thresh_elims, 0);
+        assert_eq!(stats.gauss_elims, 2);
+        assert_eq!(gauss.constraints().len(), 1);
+        assert!(gauss.idx_to_sig.contains_key(&Var::new(VarType::Inst, 0)));
+        gauss.check_all(&values);
+    }
+
+    #[quickcheck]
+    fn random_remove_redundancies(SatR1cs(r1cs, values): SatR1cs) {
+        let r1cs2 = remove_redundancies(reduce_linearities(r1cs, &CircCfg::default()));
+        r1cs2.check_all(&values);
+    }
+
+    #[test]
+    fn redundancies() {
+        let field = FieldT::from(Integer::from(101));
+        let mut r1cs = R1cs::new(field.clone(), Default::default());
+        let mut values: FxHashMap<String, Value> = Default::default();
+        for (name, val, ty) in [
+            ("x", 2, VarType::FinalWit),
+            ("y", 3, VarType::FinalWit),
+            ("z", 6, VarType::FinalWit),
+            ("dead", 7, VarType::FinalWit),
+            ("w", 5, VarType::Inst),
+            ("unused_inst", 0, VarType::Inst),
+        ] {
+            r1cs.add_var(
+                name.into(),
+                leaf_term(Op::Var(name.into(), Sort::Field(field.clone()))),
+                ty,
+            );
+            values.insert(name.into(), Value::Field(field.new_v(val)));
+        }
+        let lc = |r1cs: &R1cs, name: &str| r1cs.signal_lc(name);
+        // x * y = z, three ways
+        r1cs.constraint(lc(&r1cs, "x"), lc(&r1cs, "y"), lc(&r1cs, "z"));
+        r1cs.constraint(lc(&r1cs, "y"), lc(&r1cs, "x"), lc(&r1cs, "z"));
+        r1cs.constraint(lc(&r1cs, "x") * 2, lc(&r1cs, "y") * 3, lc(&r1cs, "z") * 6);
+        // x + y = w, two ways
+        let c = lc(&r1cs, "x") + &lc(&r1cs, "y") - &lc(&r1cs, "w");
+        r1cs.constraint(r1cs.zero(), r1cs.zero(), c.clone());
+        r1cs.constraint(r1cs.zero(), r1cs.zero(), c * -4);
+        // x + 1 = y: not a duplicate
+        let c = lc(&r1cs, "x") + 1 - &lc(&r1cs, "y");
+        r1cs.constraint(r1cs.zero(), r1cs.zero(), c);
+        r1cs.check_all(&values);
+
+        let r1cs = remove_redundancies(r1cs);
+        assert_eq!(r1cs.constraints().len(), 3);
+        assert!(r1cs.idx_to_sig.get_rev("dead").is_none());
+        assert!(r1cs.idx_to_sig.get_rev("unused_inst").is_some());
+        assert!(r1cs.idx_to_sig.get_rev("z").is_some());
+        r1cs.check_all(&values);
+    }
+}
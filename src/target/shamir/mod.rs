@@ -0,0 +1,193 @@
+//! Honest-majority N-party MPC from Shamir secret sharing
+//!
+//! We lower a computation to an arithmetic circuit over a prime field. Addition, subtraction, and
+//! scaling are local to each party; each multiplication costs one round of communication. With
+//! `n` parties, values are shared with polynomials of degree `t = (n - 1) / 2`, so the protocol is
+//! private against any `t` (semi-honest) corrupt parties.
+//!
+//! Booleans are field elements that are 0 or 1. Bit-vectors are vectors of booleans, least
+//! significant first. Field elements are themselves.
+//!
+//! See [sim] for a simulator, which runs all parties in one process.
+
+use crate::ir::term::*;
+
+use circ_fields::{FieldT, FieldV};
+use fxhash::FxHashMap as HashMap;
+use rug::Integer;
+
+pub mod sim;
+pub mod trans;
+
+/// A gate. Each gate defines one wire: the one with the gate's index.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Gate {
+    /// Element `1` of input `0` (a bit, for bit-vectors)
+    Input(usize, usize),
+    /// A constant
+    Const(FieldV),
+    /// `a + b`
+    Add(usize, usize),
+    /// `a - b`
+    Sub(usize, usize),
+    /// `c * a`
+    Scale(FieldV, usize),
+    /// `a * b`
+    Mul(usize, usize),
+}
+
+/// An input of the circuit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Input {
+    /// The IR name
+    pub name: String,
+    /// The IR sort
+    pub sort: Sort,
+    /// The party that knows it, or [None] if it is public
+    pub party: Option<PartyId>,
+}
+
+/// An output of the circuit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output {
+    /// The IR sort
+    pub sort: Sort,
+    /// The wires holding its elements
+    pub wires: Vec<usize>,
+}
+
+/// An arithmetic circuit for N-party MPC
+#[derive(Debug, Clone)]
+pub struct Circuit {
+    /// The field
+    pub field: FieldT,
+    /// The number of parties
+    pub num_parties: usize,
+    /// The inputs
+    pub inputs: Vec<Input>,
+    /// The gates, in evaluation order
+    pub gates: Vec<Gate>,
+    /// The outputs
+    pub outputs: Vec<Output>,
+}
+
+/// The number of field elements in a value of sort `s`.
+fn sort_elems(s: &Sort) -> usize {
+    match s {
+        Sort::Bool | Sort::Field(_) => 1,
+        Sort::BitVector(w) => *w,
+        _ => panic!(
+            "Shamir circuits only hold booleans, bit-vectors, and field elements, not {}",
+            s
+        ),
+    }
+}
+
+fn encode(field: &FieldT, v: &Value) -> Vec<FieldV> {
+    match v {
+        Value::Bool(b) => vec![field.new_v(*b as u8)],
+        Value::BitVector(bv) => (0..bv.width())
+            .map(|i| field.new_v(bv.uint().get_bit(i as u32) as u8))
+            .collect(),
+        Value::Field(f) => vec![f.as_ty_ref(field)],
+        _ => panic!(
+            "Shamir circuits only hold booleans, bit-vectors, and field elements, not {}",
+            v
+        ),
+    }
+}
+
+fn decode(s: &Sort, elems: &[FieldV]) -> Value {
+    let bit = |e: &FieldV| {
+        assert!(e.is_zero() || e.is_one(), "Non-boolean {} in a boolean", e);
+        e.is_one()
+    };
+    match s {
+        Sort::Bool => Value::Bool(bit(&elems[0])),
+        Sort::BitVector(w) => {
+            let mut i = Integer::from(0);
+            for (j, e) in elems.iter().enumerate() {
+                i.set_bit(j as u32, bit(e));
+            }
+            Value::BitVector(BitVector::new(i, *w))
+        }
+        Sort::Field(f) => Value::Field(elems[0].as_ty_ref(f)),
+        _ => unreachable!(),
+    }
+}
+
+impl Circuit {
+    /// The number of multiplication gates.
+    pub fn mul_count(&self) -> usize {
+        self.gates
+            .iter()
+            .filter(|g| matches!(g, Gate::Mul(..)))
+            .count()
+    }
+
+    /// The multiplicative depth: the number of rounds that multiplications take.
+    pub fn mul_depth(&self) -> usize {
+        let mut depth: Vec<usize> = Vec::with_capacity(self.gates.len());
+        for g in &self.gates {
+            let d = match g {
+                Gate::Input(..) | Gate::Const(_) => 0,
+                Gate::Add(a, b) | Gate::Sub(a, b) => depth[*a].max(depth[*b]),
+                Gate::Scale(_, a) => depth[*a],
+                Gate::Mul(a, b) => depth[*a].max(depth[*b]) + 1,
+            };
+            depth.push(d);
+        }
+        self.outputs
+            .iter()
+            .flat_map(|o| o.wires.iter().map(|w| depth[*w]))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The field elements of each input, from IR values.
+    pub fn encode_inputs(&self, values: &HashMap<String, Value>) -> Vec<Vec<FieldV>> {
+        self.inputs
+            .iter()
+            .map(|i| {
+                let v = values
+                    .get(&i.name)
+                    .unwrap_or_else(|| panic!("Missing input {}", i.name));
+                let elems = encode(&self.field, v);
+                assert_eq!(elems.len(), sort_elems(&i.sort), "Bad value for {}", i.name);
+                elems
+            })
+            .collect()
+    }
+
+    /// IR values for the outputs, from their field elements.
+    pub fn decode_outputs(&self, outputs: &[Vec<FieldV>]) -> Vec<Value> {
+        self.outputs
+            .iter()
+            .zip(outputs)
+            .map(|(o, elems)| decode(&o.sort, elems))
+            .collect()
+    }
+
+    /// Evaluate the circuit in the clear.
+    pub fn eval(&self, values: &HashMap<String, Value>) -> Vec<Value> {
+        let inputs = self.encode_inputs(values);
+        let mut wires: Vec<FieldV> = Vec::with_capacity(self.gates.len());
+        for g in &self.gates {
+            let v = match g {
+                Gate::Input(i, j) => inputs[*i][*j].clone(),
+                Gate::Const(c) => c.clone(),
+                Gate::Add(a, b) => wires[*a].clone() + &wires[*b],
+                Gate::Sub(a, b) => wires[*a].clone() - &wires[*b],
+                Gate::Scale(c, a) => c.clone() * &wires[*a],
+                Gate::Mul(a, b) => wires[*a].clone() * &wires[*b],
+            };
+            wires.push(v);
+        }
+        let outputs: Vec<Vec<FieldV>> = self
+            .outputs
+            .iter()
+            .map(|o| o.wires.iter().map(|w| wires[*w].clone()).collect())
+            .collect();
+        self.decode_outputs(&outputs)
+    }
+}
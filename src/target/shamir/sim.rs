@@ -0,0 +1,264 @@
+//! An in-process simulator for Shamir-based MPC
+//!
+//! Runs all parties of the BGW protocol (semi-honest, honest majority) in one process. Each wire
+//! holds one share per party; party `i` holds the evaluation of the wire's polynomial at `i + 1`.
+//!
+//! * inputs: the owner of a private input shares it with a random degree-`t` polynomial; public
+//!   inputs and constants are degree-0 sharings, which need no messages.
+//! * additions, subtractions, and scaling are local.
+//! * multiplications: each party multiplies its shares locally, giving a degree-`2t` sharing.
+//!   The first `2t + 1` parties reshare their products with degree-`t` polynomials, and every
+//!   party recombines the shares it receives with Lagrange coefficients.
+//! * outputs: every party sends its share to every other party. We reconstruct each output from
+//!   two different sets of `t + 1` parties, and check that they agree.
+//!
+//! Along the way, we count multiplications, rounds, and messages (one field element each).
+
+use super::{Circuit, Gate};
+use crate::ir::term::*;
+
+use circ_fields::{FieldT, FieldV};
+use fxhash::FxHashMap as HashMap;
+use rand::RngCore;
+
+/// Communication costs of a simulated execution
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of multiplication gates
+    pub multiplications: usize,
+    /// The number of communication rounds: input sharing, one per multiplicative layer, and output
+    /// opening
+    pub rounds: usize,
+    /// The number of field elements sent from one party to another
+    pub messages: usize,
+}
+
+/// The sharing parameters: `n` parties at points `1..=n`, with threshold `t`.
+struct Params {
+    field: FieldT,
+    n: usize,
+    t: usize,
+}
+
+impl Params {
+    fn new(field: FieldT, n: usize) -> Self {
+        assert!(n > 0, "Cannot simulate MPC with no parties");
+        assert!(
+            field.modulus() > &n,
+            "The field must have more elements than there are parties"
+        );
+        Self {
+            field,
+            n,
+            t: (n - 1) / 2,
+        }
+    }
+
+    fn point(&self, party: usize) -> FieldV {
+        self.field.new_v(party + 1)
+    }
+
+    /// Share `secret` with a random polynomial of degree `degree`.
+    fn share(&self, secret: &FieldV, degree: usize, rng: &mut impl RngCore) -> Vec<FieldV> {
+        let coeffs: Vec<FieldV> = (0..degree)
+            .map(|_| self.field.random_v(&mut *rng))
+            .collect();
+        (0..self.n)
+            .map(|i| {
+                // Horner's rule
+                let x = self.point(i);
+                coeffs
+                    .iter()
+                    .rev()
+                    .fold(self.field.zero(), |acc, c| acc * &x + c)
+                    * &x
+                    + secret
+            })
+            .collect()
+    }
+
+    /// The Lagrange coefficients for interpolating the value at 0 from `parties`.
+    fn lagrange(&self, parties: &[usize]) -> Vec<FieldV> {
+        parties
+            .iter()
+            .map(|i| {
+                let xi = self.point(*i);
+                let (mut num, mut den) = (self.field.new_v(1), self.field.new_v(1));
+                for j in parties.iter().filter(|j| *j != i) {
+                    let xj = self.point(*j);
+                    num *= &xj;
+                    den *= xj - &xi;
+                }
+                num * den.recip()
+            })
+            .collect()
+    }
+
+    /// Reconstruct a secret from the shares of `parties`.
+    fn open(&self, shares: &[FieldV], parties: &[usize]) -> FieldV {
+        self.lagrange(parties)
+            .into_iter()
+            .zip(parties)
+            .fold(self.field.zero(), |acc, (l, p)| acc + l * &shares[*p])
+    }
+}
+
+/// Run the MPC protocol for `circuit` on the IR inputs `values`, returning the IR outputs and the
+/// communication costs.
+pub fn simulate(
+    circuit: &Circuit,
+    values: &HashMap<String, Value>,
+    rng: &mut impl RngCore,
+) -> (Vec<Value>, Stats) {
+    let p = Params::new(circuit.field.clone(), circuit.num_parties);
+    let inputs = circuit.encode_inputs(values);
+    let mut stats = Stats::default();
+
+    // Recombination coefficients for degree-2t sharings.
+    let mul_parties: Vec<usize> = (0..2 * p.t + 1).collect();
+    let mul_lagrange = p.lagrange(&mul_parties);
+
+    // Share the inputs.
+    let mut input_shares: Vec<Vec<Vec<FieldV>>> = Vec::new();
+    let mut private_inputs = false;
+    for (input, elems) in circuit.inputs.iter().zip(&inputs) {
+        let degree = if input.party.is_some() { p.t } else { 0 };
+        private_inputs |= input.party.is_some();
+        if input.party.is_some() {
+            stats.messages += elems.len() * (p.n - 1);
+        }
+        input_shares.push(elems.iter().map(|e| p.share(e, degree, rng)).collect());
+    }
+
+    let mut wires: Vec<Vec<FieldV>> = Vec::with_capacity(circuit.gates.len());
+    for g in &circuit.gates {
+        let shares = match g {
+            Gate::Input(i, j) => input_shares[*i][*j].clone(),
+            Gate::Const(c) => vec![c.clone(); p.n],
+            Gate::Add(a, b) => (0..p.n)
+                .map(|i| wires[*a][i].clone() + &wires[*b][i])
+                .collect(),
+            Gate::Sub(a, b) => (0..p.n)
+                .map(|i| wires[*a][i].clone() - &wires[*b][i])
+                .collect(),
+            Gate::Scale(c, a) => wires[*a].iter().map(|s| c.clone() * s).collect(),
+            Gate::Mul(a, b) => {
+                stats.multiplications += 1;
+                stats.messages += mul_parties.len() * (p.n - 1);
+                // resharings[i][j]: party i's product, shared to party j
+                let resharings: Vec<Vec<FieldV>> = mul_parties
+                    .iter()
+                    .map(|i| p.share(&(wires[*a][*i].clone() * &wires[*b][*i]), p.t, rng))
+                    .collect();
+                (0..p.n)
+                    .map(|j| {
+                        resharings
+                            .iter()
+                            .zip(&mul_lagrange)
+                            .fold(p.field.zero(), |acc, (r, l)| acc + l.clone() * &r[j])
+                    })
+                    .collect()
+            }
+        };
+        wires.push(shares);
+    }
+
+    // Open the outputs.
+    let first: Vec<usize> = (0..=p.t).collect();
+    let last: Vec<usize> = (p.n - p.t - 1..p.n).collect();
+    let outputs: Vec<Vec<FieldV>> = circuit
+        .outputs
+        .iter()
+        .map(|o| {
+            o.wires
+                .iter()
+                .map(|w| {
+                    stats.messages += p.n * (p.n - 1);
+                    let v = p.open(&wires[*w], &first);
+                    assert_eq!(
+                        v,
+                        p.open(&wires[*w], &last),
+                        "Shares of wire {w} are inconsistent"
+                    );
+                    v
+                })
+                .collect()
+        })
+        .collect();
+
+    stats.rounds = private_inputs as usize + circuit.mul_depth() + 1;
+    (circuit.decode_outputs(&outputs), stats)
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::trans::to_shamir;
+    use super::*;
+    use crate::ir::term::text::{parse_computation, parse_value_map};
+
+    use rand::SeedableRng;
+    use rug::Integer;
+
+    fn rng() -> rand::rngs::StdRng {
+        rand::rngs::StdRng::seed_from_u64(0)
+    }
+
+    #[test]
+    fn sharing() {
+        let field = FieldT::from(Integer::from(1009));
+        let p = Params::new(field.clone(), 5);
+        assert_eq!(2, p.t);
+        let secret = field.new_v(123);
+        let shares = p.share(&secret, p.t, &mut rng());
+        assert_eq!(secret, p.open(&shares, &[0, 1, 2]));
+        assert_eq!(secret, p.open(&shares, &[1, 3, 4]));
+        assert_eq!(secret, p.open(&shares, &[0, 1, 2, 3, 4]));
+    }
+
+    #[test]
+    fn field_three_parties() {
+        let cs = parse_computation(
+            b"
+            (computation
+                (metadata (parties A B C) (inputs (a (mod 1009) (party 0)) (b (mod 1009) (party 1)) (c (mod 1009) (party 2)) (k (mod 1009))) (commitments))
+                (precompute () () (#t ))
+                (* (+ a k) (+ b c) c)
+            )
+        ",
+        );
+        let circuit = to_shamir(&cs, &FieldT::from(Integer::from(1009)), 3);
+        let values =
+            parse_value_map(b"(let ((a #f5m1009) (b #f7m1009) (c #f900m1009) (k #f2m1009)) false)");
+        let (outputs, stats) = simulate(&circuit, &values, &mut rng());
+        assert_eq!(cs.eval_all(&values), outputs);
+        assert_eq!(2, stats.multiplications);
+        assert_eq!(1 + 2 + 1, stats.rounds);
+        // 3 private inputs, 2 multiplications with 3 resharers, and 1 output, all to 2 others.
+        assert_eq!(3 * 2 + 2 * 3 * 2 + 3 * 2, stats.messages);
+    }
+
+    #[test]
+    fn bv_five_parties() {
+        let cs = parse_computation(
+            b"
+            (computation
+                (metadata (parties A B C D E) (inputs (a (bv 4) (party 0)) (b (bv 4) (party 1)) (c (bv 4) (party 2)) (d (bv 4) (party 3)) (e (bv 4) (party 4))) (commitments))
+                (precompute () () (#t ))
+                (ite (bvult a b) (bvadd c d) (bvmul d e))
+            )
+        ",
+        );
+        let circuit = to_shamir(&cs, &FieldT::from(Integer::from(1009)), 5);
+        assert_eq!(5, circuit.num_parties);
+        let mut rng = rng();
+        for values in [
+            b"(let ((a #x3) (b #x4) (c #x9) (d #x5) (e #xf)) false)".as_ref(),
+            b"(let ((a #x5) (b #x4) (c #x9) (d #x5) (e #xf)) false)".as_ref(),
+        ] {
+            let values = parse_value_map(values);
+            let (outputs, stats) = simulate(&circuit, &values, &mut rng);
+            assert_eq!(cs.eval_all(&values), outputs);
+            assert_eq!(circuit.mul_count(), stats.multiplications);
+        }
+    }
+}
@@ -0,0 +1,444 @@
+//! Lowering IR to arithmetic circuits for Shamir-based MPC
+//!
+//! Computations over booleans and field elements are lowered directly: `and` is a
+//! multiplication, `not` is `1 - a`, and so on. Equality and reciprocals of field elements use
+//! Fermat's little theorem, so each costs about `2 log2(p)` multiplications.
+//!
+//! Computations with bit-vectors are first lowered to boolean circuits by
+//! [crate::target::bristol], whose XOR, AND, and INV gates we then encode (XOR costs a
+//! multiplication). They may not also use field elements.
+
+use super::{Circuit, Gate, Input, Output};
+use crate::ir::term::*;
+use crate::target::bristol;
+
+use circ_fields::{FieldT, FieldV};
+use fxhash::FxHashMap as HashMap;
+use rug::Integer;
+
+/// A circuit value: a constant, or a wire.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Elem {
+    Const(FieldV),
+    Wire(usize),
+}
+
+struct Builder {
+    field: FieldT,
+    gates: Vec<Gate>,
+    cache: HashMap<Gate, usize>,
+}
+
+impl Builder {
+    fn new(field: FieldT) -> Self {
+        Self {
+            field,
+            gates: Vec::new(),
+            cache: HashMap::default(),
+        }
+    }
+
+    fn gate(&mut self, g: Gate) -> usize {
+        // Normalize commutative gates, for hashing.
+        let g = match g {
+            Gate::Add(a, b) if b < a => Gate::Add(b, a),
+            Gate::Mul(a, b) if b < a => Gate::Mul(b, a),
+            g => g,
+        };
+        if let Some(w) = self.cache.get(&g) {
+            return *w;
+        }
+        self.gates.push(g.clone());
+        self.cache.insert(g, self.gates.len() - 1);
+        self.gates.len() - 1
+    }
+
+    fn constant(&self, i: i64) -> Elem {
+        Elem::Const(self.field.new_v(i))
+    }
+
+    /// A wire holding `e`.
+    fn wire(&mut self, e: &Elem) -> usize {
+        match e {
+            Elem::Const(c) => self.gate(Gate::Const(c.clone())),
+            Elem::Wire(w) => *w,
+        }
+    }
+
+    fn add(&mut self, a: &Elem, b: &Elem) -> Elem {
+        match (a, b) {
+            (Elem::Const(a), Elem::Const(b)) => Elem::Const(a.clone() + b),
+            (Elem::Const(c), e) | (e, Elem::Const(c)) if c.is_zero() => e.clone(),
+            _ => {
+                let (a, b) = (self.wire(a), self.wire(b));
+                Elem::Wire(self.gate(Gate::Add(a, b)))
+            }
+        }
+    }
+
+    fn sub(&mut self, a: &Elem, b: &Elem) -> Elem {
+        match (a, b) {
+            (Elem::Const(a), Elem::Const(b)) => Elem::Const(a.clone() - b),
+            (e, Elem::Const(c)) if c.is_zero() => e.clone(),
+            _ if a == b => self.constant(0),
+            _ => {
+                let (a, b) = (self.wire(a), self.wire(b));
+                Elem::Wire(self.gate(Gate::Sub(a, b)))
+            }
+        }
+    }
+
+    fn scale(&mut self, c: &FieldV, a: &Elem) -> Elem {
+        match a {
+            Elem::Const(a) => Elem::Const(c.clone() * a),
+            _ if c.is_zero() => self.constant(0),
+            _ if c.is_one() => a.clone(),
+            Elem::Wire(w) => Elem::Wire(self.gate(Gate::Scale(c.clone(), *w))),
+        }
+    }
+
+    fn mul(&mut self, a: &Elem, b: &Elem) -> Elem {
+        match (a, b) {
+            (Elem::Const(c), e) | (e, Elem::Const(c)) => self.scale(c, e),
+            (Elem::Wire(a), Elem::Wire(b)) => Elem::Wire(self.gate(Gate::Mul(*a, *b))),
+        }
+    }
+
+    /// A product, as a balanced tree.
+    fn product(&mut self, es: &[Elem]) -> Elem {
+        match es {
+            [] => self.constant(1),
+            [e] => e.clone(),
+            _ => {
+                let (l, r) = es.split_at(es.len() / 2);
+                let (l, r) = (self.product(l), self.product(r));
+                self.mul(&l, &r)
+            }
+        }
+    }
+
+    /// `a^e`, by square-and-multiply.
+    fn pow(&mut self, a: &Elem, e: &Integer) -> Elem {
+        let mut acc = self.constant(1);
+        for i in (0..e.significant_bits()).rev() {
+            acc = self.mul(&acc.clone(), &acc);
+            if e.get_bit(i) {
+                acc = self.mul(&acc, a);
+            }
+        }
+        acc
+    }
+
+    /// 1 if `a` is zero; 0 otherwise.
+    fn is_zero(&mut self, a: &Elem) -> Elem {
+        let e = Integer::from(self.field.modulus() - 1);
+        let nonzero = self.pow(a, &e);
+        self.not(&nonzero)
+    }
+
+    fn recip(&mut self, a: &Elem) -> Elem {
+        let e = Integer::from(self.field.modulus() - 2);
+        self.pow(a, &e)
+    }
+
+    fn not(&mut self, a: &Elem) -> Elem {
+        let one = self.constant(1);
+        self.sub(&one, a)
+    }
+
+    fn xor(&mut self, a: &Elem, b: &Elem) -> Elem {
+        // a + b - 2ab
+        let sum = self.add(a, b);
+        let prod = self.mul(a, b);
+        let two = self.field.new_v(2);
+        let twice = self.scale(&two, &prod);
+        self.sub(&sum, &twice)
+    }
+
+    fn or(&mut self, a: &Elem, b: &Elem) -> Elem {
+        let (na, nb) = (self.not(a), self.not(b));
+        let nor = self.mul(&na, &nb);
+        self.not(&nor)
+    }
+
+    /// `s ? t : f`
+    fn mux(&mut self, s: &Elem, t: &Elem, f: &Elem) -> Elem {
+        let diff = self.sub(t, f);
+        let sel = self.mul(s, &diff);
+        self.add(f, &sel)
+    }
+}
+
+/// Lower a computation over booleans and field elements.
+struct ToShamir {
+    b: Builder,
+    inputs: Vec<Input>,
+    cache: TermMap<Elem>,
+}
+
+impl ToShamir {
+    fn get(&self, t: &Term) -> Elem {
+        self.cache.get(t).unwrap().clone()
+    }
+
+    fn embed(&mut self, t: &Term) -> Elem {
+        let cs: Vec<Elem> = t.cs().iter().map(|c| self.get(c)).collect();
+        match t.op() {
+            Op::Var(name, sort) => {
+                self.inputs.push(Input {
+                    name: name.clone(),
+                    sort: sort.clone(),
+                    party: None,
+                });
+                Elem::Wire(self.b.gate(Gate::Input(self.inputs.len() - 1, 0)))
+            }
+            Op::Const(Value::Bool(b)) => self.b.constant(*b as i64),
+            Op::Const(Value::Field(f)) => Elem::Const(f.as_ty_ref(&self.b.field)),
+            Op::Not => self.b.not(&cs[0]),
+            Op::Implies => {
+                let na = self.b.not(&cs[0]);
+                self.b.or(&na, &cs[1])
+            }
+            Op::BoolNaryOp(BoolNaryOp::And) => self.b.product(&cs),
+            Op::BoolNaryOp(BoolNaryOp::Or) => {
+                let nots: Vec<Elem> = cs.iter().map(|c| self.b.not(c)).collect();
+                let nor = self.b.product(&nots);
+                self.b.not(&nor)
+            }
+            Op::BoolNaryOp(BoolNaryOp::Xor) => {
+                let first = cs[0].clone();
+                cs[1..].iter().fold(first, |acc, c| self.b.xor(&acc, c))
+            }
+            Op::BoolMaj => {
+                // ab + c(a ^ b)
+                let ab = self.b.mul(&cs[0], &cs[1]);
+                let a_xor_b = self.b.xor(&cs[0], &cs[1]);
+                let c = self.b.mul(&cs[2], &a_xor_b);
+                self.b.add(&ab, &c)
+            }
+            Op::Ite => self.b.mux(&cs[0], &cs[1], &cs[2]),
+            Op::Eq => {
+                let diff = self.b.sub(&cs[0], &cs[1]);
+                match check(&t.cs()[0]) {
+                    // For bits, a - b is 0 or +-1.
+                    Sort::Bool => {
+                        let sq = self.b.mul(&diff, &diff);
+                        self.b.not(&sq)
+                    }
+                    _ => self.b.is_zero(&diff),
+                }
+            }
+            Op::PfNaryOp(PfNaryOp::Add) => {
+                let first = cs[0].clone();
+                cs[1..].iter().fold(first, |acc, c| self.b.add(&acc, c))
+            }
+            Op::PfNaryOp(PfNaryOp::Mul) => self.b.product(&cs),
+            Op::PfUnOp(PfUnOp::Neg) => {
+                let zero = self.b.constant(0);
+                self.b.sub(&zero, &cs[0])
+            }
+            Op::PfUnOp(PfUnOp::Recip) => self.b.recip(&cs[0]),
+            op => panic!("Unsupported op in Shamir lowering: {}", op),
+        }
+    }
+}
+
+fn has_bv(cs: &Computation) -> bool {
+    PostOrderIter::from_roots_and_skips(cs.outputs.iter().cloned(), TermSet::default())
+        .any(|t| matches!(check(&t), Sort::BitVector(_)))
+}
+
+/// Lower a Bristol Fashion circuit.
+fn from_bristol(circuit: &bristol::Circuit, b: &mut Builder) -> (Vec<Input>, Vec<Output>) {
+    let netlist = &circuit.netlist;
+    let mut wires: Vec<Option<Elem>> = vec![None; netlist.num_wires];
+    let mut inputs = Vec::new();
+    let mut next = 0;
+    for group in &circuit.inputs {
+        for (name, sort) in &group.vars {
+            for j in 0..super::sort_elems(sort) {
+                wires[next] = Some(Elem::Wire(b.gate(Gate::Input(inputs.len(), j))));
+                next += 1;
+            }
+            inputs.push(Input {
+                name: name.clone(),
+                sort: sort.clone(),
+                party: group.party,
+            });
+        }
+    }
+    let get = |wires: &[Option<Elem>], w: usize| wires[w].clone().unwrap();
+    for g in &netlist.gates {
+        let e = match g {
+            bristol::Gate::Xor(x, y, _) => b.xor(&get(&wires, *x), &get(&wires, *y)),
+            bristol::Gate::And(x, y, _) => b.mul(&get(&wires, *x), &get(&wires, *y)),
+            bristol::Gate::Inv(x, _) => b.not(&get(&wires, *x)),
+            bristol::Gate::Eq(c, _) => b.constant(*c as i64),
+        };
+        wires[g.output()] = Some(e);
+    }
+    let mut next = netlist.num_wires - netlist.outputs.iter().sum::<usize>();
+    let outputs = circuit
+        .outputs
+        .iter()
+        .map(|sort| {
+            let n = super::sort_elems(sort);
+            let out = Output {
+                sort: sort.clone(),
+                wires: (next..next + n).map(|w| b.wire(&get(&wires, w))).collect(),
+            };
+            next += n;
+            out
+        })
+        .collect();
+    (inputs, outputs)
+}
+
+/// Lower a computation to an arithmetic circuit over `field`, for at least `num_parties` parties.
+pub fn to_shamir(cs: &Computation, field: &FieldT, num_parties: usize) -> Circuit {
+    let md = &cs.metadata;
+    let mut b = Builder::new(field.clone());
+    let (inputs, outputs) = if has_bv(cs) {
+        from_bristol(&bristol::trans::to_bristol(cs), &mut b)
+    } else {
+        let mut to = ToShamir {
+            b,
+            inputs: Vec::new(),
+            cache: TermMap::default(),
+        };
+        for t in PostOrderIter::from_roots_and_skips(cs.outputs.iter().cloned(), TermSet::default())
+        {
+            let e = to.embed(&t);
+            to.cache.insert(t, e);
+        }
+        for i in &mut to.inputs {
+            i.party = md.get_input_visibility(&i.name);
+        }
+        let outputs = cs
+            .outputs
+            .iter()
+            .map(|o| {
+                let e = to.get(o);
+                Output {
+                    sort: check(o),
+                    wires: vec![to.b.wire(&e)],
+                }
+            })
+            .collect();
+        b = to.b;
+        (to.inputs, outputs)
+    };
+    let num_parties = inputs
+        .iter()
+        .filter_map(|i| i.party)
+        .map(|p| p as usize + 1)
+        .chain([num_parties, md.num_parties()])
+        .max()
+        .unwrap();
+    assert!(
+        field.modulus() > &num_parties,
+        "The field must have more elements than there are parties"
+    );
+    Circuit {
+        field: field.clone(),
+        num_parties,
+        inputs,
+        gates: b.gates,
+        outputs,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::term::text::{parse_computation, parse_value_map};
+
+    fn small_field() -> FieldT {
+        FieldT::from(Integer::from(1009))
+    }
+
+    #[test]
+    fn field_ops() {
+        let cs = parse_computation(
+            b"
+            (computation
+                (metadata (parties A B C) (inputs (a (mod 1009) (party 0)) (b (mod 1009) (party 1)) (c (mod 1009) (party 2))) (commitments))
+                (precompute () () (#t ))
+                (tuple
+                    (* a (+ b c #f3m1009))
+                    (pfrecip a)
+                    (= a b)
+                    (ite (= b c) (- a) c))
+            )
+        ",
+        );
+        let mut cs = cs;
+        crate::ir::opt::tuple::eliminate_tuples(&mut cs);
+        let circuit = to_shamir(&cs, &small_field(), 3);
+        assert_eq!(3, circuit.num_parties);
+        let mut parties: Vec<(&str, Option<PartyId>)> = circuit
+            .inputs
+            .iter()
+            .map(|i| (i.name.as_str(), i.party))
+            .collect();
+        parties.sort();
+        assert_eq!(
+            vec![("a", Some(0)), ("b", Some(1)), ("c", Some(2))],
+            parties
+        );
+        for values in [
+            b"(let ((a #f5m1009) (b #f7m1009) (c #f7m1009)) false)".as_ref(),
+            b"(let ((a #f1000m1009) (b #f1000m1009) (c #f0m1009)) false)".as_ref(),
+        ] {
+            let values = parse_value_map(values);
+            assert_eq!(cs.eval_all(&values), circuit.eval(&values));
+        }
+    }
+
+    #[test]
+    fn bool_ops() {
+        let cs = parse_computation(
+            b"
+            (computation
+                (metadata (parties A B C) (inputs (a bool (party 0)) (b bool (party 1)) (c bool (party 2))) (commitments))
+                (precompute () () (#t ))
+                (ite (xor a b c) (and a (=> b c)) (maj a b (not c)))
+            )
+        ",
+        );
+        let circuit = to_shamir(&cs, &small_field(), 3);
+        for bits in 0..8 {
+            let values = (0..3)
+                .map(|i| {
+                    (
+                        ["a", "b", "c"][i].to_owned(),
+                        Value::Bool((bits >> i) & 1 == 1),
+                    )
+                })
+                .collect();
+            assert_eq!(cs.eval_all(&values), circuit.eval(&values));
+        }
+    }
+
+    #[test]
+    fn bv_ops() {
+        let cs = parse_computation(
+            b"
+            (computation
+                (metadata (parties A B C D) (inputs (a (bv 8) (party 0)) (b (bv 8) (party 1)) (c (bv 8) (party 2)) (d (bv 8) (party 3))) (commitments))
+                (precompute () () (#t ))
+                (ite (bvult (bvadd a b) c) (bvmul a d) (bvsub c d))
+            )
+        ",
+        );
+        let circuit = to_shamir(&cs, &small_field(), 4);
+        assert_eq!(4, circuit.num_parties);
+        for values in [
+            b"(let ((a #x03) (b #x04) (c #x10) (d #x05)) false)".as_ref(),
+            b"(let ((a #xf3) (b #x04) (c #x10) (d #x05)) false)".as_ref(),
+        ] {
+            let values = parse_value_map(values);
+            assert_eq!(cs.eval_all(&values), circuit.eval(&values));
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Bounded model checking of front-end programs
+//!
+//! Front-ends unroll loops and inline calls, so the [Computation] they produce (in
+//! [crate::front::Mode::Proof]) is already a bounded program. To check it, we ask the SMT solver
+//! for inputs under which its assertions fail.
+
+use super::find_model;
+use crate::ir::term::*;
+
+use fxhash::FxHashMap as HashMap;
+
+/// The outcome of a bounded model check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    /// No inputs violate the property
+    Holds,
+    /// These inputs violate the property
+    Violated(HashMap<String, Value>),
+}
+
+/// The condition under which some assertion of `c` fails: the negation of the conjunction of its
+/// outputs.
+pub fn assertions_fail(c: &Computation) -> Term {
+    let assertions = c.outputs.iter().cloned().chain([bool_lit(true)]).collect();
+    term![NOT; term(AND, assertions)]
+}
+
+/// Search for inputs to `c` that satisfy `violation`.
+///
+/// Inputs that `c` precomputes (like the `return` input of Z#) are fixed to their precomputed
+/// values, so that a counterexample chooses only the real inputs. The counterexample binds every
+/// real input: those that `violation` does not depend on get default values.
+pub fn find_violation(c: &Computation, violation: Term) -> Verdict {
+    let precomputed = c.precomputes.outputs();
+    let query = term(
+        AND,
+        precomputed
+            .iter()
+            .map(|(name, t)| term![EQ; leaf_term(Op::Var(name.clone(), check(t))), t.clone()])
+            .chain([violation])
+            .collect(),
+    );
+    match find_model(&query) {
+        None => Verdict::Holds,
+        Some(model) => Verdict::Violated(
+            c.metadata
+                .ordered_input_names()
+                .into_iter()
+                .filter(|name| !precomputed.contains_key(name))
+                .map(|name| {
+                    let value = model
+                        .get(&name)
+                        .cloned()
+                        .unwrap_or_else(|| c.metadata.input_sort(&name).default_value());
+                    (name, value)
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// The located assertions of `c` that fail on `inputs` (e.g., a counterexample from
+/// [find_violation]), in source order. Assertions that depend on other variables are skipped.
+pub fn failed_assertions<'c>(
+    c: &'c Computation,
+    inputs: &HashMap<String, Value>,
+) -> Vec<&'c SourceLoc> {
+    let env = c.precomputes.eval(inputs);
+    let mut cache = TermMap::default();
+    let mut failed: Vec<&SourceLoc> = c
+        .metadata
+        .source_map()
+        .assertions()
+        .filter(|(t, _)| {
+            extras::free_variables((*t).clone())
+                .iter()
+                .all(|v| env.contains_key(v))
+        })
+        .filter(|(t, _)| !eval_cached(t, &env, &mut cache).as_bool())
+        .map(|(_, loc)| loc)
+        .collect();
+    failed.sort();
+    failed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// `return = x + 1`, asserting that `x` is not `bad` (when given).
+    fn incr(bad: Option<u8>) -> Computation {
+        let mut c = Computation::new();
+        let x = c.new_var("x", Sort::BitVector(4), None, None);
+        c.new_var("y", Sort::Bool, None, None);
+        let ret = term![BV_ADD; x.clone(), bv_lit(1, 4)];
+        let r = c.new_var("return", Sort::BitVector(4), None, Some(ret.clone()));
+        c.assert(term![EQ; r, ret]);
+        if let Some(bad) = bad {
+            let a = term![NOT; term![EQ; x, bv_lit(bad, 4)]];
+            c.metadata.source_map_mut().insert(a.clone(), bad_loc());
+            c.assert(a);
+        }
+        c
+    }
+
+    fn bad_loc() -> SourceLoc {
+        SourceLoc {
+            file: "incr.zok".into(),
+            line: 3,
+            column: 5,
+            end_line: 3,
+            end_column: 20,
+            function: "main".into(),
+            name: None,
+        }
+    }
+
+    #[test]
+    fn holds() {
+        let c = incr(None);
+        assert_eq!(Verdict::Holds, find_violation(&c, assertions_fail(&c)));
+    }
+
+    #[test]
+    fn violated() {
+        let c = incr(Some(11));
+        let expected: HashMap<String, Value> = vec![
+            (
+                "x".to_owned(),
+                Value::BitVector(BitVector::new(11.into(), 4)),
+            ),
+            ("y".to_owned(), Value::Bool(false)),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            Verdict::Violated(expected.clone()),
+            find_violation(&c, assertions_fail(&c))
+        );
+        assert_eq!(failed_assertions(&c, &expected), vec![&bad_loc()]);
+    }
+
+    #[test]
+    fn custom_violation() {
+        // does `return` ever overflow to zero?
+        let c = incr(None);
+        let ret = leaf_term(Op::Var("return".into(), Sort::BitVector(4)));
+        match find_violation(&c, term![EQ; ret, bv_lit(0, 4)]) {
+            Verdict::Violated(inputs) => {
+                assert_eq!(inputs["x"], Value::BitVector(BitVector::new(15.into(), 4)));
+            }
+            Verdict::Holds => panic!("x = 15 is a counterexample"),
+        }
+    }
+}